@@ -10,9 +10,11 @@
 extern crate alloc;
 
 use alloc::{
+    collections::BTreeMap,
     string::{String, ToString},
     vec::Vec,
 };
+use core::str::FromStr;
 use ibc::apps::transfer::types::PORT_ID_STR;
 use near_contract_standards::fungible_token::core::ext_ft_core;
 use near_sdk::{
@@ -27,15 +29,19 @@ use near_sdk::{
 };
 use utils::{
     interfaces::{
-        ext_transfer_request_handler, ChannelEscrow, NearIbcAccountAssertion,
+        ext_transfer_request_handler, ext_wrapped_token, ChannelEscrow, NearIbcAccountAssertion,
         ProcessTransferRequestCallback,
     },
-    types::{AssetDenom, Ics20TransferRequest},
+    types::{
+        AssetDenom, Ics20MultiTransferRequest, Ics20Token, Ics20TransferRequest, PacketFee, Page,
+    },
 };
 
 mod migration;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The `msg` of `ft_transfer_call` for depositing tokens for multi-denom transfers.
+const BATCH_DEPOSIT_MSG: &str = "batch_deposit";
 
 #[derive(BorshSerialize, BorshStorageKey)]
 #[borsh(crate = "near_sdk::borsh")]
@@ -43,6 +49,10 @@ pub enum StorageKey {
     TokenContracts,
     PendingTransferRequests,
     DenomToTokenContractMap,
+    PendingTransferRequestsById,
+    BatchDeposits,
+    PendingMultiTransferRequests,
+    WrappedTokenContracts,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -51,6 +61,11 @@ pub struct FtOnTransferMsg {
     pub receiver: String,
     #[serde(default)]
     pub timeout_seconds: Option<U64>,
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// The fee for relayers (ICS-29), which is deducted from the transferred amount.
+    #[serde(default)]
+    pub fee: Option<PacketFee>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -68,10 +83,20 @@ pub struct Contract {
     near_ibc_account: AccountId,
     /// The token accounts that this contract is allowed to send tokens to.
     token_contracts: UnorderedMap<AccountId, AssetDenom>,
-    /// Accounting for the pending transfer requests.
-    pending_transfer_requests: UnorderedMap<AccountId, Ics20TransferRequest>,
+    /// Accounting for the pending transfer requests, keyed by request id.
+    pending_transfer_requests: UnorderedMap<u64, Ics20TransferRequest>,
     /// The mapping from the asset denom to the token contract account id.
     denom_to_token_contract_map: LookupMap<AssetDenom, AccountId>,
+    /// The id for the next transfer request.
+    next_request_id: u64,
+    /// Whether the outgoing transfers are paused.
+    paused: bool,
+    /// The tokens deposited by accounts for multi-denom transfers.
+    batch_deposits: LookupMap<AccountId, BTreeMap<AssetDenom, u128>>,
+    /// Accounting for the pending multi-denom transfer requests, keyed by request id.
+    pending_multi_transfer_requests: UnorderedMap<u64, Ics20MultiTransferRequest>,
+    /// The wrapped token contracts which have locked tokens for multi-denom transfers.
+    wrapped_token_contracts: LookupMap<AssetDenom, AccountId>,
 }
 
 #[near_bindgen]
@@ -87,8 +112,15 @@ impl Contract {
         Self {
             near_ibc_account,
             token_contracts: UnorderedMap::new(StorageKey::TokenContracts),
-            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequests),
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
             denom_to_token_contract_map: LookupMap::new(StorageKey::DenomToTokenContractMap),
+            next_request_id: 1,
+            paused: false,
+            batch_deposits: LookupMap::new(StorageKey::BatchDeposits),
+            pending_multi_transfer_requests: UnorderedMap::new(
+                StorageKey::PendingMultiTransferRequests,
+            ),
+            wrapped_token_contracts: LookupMap::new(StorageKey::WrappedTokenContracts),
         }
     }
     ///
@@ -102,12 +134,14 @@ impl Contract {
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
         let token_denom = self.token_contracts.get(&env::predecessor_account_id());
         assert!(token_denom.is_some(), "ERR_UNREGISTERED_TOKEN_CONTRACT");
-        assert!(
-            !self.pending_transfer_requests.contains_key(&sender_id),
-            "ERR_PENDING_TRANSFER_REQUEST_EXISTS"
-        );
+        if msg == BATCH_DEPOSIT_MSG {
+            let asset_denom = token_denom.unwrap().clone();
+            self.internal_add_batch_deposit(&sender_id, asset_denom, amount.0);
+            return PromiseOrValue::Value(0.into());
+        }
         let parse_result: Result<FtOnTransferMsg, _> = serde_json::from_str(msg.as_str());
         assert!(
             parse_result.is_ok(),
@@ -115,60 +149,211 @@ impl Contract {
             msg
         );
         let msg = parse_result.unwrap();
+        utils::assert_valid_memo(&msg.memo);
         let current_account_id = env::current_account_id();
         let (channel_id, _) = current_account_id.as_str().split_once(".").unwrap();
         let token_denom = token_denom.unwrap();
+        let fee_amount = msg.fee.as_ref().map_or(0, |fee| fee.total());
+        assert!(amount.0 > fee_amount, "ERR_AMOUNT_NOT_ENOUGH_FOR_FEE");
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
         let transfer_request = Ics20TransferRequest {
+            request_id: U64(request_id),
             port_on_a: PORT_ID_STR.to_string(),
             chan_on_a: channel_id.to_string(),
             token_trace_path: token_denom.trace_path.clone(),
             token_denom: token_denom.base_denom.clone(),
-            amount,
+            amount: U128(amount.0 - fee_amount),
             sender: sender_id.to_string(),
             receiver: msg.receiver,
             timeout_seconds: msg.timeout_seconds,
+            memo: msg.memo,
         };
         ext_transfer_request_handler::ext(self.near_ibc_account())
             .with_attached_deposit(NearToken::from_yoctonear(0))
             .with_static_gas(utils::GAS_FOR_COMPLEX_FUNCTION_CALL)
             .with_unused_gas_weight(0)
-            .process_transfer_request(transfer_request.clone());
+            .process_transfer_request(transfer_request.clone(), msg.fee);
         self.pending_transfer_requests
-            .insert(sender_id, transfer_request);
+            .insert(request_id, transfer_request);
 
         PromiseOrValue::Value(0.into())
     }
-    /// Assert that the given account has a pending burning request with the given amount.
-    fn checked_remove_pending_transfer_request(
+    /// Request a multi-denom transfer (ICS-20 v2) of the given tokens, which are
+    /// deposited by the caller in advance.
+    ///
+    /// The tokens native in NEAR protocol are deposited by `ft_transfer_call` with
+    /// `batch_deposit` as the `msg`, and the wrapped tokens from the counterparty chain
+    /// are deposited by `deposit_for_multi_transfer` of the wrapped token contracts.
+    ///
+    /// The optional `fee` is the fee for relayers (ICS-29), which is paid in NEAR
+    /// and must be attached exactly.
+    #[payable]
+    pub fn request_multi_transfer(
         &mut self,
-        trace_path: &String,
-        base_denom: &String,
-        account_id: &AccountId,
-        amount: U128,
+        tokens: Vec<Ics20Token>,
+        receiver: String,
+        timeout_seconds: Option<U64>,
+        memo: Option<String>,
+        fee: Option<PacketFee>,
     ) {
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
         assert!(
-            self.pending_transfer_requests.contains_key(account_id),
-            "ERR_NO_PENDING_TRANSFER_REQUEST"
+            !tokens.is_empty() && tokens.len() <= utils::MAX_TOKENS_IN_MULTI_DENOM_TRANSFER,
+            "ERR_INVALID_NUMBER_OF_TOKENS"
+        );
+        utils::assert_valid_memo(&memo);
+        let fee_amount = fee.as_ref().map_or(0, |fee| fee.total());
+        assert_eq!(
+            env::attached_deposit().as_yoctonear(),
+            fee_amount,
+            "ERR_ATTACHED_DEPOSIT_MUST_BE_EQUAL_TO_THE_FEE"
         );
-        let req = self.pending_transfer_requests.get(account_id).unwrap();
+        let sender_id = env::predecessor_account_id();
+        let mut deposits = self
+            .batch_deposits
+            .get(&sender_id)
+            .cloned()
+            .unwrap_or_default();
+        for token in tokens.iter() {
+            assert!(token.amount.0 > 0, "ERR_AMOUNT_MUST_BE_GREATER_THAN_ZERO");
+            let deposit = deposits
+                .get_mut(&AssetDenom {
+                    trace_path: token.trace_path.clone(),
+                    base_denom: token.base_denom.clone(),
+                })
+                .filter(|deposit| **deposit >= token.amount.0)
+                .expect("ERR_NOT_ENOUGH_BATCH_DEPOSIT");
+            *deposit -= token.amount.0;
+        }
+        deposits.retain(|_, amount| *amount > 0);
+        match deposits.is_empty() {
+            true => self.batch_deposits.remove(&sender_id),
+            false => self.batch_deposits.insert(sender_id.clone(), deposits),
+        };
+        let current_account_id = env::current_account_id();
+        let (channel_id, _) = current_account_id.as_str().split_once(".").unwrap();
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let transfer_request = Ics20MultiTransferRequest {
+            request_id: U64(request_id),
+            port_on_a: PORT_ID_STR.to_string(),
+            chan_on_a: channel_id.to_string(),
+            tokens,
+            sender: sender_id.to_string(),
+            receiver,
+            timeout_seconds,
+            memo,
+        };
+        ext_transfer_request_handler::ext(self.near_ibc_account())
+            .with_attached_deposit(NearToken::from_yoctonear(fee_amount))
+            .with_static_gas(utils::GAS_FOR_COMPLEX_FUNCTION_CALL)
+            .with_unused_gas_weight(0)
+            .process_multi_transfer_request(transfer_request.clone(), fee);
+        self.pending_multi_transfer_requests
+            .insert(request_id, transfer_request);
+    }
+    /// Withdraw a certain amount (or all if not specified) of the tokens deposited
+    /// for multi-denom transfers.
+    #[payable]
+    pub fn withdraw_batch_deposit(
+        &mut self,
+        trace_path: String,
+        base_denom: String,
+        amount: Option<U128>,
+    ) {
+        near_sdk::assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let asset_denom = AssetDenom {
+            trace_path,
+            base_denom,
+        };
+        let mut deposits = self
+            .batch_deposits
+            .get(&sender_id)
+            .cloned()
+            .expect("ERR_NO_BATCH_DEPOSIT");
+        let deposit = deposits
+            .get_mut(&asset_denom)
+            .expect("ERR_NO_BATCH_DEPOSIT");
+        let amount = amount.map_or(*deposit, |amount| amount.0);
         assert!(
-            req.amount == amount
-                && req.token_denom.eq(base_denom)
-                && req.token_trace_path.eq(trace_path),
-            "ERR_PENDING_TRANSFER_REQUEST_NOT_MATCHED"
+            amount > 0 && amount <= *deposit,
+            "ERR_NOT_ENOUGH_BATCH_DEPOSIT"
         );
-        self.pending_transfer_requests.remove(account_id);
+        *deposit -= amount;
+        deposits.retain(|_, amount| *amount > 0);
+        match deposits.is_empty() {
+            true => self.batch_deposits.remove(&sender_id),
+            false => self.batch_deposits.insert(sender_id.clone(), deposits),
+        };
+        self.internal_return_tokens(&asset_denom, sender_id, amount);
+    }
+    /// Remove the pending transfer request with the given id and return it.
+    fn checked_remove_pending_transfer_request(&mut self, request_id: U64) -> Ics20TransferRequest {
+        self.pending_transfer_requests
+            .remove(&request_id.0)
+            .expect("ERR_NO_PENDING_TRANSFER_REQUEST")
+    }
+    /// Add a certain amount of tokens to the batch deposit of the given account.
+    fn internal_add_batch_deposit(
+        &mut self,
+        account_id: &AccountId,
+        asset_denom: AssetDenom,
+        amount: u128,
+    ) {
+        let mut deposits = self
+            .batch_deposits
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default();
+        *deposits.entry(asset_denom.clone()).or_default() += amount;
+        self.batch_deposits.insert(account_id.clone(), deposits);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"BATCH_DEPOSIT_ADDED","account_id":"{}","trace_path":"{}","base_denom":"{}","amount":"{}"}}"#,
+            account_id,
+            asset_denom.trace_path,
+            asset_denom.base_denom,
+            amount,
+        );
+    }
+    /// Return a certain amount of the tokens held (or locked in the wrapped token contract)
+    /// for the given denom to the given account.
+    fn internal_return_tokens(
+        &self,
+        asset_denom: &AssetDenom,
+        receiver_id: AccountId,
+        amount: u128,
+    ) {
+        if let Some(token_contract) = self.wrapped_token_contracts.get(asset_denom) {
+            ext_wrapped_token::ext(token_contract.clone())
+                .with_attached_deposit(NearToken::from_yoctonear(0))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+                .with_unused_gas_weight(0)
+                .unlock_tokens(receiver_id, U128(amount));
+            return;
+        }
+        let token_contract = self
+            .denom_to_token_contract_map
+            .get(asset_denom)
+            .expect("ERR_INVALID_TOKEN_DENOM");
+        ext_ft_core::ext(token_contract.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+            .with_unused_gas_weight(0)
+            .ft_transfer(receiver_id, U128(amount), None);
     }
 }
 
 #[ext_contract(ext_ft_transfer_callback)]
 pub trait FtTransferCallback {
+    /// Returns the amount transferred, which is `0` if the transfer failed.
     fn ft_transfer_callback(
         &mut self,
         token_contract: AccountId,
         receiver_id: AccountId,
         amount: U128,
-    );
+    ) -> U128;
 }
 
 #[near_bindgen]
@@ -179,7 +364,7 @@ impl FtTransferCallback for Contract {
         token_contract: AccountId,
         receiver_id: AccountId,
         amount: U128,
-    ) {
+    ) -> U128 {
         match env::promise_result(0) {
             PromiseResult::Successful(_bytes) => {
                 log!(
@@ -188,6 +373,7 @@ impl FtTransferCallback for Contract {
                     receiver_id,
                     amount.0,
                 );
+                amount
             }
             PromiseResult::Failed => {
                 log!(
@@ -196,6 +382,61 @@ impl FtTransferCallback for Contract {
                     receiver_id,
                     amount.0,
                 );
+                U128(0)
+            }
+        }
+    }
+}
+
+#[ext_contract(ext_ft_transfer_call_callback)]
+pub trait FtTransferCallCallback {
+    fn ft_transfer_call_callback(
+        &mut self,
+        token_contract: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        refund_receiver: AccountId,
+    ) -> U128;
+}
+
+#[near_bindgen]
+impl FtTransferCallCallback for Contract {
+    #[private]
+    fn ft_transfer_call_callback(
+        &mut self,
+        token_contract: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        refund_receiver: AccountId,
+    ) -> U128 {
+        match env::promise_result(0) {
+            PromiseResult::Successful(bytes) => {
+                let used_amount = serde_json::from_slice::<U128>(&bytes).map_or(0, |v| v.0);
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"FT_TRANSFER_CALL_SUCCEEDED","token_contract":"{}","receiver_id":"{}","amount":"{}","used_amount":"{}"}}"#,
+                    token_contract,
+                    receiver_id,
+                    amount.0,
+                    used_amount,
+                );
+                // If none of the tokens are used, they will be refunded on the source chain.
+                if used_amount > 0 && used_amount < amount.0 {
+                    ext_ft_core::ext(token_contract)
+                        .with_attached_deposit(NearToken::from_yoctonear(1))
+                        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+                        .with_unused_gas_weight(0)
+                        .ft_transfer(refund_receiver, U128(amount.0 - used_amount), None);
+                }
+                U128(used_amount)
+            }
+            PromiseResult::Failed => {
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ERR_FT_TRANSFER_CALL","token_contract":"{}","receiver_id":"{}","amount":"{}"}}"#,
+                    token_contract,
+                    receiver_id,
+                    amount.0,
+                );
+                U128(0)
             }
         }
     }
@@ -245,7 +486,7 @@ impl ChannelEscrow for Contract {
         base_denom: String,
         receiver_id: AccountId,
         amount: U128,
-    ) {
+    ) -> Promise {
         self.assert_near_ibc_account();
         let asset_denom = AssetDenom {
             trace_path,
@@ -268,72 +509,143 @@ impl ChannelEscrow for Contract {
                     .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
                     .with_unused_gas_weight(0)
                     .ft_transfer_callback(token_contract.clone(), receiver_id, amount),
-            );
-    }
-}
-
-#[near_bindgen]
-impl ProcessTransferRequestCallback for Contract {
-    fn apply_transfer_request(
+            )
+    } //
+    #[payable]
+    fn do_transfer_call(
         &mut self,
         trace_path: String,
         base_denom: String,
-        sender_id: AccountId,
+        receiver_id: AccountId,
         amount: U128,
-    ) {
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise {
         self.assert_near_ibc_account();
         let asset_denom = AssetDenom {
             trace_path,
             base_denom,
         };
-        let maybe_existed_token_contract = self
-            .denom_to_token_contract_map
-            .get(&asset_denom)
-            .map(|v| v.clone());
+        let maybe_existed_token_contract = self.denom_to_token_contract_map.get(&asset_denom);
         assert!(
             maybe_existed_token_contract.is_some(),
             "ERR_INVALID_TOKEN_DENOM"
         );
-        self.checked_remove_pending_transfer_request(
-            &asset_denom.trace_path,
-            &asset_denom.base_denom,
-            &sender_id,
-            amount,
-        );
+        near_sdk::assert_one_yocto();
+        let token_contract = maybe_existed_token_contract.unwrap();
+        ext_ft_core::ext(token_contract.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(6))
+            .with_unused_gas_weight(1)
+            .ft_transfer_call(receiver_id.clone(), amount, None, msg)
+            .then(
+                ext_ft_transfer_call_callback::ext(env::current_account_id())
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(3))
+                    .with_unused_gas_weight(0)
+                    .ft_transfer_call_callback(
+                        token_contract.clone(),
+                        receiver_id,
+                        amount,
+                        refund_receiver,
+                    ),
+            )
     }
-
-    fn cancel_transfer_request(
+    //
+    fn set_paused(&mut self, paused: bool) {
+        self.assert_near_ibc_account();
+        self.paused = paused;
+    }
+    //
+    fn on_wrapped_token_deposit(
         &mut self,
+        sender_id: AccountId,
         trace_path: String,
         base_denom: String,
-        sender_id: AccountId,
         amount: U128,
     ) {
-        self.assert_near_ibc_account();
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
+        let token_contract = env::predecessor_account_id();
+        let token_factory_suffix = format!(".tf.{}.{}", PORT_ID_STR, self.near_ibc_account);
+        assert!(
+            token_contract
+                .as_str()
+                .strip_suffix(token_factory_suffix.as_str())
+                .is_some_and(|asset_id| !asset_id.contains('.')),
+            "ERR_ONLY_WRAPPED_TOKEN_CAN_CALL_THIS_METHOD"
+        );
+        let current_account_id = env::current_account_id();
+        let (channel_id, _) = current_account_id.as_str().split_once(".").unwrap();
+        assert!(
+            trace_path.starts_with(format!("{}/{}/", PORT_ID_STR, channel_id).as_str()),
+            "ERR_WRAPPED_TOKEN_IS_NOT_FROM_THIS_CHANNEL"
+        );
         let asset_denom = AssetDenom {
             trace_path,
             base_denom,
         };
-        let maybe_existed_token_contract = self
+        self.wrapped_token_contracts
+            .insert(asset_denom.clone(), token_contract);
+        self.internal_add_batch_deposit(&sender_id, asset_denom, amount.0);
+    }
+}
+
+#[near_bindgen]
+impl ProcessTransferRequestCallback for Contract {
+    fn apply_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        if let Some(req) = self.pending_multi_transfer_requests.remove(&request_id.0) {
+            // The wrapped tokens are sent back to the source chain, burn them.
+            req.tokens.iter().for_each(|token| {
+                if let Some(token_contract) = self.wrapped_token_contracts.get(&AssetDenom {
+                    trace_path: token.trace_path.clone(),
+                    base_denom: token.base_denom.clone(),
+                }) {
+                    ext_wrapped_token::ext(token_contract.clone())
+                        .with_attached_deposit(NearToken::from_yoctonear(0))
+                        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+                        .with_unused_gas_weight(0)
+                        .burn_locked_tokens(token.amount);
+                }
+            });
+            return;
+        }
+        self.checked_remove_pending_transfer_request(request_id);
+    }
+
+    fn cancel_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        if let Some(req) = self.pending_multi_transfer_requests.remove(&request_id.0) {
+            let sender_id = AccountId::from_str(req.sender.as_str()).unwrap();
+            req.tokens.iter().for_each(|token| {
+                self.internal_return_tokens(
+                    &AssetDenom {
+                        trace_path: token.trace_path.clone(),
+                        base_denom: token.base_denom.clone(),
+                    },
+                    sender_id.clone(),
+                    token.amount.0,
+                )
+            });
+            return;
+        }
+        let req = self.checked_remove_pending_transfer_request(request_id);
+        let asset_denom = AssetDenom {
+            trace_path: req.token_trace_path,
+            base_denom: req.token_denom,
+        };
+        let token_contract = self
             .denom_to_token_contract_map
             .get(&asset_denom)
-            .map(|v| v.clone());
-        assert!(
-            maybe_existed_token_contract.is_some(),
-            "ERR_INVALID_TOKEN_DENOM"
-        );
-        self.checked_remove_pending_transfer_request(
-            &asset_denom.trace_path,
-            &asset_denom.base_denom,
-            &sender_id,
-            amount,
-        );
-        let token_contract = maybe_existed_token_contract.unwrap();
+            .expect("ERR_INVALID_TOKEN_DENOM");
         ext_ft_core::ext(token_contract.clone())
             .with_attached_deposit(NearToken::from_yoctonear(1))
             .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
             .with_unused_gas_weight(0)
-            .ft_transfer(sender_id, amount.into(), None);
+            .ft_transfer(
+                AccountId::from_str(req.sender.as_str()).unwrap(),
+                req.amount,
+                None,
+            );
     }
 }
 
@@ -347,13 +659,39 @@ impl NearIbcAccountAssertion for Contract {
 pub trait Viewer {
     /// Get all registered assets.
     fn get_registered_assets(&self) -> Vec<RegisteredAsset>;
-    /// Get all pending accounts.
+    /// Get all accounts which have pending transfer requests.
     fn get_pending_accounts(&self) -> Vec<AccountId>;
-    /// Get pending transfer request of the given account.
-    fn get_pending_transfer_request_of(
+    /// Get pending transfer requests of the given account.
+    fn get_pending_transfer_requests_of(&self, account_id: AccountId) -> Vec<Ics20TransferRequest>;
+    /// Get the pending transfer request with the given id.
+    fn get_pending_transfer_request(&self, request_id: U64) -> Option<Ics20TransferRequest>;
+    /// Whether the outgoing transfers are paused.
+    fn is_paused(&self) -> bool;
+    /// Get the tokens deposited by the given account for multi-denom transfers.
+    fn get_batch_deposits_of(&self, account_id: AccountId) -> Vec<Ics20Token>;
+    /// Get the pending multi-denom transfer request with the given id.
+    fn get_pending_multi_transfer_request(
+        &self,
+        request_id: U64,
+    ) -> Option<Ics20MultiTransferRequest>;
+    /// Get a page of the pending transfer requests, optionally filtered by the sender.
+    ///
+    /// At most `limit` requests (100 by default, 500 at most) are scanned from `from_index`
+    /// for a page, and `next_index` of the returned page is the `from_index` of the next page.
+    fn get_pending_transfer_requests_paged(
         &self,
-        account_id: AccountId,
-    ) -> Option<Ics20TransferRequest>;
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        account_id: Option<AccountId>,
+    ) -> Page<Ics20TransferRequest>;
+    /// Get a page of the pending multi-denom transfer requests, optionally filtered
+    /// by the sender.
+    fn get_pending_multi_transfer_requests_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        account_id: Option<AccountId>,
+    ) -> Page<Ics20MultiTransferRequest>;
 }
 
 #[near_bindgen]
@@ -370,20 +708,103 @@ impl Viewer for Contract {
     }
     ///
     fn get_pending_accounts(&self) -> Vec<AccountId> {
+        let mut accounts: Vec<AccountId> = self
+            .pending_transfer_requests
+            .values()
+            .map(|req| AccountId::from_str(req.sender.as_str()).unwrap())
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+    ///
+    fn get_pending_transfer_requests_of(&self, account_id: AccountId) -> Vec<Ics20TransferRequest> {
         self.pending_transfer_requests
-            .keys()
-            .map(|account_id| account_id.clone())
+            .values()
+            .filter(|req| req.sender.eq(account_id.as_str()))
+            .map(|req| req.clone())
             .collect()
     }
     ///
-    fn get_pending_transfer_request_of(
-        &self,
-        account_id: AccountId,
-    ) -> Option<Ics20TransferRequest> {
+    fn get_pending_transfer_request(&self, request_id: U64) -> Option<Ics20TransferRequest> {
         self.pending_transfer_requests
+            .get(&request_id.0)
+            .map(|req| req.clone())
+    }
+    ///
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+    ///
+    fn get_batch_deposits_of(&self, account_id: AccountId) -> Vec<Ics20Token> {
+        self.batch_deposits
             .get(&account_id)
+            .map_or_else(Vec::new, |deposits| {
+                deposits
+                    .iter()
+                    .map(|(asset_denom, amount)| Ics20Token {
+                        trace_path: asset_denom.trace_path.clone(),
+                        base_denom: asset_denom.base_denom.clone(),
+                        amount: U128(*amount),
+                    })
+                    .collect()
+            })
+    }
+    ///
+    fn get_pending_multi_transfer_request(
+        &self,
+        request_id: U64,
+    ) -> Option<Ics20MultiTransferRequest> {
+        self.pending_multi_transfer_requests
+            .get(&request_id.0)
             .map(|req| req.clone())
     }
+    ///
+    fn get_pending_transfer_requests_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        account_id: Option<AccountId>,
+    ) -> Page<Ics20TransferRequest> {
+        Page::scan(
+            self.pending_transfer_requests.values(),
+            self.pending_transfer_requests.len().into(),
+            from_index,
+            limit,
+            |req| {
+                if account_id
+                    .as_ref()
+                    .is_some_and(|account_id| !req.sender.eq(account_id.as_str()))
+                {
+                    return None;
+                }
+                Some(req.clone())
+            },
+        )
+    }
+    ///
+    fn get_pending_multi_transfer_requests_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        account_id: Option<AccountId>,
+    ) -> Page<Ics20MultiTransferRequest> {
+        Page::scan(
+            self.pending_multi_transfer_requests.values(),
+            self.pending_multi_transfer_requests.len().into(),
+            from_index,
+            limit,
+            |req| {
+                if account_id
+                    .as_ref()
+                    .is_some_and(|account_id| !req.sender.eq(account_id.as_str()))
+                {
+                    return None;
+                }
+                Some(req.clone())
+            },
+        )
+    }
 }
 
 /// Re-deploy the contract code.
@@ -4,6 +4,19 @@ pub trait StorageMigration {
     fn migrate_state() -> Self;
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldIcs20TransferRequest {
+    pub port_on_a: String,
+    pub chan_on_a: String,
+    pub token_trace_path: String,
+    pub token_denom: String,
+    pub amount: U128,
+    pub sender: String,
+    pub receiver: String,
+    pub timeout_seconds: Option<U64>,
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 #[borsh(crate = "near_sdk::borsh")]
 pub struct OldContract {
@@ -12,7 +25,9 @@ pub struct OldContract {
     /// The token accounts that this contract is allowed to send tokens to.
     token_contracts: UnorderedMap<AccountId, AssetDenom>,
     /// Accounting for the pending transfer requests.
-    pending_transfer_requests: UnorderedMap<AccountId, Ics20TransferRequest>,
+    pending_transfer_requests: UnorderedMap<AccountId, OldIcs20TransferRequest>,
+    /// The mapping from the asset denom to the token contract account id.
+    denom_to_token_contract_map: LookupMap<AssetDenom, AccountId>,
 }
 
 #[near_bindgen]
@@ -20,7 +35,7 @@ impl StorageMigration for Contract {
     #[init(ignore_state)]
     fn migrate_state() -> Self {
         // Deserialize the state using the old contract structure.
-        let old_contract: OldContract = env::state_read().expect("Old state doesn't exist");
+        let mut old_contract: OldContract = env::state_read().expect("Old state doesn't exist");
         //
         utils::assert_parent_account();
         //
@@ -28,15 +43,42 @@ impl StorageMigration for Contract {
         let mut new_contract = Contract {
             near_ibc_account: old_contract.near_ibc_account,
             token_contracts: old_contract.token_contracts,
-            pending_transfer_requests: old_contract.pending_transfer_requests,
-            denom_to_token_contract_map: LookupMap::new(StorageKey::DenomToTokenContractMap),
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
+            denom_to_token_contract_map: old_contract.denom_to_token_contract_map,
+            next_request_id: 1,
+            paused: false,
+            batch_deposits: LookupMap::new(StorageKey::BatchDeposits),
+            pending_multi_transfer_requests: UnorderedMap::new(
+                StorageKey::PendingMultiTransferRequests,
+            ),
+            wrapped_token_contracts: LookupMap::new(StorageKey::WrappedTokenContracts),
         };
         //
-        new_contract.token_contracts.into_iter().for_each(|(k, v)| {
-            new_contract
-                .denom_to_token_contract_map
-                .insert(v.clone(), k.clone());
-        });
+        // Move the pending transfer requests to the new collection keyed by request id.
+        old_contract
+            .pending_transfer_requests
+            .iter()
+            .for_each(|(_, req)| {
+                let request_id = new_contract.next_request_id;
+                new_contract.next_request_id += 1;
+                new_contract.pending_transfer_requests.insert(
+                    request_id,
+                    Ics20TransferRequest {
+                        request_id: U64(request_id),
+                        port_on_a: req.port_on_a.clone(),
+                        chan_on_a: req.chan_on_a.clone(),
+                        token_trace_path: req.token_trace_path.clone(),
+                        token_denom: req.token_denom.clone(),
+                        amount: req.amount,
+                        sender: req.sender.clone(),
+                        receiver: req.receiver.clone(),
+                        timeout_seconds: req.timeout_seconds,
+                        memo: None,
+                    },
+                );
+            });
+        old_contract.pending_transfer_requests.clear();
+        old_contract.pending_transfer_requests.flush();
         //
         new_contract
     }
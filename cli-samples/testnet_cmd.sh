@@ -36,7 +36,29 @@ export ACCOUNT_ID=v9.nearibc.testnet
 #
 #
 #
+# cp ~/.near-credentials/testnet/$ACCOUNT_ID.json ~/.near-credentials/testnet/nft-transfer.$ACCOUNT_ID.json
+# sed -i '' "s/$ACCOUNT_ID/nft-transfer.$ACCOUNT_ID/" ~/.near-credentials/testnet/nft-transfer.$ACCOUNT_ID.json
+# near create-account nft-transfer.$ACCOUNT_ID --masterAccount $ACCOUNT_ID --initialBalance 30 --publicKey "ed25519:2o5tiq68jntS8hunVjhsMfEcXnStecYf6TamQSg28ffz"
+#
+# cp ~/.near-credentials/testnet/nft-transfer.$ACCOUNT_ID.json ~/.near-credentials/testnet/nf.nft-transfer.$ACCOUNT_ID.json
+# sed -i '' "s/nft-transfer.$ACCOUNT_ID/nf.nft-transfer.$ACCOUNT_ID/" ~/.near-credentials/testnet/nf.nft-transfer.$ACCOUNT_ID.json
+# near create-account nf.nft-transfer.$ACCOUNT_ID --masterAccount nft-transfer.$ACCOUNT_ID --initialBalance 10 --publicKey "ed25519:2o5tiq68jntS8hunVjhsMfEcXnStecYf6TamQSg28ffz"
+# near deploy --accountId nf.nft-transfer.$ACCOUNT_ID --initFunction 'new' --initArgs '{}' --wasmFile res/nft_factory.wasm
+# WASM_BYTES='cat res/wrapped_nft.wasm | base64'
+# near call nf.nft-transfer.$ACCOUNT_ID store_wasm_of_nft_contract $(eval "$WASM_BYTES") --base64 --accountId nf.nft-transfer.$ACCOUNT_ID --gas 200000000000000
+#
+# cp ~/.near-credentials/testnet/nft-transfer.$ACCOUNT_ID.json ~/.near-credentials/testnet/ef.nft-transfer.$ACCOUNT_ID.json
+# sed -i '' "s/nft-transfer.$ACCOUNT_ID/ef.nft-transfer.$ACCOUNT_ID/" ~/.near-credentials/testnet/ef.nft-transfer.$ACCOUNT_ID.json
+# near create-account ef.nft-transfer.$ACCOUNT_ID --masterAccount nft-transfer.$ACCOUNT_ID --initialBalance 10 --publicKey "ed25519:2o5tiq68jntS8hunVjhsMfEcXnStecYf6TamQSg28ffz"
+# near deploy --accountId ef.nft-transfer.$ACCOUNT_ID --initFunction 'new' --initArgs '{}' --wasmFile res/escrow_factory.wasm
+# WASM_BYTES='cat res/nft_escrow.wasm | base64'
+# near call ef.nft-transfer.$ACCOUNT_ID store_wasm_of_channel_escrow $(eval "$WASM_BYTES") --base64 --accountId ef.nft-transfer.$ACCOUNT_ID --gas 200000000000000
+#
+#
+#
 # near deploy --accountId $ACCOUNT_ID --initFunction 'init' --initArgs '{"appchain_registry_account":"registry.test_oct.testnet"}' --wasmFile res/near_ibc.wasm
+# WASM_BYTES='cat res/interchain_account.wasm | base64'
+# near call $ACCOUNT_ID store_wasm_of_interchain_account $(eval "$WASM_BYTES") --base64 --accountId $ACCOUNT_ID --gas 200000000000000
 #
 #
 #
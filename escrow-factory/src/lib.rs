@@ -24,7 +24,11 @@ use near_sdk::{
     store::UnorderedSet,
     AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise,
 };
-use utils::{interfaces::EscrowFactory, ExtraDepositCost};
+use utils::{
+    interfaces::{EscrowFactory, RoleHoldersReceiver},
+    types::{Role, RoleHolders},
+    ExtraDepositCost,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -106,6 +110,14 @@ impl EscrowFactory for Contract {
     }
 }
 
+#[near_bindgen]
+impl RoleHoldersReceiver for Contract {
+    fn sync_role_holders(&mut self, role_holders: RoleHolders) {
+        utils::assert_ancestor_account();
+        utils::set_synced_role_holders(&role_holders);
+    }
+}
+
 /// View functions.
 pub trait Viewer {
     /// Get all channel ids on which the escrow contract is deployed.
@@ -125,11 +137,7 @@ impl Viewer for Contract {
 pub extern "C" fn store_wasm_of_channel_escrow() {
     env::setup_panic_hook();
     let _contract: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-    assert_eq!(
-        env::predecessor_account_id(),
-        env::current_account_id(),
-        "ERR_NOT_ALLOWED"
-    );
+    utils::assert_self_or_role(Role::Operator);
     let input = env::input().expect("ERR_NO_INPUT");
     let sha256_hash = env::sha256(&input);
 
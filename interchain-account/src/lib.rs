@@ -0,0 +1,121 @@
+#![deny(
+    warnings,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_import_braces,
+    unused_qualifications,
+    rust_2018_idioms
+)]
+
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::Base64VecU8,
+    near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise, PromiseResult,
+};
+use utils::{
+    interfaces::InterchainAccount,
+    types::{InterchainAccountAction, InterchainAccountActionResult},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The account id of IBC/TAO implementation.
+    near_ibc_account: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(near_ibc_account: AccountId) -> Self {
+        utils::assert_parent_account();
+        Self { near_ibc_account }
+    }
+    ///
+    pub fn version(&self) -> String {
+        VERSION.to_string()
+    }
+    ///
+    pub fn near_ibc_account(&self) -> AccountId {
+        self.near_ibc_account.clone()
+    }
+}
+
+/// The callback interface of `execute`.
+#[ext_contract(ext_execute_callback)]
+pub trait ExecuteCallback {
+    /// Collect the results of the given number of actions.
+    fn on_actions_executed(&mut self, count: u32) -> Vec<InterchainAccountActionResult>;
+}
+
+#[near_bindgen]
+impl InterchainAccount for Contract {
+    //
+    fn execute(&mut self, actions: Vec<InterchainAccountAction>) -> Promise {
+        utils::assert_parent_account();
+        assert!(!actions.is_empty(), "ERR_EMPTY_ACTIONS");
+        let count = actions.len() as u32;
+        let promise = actions
+            .into_iter()
+            .map(|action| match action {
+                InterchainAccountAction::FunctionCall {
+                    receiver_id,
+                    method_name,
+                    args,
+                    deposit,
+                    gas,
+                } => Promise::new(receiver_id).function_call(
+                    method_name,
+                    args.0,
+                    NearToken::from_yoctonear(deposit.0),
+                    Gas::from_gas(gas.0),
+                ),
+                InterchainAccountAction::Transfer {
+                    receiver_id,
+                    amount,
+                } => Promise::new(receiver_id).transfer(NearToken::from_yoctonear(amount.0)),
+            })
+            .reduce(|joint, promise| joint.and(promise))
+            .unwrap();
+        promise.then(
+            ext_execute_callback::ext(env::current_account_id())
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                .with_unused_gas_weight(0)
+                .on_actions_executed(count),
+        )
+    }
+}
+
+#[near_bindgen]
+impl ExecuteCallback for Contract {
+    #[private]
+    fn on_actions_executed(&mut self, count: u32) -> Vec<InterchainAccountActionResult> {
+        assert_eq!(
+            env::promise_results_count(),
+            count as u64,
+            "ERR_INVALID_PROMISE_RESULTS_COUNT"
+        );
+        (0..env::promise_results_count())
+            .map(|index| match env::promise_result(index) {
+                PromiseResult::Successful(bytes) => InterchainAccountActionResult {
+                    success: true,
+                    result: Some(Base64VecU8(bytes)),
+                },
+                PromiseResult::Failed => InterchainAccountActionResult {
+                    success: false,
+                    result: None,
+                },
+            })
+            .collect()
+    }
+}
@@ -111,6 +111,66 @@ where
             self.pop_front();
         }
     }
+    /// Insert an element to the queue, keeping the keys in ascending order.
+    ///
+    /// A key greater than the latest key (the usual case) is appended without any lookup.
+    /// The value is replaced if the key already exists. Inserting a key which is less than
+    /// the latest key will shift the index of all the greater keys.
+    /// If the queue exceeds max length, the oldest (first) element will be removed.
+    pub fn insert(&mut self, element: (K, V)) {
+        if self
+            .last_key()
+            .map_or(true, |last_key| &element.0 > last_key)
+        {
+            self.push_back(element);
+            return;
+        }
+        if self.value_map.contains_key(&element.0) {
+            self.value_map.insert(element.0, element.1);
+            return;
+        }
+        let insert_index = match self.get_next_key_by_key(&element.0).cloned() {
+            Some(next_key) => self.get_index_of_key(&next_key).unwrap(),
+            None => {
+                self.push_back(element);
+                return;
+            }
+        };
+        if insert_index == self.start_index && self.start_index > 1 {
+            self.start_index -= 1;
+            self.index_map.insert(self.start_index, element.0.clone());
+        } else {
+            for index in (insert_index..self.end_index + 1).rev() {
+                let key = self.index_map.get(&index).unwrap().clone();
+                self.index_map.insert(index + 1, key);
+            }
+            self.index_map.insert(insert_index, element.0.clone());
+            self.end_index += 1;
+        }
+        self.value_map.insert(element.0, element.1);
+        if self.end_index - self.start_index + 1 > self.max_length {
+            self.pop_front();
+        }
+    }
+    /// Remove an element from the queue by key, and shift the index of all the greater keys.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let remove_index = self.get_index_of_key(key)?;
+        if remove_index == self.start_index {
+            let value = self.pop_front().and_then(|(_, value)| value);
+            if self.start_index > self.end_index {
+                self.start_index = 0;
+                self.end_index = 0;
+            }
+            return value;
+        }
+        for index in remove_index..self.end_index {
+            let key = self.index_map.get(&(index + 1)).unwrap().clone();
+            self.index_map.insert(index, key);
+        }
+        self.index_map.remove(&self.end_index);
+        self.end_index -= 1;
+        self.value_map.remove(key)
+    }
     /// Get value by key.
     pub fn get_value_by_key_mut(&mut self, key: &K) -> Option<&mut V> {
         self.value_map.get_mut(key)
@@ -261,3 +321,125 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+    use ibc::core::host::types::identifiers::ClientType;
+
+    fn queue_of(keys: &[u64], max_length: u64) -> IndexedAscendingLookupQueue<u64, u64> {
+        let client_id = ClientId::new(ClientType::from_str("07-tendermint").unwrap(), 0).unwrap();
+        let mut queue = IndexedAscendingLookupQueue::new(
+            StorageKey::ClientConsensusStateHeightsIndexMap {
+                client_id: client_id.clone(),
+            },
+            StorageKey::ClientConsensusStateHeightsValueMap { client_id },
+            max_length,
+        );
+        keys.iter()
+            .for_each(|key| queue.push_back((*key, *key * 10)));
+        queue
+    }
+
+    fn keys_of(queue: &IndexedAscendingLookupQueue<u64, u64>) -> Vec<u64> {
+        queue.keys().into_iter().flatten().cloned().collect()
+    }
+
+    #[test]
+    fn test_insert_before_start() {
+        let mut queue = queue_of(&[2, 3, 4], u64::MAX);
+        queue.insert((1, 10));
+        assert_eq!(keys_of(&queue), vec![1, 2, 3, 4]);
+        assert_eq!((queue.start_index(), queue.end_index()), (1, 4));
+        // The index before the start index is reused after the first element is removed.
+        let mut queue = queue_of(&[1, 3, 4], u64::MAX);
+        queue.remove(&1);
+        queue.insert((2, 20));
+        assert_eq!(keys_of(&queue), vec![2, 3, 4]);
+        assert_eq!((queue.start_index(), queue.end_index()), (1, 3));
+        assert_eq!(queue.get_value_by_key(&2), Some(&20));
+    }
+
+    #[test]
+    fn test_insert_in_middle() {
+        let mut queue = queue_of(&[1, 3, 5], u64::MAX);
+        queue.insert((4, 40));
+        queue.insert((2, 20));
+        assert_eq!(keys_of(&queue), vec![1, 2, 3, 4, 5]);
+        assert_eq!(queue.get_index_of_key(&4), Some(4));
+        assert_eq!(queue.get_value_by_key(&4), Some(&40));
+        // The value of an existing key is replaced without changing the index.
+        queue.insert((3, 33));
+        assert_eq!(keys_of(&queue), vec![1, 2, 3, 4, 5]);
+        assert_eq!(queue.get_value_by_key(&3), Some(&33));
+    }
+
+    #[test]
+    fn test_insert_past_end() {
+        let mut queue = queue_of(&[], u64::MAX);
+        queue.insert((2, 20));
+        queue.insert((5, 50));
+        assert_eq!(keys_of(&queue), vec![2, 5]);
+        assert_eq!((queue.start_index(), queue.end_index()), (1, 2));
+        assert_eq!(queue.last_key(), Some(&5));
+    }
+
+    #[test]
+    fn test_insert_after_last() {
+        let mut queue = queue_of(&[1, 3], u64::MAX);
+        queue.insert((4, 40));
+        queue.insert((7, 70));
+        assert_eq!(keys_of(&queue), vec![1, 3, 4, 7]);
+        assert_eq!((queue.start_index(), queue.end_index()), (1, 4));
+        assert_eq!(queue.get_value_by_key(&7), Some(&70));
+        // The last key is replaced in place.
+        queue.insert((7, 77));
+        assert_eq!(keys_of(&queue), vec![1, 3, 4, 7]);
+        assert_eq!(queue.get_value_by_key(&7), Some(&77));
+    }
+
+    #[test]
+    fn test_insert_at_max_length() {
+        let mut queue = queue_of(&[1, 3, 5], 3);
+        // The first element is removed as the queue exceeds max length.
+        queue.insert((2, 20));
+        assert_eq!(keys_of(&queue), vec![2, 3, 5]);
+        assert_eq!(queue.get_value_by_key(&1), None);
+        queue.insert((6, 60));
+        assert_eq!(keys_of(&queue), vec![3, 5, 6]);
+        // An element less than the first one is removed right after it is inserted.
+        queue.insert((0, 0));
+        assert_eq!(keys_of(&queue), vec![3, 5, 6]);
+        assert_eq!(queue.get_value_by_key(&0), None);
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[test]
+    fn test_remove_only_element() {
+        let mut queue = queue_of(&[1], u64::MAX);
+        assert_eq!(queue.remove(&1), Some(10));
+        assert!(queue.is_empty());
+        assert_eq!((queue.start_index(), queue.end_index()), (0, 0));
+        queue.insert((2, 20));
+        assert_eq!(keys_of(&queue), vec![2]);
+        assert_eq!((queue.start_index(), queue.end_index()), (1, 1));
+    }
+
+    #[test]
+    fn test_remove_first_middle_and_last() {
+        let mut queue = queue_of(&[1, 2, 3, 4, 5], u64::MAX);
+        assert_eq!(queue.remove(&1), Some(10));
+        assert_eq!(keys_of(&queue), vec![2, 3, 4, 5]);
+        assert_eq!(queue.first_key(), Some(&2));
+        assert_eq!(queue.remove(&3), Some(30));
+        assert_eq!(keys_of(&queue), vec![2, 4, 5]);
+        assert_eq!(queue.get_index_of_key(&4), Some(3));
+        assert_eq!(queue.remove(&5), Some(50));
+        assert_eq!(keys_of(&queue), vec![2, 4]);
+        assert_eq!(queue.last_key(), Some(&4));
+        assert_eq!(queue.get_value_by_key(&5), None);
+        assert_eq!(queue.remove(&3), None);
+        assert_eq!(queue.len(), 2);
+    }
+}
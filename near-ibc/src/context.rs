@@ -1,5 +1,16 @@
 use crate::{
-    collections::IndexedAscendingLookupQueue, prelude::*, types::ProcessingResult, StorageKey,
+    collections::IndexedAscendingLookupQueue,
+    ibc_impl::{
+        applications::transfer::forward::InFlightForward,
+        core::{
+            consensus_state_retention::{ConsensusStateHeights, ConsensusStateRetention},
+            packet_events::PacketEventKey,
+        },
+    },
+    prelude::*,
+    storage_deposit::StorageDeposit,
+    types::ProcessingResult,
+    StorageKey,
 };
 use core::fmt::{Debug, Formatter};
 use ibc::core::{
@@ -13,11 +24,11 @@ use ibc::core::{
         },
     },
 };
-use itertools::Itertools;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     env, log,
     store::{LookupMap, UnorderedMap, UnorderedSet},
+    AccountId,
 };
 use serde::{Deserialize, Serialize};
 
@@ -32,9 +43,9 @@ pub struct NearIbcStore {
     pub client_counter: u64,
     pub client_processed_times: LookupMap<ClientId, UnorderedMap<Height, NearTimeStamp>>,
     pub client_processed_heights: LookupMap<ClientId, UnorderedMap<Height, HostHeight>>,
-    /// This collection contains the heights corresponding to all consensus states of
-    /// all clients stored in the contract.
-    pub client_consensus_state_height_sets: LookupMap<ClientId, UnorderedSet<Height>>,
+    /// This collection contains the heights (in ascending order) and timestamps
+    /// corresponding to all consensus states of all clients stored in the contract.
+    pub client_consensus_state_heights: LookupMap<ClientId, ConsensusStateHeights>,
     /// The connection ids of the connections.
     pub connection_id_set: UnorderedSet<ConnectionId>,
     pub connection_counter: u64,
@@ -53,6 +64,34 @@ pub struct NearIbcStore {
     /// The max height difference between the latest height and the oldest height
     /// in IbcEventsHistory.
     pub max_ibc_events_height_difference: u64,
+    /// The forwarded packets whose original packets are waiting for acknowledgement,
+    /// keyed by the port id, channel id and sequence of the forwarded packets.
+    pub in_flight_forwards: LookupMap<(PortId, ChannelId, Sequence), InFlightForward>,
+    /// The encoded consensus states of NEAR protocol at the heights of IBC events.
+    pub host_consensus_states: IndexedAscendingLookupQueue<Height, Vec<u8>>,
+    /// The heights of the packet events in the IBC events history, keyed by the port id,
+    /// channel id (on the sending chain), sequence of the packets and the event type.
+    pub packet_event_heights: LookupMap<PacketEventKey, Height>,
+    /// The block height since which the packet events are indexed.
+    pub packet_events_indexed_since: u64,
+    /// The number of the latest packet receipts and acknowledgements kept by pruning,
+    /// for the channels whose packet states can be pruned.
+    pub packet_state_retentions: LookupMap<(PortId, ChannelId), u64>,
+    /// The sequences below which the packet receipts and acknowledgements have been pruned.
+    pub pruned_packet_sequences: LookupMap<(PortId, ChannelId), Sequence>,
+    /// The storage deposits of the relayers.
+    pub storage_deposits: UnorderedMap<AccountId, StorageDeposit>,
+    /// The retentions of the consensus states of the clients which are not the default.
+    pub consensus_state_retentions: LookupMap<ClientId, ConsensusStateRetention>,
+    /// The clients whose consensus state heights are not yet moved from the unordered sets
+    /// of the previous version, refer to `migrate_consensus_state_heights`.
+    pub unmigrated_consensus_state_height_clients: Vec<ClientId>,
+    /// The account paying for the storage written in the current function call, if any.
+    #[borsh(skip)]
+    pub storage_payer: Option<AccountId>,
+    /// The storage (in bytes) released and credited to the payers in the current function call.
+    #[borsh(skip)]
+    pub released_storage_bytes: u64,
 }
 
 pub trait NearIbcStoreHost {
@@ -84,9 +123,7 @@ impl NearIbcStore {
             client_counter: 0,
             client_processed_times: LookupMap::new(StorageKey::ClientProcessedTimes),
             client_processed_heights: LookupMap::new(StorageKey::ClientProcessedHeights),
-            client_consensus_state_height_sets: LookupMap::new(
-                StorageKey::ClientConsensusStateHeightSets,
-            ),
+            client_consensus_state_heights: LookupMap::new(StorageKey::ClientConsensusStateHeights),
             connection_id_set: UnorderedSet::new(StorageKey::ConnectionIdSet),
             connection_counter: 0,
             port_channel_id_set: UnorderedSet::new(StorageKey::PortChannelIdSet),
@@ -104,6 +141,21 @@ impl NearIbcStore {
                 u64::MAX,
             ),
             max_ibc_events_height_difference: u64::MAX,
+            in_flight_forwards: LookupMap::new(StorageKey::InFlightForwards),
+            host_consensus_states: IndexedAscendingLookupQueue::new(
+                StorageKey::HostConsensusStatesIndexMap,
+                StorageKey::HostConsensusStatesValueMap,
+                u64::MAX,
+            ),
+            packet_event_heights: LookupMap::new(StorageKey::PacketEventHeights),
+            packet_events_indexed_since: 0,
+            packet_state_retentions: LookupMap::new(StorageKey::PacketStateRetentions),
+            pruned_packet_sequences: LookupMap::new(StorageKey::PrunedPacketSequences),
+            storage_deposits: UnorderedMap::new(StorageKey::StorageDeposits),
+            consensus_state_retentions: LookupMap::new(StorageKey::ConsensusStateRetentions),
+            unmigrated_consensus_state_height_clients: vec![],
+            storage_payer: None,
+            released_storage_bytes: 0,
         }
     }
     ///
@@ -125,23 +177,26 @@ impl NearIbcStore {
                 .to_string()
                 .into_bytes(),
         );
-        self.client_consensus_state_height_sets
-            .get(client_id)
-            .map(|heights| {
-                heights.iter().for_each(|height| {
-                    env::storage_remove(
-                        &ClientConsensusStatePath::new(
-                            client_id.clone(),
-                            height.revision_number(),
-                            height.revision_height(),
-                        )
-                        .to_string()
-                        .into_bytes(),
-                    );
-                });
-            });
-        self.client_consensus_state_height_sets.remove(client_id);
-        self.client_consensus_state_height_sets.flush();
+        let heights = self.consensus_state_heights_of(client_id);
+        heights.iter().for_each(|height| {
+            self.remove_with_storage_payer(
+                &ClientConsensusStatePath::new(
+                    client_id.clone(),
+                    height.revision_number(),
+                    height.revision_height(),
+                )
+                .to_string()
+                .into_bytes(),
+            );
+        });
+        if let Some(queue) = self.client_consensus_state_heights.get_mut(client_id) {
+            queue.clear(None);
+            queue.flush();
+        }
+        self.client_consensus_state_heights.remove(client_id);
+        self.client_consensus_state_heights.flush();
+        self.consensus_state_retentions.remove(client_id);
+        self.consensus_state_retentions.flush();
         env::storage_remove(&ClientStatePath::new(client_id).to_string().into_bytes());
         self.client_id_set.remove(client_id);
         self.client_id_set.flush();
@@ -167,28 +222,28 @@ impl NearIbcStore {
                     );
                 })
             });
-        self.packet_receipt_sequence_sets
+        let receipt_sequences: Vec<Sequence> = self
+            .packet_receipt_sequence_sets
             .get(port_channel_id)
-            .map(|set| {
-                set.iter().for_each(|sequence| {
-                    env::storage_remove(
-                        &ReceiptPath::new(&port_channel_id.0, &port_channel_id.1, *sequence)
-                            .to_string()
-                            .into_bytes(),
-                    );
-                });
-            });
-        self.packet_acknowledgement_sequence_sets
+            .map_or(vec![], |set| set.iter().cloned().collect());
+        receipt_sequences.iter().for_each(|sequence| {
+            self.remove_with_storage_payer(
+                &ReceiptPath::new(&port_channel_id.0, &port_channel_id.1, *sequence)
+                    .to_string()
+                    .into_bytes(),
+            );
+        });
+        let ack_sequences: Vec<Sequence> = self
+            .packet_acknowledgement_sequence_sets
             .get(port_channel_id)
-            .map(|set| {
-                set.iter().for_each(|sequence| {
-                    env::storage_remove(
-                        &AckPath::new(&port_channel_id.0, &port_channel_id.1, *sequence)
-                            .to_string()
-                            .into_bytes(),
-                    );
-                });
-            });
+            .map_or(vec![], |set| set.iter().cloned().collect());
+        ack_sequences.iter().for_each(|sequence| {
+            self.remove_with_storage_payer(
+                &AckPath::new(&port_channel_id.0, &port_channel_id.1, *sequence)
+                    .to_string()
+                    .into_bytes(),
+            );
+        });
         env::storage_remove(
             &SeqSendPath(port_channel_id.0.clone(), port_channel_id.1.clone())
                 .to_string()
@@ -204,6 +259,10 @@ impl NearIbcStore {
                 .to_string()
                 .into_bytes(),
         );
+        self.packet_state_retentions.remove(port_channel_id);
+        self.packet_state_retentions.flush();
+        self.pruned_packet_sequences.remove(port_channel_id);
+        self.pruned_packet_sequences.flush();
         self.port_channel_id_set.remove(port_channel_id);
         self.port_channel_id_set.flush();
         log!(
@@ -218,46 +277,17 @@ impl NearIbcStore {
         client_id: &ClientId,
         lt_height: Option<&Height>,
     ) -> ProcessingResult {
-        let max_gas = env::prepaid_gas().saturating_mul(2).saturating_div(5);
-        let height_set = self
-            .client_consensus_state_height_sets
-            .get_mut(client_id)
-            .unwrap();
-        let height_iter: Box<dyn Iterator<Item = &Height>> = if lt_height.is_some() {
-            Box::new(height_set.iter().sorted())
-        } else {
-            Box::new(height_set.iter())
-        };
-
-        let mut result = ProcessingResult::Ok;
-        let mut need_remove_heights = vec![];
-        for height in height_iter {
-            if lt_height.is_some() && height.ge(lt_height.unwrap()) {
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        for height in self.consensus_state_heights_of(client_id) {
+            if lt_height.is_some_and(|lt_height| height.ge(lt_height)) {
                 break;
             }
-            env::storage_remove(
-                &ClientConsensusStatePath::new(
-                    client_id.clone(),
-                    height.revision_number(),
-                    height.revision_height(),
-                )
-                .to_string()
-                .into_bytes(),
-            );
-            need_remove_heights.push(height.clone());
-
+            self.remove_consensus_state_at(client_id, &height);
             if env::used_gas() >= max_gas {
-                result = ProcessingResult::NeedMoreGas;
-                break;
+                return ProcessingResult::NeedMoreGas;
             }
         }
-
-        // It is why max_gas is 2/5 prepaid_gas
-        for height in need_remove_heights {
-            height_set.remove(&height);
-        }
-        self.client_consensus_state_height_sets.flush();
-        result
+        ProcessingResult::Ok
     }
     ///
     pub fn clear_counters(&mut self) {
@@ -266,24 +296,24 @@ impl NearIbcStore {
         self.channel_counter = 0;
     }
     ///
-    pub fn clear_ibc_events_history(
-        &mut self,
-        less_than_height: Option<&Height>,
-    ) -> ProcessingResult {
-        self.ibc_events_history.clear(less_than_height)
-    }
-    ///
     pub fn flush(&mut self) {
         self.client_id_set.flush();
         self.client_processed_heights.flush();
         self.client_processed_times.flush();
-        self.client_consensus_state_height_sets.flush();
+        self.client_consensus_state_heights.flush();
         self.connection_id_set.flush();
         self.port_channel_id_set.flush();
         self.packet_commitment_sequence_sets.flush();
         self.packet_receipt_sequence_sets.flush();
         self.packet_acknowledgement_sequence_sets.flush();
         self.ibc_events_history.flush();
+        self.in_flight_forwards.flush();
+        self.host_consensus_states.flush();
+        self.packet_event_heights.flush();
+        self.packet_state_retentions.flush();
+        self.pruned_packet_sequences.flush();
+        self.storage_deposits.flush();
+        self.consensus_state_retentions.flush();
     }
 }
 
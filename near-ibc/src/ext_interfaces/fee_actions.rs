@@ -0,0 +1,151 @@
+use crate::{
+    ibc_impl::applications::fee::{self, EscrowedPacketFee, FeeAsset},
+    *,
+};
+use ibc::core::host::{
+    types::{identifiers::Sequence, path::CommitmentPath},
+    ValidationContext,
+};
+use near_sdk::json_types::{U128, U64};
+use utils::types::PacketFee;
+
+/// The actions of ICS-29 (fee middleware) for relayers and users.
+pub trait FeeActions {
+    /// Register the payee in NEAR protocol of the caller (as a relayer) for the given channel.
+    ///
+    /// The fees for the acknowledgement and timeout relayed by the caller will be paid
+    /// to the payee, rather than the caller.
+    fn register_payee(&mut self, channel_id: ChannelId, payee: AccountId);
+    /// Register the payee on the counterparty chain of the caller (as a relayer)
+    /// for the given channel.
+    ///
+    /// The receiving fees of the packets received by the caller will be paid to
+    /// the payee on the counterparty chain.
+    fn register_counterparty_payee(&mut self, channel_id: ChannelId, counterparty_payee: String);
+    /// Escrow a fee (paid in the attached NEAR) for a packet already sent
+    /// in a fee enabled channel.
+    fn pay_packet_fee_async(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: U64,
+        fee: PacketFee,
+    );
+    /// Refund the fees escrowed for the given packets of a channel whose fees are disabled
+    /// (by closing the channel or upgrading it to a version without ICS-29), to the accounts
+    /// which paid them. Returns the sequences of the packets whose fees are refunded.
+    ///
+    /// Anyone can call this function.
+    fn refund_packet_fees_of_disabled_channel(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequences: Vec<U64>,
+    ) -> Vec<U64>;
+    /// Claim the token fees which failed to be paid to the caller, e.g. as the caller was
+    /// not registered in the token contract. Returns the claimed amount.
+    ///
+    /// The caller should attach 1 yocto NEAR.
+    fn claim_unpaid_token_fee(&mut self, asset: FeeAsset) -> U128;
+}
+
+#[near_bindgen]
+impl FeeActions for NearIbcContract {
+    //
+    #[payable]
+    fn register_payee(&mut self, channel_id: ChannelId, payee: AccountId) {
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        self.module_holder.transfer_module.register_payee(
+            channel_id,
+            env::predecessor_account_id(),
+            payee,
+        );
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn register_counterparty_payee(&mut self, channel_id: ChannelId, counterparty_payee: String) {
+        assert!(
+            !counterparty_payee.trim().is_empty(),
+            "ERR_EMPTY_COUNTERPARTY_PAYEE"
+        );
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        self.module_holder
+            .transfer_module
+            .register_counterparty_payee(
+                channel_id,
+                env::predecessor_account_id(),
+                counterparty_payee,
+            );
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn pay_packet_fee_async(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: U64,
+        fee: PacketFee,
+    ) {
+        assert!(fee.total() > 0, "ERR_EMPTY_FEE");
+        assert!(
+            env::attached_deposit().as_yoctonear() >= fee.total(),
+            "ERR_NOT_ENOUGH_DEPOSIT, must not less than {} yocto",
+            fee.total()
+        );
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store
+            .get_packet_commitment(&CommitmentPath::new(
+                &port_id,
+                &channel_id,
+                Sequence::from(sequence.0),
+            ))
+            .expect("ERR_PACKET_COMMITMENT_NOT_FOUND");
+        ExtraDepositCost::add(fee.total());
+        self.module_holder.transfer_module.escrow_packet_fee(
+            (port_id, channel_id, sequence.0),
+            EscrowedPacketFee {
+                fee,
+                asset: FeeAsset::Near,
+                refund_account: env::predecessor_account_id(),
+            },
+        );
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn refund_packet_fees_of_disabled_channel(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequences: Vec<U64>,
+    ) -> Vec<U64> {
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let sequences = sequences
+            .iter()
+            .map(|sequence| sequence.0)
+            .collect::<Vec<u64>>();
+        let refunded_sequences = self
+            .module_holder
+            .transfer_module
+            .refund_fees_of_disabled_channel(&port_id, &channel_id, &sequences);
+        utils::refund_deposit(used_bytes);
+        refunded_sequences.into_iter().map(U64).collect()
+    }
+    //
+    #[payable]
+    fn claim_unpaid_token_fee(&mut self, asset: FeeAsset) -> U128 {
+        near_sdk::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let amount = fee::take_unpaid_token_fee(&account_id, &asset);
+        assert!(amount > 0, "ERR_NO_UNPAID_TOKEN_FEE");
+        fee::pay_fee(&asset, &account_id, amount);
+        U128(amount)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    ibc_impl::applications::fee::{self, FeeAsset, FeeCallback},
+    *,
+};
+use near_sdk::{json_types::U128, PromiseResult};
+
+#[near_bindgen]
+impl FeeCallback for NearIbcContract {
+    #[private]
+    fn on_token_fee_paid(&mut self, asset: FeeAsset, receiver_id: AccountId, amount: U128) {
+        let transferred_amount = match env::promise_result(0) {
+            // The channel escrows of old versions return nothing for the transfers,
+            // whose results are unknown, so they are treated as transferred.
+            PromiseResult::Successful(bytes) if bytes.is_empty() => amount.0,
+            PromiseResult::Successful(bytes) => {
+                serde_json::from_slice::<U128>(&bytes).map_or(0, |amount| amount.0)
+            }
+            PromiseResult::Failed => 0,
+        };
+        if transferred_amount >= amount.0 {
+            return;
+        }
+        let unpaid_amount = amount.0 - transferred_amount;
+        fee::credit_unpaid_token_fee(&receiver_id, &asset, unpaid_amount);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"UNPAID_TOKEN_FEE_CREDITED","receiver_id":"{}","asset":{},"amount":"{}"}}"#,
+            receiver_id,
+            serde_json::to_string(&asset).unwrap(),
+            unpaid_amount
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{
+    ibc_impl::applications::transfer::{async_ack::error_ack, hooks::IbcHooksCallback},
+    *,
+};
+use ibc::{
+    apps::transfer::types::ack_success_b64,
+    core::channel::types::{acknowledgement::AcknowledgementStatus, packet::Packet},
+};
+use ibc_proto::{ibc::core::channel::v1::Packet as RawPacket, Protobuf};
+use near_sdk::{json_types::U128, PromiseResult};
+
+#[near_bindgen]
+impl IbcHooksCallback for NearIbcContract {
+    #[private]
+    fn on_ibc_hook_executed(&mut self, packet: Base64VecU8) {
+        let packet: Packet =
+            Protobuf::<RawPacket>::decode_vec(&packet.0).expect("ERR_INVALID_PACKET");
+        let ack = match env::promise_result(0) {
+            PromiseResult::Successful(bytes) => match serde_json::from_slice::<U128>(&bytes) {
+                Ok(used_amount) if used_amount.0 > 0 => {
+                    AcknowledgementStatus::success(ack_success_b64()).into()
+                }
+                Ok(_) => error_ack("ERR_IBC_HOOK_FAILED: no tokens were used".to_string()),
+                Err(e) => error_ack(format!("ERR_IBC_HOOK_FAILED: {}", e)),
+            },
+            PromiseResult::Failed => {
+                error_ack("ERR_IBC_HOOK_FAILED: the promise failed".to_string())
+            }
+        };
+        log!(
+            "IBC hook of packet {}/{}/{} executed, acknowledgement: {}",
+            packet.port_id_on_b,
+            packet.chan_id_on_b,
+            packet.seq_on_a,
+            String::from_utf8(ack.as_bytes().to_vec()).unwrap_or_default()
+        );
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.write_async_acknowledgement(&packet, ack);
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+    }
+}
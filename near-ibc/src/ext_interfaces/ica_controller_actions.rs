@@ -0,0 +1,140 @@
+use crate::{
+    ibc_impl::applications::ica::{
+        controller::IcaControllerModule, IcaMetadata, IcaPacketData, HOST_PORT_ID_STR,
+    },
+    *,
+};
+use ibc::core::{
+    channel::{
+        handler::send_packet,
+        types::{
+            channel::Order,
+            msgs::{ChannelMsg, MsgChannelOpenInit},
+            packet::Packet,
+            Version,
+        },
+    },
+    host::{
+        types::path::{ChannelEndPath, SeqSendPath},
+        ValidationContext,
+    },
+};
+use near_sdk::json_types::U64;
+
+pub trait IcaControllerActions {
+    /// Register an interchain account for the caller on the chain of the given connection.
+    ///
+    /// The interchain account is available after the ICA channel is opened,
+    /// which will be completed by relayers.
+    fn register_interchain_account(&mut self, connection_id: ConnectionId);
+    /// Send a `MsgSendTx` packet to execute the given messages by the interchain account
+    /// of the caller on the chain of the given connection.
+    ///
+    /// Returns the sequence of the packet.
+    fn send_interchain_tx(
+        &mut self,
+        connection_id: ConnectionId,
+        messages: Vec<Any>,
+        memo: Option<String>,
+        timeout_seconds: Option<U64>,
+    ) -> U64;
+}
+
+#[near_bindgen]
+impl IcaControllerActions for NearIbcContract {
+    //
+    #[payable]
+    fn register_interchain_account(&mut self, connection_id: ConnectionId) {
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let owner = env::predecessor_account_id();
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let connection_end = near_ibc_store
+            .connection_end(&connection_id)
+            .expect("ERR_CONNECTION_NOT_FOUND");
+        let host_connection_id = connection_end
+            .counterparty()
+            .connection_id()
+            .expect("ERR_COUNTERPARTY_CONNECTION_NOT_OPENED")
+            .clone();
+        let metadata = IcaMetadata::new(&connection_id, &host_connection_id, String::new());
+        let msg = MsgChannelOpenInit {
+            port_id_on_a: IcaControllerModule::port_id_of(&owner),
+            connection_hops_on_a: vec![connection_id],
+            port_id_on_b: PortId::from_str(HOST_PORT_ID_STR).unwrap(),
+            ordering: Order::Ordered,
+            signer: Signer::from(owner.to_string()),
+            version_proposal: Version::new(serde_json::to_string(&metadata).unwrap()),
+        };
+        if let Err(e) = ibc::core::handler::entrypoint::dispatch(
+            &mut near_ibc_store,
+            self,
+            MsgEnvelope::Channel(ChannelMsg::OpenInit(msg)),
+        ) {
+            panic!("ERR_REGISTER_INTERCHAIN_ACCOUNT: {:?}", e);
+        }
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        // Refund unused deposit.
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn send_interchain_tx(
+        &mut self,
+        connection_id: ConnectionId,
+        messages: Vec<Any>,
+        memo: Option<String>,
+        timeout_seconds: Option<U64>,
+    ) -> U64 {
+        utils::assert_valid_memo(&memo);
+        assert!(!messages.is_empty(), "ERR_EMPTY_MESSAGES");
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let owner = env::predecessor_account_id();
+        let account = self
+            .module_holder
+            .ica_controller_module
+            .get_interchain_account(&owner, &connection_id)
+            .expect("ERR_INTERCHAIN_ACCOUNT_NOT_REGISTERED");
+        assert!(
+            account.address.is_some(),
+            "ERR_INTERCHAIN_ACCOUNT_NOT_READY"
+        );
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let channel_end = near_ibc_store
+            .channel_end(&ChannelEndPath::new(&account.port_id, &account.channel_id))
+            .expect("ERR_ICA_CHANNEL_NOT_FOUND");
+        assert!(channel_end.is_open(), "ERR_ICA_CHANNEL_NOT_OPENED");
+        let sequence = near_ibc_store
+            .get_next_sequence_send(&SeqSendPath::new(&account.port_id, &account.channel_id))
+            .expect("ERR_NEXT_SEQUENCE_SEND_NOT_FOUND");
+        let packet_data = IcaPacketData::execute_tx(messages, memo.unwrap_or_default());
+        let timeout_seconds = timeout_seconds.map_or(DEFAULT_TIMEOUT_SECONDS, |value| value.0);
+        let packet = Packet {
+            seq_on_a: sequence,
+            port_id_on_a: account.port_id,
+            chan_id_on_a: account.channel_id,
+            port_id_on_b: channel_end.counterparty().port_id().clone(),
+            chan_id_on_b: channel_end
+                .counterparty()
+                .channel_id()
+                .expect("ERR_COUNTERPARTY_CHANNEL_NOT_FOUND")
+                .clone(),
+            data: serde_json::to_vec(&packet_data).unwrap(),
+            timeout_height_on_b: TimeoutHeight::Never {},
+            timeout_timestamp_on_b: Timestamp::from_nanoseconds(
+                env::block_timestamp() + timeout_seconds * 1000000000,
+            )
+            .unwrap(),
+        };
+        if let Err(e) = send_packet(&mut near_ibc_store, packet) {
+            panic!("ERR_SEND_INTERCHAIN_TX: {:?}", e);
+        }
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        // Refund unused deposit.
+        utils::refund_deposit(used_bytes);
+        U64(u64::from(sequence))
+    }
+}
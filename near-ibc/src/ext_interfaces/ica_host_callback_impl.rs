@@ -0,0 +1,49 @@
+use crate::{
+    ibc_impl::applications::{ica::host::IcaHostCallback, transfer::async_ack::error_ack},
+    *,
+};
+use ibc::core::channel::types::{
+    acknowledgement::{AcknowledgementStatus, StatusValue},
+    packet::Packet,
+};
+use ibc_proto::{ibc::core::channel::v1::Packet as RawPacket, Protobuf};
+use near_sdk::{serde_json::Value, PromiseResult};
+use utils::types::InterchainAccountActionResult;
+
+#[near_bindgen]
+impl IcaHostCallback for NearIbcContract {
+    #[private]
+    fn on_interchain_tx_executed(&mut self, packet: Base64VecU8) {
+        let packet: Packet =
+            Protobuf::<RawPacket>::decode_vec(&packet.0).expect("ERR_INVALID_PACKET");
+        let ack = match env::promise_result(0) {
+            PromiseResult::Successful(bytes) => {
+                match serde_json::from_slice::<Vec<InterchainAccountActionResult>>(&bytes) {
+                    Ok(results) => match serde_json::to_value(Base64VecU8(
+                        serde_json::to_vec(&results).unwrap(),
+                    )) {
+                        Ok(Value::String(result)) => {
+                            AcknowledgementStatus::success(StatusValue::new(result).unwrap()).into()
+                        }
+                        _ => error_ack("ERR_ENCODE_INTERCHAIN_TX_RESULTS".to_string()),
+                    },
+                    Err(e) => error_ack(format!("ERR_INTERCHAIN_TX_FAILED: {}", e)),
+                }
+            }
+            PromiseResult::Failed => {
+                error_ack("ERR_INTERCHAIN_TX_FAILED: the promise failed".to_string())
+            }
+        };
+        log!(
+            "Interchain tx of packet {}/{}/{} executed, acknowledgement: {}",
+            packet.port_id_on_b,
+            packet.chan_id_on_b,
+            packet.seq_on_a,
+            String::from_utf8(ack.as_bytes().to_vec()).unwrap_or_default()
+        );
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.write_async_acknowledgement(&packet, ack);
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+    }
+}
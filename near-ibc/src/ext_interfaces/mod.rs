@@ -1,2 +1,9 @@
+mod fee_actions;
+mod fee_callback_impl;
+mod ibc_hooks_callback_impl;
+mod ica_controller_actions;
+mod ica_host_callback_impl;
+mod nft_transfer_request_handler_impl;
 mod octopus_appchain_anchor_actions;
 mod transfer_request_handler_impl;
+mod wasm_light_client_actions;
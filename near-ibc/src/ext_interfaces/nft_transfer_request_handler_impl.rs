@@ -0,0 +1,192 @@
+use crate::{ibc_impl::applications::nft_transfer::NftPacketData, *};
+use ibc::core::{
+    channel::{handler::send_packet, types::packet::Packet},
+    host::{
+        types::path::{ChannelEndPath, SeqSendPath},
+        ValidationContext,
+    },
+};
+use utils::{interfaces::NftTransferRequestHandler, types::Ics721TransferRequest};
+
+#[near_bindgen]
+impl NftTransferRequestHandler for NearIbcContract {
+    //
+    fn process_nft_transfer_request(&mut self, transfer_request: Ics721TransferRequest) {
+        assert_nft_transfer_request_sender(&transfer_request);
+        let memo = transfer_request.memo.clone().unwrap_or_default();
+        if memo.len() > utils::MAX_LENGTH_OF_MEMO {
+            log!(
+                "ERR_SEND_NFT_TRANSFER: the length of memo exceeds {} bytes.",
+                utils::MAX_LENGTH_OF_MEMO
+            );
+            cancel_nft_transfer_request(transfer_request);
+            return;
+        }
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        if let Err(e) = send_nft_packet(&mut near_ibc_store, &transfer_request) {
+            log!("ERR_SEND_NFT_TRANSFER: {}", e);
+            cancel_nft_transfer_request(transfer_request);
+        } else {
+            ext_process_transfer_request_callback::ext(env::predecessor_account_id())
+                .with_attached_deposit(NearToken::from_yoctonear(0))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+                .with_unused_gas_weight(0)
+                .apply_transfer_request(transfer_request.request_id);
+        }
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+    }
+}
+
+/// Assert that the predecessor account is either the NFT escrow contract of the channel
+/// (for NFTs native in NEAR protocol), or the wrapped NFT contract of the class id
+/// of the request (for NFTs from other chains), so that the NFT of the request
+/// is guaranteed to be locked by the predecessor.
+fn assert_nft_transfer_request_sender(transfer_request: &Ics721TransferRequest) {
+    let predecessor = env::predecessor_account_id();
+    let escrow_account = format!(
+        "{}.{}",
+        transfer_request.chan_on_a,
+        utils::get_nft_escrow_factory_contract_id()
+    );
+    if predecessor.as_str() == escrow_account {
+        assert!(
+            !transfer_request.class_id.contains('/'),
+            "ERR_INVALID_NATIVE_CLASS_ID"
+        );
+        return;
+    }
+    assert!(
+        transfer_request.class_id.starts_with(
+            format!(
+                "{}/{}/",
+                transfer_request.port_on_a, transfer_request.chan_on_a
+            )
+            .as_str()
+        ) && predecessor == utils::wrapped_nft_contract_id_of(&transfer_request.class_id),
+        "ERR_ONLY_NFT_ESCROW_OR_WRAPPED_NFT_CAN_CALL_THIS_METHOD"
+    );
+}
+
+/// Build the ICS-721 packet of the given request and send it through the channel.
+fn send_nft_packet(
+    near_ibc_store: &mut NearIbcStore,
+    transfer_request: &Ics721TransferRequest,
+) -> Result<(), String> {
+    let port_id_on_a =
+        PortId::from_str(transfer_request.port_on_a.as_str()).map_err(|e| format!("{:?}", e))?;
+    let chan_id_on_a =
+        ChannelId::from_str(transfer_request.chan_on_a.as_str()).map_err(|e| format!("{:?}", e))?;
+    let packet_data = NftPacketData {
+        class_id: transfer_request.class_id.clone(),
+        class_uri: transfer_request.class_uri.clone(),
+        class_data: transfer_request.class_data.clone(),
+        token_ids: vec![transfer_request.token_id.clone()],
+        token_uris: transfer_request.token_uri.clone().into_iter().collect(),
+        token_data: transfer_request.token_data.clone().into_iter().collect(),
+        sender: transfer_request.sender.clone(),
+        receiver: transfer_request.receiver.clone(),
+        memo: transfer_request.memo.clone(),
+    };
+    packet_data.validate_basic()?;
+    let channel_end = near_ibc_store
+        .channel_end(&ChannelEndPath::new(&port_id_on_a, &chan_id_on_a))
+        .map_err(|e| format!("{:?}", e))?;
+    if !channel_end.is_open() {
+        return Err("ERR_NFT_TRANSFER_CHANNEL_NOT_OPENED".to_string());
+    }
+    let sequence = near_ibc_store
+        .get_next_sequence_send(&SeqSendPath::new(&port_id_on_a, &chan_id_on_a))
+        .map_err(|e| format!("{:?}", e))?;
+    let timeout_seconds = transfer_request
+        .timeout_seconds
+        .map_or(DEFAULT_TIMEOUT_SECONDS, |value| value.0);
+    let packet = Packet {
+        seq_on_a: sequence,
+        port_id_on_a,
+        chan_id_on_a,
+        port_id_on_b: channel_end.counterparty().port_id().clone(),
+        chan_id_on_b: channel_end
+            .counterparty()
+            .channel_id()
+            .ok_or_else(|| "ERR_COUNTERPARTY_CHANNEL_NOT_FOUND".to_string())?
+            .clone(),
+        data: serde_json::to_vec(&packet_data).unwrap(),
+        timeout_height_on_b: TimeoutHeight::Never {},
+        timeout_timestamp_on_b: Timestamp::from_nanoseconds(
+            env::block_timestamp() + timeout_seconds * 1000000000,
+        )
+        .unwrap(),
+    };
+    send_packet(near_ibc_store, packet).map_err(|e| format!("{:?}", e))
+}
+
+/// Schedule a call to `cancel_transfer_request` of the predecessor account.
+fn cancel_nft_transfer_request(transfer_request: Ics721TransferRequest) {
+    log!(
+        "Cancelling NFT transfer request {} for account {}, class id {} with token id {}",
+        transfer_request.request_id.0,
+        transfer_request.sender,
+        transfer_request.class_id,
+        transfer_request.token_id
+    );
+    ext_process_transfer_request_callback::ext(env::predecessor_account_id())
+        .with_attached_deposit(NearToken::from_yoctonear(0))
+        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+        .with_unused_gas_weight(0)
+        .cancel_transfer_request(transfer_request.request_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    fn transfer_request(class_id: &str) -> Ics721TransferRequest {
+        Ics721TransferRequest {
+            request_id: U64(0),
+            port_on_a: "nft-transfer".to_string(),
+            chan_on_a: "channel-0".to_string(),
+            class_id: class_id.to_string(),
+            class_uri: None,
+            class_data: None,
+            token_id: "1".to_string(),
+            token_uri: None,
+            token_data: None,
+            sender: "alice.near".to_string(),
+            receiver: "cosmos1receiver".to_string(),
+            timeout_seconds: None,
+            memo: None,
+        }
+    }
+
+    fn set_predecessor(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("v1.nearibc.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    #[test]
+    fn test_nft_transfer_request_from_nft_escrow() {
+        set_predecessor("channel-0.ef.nft-transfer.v1.nearibc.near");
+        assert_nft_transfer_request_sender(&transfer_request("nft.near"));
+    }
+
+    #[test]
+    fn test_nft_transfer_request_from_wrapped_nft() {
+        set_predecessor("v1.nearibc.near");
+        let wrapped_nft = utils::wrapped_nft_contract_id_of("nft-transfer/channel-0/kitties");
+        set_predecessor(wrapped_nft.as_str());
+        assert_nft_transfer_request_sender(&transfer_request("nft-transfer/channel-0/kitties"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ONLY_NFT_ESCROW_OR_WRAPPED_NFT_CAN_CALL_THIS_METHOD")]
+    fn test_nft_transfer_request_from_wrapped_nft_of_another_class() {
+        set_predecessor("v1.nearibc.near");
+        let wrapped_nft = utils::wrapped_nft_contract_id_of("nft-transfer/channel-0/punks");
+        set_predecessor(wrapped_nft.as_str());
+        assert_nft_transfer_request_sender(&transfer_request("nft-transfer/channel-0/kitties"));
+    }
+}
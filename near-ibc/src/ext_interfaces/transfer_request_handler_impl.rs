@@ -1,20 +1,96 @@
-use crate::*;
+use crate::{
+    ibc_impl::applications::{
+        fee::{self, EscrowedPacketFee, FeeAsset},
+        transfer::{escrow_account_of, multi_denom},
+    },
+    *,
+};
+use ibc::core::host::{types::path::SeqSendPath, ValidationContext};
+use utils::types::{Ics20MultiTransferRequest, PacketFee};
+
+/// Assert that the predecessor account is either the channel escrow of the channel
+/// (for tokens native in NEAR protocol), or the wrapped token contract of the denom
+/// of the request (for tokens from other chains), so that the tokens of the request
+/// are guaranteed to be locked by the predecessor.
+fn assert_transfer_request_sender(transfer_request: &Ics20TransferRequest) {
+    let predecessor = env::predecessor_account_id();
+    let port_id_on_a = PortId::from_str(transfer_request.port_on_a.as_str())
+        .expect("ERR_INVALID_PORT_ID_IN_TRANSFER_REQUEST");
+    let chan_id_on_a = ChannelId::from_str(transfer_request.chan_on_a.as_str())
+        .expect("ERR_INVALID_CHANNEL_ID_IN_TRANSFER_REQUEST");
+    let channel_prefix = format!("{}/{}", port_id_on_a, chan_id_on_a);
+    let is_wrapped_denom = transfer_request.token_trace_path == channel_prefix
+        || transfer_request
+            .token_trace_path
+            .starts_with(format!("{}/", channel_prefix).as_str());
+    if predecessor.as_str() == escrow_account_of(&port_id_on_a, &chan_id_on_a) {
+        assert!(!is_wrapped_denom, "ERR_INVALID_NATIVE_DENOM");
+        return;
+    }
+    assert!(
+        is_wrapped_denom
+            && predecessor
+                == utils::wrapped_token_contract_id_of(&AssetDenom {
+                    trace_path: transfer_request.token_trace_path.clone(),
+                    base_denom: transfer_request.token_denom.clone(),
+                }),
+        "ERR_ONLY_CHANNEL_ESCROW_OR_WRAPPED_TOKEN_CAN_CALL_THIS_METHOD"
+    );
+}
 
 #[near_bindgen]
 impl TransferRequestHandler for NearIbcContract {
     //
-    fn process_transfer_request(&mut self, transfer_request: Ics20TransferRequest) {
-        utils::assert_sub_account();
+    #[payable]
+    fn process_transfer_request(
+        &mut self,
+        transfer_request: Ics20TransferRequest,
+        fee: Option<PacketFee>,
+    ) {
+        assert_transfer_request_sender(&transfer_request);
+        let fee = fee.filter(|fee| fee.total() > 0);
+        let memo = transfer_request.memo.clone().unwrap_or_default();
+        if memo.len() > utils::MAX_LENGTH_OF_MEMO {
+            log!(
+                "ERR_SEND_TRANSFER: the length of memo exceeds {} bytes.",
+                utils::MAX_LENGTH_OF_MEMO
+            );
+            cancel_transfer_request(transfer_request, fee);
+            return;
+        }
+        let port_id_on_a = PortId::from_str(transfer_request.port_on_a.as_str()).unwrap();
+        let chan_id_on_a = ChannelId::from_str(transfer_request.chan_on_a.as_str()).unwrap();
+        if let Some(packet_fee) = fee.as_ref() {
+            if !self
+                .module_holder
+                .transfer_module
+                .is_fee_enabled(&port_id_on_a, &chan_id_on_a)
+            {
+                log!("ERR_SEND_TRANSFER: the channel is not fee enabled.");
+                cancel_transfer_request(transfer_request, fee);
+                return;
+            }
+            if fee_asset_of(&transfer_request) == FeeAsset::Near
+                && env::attached_deposit().as_yoctonear() < packet_fee.total()
+            {
+                log!("ERR_SEND_TRANSFER: the attached deposit is not enough for the fee.");
+                cancel_transfer_request(transfer_request, fee);
+                return;
+            }
+        }
         let mut near_ibc_store = self.near_ibc_store.get().unwrap();
         let timeout_seconds = transfer_request
             .timeout_seconds
             .map_or_else(|| DEFAULT_TIMEOUT_SECONDS, |value| value.0);
+        let sequence = near_ibc_store
+            .get_next_sequence_send(&SeqSendPath::new(&port_id_on_a, &chan_id_on_a))
+            .ok();
         if let Err(e) = ibc::apps::transfer::handler::send_transfer(
             &mut near_ibc_store,
             &mut TransferModule(),
             MsgTransfer {
-                port_id_on_a: PortId::from_str(transfer_request.port_on_a.as_str()).unwrap(),
-                chan_id_on_a: ChannelId::from_str(transfer_request.chan_on_a.as_str()).unwrap(),
+                port_id_on_a: port_id_on_a.clone(),
+                chan_id_on_a: chan_id_on_a.clone(),
                 packet_data: PacketData {
                     token: PrefixedCoin {
                         denom: PrefixedDenom {
@@ -30,7 +106,7 @@ impl TransferRequestHandler for NearIbcContract {
                     },
                     sender: Signer::from(transfer_request.sender.clone()),
                     receiver: Signer::from(transfer_request.receiver.clone()),
-                    memo: Memo::from_str("").unwrap(),
+                    memo: Memo::from_str(memo.as_str()).unwrap(),
                 },
                 timeout_height_on_b: TimeoutHeight::Never {},
                 timeout_timestamp_on_b: Timestamp::from_nanoseconds(
@@ -40,24 +116,245 @@ impl TransferRequestHandler for NearIbcContract {
             },
         ) {
             log!("ERR_SEND_TRANSFER: {:?}", e);
-            log!(
-                "Cancelling transfer request for account {}, trace path {}, base denom {} with amount {}",
-                transfer_request.sender,
-                transfer_request.token_trace_path,
-                transfer_request.token_denom,
-                transfer_request.amount.0
-            );
+            cancel_transfer_request(transfer_request, fee);
+        } else {
+            if let (Some(fee), Some(sequence)) = (fee, sequence) {
+                self.module_holder.transfer_module.escrow_packet_fee(
+                    (port_id_on_a, chan_id_on_a, u64::from(sequence)),
+                    EscrowedPacketFee {
+                        fee,
+                        asset: fee_asset_of(&transfer_request),
+                        refund_account: AccountId::from_str(transfer_request.sender.as_str())
+                            .unwrap(),
+                    },
+                );
+            }
             ext_process_transfer_request_callback::ext(env::predecessor_account_id())
                 .with_attached_deposit(NearToken::from_yoctonear(0))
-                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
                 .with_unused_gas_weight(0)
-                .cancel_transfer_request(
-                    transfer_request.token_trace_path,
-                    transfer_request.token_denom,
-                    AccountId::from_str(transfer_request.sender.as_str()).unwrap(),
-                    transfer_request.amount,
-                );
+                .apply_transfer_request(transfer_request.request_id);
+        }
+        self.near_ibc_store.set(&near_ibc_store);
+    }
+    //
+    #[payable]
+    fn process_multi_transfer_request(
+        &mut self,
+        transfer_request: Ics20MultiTransferRequest,
+        fee: Option<PacketFee>,
+    ) {
+        let port_id_on_a = PortId::from_str(transfer_request.port_on_a.as_str()).unwrap();
+        let chan_id_on_a = ChannelId::from_str(transfer_request.chan_on_a.as_str()).unwrap();
+        assert_eq!(
+            env::predecessor_account_id().to_string(),
+            escrow_account_of(&port_id_on_a, &chan_id_on_a),
+            "ERR_ONLY_CHANNEL_ESCROW_CAN_CALL_THIS_METHOD"
+        );
+        let fee = fee.filter(|fee| fee.total() > 0);
+        let memo = transfer_request.memo.clone().unwrap_or_default();
+        if memo.len() > utils::MAX_LENGTH_OF_MEMO {
+            log!(
+                "ERR_SEND_MULTI_TRANSFER: the length of memo exceeds {} bytes.",
+                utils::MAX_LENGTH_OF_MEMO
+            );
+            cancel_multi_transfer_request(transfer_request);
+            return;
+        }
+        if let Some(packet_fee) = fee.as_ref() {
+            if !self
+                .module_holder
+                .transfer_module
+                .is_fee_enabled(&port_id_on_a, &chan_id_on_a)
+            {
+                log!("ERR_SEND_MULTI_TRANSFER: the channel is not fee enabled.");
+                cancel_multi_transfer_request(transfer_request);
+                return;
+            }
+            if env::attached_deposit().as_yoctonear() < packet_fee.total() {
+                log!("ERR_SEND_MULTI_TRANSFER: the attached deposit is not enough for the fee.");
+                cancel_multi_transfer_request(transfer_request);
+                return;
+            }
+        }
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        match multi_denom::send_multi_denom_packet(&mut near_ibc_store, &transfer_request) {
+            Ok(sequence) => {
+                if let Some(fee) = fee {
+                    self.module_holder.transfer_module.escrow_packet_fee(
+                        (port_id_on_a, chan_id_on_a, u64::from(sequence)),
+                        EscrowedPacketFee {
+                            fee,
+                            asset: FeeAsset::Near,
+                            refund_account: AccountId::from_str(transfer_request.sender.as_str())
+                                .unwrap(),
+                        },
+                    );
+                }
+                // The wrapped tokens in the request are burnt by the channel escrow
+                // when the request is applied.
+                ext_process_transfer_request_callback::ext(env::predecessor_account_id())
+                    .with_attached_deposit(NearToken::from_yoctonear(0))
+                    .with_static_gas(
+                        utils::GAS_FOR_SIMPLE_FUNCTION_CALL
+                            .saturating_mul(2 + 3 * transfer_request.tokens.len() as u64),
+                    )
+                    .with_unused_gas_weight(0)
+                    .apply_transfer_request(transfer_request.request_id);
+            }
+            Err(e) => {
+                log!("ERR_SEND_MULTI_TRANSFER: {}", e);
+                cancel_multi_transfer_request(transfer_request);
+            }
         }
+        near_ibc_store.flush();
         self.near_ibc_store.set(&near_ibc_store);
     }
 }
+
+/// Get the asset of the fee attached to the given transfer request.
+///
+/// The fee is paid in the transferring tokens if the request is created by a channel escrow,
+/// otherwise it is paid in NEAR.
+fn fee_asset_of(transfer_request: &Ics20TransferRequest) -> FeeAsset {
+    let predecessor = env::predecessor_account_id();
+    let prefixed_ef = format!(".ef.transfer.{}", env::current_account_id());
+    match predecessor.as_str().ends_with(prefixed_ef.as_str()) {
+        true => FeeAsset::Token {
+            escrow_account: predecessor,
+            trace_path: transfer_request.token_trace_path.clone(),
+            base_denom: transfer_request.token_denom.clone(),
+        },
+        false => FeeAsset::Near,
+    }
+}
+
+/// Schedule a call to `cancel_transfer_request` of the predecessor account,
+/// and refund the fee of the request (or the NEAR attached) to the sender.
+fn cancel_transfer_request(transfer_request: Ics20TransferRequest, fee: Option<PacketFee>) {
+    log!(
+        "Cancelling transfer request {} for account {}, trace path {}, base denom {} with amount {}",
+        transfer_request.request_id.0,
+        transfer_request.sender,
+        transfer_request.token_trace_path,
+        transfer_request.token_denom,
+        transfer_request.amount.0
+    );
+    ext_process_transfer_request_callback::ext(env::predecessor_account_id())
+        .with_attached_deposit(NearToken::from_yoctonear(0))
+        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+        .with_unused_gas_weight(0)
+        .cancel_transfer_request(transfer_request.request_id);
+    let fee_asset = fee_asset_of(&transfer_request);
+    let refund_amount = match fee_asset {
+        FeeAsset::Near => env::attached_deposit().as_yoctonear(),
+        FeeAsset::Token { .. } => fee.map_or(0, |fee| fee.total()),
+    };
+    fee::pay_fee(
+        &fee_asset,
+        &AccountId::from_str(transfer_request.sender.as_str()).unwrap(),
+        refund_amount,
+    );
+}
+
+/// Schedule a call to `cancel_transfer_request` of the predecessor account (the channel
+/// escrow), and refund the NEAR attached for the fee to the sender.
+fn cancel_multi_transfer_request(transfer_request: Ics20MultiTransferRequest) {
+    log!(
+        "Cancelling multi-denom transfer request {} for account {} with {} tokens",
+        transfer_request.request_id.0,
+        transfer_request.sender,
+        transfer_request.tokens.len()
+    );
+    ext_process_transfer_request_callback::ext(env::predecessor_account_id())
+        .with_attached_deposit(NearToken::from_yoctonear(0))
+        .with_static_gas(
+            utils::GAS_FOR_SIMPLE_FUNCTION_CALL
+                .saturating_mul(2 + 3 * transfer_request.tokens.len() as u64),
+        )
+        .with_unused_gas_weight(0)
+        .cancel_transfer_request(transfer_request.request_id);
+    fee::pay_fee(
+        &FeeAsset::Near,
+        &AccountId::from_str(transfer_request.sender.as_str()).unwrap(),
+        env::attached_deposit().as_yoctonear(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::applications::ica::host::IcaHostModule;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    fn transfer_request(trace_path: &str, base_denom: &str) -> Ics20TransferRequest {
+        Ics20TransferRequest {
+            request_id: U64(0),
+            port_on_a: "transfer".to_string(),
+            chan_on_a: "channel-0".to_string(),
+            token_trace_path: trace_path.to_string(),
+            token_denom: base_denom.to_string(),
+            amount: U128(100),
+            sender: "alice.near".to_string(),
+            receiver: "cosmos1receiver".to_string(),
+            timeout_seconds: None,
+            memo: None,
+        }
+    }
+
+    fn set_predecessor(predecessor: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("v1.nearibc.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .build());
+    }
+
+    #[test]
+    fn test_transfer_request_from_channel_escrow() {
+        set_predecessor("channel-0.ef.transfer.v1.nearibc.near");
+        assert_transfer_request_sender(&transfer_request("", "wrap.near"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_NATIVE_DENOM")]
+    fn test_wrapped_denom_from_channel_escrow() {
+        set_predecessor("channel-0.ef.transfer.v1.nearibc.near");
+        assert_transfer_request_sender(&transfer_request("transfer/channel-0", "uatom"));
+    }
+
+    #[test]
+    fn test_transfer_request_from_wrapped_token() {
+        set_predecessor("v1.nearibc.near");
+        let wrapped_token = utils::wrapped_token_contract_id_of(&AssetDenom {
+            trace_path: "transfer/channel-0".to_string(),
+            base_denom: "uatom".to_string(),
+        });
+        set_predecessor(wrapped_token.as_str());
+        assert_transfer_request_sender(&transfer_request("transfer/channel-0", "uatom"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ONLY_CHANNEL_ESCROW_OR_WRAPPED_TOKEN_CAN_CALL_THIS_METHOD")]
+    fn test_transfer_request_from_wrapped_token_of_another_denom() {
+        set_predecessor("v1.nearibc.near");
+        let wrapped_token = utils::wrapped_token_contract_id_of(&AssetDenom {
+            trace_path: "transfer/channel-0".to_string(),
+            base_denom: "uosmo".to_string(),
+        });
+        set_predecessor(wrapped_token.as_str());
+        assert_transfer_request_sender(&transfer_request("transfer/channel-0", "uatom"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ONLY_CHANNEL_ESCROW_OR_WRAPPED_TOKEN_CAN_CALL_THIS_METHOD")]
+    fn test_transfer_request_from_interchain_account() {
+        set_predecessor("v1.nearibc.near");
+        let interchain_account = IcaHostModule::account_id_of(
+            &ConnectionId::new(0),
+            &PortId::from_str("icacontroller-cosmos1attacker").unwrap(),
+        );
+        assert!(interchain_account.as_str().starts_with("ica-"));
+        set_predecessor(interchain_account.as_str());
+        assert_transfer_request_sender(&transfer_request("", "wrap.near"));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{ibc_impl::clients::wasm, *};
+use near_sdk::json_types::U64;
+
+/// The actions of the light client contracts of `08-wasm` clients.
+///
+/// Only the light client contract registered with the checksum in the client state
+/// can call these functions for the client.
+pub trait WasmLightClientActions {
+    /// Store a new consensus state of the given client at the given height, which is
+    /// verified by the light client contract. The latest height of the client is updated
+    /// if the given height is higher, and the data of the client state is replaced if provided.
+    fn update_wasm_client(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        consensus_state_data: Base64VecU8,
+        timestamp: U64,
+        client_state_data: Option<Base64VecU8>,
+    );
+    /// Record the values of IBC paths of the counterparty chain, which are verified by
+    /// the light client contract under the consensus state of the given client
+    /// at the given height.
+    fn submit_wasm_client_proofs(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        values: Vec<VerifiedPathValue>,
+    );
+    /// Freeze the given client, as misbehaviour is detected by the light client contract.
+    fn freeze_wasm_client(&mut self, client_id: ClientId);
+}
+
+#[near_bindgen]
+impl WasmLightClientActions for NearIbcContract {
+    //
+    #[payable]
+    fn update_wasm_client(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        consensus_state_data: Base64VecU8,
+        timestamp: U64,
+        client_state_data: Option<Base64VecU8>,
+    ) {
+        assert!(timestamp.0 > 0, "ERR_INVALID_TIMESTAMP");
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let client_state = wasm::assert_light_client_contract_of(&near_ibc_store, &client_id);
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        wasm::update_client(
+            &mut near_ibc_store,
+            &client_id,
+            client_state,
+            height,
+            consensus_state_data.0,
+            timestamp.0,
+            client_state_data.map(|data| data.0),
+        )
+        .unwrap_or_else(|e| panic!("ERR_UPDATE_WASM_CLIENT: {:?}", e));
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn submit_wasm_client_proofs(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        values: Vec<VerifiedPathValue>,
+    ) {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        wasm::assert_light_client_contract_of(&near_ibc_store, &client_id);
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        wasm::record_verified_values(
+            &near_ibc_store,
+            &client_id,
+            &height,
+            values
+                .into_iter()
+                .map(|value| (value.path, value.value.map(|value| value.0)))
+                .collect(),
+        )
+        .unwrap_or_else(|e| panic!("ERR_SUBMIT_WASM_CLIENT_PROOFS: {:?}", e));
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    fn freeze_wasm_client(&mut self, client_id: ClientId) {
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let client_state = wasm::assert_light_client_contract_of(&near_ibc_store, &client_id);
+        wasm::freeze_client(&mut near_ibc_store, &client_id, client_state)
+            .unwrap_or_else(|e| panic!("ERR_FREEZE_WASM_CLIENT: {:?}", e));
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"WASM_CLIENT_FROZEN","client_id":"{}"}}"#,
+            client_id
+        );
+    }
+}
@@ -0,0 +1,600 @@
+//! The governance council of this contract.
+//!
+//! The members of the council can propose calls of the sudo functions of this contract.
+//! A proposal can be executed by anyone, after it is approved by enough members and
+//! the timelock is passed. The proposal is executed by a function call to this contract
+//! itself, so the governance account must be this contract (which is the default value).
+//! Once the governance account is transferred to another account, proposals can no longer be
+//! created or executed, and the pending ones can only be cancelled.
+//!
+//! If the function call of an executed proposal fails, the proposal is restored as pending
+//! and the attached deposit is refunded to the executor.
+use crate::*;
+use near_sdk::{
+    ext_contract,
+    json_types::{U128, U64},
+    store::UnorderedMap,
+    Gas, Promise, PromiseResult,
+};
+
+/// The names of the functions which can be called by proposals.
+pub const SUDO_FUNCTION_NAMES: [&str; 29] = [
+    "cancel_transfer_request_in_channel_escrow",
+    "setup_wrapped_token",
+    "set_max_length_of_ibc_events_history",
+    "setup_channel_escrow",
+    "setup_nft_channel_escrow",
+    "register_asset_for_channel",
+    "unregister_asset_from_channel",
+    "force_clear_frozen_height_of_tendermint_client",
+    "change_max_ibc_events_height_difference",
+    "set_rate_limit",
+    "remove_rate_limit",
+    "reset_rate_limit_usage",
+    "set_deliver_paused",
+    "set_message_category_paused",
+    "set_port_paused",
+    "set_channel_paused",
+    "set_channel_escrow_paused",
+    "set_wrapped_token_paused",
+    "set_governance_council",
+    "grant_role",
+    "revoke_role",
+    "propose_governance_account",
+    "register_wasm_light_client",
+    "unregister_wasm_light_client",
+    "set_host_client_params",
+    "init_channel_upgrade",
+    "cancel_channel_upgrade",
+    "set_packet_state_retention",
+    "set_consensus_state_retention",
+];
+
+/// A function call to a sudo function of this contract.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SudoAction {
+    pub method_name: String,
+    /// The arguments of the function, in JSON format.
+    pub args: String,
+    /// The deposit attached to the function call, which should be attached
+    /// when executing the proposal.
+    #[serde(default)]
+    pub deposit: U128,
+    /// The gas for the function call, `GAS_FOR_COMPLEX_FUNCTION_CALL` if `None`.
+    #[serde(default)]
+    pub gas: Option<U64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub id: U64,
+    pub proposer: AccountId,
+    pub description: String,
+    pub action: SudoAction,
+    /// The council members who approved the proposal.
+    pub approvals: Vec<AccountId>,
+    /// The time when the proposal is created, in nanoseconds.
+    pub created_at: U64,
+    /// The time when the proposal gets enough approvals, in nanoseconds.
+    pub approved_at: Option<U64>,
+}
+
+/// The status of the governance council, for view functions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GovernanceCouncilStatus {
+    pub members: Vec<AccountId>,
+    pub threshold: u32,
+    pub timelock_seconds: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct GovernanceCouncil {
+    members: Vec<AccountId>,
+    /// The number of approvals needed for executing a proposal.
+    threshold: u32,
+    /// The delay between a proposal gets enough approvals and it can be executed.
+    timelock_seconds: u64,
+    /// The pending proposals, keyed by proposal id.
+    proposals: UnorderedMap<u64, Proposal>,
+    next_proposal_id: u64,
+}
+
+impl GovernanceCouncil {
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+            threshold: 0,
+            timelock_seconds: 0,
+            proposals: UnorderedMap::new(StorageKey::GovernanceProposals),
+            next_proposal_id: 1,
+        }
+    }
+    /// Set the members, the threshold of approvals and the timelock of the council.
+    pub fn set_council(&mut self, members: Vec<AccountId>, threshold: u32, timelock_seconds: u64) {
+        let mut members = members;
+        members.sort();
+        members.dedup();
+        assert!(
+            threshold > 0 && threshold as usize <= members.len(),
+            "ERR_INVALID_THRESHOLD"
+        );
+        self.members = members;
+        self.threshold = threshold;
+        self.timelock_seconds = timelock_seconds;
+        // The approvals of the pending proposals are re-counted by the new council.
+        let ids: Vec<u64> = self.proposals.keys().cloned().collect();
+        for id in ids {
+            let proposal = self.proposals.get_mut(&id).unwrap();
+            proposal.approved_at = None;
+            Self::check_approvals(&self.members, self.threshold, proposal);
+        }
+    }
+    /// Get the members, the threshold and the timelock of the council.
+    pub fn get_status(&self) -> GovernanceCouncilStatus {
+        GovernanceCouncilStatus {
+            members: self.members.clone(),
+            threshold: self.threshold,
+            timelock_seconds: U64(self.timelock_seconds),
+        }
+    }
+    /// Get all the pending proposals, which are not executed or cancelled.
+    pub fn get_pending_proposals(&self) -> Vec<Proposal> {
+        self.proposals.values().cloned().collect()
+    }
+    //
+    fn assert_member(&self, account_id: &AccountId) {
+        assert!(
+            self.members.contains(account_id),
+            "ERR_NOT_GOVERNANCE_COUNCIL_MEMBER"
+        );
+    }
+    // Record the approval time if the proposal gets enough approvals from the given members.
+    fn check_approvals(members: &[AccountId], threshold: u32, proposal: &mut Proposal) {
+        let approvals = proposal
+            .approvals
+            .iter()
+            .filter(|account_id| members.contains(account_id))
+            .count();
+        if proposal.approved_at.is_none() && approvals >= threshold as usize {
+            proposal.approved_at = Some(U64(env::block_timestamp()));
+        }
+    }
+}
+
+/// The actions of the governance council.
+pub trait GovernanceActions {
+    /// Create a proposal for calling a sudo function of this contract, returns the proposal id.
+    ///
+    /// Only the members of the governance council can call this function,
+    /// and the proposer approves the proposal automatically.
+    fn create_proposal(&mut self, description: String, action: SudoAction) -> U64;
+    /// Approve the given proposal.
+    ///
+    /// Only the members of the governance council can call this function.
+    fn approve_proposal(&mut self, proposal_id: U64);
+    /// Execute the given proposal, if it gets enough approvals and the timelock is passed.
+    ///
+    /// The deposit of the action in the proposal should be attached.
+    fn execute_proposal(&mut self, proposal_id: U64);
+    /// Cancel the given proposal.
+    ///
+    /// Only the proposer or the governance account can call this function.
+    fn cancel_proposal(&mut self, proposal_id: U64);
+}
+
+/// The callback interface for the promise of executing proposals.
+#[ext_contract(ext_governance_callback)]
+pub trait GovernanceCallback {
+    /// Restore the given proposal and refund the deposit to the executor,
+    /// if the function call of the proposal failed.
+    fn on_proposal_executed(&mut self, proposal: Proposal, executor: AccountId, deposit: U128);
+}
+
+impl NearIbcContract {
+    // Assert that the governance account is this contract itself, which is the caller
+    // of the function calls of proposals.
+    fn assert_governed_by_council(&self) {
+        assert_eq!(
+            self.governance_account,
+            env::current_account_id(),
+            "ERR_GOVERNANCE_ACCOUNT_IS_NOT_CURRENT_ACCOUNT"
+        );
+    }
+}
+
+#[near_bindgen]
+impl GovernanceActions for NearIbcContract {
+    #[payable]
+    fn create_proposal(&mut self, description: String, action: SudoAction) -> U64 {
+        self.assert_governed_by_council();
+        let proposer = env::predecessor_account_id();
+        self.governance_council.assert_member(&proposer);
+        assert!(
+            SUDO_FUNCTION_NAMES.contains(&action.method_name.as_str()),
+            "ERR_INVALID_SUDO_FUNCTION"
+        );
+        assert!(
+            serde_json::from_str::<serde_json::Value>(action.args.as_str()).is_ok(),
+            "ERR_INVALID_ARGS"
+        );
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let council = &mut self.governance_council;
+        let id = council.next_proposal_id;
+        council.next_proposal_id += 1;
+        let mut proposal = Proposal {
+            id: U64(id),
+            proposer: proposer.clone(),
+            description,
+            action,
+            approvals: vec![proposer],
+            created_at: U64(env::block_timestamp()),
+            approved_at: None,
+        };
+        GovernanceCouncil::check_approvals(&council.members, council.threshold, &mut proposal);
+        council.proposals.insert(id, proposal);
+        council.proposals.flush();
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PROPOSAL_CREATED","proposal_id":"{}"}}"#,
+            id
+        );
+        utils::refund_deposit(used_bytes);
+        U64(id)
+    }
+    //
+    #[payable]
+    fn approve_proposal(&mut self, proposal_id: U64) {
+        let account_id = env::predecessor_account_id();
+        self.governance_council.assert_member(&account_id);
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        let council = &mut self.governance_council;
+        let proposal = council
+            .proposals
+            .get_mut(&proposal_id.0)
+            .expect("ERR_PROPOSAL_NOT_FOUND");
+        assert!(
+            !proposal.approvals.contains(&account_id),
+            "ERR_PROPOSAL_ALREADY_APPROVED"
+        );
+        proposal.approvals.push(account_id);
+        GovernanceCouncil::check_approvals(&council.members, council.threshold, proposal);
+        council.proposals.flush();
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PROPOSAL_APPROVED","proposal_id":"{}","approver":"{}"}}"#,
+            proposal_id.0,
+            env::predecessor_account_id()
+        );
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
+    fn execute_proposal(&mut self, proposal_id: U64) {
+        self.assert_governed_by_council();
+        let council = &mut self.governance_council;
+        let proposal = council
+            .proposals
+            .get(&proposal_id.0)
+            .expect("ERR_PROPOSAL_NOT_FOUND");
+        let approved_at = proposal.approved_at.expect("ERR_PROPOSAL_NOT_APPROVED").0;
+        assert!(
+            env::block_timestamp()
+                >= approved_at
+                    .saturating_add(council.timelock_seconds.saturating_mul(1_000_000_000)),
+            "ERR_PROPOSAL_IN_TIMELOCK"
+        );
+        assert!(
+            env::attached_deposit().as_yoctonear() >= proposal.action.deposit.0,
+            "ERR_NOT_ENOUGH_DEPOSIT, must not less than {} yocto",
+            proposal.action.deposit.0
+        );
+        let gas = proposal
+            .action
+            .gas
+            .map_or(utils::GAS_FOR_COMPLEX_FUNCTION_CALL, |gas| {
+                Gas::from_gas(gas.0)
+            });
+        assert!(
+            env::prepaid_gas()
+                > gas.saturating_add(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2)),
+            "ERR_NOT_ENOUGH_GAS"
+        );
+        let proposal = council.proposals.remove(&proposal_id.0).unwrap();
+        council.proposals.flush();
+        Promise::new(env::current_account_id())
+            .function_call(
+                proposal.action.method_name.clone(),
+                proposal.action.args.clone().into_bytes(),
+                env::attached_deposit(),
+                gas,
+            )
+            .then(
+                ext_governance_callback::ext(env::current_account_id())
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                    .with_unused_gas_weight(0)
+                    .on_proposal_executed(
+                        proposal.clone(),
+                        env::predecessor_account_id(),
+                        U128(env::attached_deposit().as_yoctonear()),
+                    ),
+            );
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PROPOSAL_EXECUTED","proposal_id":"{}","method_name":"{}"}}"#,
+            proposal_id.0,
+            proposal.action.method_name
+        );
+    }
+    //
+    fn cancel_proposal(&mut self, proposal_id: U64) {
+        let council = &mut self.governance_council;
+        let proposal = council
+            .proposals
+            .get(&proposal_id.0)
+            .expect("ERR_PROPOSAL_NOT_FOUND");
+        assert!(
+            env::predecessor_account_id() == proposal.proposer
+                || env::predecessor_account_id() == self.governance_account,
+            "ERR_NOT_PROPOSER_OR_GOVERNANCE_ACCOUNT"
+        );
+        council.proposals.remove(&proposal_id.0);
+        council.proposals.flush();
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PROPOSAL_CANCELLED","proposal_id":"{}"}}"#,
+            proposal_id.0
+        );
+    }
+}
+
+#[near_bindgen]
+impl GovernanceCallback for NearIbcContract {
+    #[private]
+    fn on_proposal_executed(&mut self, proposal: Proposal, executor: AccountId, deposit: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        let council = &mut self.governance_council;
+        let mut proposal = proposal;
+        // The council may have been changed after the proposal was approved.
+        let approvals = proposal
+            .approvals
+            .iter()
+            .filter(|account_id| council.members.contains(account_id))
+            .count();
+        if approvals < council.threshold as usize {
+            proposal.approved_at = None;
+        }
+        let proposal_id = proposal.id;
+        council.proposals.insert(proposal_id.0, proposal);
+        council.proposals.flush();
+        if deposit.0 > 0 {
+            Promise::new(executor).transfer(NearToken::from_yoctonear(deposit.0));
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PROPOSAL_EXECUTION_FAILED","proposal_id":"{}"}}"#,
+            proposal_id.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sudo_functions::SudoFunctions,
+        test_fixtures::{account, call_at, callback_with, setup_contract, CONTRACT_ACCOUNT},
+    };
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn setup() -> NearIbcContract {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        contract.set_governance_council(
+            vec![
+                account("alice.testnet"),
+                account("bob.testnet"),
+                account("carol.testnet"),
+            ],
+            2,
+            U64(60),
+        );
+        contract
+    }
+
+    fn action() -> SudoAction {
+        SudoAction {
+            method_name: "set_deliver_paused".to_string(),
+            args: r#"{"paused":true}"#.to_string(),
+            deposit: U128(0),
+            gas: None,
+        }
+    }
+
+    fn proposal(contract: &NearIbcContract, id: U64) -> Option<Proposal> {
+        contract.governance_council.proposals.get(&id.0).cloned()
+    }
+
+    #[test]
+    fn test_proposal_threshold() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        assert!(proposal(&contract, id).unwrap().approved_at.is_none());
+        call_at("bob.testnet", 2 * SECOND);
+        contract.approve_proposal(id);
+        let proposal = proposal(&contract, id).unwrap();
+        assert_eq!(proposal.approved_at, Some(U64(2 * SECOND)));
+        assert_eq!(
+            proposal.approvals,
+            vec![account("alice.testnet"), account("bob.testnet")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_COUNCIL_MEMBER")]
+    fn test_approve_proposal_by_non_member() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("dave.testnet", SECOND);
+        contract.approve_proposal(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_NOT_APPROVED")]
+    fn test_execute_proposal_without_enough_approvals() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("dave.testnet", 1000 * SECOND);
+        contract.execute_proposal(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PROPOSAL_IN_TIMELOCK")]
+    fn test_execute_proposal_in_timelock() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("bob.testnet", 2 * SECOND);
+        contract.approve_proposal(id);
+        call_at("dave.testnet", 61 * SECOND);
+        contract.execute_proposal(id);
+    }
+
+    #[test]
+    fn test_execute_proposal_after_timelock() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("bob.testnet", 2 * SECOND);
+        contract.approve_proposal(id);
+        call_at("dave.testnet", 62 * SECOND);
+        contract.execute_proposal(id);
+        assert!(proposal(&contract, id).is_none());
+    }
+
+    #[test]
+    fn test_proposal_restored_on_failed_execution() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("bob.testnet", 2 * SECOND);
+        contract.approve_proposal(id);
+        let executed = proposal(&contract, id).unwrap();
+        call_at("dave.testnet", 62 * SECOND);
+        contract.execute_proposal(id);
+        callback_with(vec![PromiseResult::Failed]);
+        contract.on_proposal_executed(executed, account("dave.testnet"), U128(0));
+        assert_eq!(
+            proposal(&contract, id).unwrap().approved_at,
+            Some(U64(2 * SECOND))
+        );
+    }
+
+    #[test]
+    fn test_set_council_recounts_approvals() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("bob.testnet", 2 * SECOND);
+        contract.approve_proposal(id);
+        // Bob leaves the council, so the proposal has only one valid approval.
+        call_at(CONTRACT_ACCOUNT, 3 * SECOND);
+        contract.set_governance_council(
+            vec![
+                account("alice.testnet"),
+                account("carol.testnet"),
+                account("dave.testnet"),
+            ],
+            2,
+            U64(60),
+        );
+        assert!(proposal(&contract, id).unwrap().approved_at.is_none());
+        // The timelock restarts from the approval of the new member.
+        call_at("carol.testnet", 4 * SECOND);
+        contract.approve_proposal(id);
+        assert_eq!(
+            proposal(&contract, id).unwrap().approved_at,
+            Some(U64(4 * SECOND))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_COUNCIL_MEMBER")]
+    fn test_create_proposal_by_removed_member() {
+        let mut contract = setup();
+        call_at(CONTRACT_ACCOUNT, SECOND);
+        contract.set_governance_council(
+            vec![account("alice.testnet"), account("carol.testnet")],
+            1,
+            U64(0),
+        );
+        call_at("bob.testnet", SECOND);
+        contract.create_proposal("pause".to_string(), action());
+    }
+
+    #[test]
+    fn test_cancel_proposal_permissions() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let first = contract.create_proposal("pause".to_string(), action());
+        let second = contract.create_proposal("pause".to_string(), action());
+        // The proposer can cancel the proposal.
+        contract.cancel_proposal(first);
+        assert!(proposal(&contract, first).is_none());
+        // The governance account can cancel the proposal.
+        call_at(CONTRACT_ACCOUNT, SECOND);
+        contract.cancel_proposal(second);
+        assert!(proposal(&contract, second).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PROPOSER_OR_GOVERNANCE_ACCOUNT")]
+    fn test_cancel_proposal_by_other_member() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at("bob.testnet", SECOND);
+        contract.cancel_proposal(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PENDING_GOVERNANCE_ACCOUNT")]
+    fn test_accept_governance_account_by_wrong_account() {
+        let mut contract = setup();
+        call_at(CONTRACT_ACCOUNT, SECOND);
+        contract.propose_governance_account(account("dao.testnet"));
+        call_at("mallory.testnet", SECOND);
+        contract.accept_governance_account();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_GOVERNANCE_ACCOUNT_IS_NOT_CURRENT_ACCOUNT")]
+    fn test_council_proposal_after_governance_transferred() {
+        let mut contract = setup();
+        call_at("alice.testnet", SECOND);
+        let id = contract.create_proposal("pause".to_string(), action());
+        call_at(CONTRACT_ACCOUNT, SECOND);
+        contract.propose_governance_account(account("dao.testnet"));
+        assert_eq!(
+            contract.pending_governance_account,
+            Some(account("dao.testnet"))
+        );
+        call_at("dao.testnet", SECOND);
+        contract.accept_governance_account();
+        assert_eq!(contract.governance_account, account("dao.testnet"));
+        assert!(contract.pending_governance_account.is_none());
+        // The new governance account can cancel the pending proposals.
+        contract.cancel_proposal(id);
+        assert!(proposal(&contract, id).is_none());
+        // But the council can no longer create proposals.
+        call_at("alice.testnet", SECOND);
+        contract.create_proposal("pause".to_string(), action());
+    }
+}
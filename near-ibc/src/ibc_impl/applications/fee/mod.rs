@@ -0,0 +1,1096 @@
+//! The implementation of ICS-29 (fee middleware) for relayer incentivization.
+//!
+//! The middleware wraps an application module. For the channels whose version is negotiated
+//! with `ics29-1`, the acknowledgements of received packets are wrapped as incentivized
+//! acknowledgements, and the fees escrowed for sent packets are paid to the relayers
+//! when the acknowledgement or timeout of the packets is relayed back.
+//!
+//! The fees paid in NEP-141 tokens which fail to be transferred to the receivers (e.g. the
+//! receivers are not registered in the token contracts) are credited to the receivers, who can
+//! claim them later. When the fees of a channel are disabled (by closing or upgrading it), the
+//! fees escrowed for its packets are refunded lazily by anyone, as they may be too many to be
+//! refunded in one transaction.
+use super::transfer::async_ack::is_async_ack_placeholder;
+use crate::{
+    ibc_impl::core::channel_upgrade::ChannelUpgradeModule, prelude::*, types::Page, StorageKey,
+};
+use core::{fmt::Debug, str::FromStr};
+use ibc::{
+    core::{
+        channel::types::{
+            acknowledgement::{Acknowledgement, AcknowledgementStatus},
+            channel::{Counterparty, Order},
+            error::{ChannelError, PacketError},
+            packet::Packet,
+            Version,
+        },
+        host::types::identifiers::{ChannelId, ConnectionId, PortId},
+        router::{module::Module, types::module::ModuleExtras},
+    },
+    primitives::Signer,
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::{Base64VecU8, U128, U64},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json,
+    store::{LookupMap, UnorderedMap, UnorderedSet},
+    AccountId, NearToken, Promise,
+};
+use utils::{interfaces::ext_channel_escrow, types::PacketFee, ExtraDepositCost};
+
+/// The version of ICS-29.
+pub const FEE_VERSION: &str = "ics29-1";
+
+/// The key of a packet: (port id, channel id, sequence) on the sending chain.
+pub type PacketKey = (PortId, ChannelId, u64);
+
+/// The channel version of a fee enabled channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeMetadata {
+    pub fee_version: String,
+    pub app_version: String,
+}
+
+/// The acknowledgement written for the packets received in a fee enabled channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IncentivizedAcknowledgement {
+    pub app_acknowledgement: Base64VecU8,
+    /// The payee (on the counterparty chain) of the relayer who relayed the packet.
+    pub forward_relayer_address: String,
+    pub underlying_app_success: bool,
+}
+
+/// The asset in which the fees are escrowed.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum FeeAsset {
+    /// NEAR held by this contract.
+    Near,
+    /// The NEP-141 tokens held by a channel escrow.
+    Token {
+        escrow_account: AccountId,
+        trace_path: String,
+        base_denom: String,
+    },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowedPacketFee {
+    pub fee: PacketFee,
+    pub asset: FeeAsset,
+    /// The account to receive the unused fees.
+    pub refund_account: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct FeeMiddleware<M> {
+    /// The wrapped application module.
+    pub app: M,
+    /// The channels whose version is negotiated with ICS-29.
+    fee_enabled_channels: UnorderedSet<(PortId, ChannelId)>,
+    /// The payees of relayers in NEAR protocol, keyed by channel id and relayer.
+    payees: LookupMap<(ChannelId, AccountId), AccountId>,
+    /// The payees of relayers on counterparty chains, keyed by channel id and relayer.
+    counterparty_payees: LookupMap<(ChannelId, AccountId), String>,
+    /// The fees escrowed for the packets sent by this contract.
+    escrowed_fees: UnorderedMap<PacketKey, Vec<EscrowedPacketFee>>,
+}
+
+/// The forward relayers of the packets whose acknowledgement is written asynchronously.
+///
+/// As the asynchronous acknowledgement is written without loading the modules,
+/// this collection is not a member of `FeeMiddleware`.
+fn async_ack_forward_relayers() -> LookupMap<PacketKey, String> {
+    LookupMap::new(StorageKey::FeeAsyncAckForwardRelayers)
+}
+
+/// The token fees which failed to be paid, keyed by the receiver and the asset.
+///
+/// As the fees are credited in the callback of paying them, without loading the modules,
+/// this collection is not a member of `FeeMiddleware`.
+fn unpaid_token_fees() -> LookupMap<(AccountId, FeeAsset), u128> {
+    LookupMap::new(StorageKey::UnpaidTokenFees)
+}
+
+/// Get the amount of the token fees which failed to be paid to the given account.
+pub fn unpaid_token_fee_of(account_id: &AccountId, asset: &FeeAsset) -> u128 {
+    unpaid_token_fees()
+        .get(&(account_id.clone(), asset.clone()))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Credit the given amount of token fees which failed to be paid to the given account.
+pub fn credit_unpaid_token_fee(account_id: &AccountId, asset: &FeeAsset, amount: u128) {
+    let mut unpaid_fees = unpaid_token_fees();
+    let key = (account_id.clone(), asset.clone());
+    let balance = unpaid_fees.get(&key).copied().unwrap_or(0);
+    unpaid_fees.insert(key, balance.saturating_add(amount));
+    unpaid_fees.flush();
+}
+
+/// Remove the token fees which failed to be paid to the given account, returns the amount.
+pub fn take_unpaid_token_fee(account_id: &AccountId, asset: &FeeAsset) -> u128 {
+    let mut unpaid_fees = unpaid_token_fees();
+    let amount = unpaid_fees
+        .remove(&(account_id.clone(), asset.clone()))
+        .unwrap_or(0);
+    unpaid_fees.flush();
+    amount
+}
+
+/// The callback interface for the promise of paying token fees.
+#[ext_contract(ext_fee_callback)]
+pub trait FeeCallback {
+    /// Credit the given fee to the receiver, if it failed to be transferred.
+    fn on_token_fee_paid(&mut self, asset: FeeAsset, receiver_id: AccountId, amount: U128);
+}
+
+/// Wrap the given acknowledgement as an incentivized acknowledgement.
+fn incentivized_ack(ack: Acknowledgement, forward_relayer_address: String) -> Acknowledgement {
+    let underlying_app_success = matches!(
+        serde_json::from_slice::<AcknowledgementStatus>(ack.as_bytes()),
+        Ok(AcknowledgementStatus::Success(_))
+    );
+    let ack = IncentivizedAcknowledgement {
+        app_acknowledgement: Base64VecU8::from(ack.as_bytes().to_vec()),
+        forward_relayer_address,
+        underlying_app_success,
+    };
+    Acknowledgement::try_from(serde_json::to_vec(&ack).unwrap()).unwrap()
+}
+
+/// Wrap the acknowledgement which is written asynchronously,
+/// if the packet is received in a fee enabled channel.
+pub fn wrap_async_acknowledgement(packet: &Packet, ack: Acknowledgement) -> Acknowledgement {
+    let key = (
+        packet.port_id_on_b.clone(),
+        packet.chan_id_on_b.clone(),
+        u64::from(packet.seq_on_a),
+    );
+    match async_ack_forward_relayers().remove(&key) {
+        Some(forward_relayer_address) => incentivized_ack(ack, forward_relayer_address),
+        None => ack,
+    }
+}
+
+/// Pay the given amount of the given asset to the given account.
+///
+/// The token fees are credited to the account by `on_token_fee_paid` if the transfer fails.
+pub fn pay_fee(asset: &FeeAsset, receiver_id: &AccountId, amount: u128) {
+    if amount == 0 {
+        return;
+    }
+    match asset {
+        FeeAsset::Near => {
+            Promise::new(receiver_id.clone()).transfer(NearToken::from_yoctonear(amount));
+        }
+        FeeAsset::Token {
+            escrow_account,
+            trace_path,
+            base_denom,
+        } => {
+            ext_channel_escrow::ext(escrow_account.clone())
+                .with_attached_deposit(NearToken::from_yoctonear(1))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(6))
+                .with_unused_gas_weight(0)
+                .do_transfer(
+                    trace_path.clone(),
+                    base_denom.clone(),
+                    receiver_id.clone(),
+                    U128(amount),
+                )
+                .then(
+                    ext_fee_callback::ext(env::current_account_id())
+                        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                        .with_unused_gas_weight(0)
+                        .on_token_fee_paid(asset.clone(), receiver_id.clone(), U128(amount)),
+                );
+            ExtraDepositCost::add(1);
+        }
+    }
+}
+
+/// Split the given channel version to the app version, if it is a version of ICS-29.
+fn split_version(version: &Version) -> Result<Option<Version>, ChannelError> {
+    match serde_json::from_str::<FeeMetadata>(version.to_string().as_str()) {
+        Ok(metadata) => {
+            if metadata.fee_version != FEE_VERSION {
+                return Err(ChannelError::AppModule {
+                    description: format!("ERR_INVALID_FEE_VERSION: {}", metadata.fee_version),
+                });
+            }
+            Ok(Some(Version::new(metadata.app_version)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Get the app version of the given channel version, which may be a version of ICS-29.
+pub fn app_version_of(version: &Version) -> Version {
+    split_version(version)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| version.clone())
+}
+
+/// Wrap the given app version as a version of ICS-29.
+fn wrap_version(app_version: &Version) -> Version {
+    Version::new(
+        serde_json::to_string(&FeeMetadata {
+            fee_version: FEE_VERSION.to_string(),
+            app_version: app_version.to_string(),
+        })
+        .unwrap(),
+    )
+}
+
+impl<M: Module> FeeMiddleware<M> {
+    pub fn new(app: M) -> Self {
+        Self {
+            app,
+            fee_enabled_channels: UnorderedSet::new(StorageKey::FeeEnabledChannels),
+            payees: LookupMap::new(StorageKey::FeePayees),
+            counterparty_payees: LookupMap::new(StorageKey::FeeCounterpartyPayees),
+            escrowed_fees: UnorderedMap::new(StorageKey::EscrowedPacketFees),
+        }
+    }
+    ///
+    pub fn is_fee_enabled(&self, port_id: &PortId, channel_id: &ChannelId) -> bool {
+        self.fee_enabled_channels
+            .contains(&(port_id.clone(), channel_id.clone()))
+    }
+    ///
+    pub fn get_fee_enabled_channels(&self) -> Vec<(PortId, ChannelId)> {
+        self.fee_enabled_channels.iter().cloned().collect()
+    }
+    ///
+    pub fn get_fee_enabled_channels_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<(PortId, ChannelId)> {
+        Page::scan(
+            self.fee_enabled_channels.iter(),
+            self.fee_enabled_channels.len().into(),
+            from_index,
+            limit,
+            |channel| Some(channel.clone()),
+        )
+    }
+    ///
+    pub fn get_escrowed_fees(&self, packet_key: &PacketKey) -> Vec<EscrowedPacketFee> {
+        self.escrowed_fees
+            .get(packet_key)
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// Register the payee in NEAR protocol of a relayer for the given channel.
+    pub fn register_payee(&mut self, channel_id: ChannelId, relayer: AccountId, payee: AccountId) {
+        self.payees.insert((channel_id, relayer), payee);
+    }
+    ///
+    pub fn get_payee(&self, channel_id: &ChannelId, relayer: &AccountId) -> Option<AccountId> {
+        self.payees
+            .get(&(channel_id.clone(), relayer.clone()))
+            .cloned()
+    }
+    /// Register the payee on the counterparty chain of a relayer for the given channel.
+    pub fn register_counterparty_payee(
+        &mut self,
+        channel_id: ChannelId,
+        relayer: AccountId,
+        counterparty_payee: String,
+    ) {
+        self.counterparty_payees
+            .insert((channel_id, relayer), counterparty_payee);
+    }
+    ///
+    pub fn get_counterparty_payee(
+        &self,
+        channel_id: &ChannelId,
+        relayer: &AccountId,
+    ) -> Option<String> {
+        self.counterparty_payees
+            .get(&(channel_id.clone(), relayer.clone()))
+            .cloned()
+    }
+    /// Escrow a fee for the given packet.
+    ///
+    /// The caller should have already received the asset of the fee.
+    pub fn escrow_packet_fee(&mut self, packet_key: PacketKey, escrowed_fee: EscrowedPacketFee) {
+        assert!(
+            self.is_fee_enabled(&packet_key.0, &packet_key.1),
+            "ERR_CHANNEL_IS_NOT_FEE_ENABLED"
+        );
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_FEE_ESCROWED","port_id":"{}","channel_id":"{}","sequence":"{}","fee":{}}}"#,
+            packet_key.0,
+            packet_key.1,
+            packet_key.2,
+            serde_json::to_string(&escrowed_fee).unwrap()
+        );
+        let mut fees = self.get_escrowed_fees(&packet_key);
+        fees.push(escrowed_fee);
+        self.escrowed_fees.insert(packet_key, fees);
+    }
+    /// Get the account to receive the fees for the given relayer.
+    fn payee_of(&self, channel_id: &ChannelId, relayer: &Signer) -> Option<AccountId> {
+        let relayer = AccountId::from_str(relayer.as_ref()).ok()?;
+        Some(self.get_payee(channel_id, &relayer).unwrap_or(relayer))
+    }
+    /// Pay the receiving fees and acknowledgement fees of the given packet to the relayers,
+    /// and refund the timeout fees.
+    fn distribute_fees_on_ack(
+        &mut self,
+        packet: &Packet,
+        forward_relayer_address: &str,
+        reverse_relayer: &Signer,
+    ) {
+        let key = packet_key_of(packet);
+        let fees = match self.escrowed_fees.remove(&key) {
+            Some(fees) => fees,
+            None => return,
+        };
+        let forward_relayer = AccountId::from_str(forward_relayer_address).ok();
+        let reverse_relayer = self.payee_of(&packet.chan_id_on_a, reverse_relayer);
+        for escrowed_fee in fees.iter() {
+            let mut refund_amount = escrowed_fee.fee.timeout_fee.0;
+            match &forward_relayer {
+                Some(account) => pay_fee(&escrowed_fee.asset, account, escrowed_fee.fee.recv_fee.0),
+                None => refund_amount += escrowed_fee.fee.recv_fee.0,
+            }
+            match &reverse_relayer {
+                Some(account) => pay_fee(&escrowed_fee.asset, account, escrowed_fee.fee.ack_fee.0),
+                None => refund_amount += escrowed_fee.fee.ack_fee.0,
+            }
+            pay_fee(
+                &escrowed_fee.asset,
+                &escrowed_fee.refund_account,
+                refund_amount,
+            );
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_FEES_DISTRIBUTED","port_id":"{}","channel_id":"{}","sequence":"{}","forward_relayer":"{}","reverse_relayer":"{}"}}"#,
+            key.0,
+            key.1,
+            key.2,
+            forward_relayer_address,
+            reverse_relayer.map_or(String::new(), |account| account.to_string())
+        );
+    }
+    /// Pay the timeout fees of the given packet to the relayer,
+    /// and refund the receiving fees and acknowledgement fees.
+    fn distribute_fees_on_timeout(&mut self, packet: &Packet, timeout_relayer: &Signer) {
+        let key = packet_key_of(packet);
+        let fees = match self.escrowed_fees.remove(&key) {
+            Some(fees) => fees,
+            None => return,
+        };
+        let timeout_relayer = self.payee_of(&packet.chan_id_on_a, timeout_relayer);
+        for escrowed_fee in fees.iter() {
+            let mut refund_amount = escrowed_fee.fee.recv_fee.0 + escrowed_fee.fee.ack_fee.0;
+            match &timeout_relayer {
+                Some(account) => {
+                    pay_fee(&escrowed_fee.asset, account, escrowed_fee.fee.timeout_fee.0)
+                }
+                None => refund_amount += escrowed_fee.fee.timeout_fee.0,
+            }
+            pay_fee(
+                &escrowed_fee.asset,
+                &escrowed_fee.refund_account,
+                refund_amount,
+            );
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_FEES_DISTRIBUTED","port_id":"{}","channel_id":"{}","sequence":"{}","timeout_relayer":"{}"}}"#,
+            key.0,
+            key.1,
+            key.2,
+            timeout_relayer.map_or(String::new(), |account| account.to_string())
+        );
+    }
+    /// Disable the fees of the given channel. The fees escrowed for the packets of the
+    /// channel are refunded by `refund_fees_of_disabled_channel`.
+    fn on_fee_enabled_channel_closed(&mut self, port_id: &PortId, channel_id: &ChannelId) {
+        self.fee_enabled_channels
+            .remove(&(port_id.clone(), channel_id.clone()));
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"CHANNEL_FEES_DISABLED","port_id":"{}","channel_id":"{}"}}"#,
+            port_id,
+            channel_id
+        );
+    }
+    /// Refund all fees escrowed for the given packets of a channel whose fees are disabled,
+    /// returns the sequences of the packets whose fees are refunded.
+    pub fn refund_fees_of_disabled_channel(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        sequences: &[u64],
+    ) -> Vec<u64> {
+        assert!(
+            !self.is_fee_enabled(port_id, channel_id),
+            "ERR_CHANNEL_IS_FEE_ENABLED"
+        );
+        let mut refunded_sequences = vec![];
+        for sequence in sequences {
+            let key = (port_id.clone(), channel_id.clone(), *sequence);
+            if let Some(fees) = self.escrowed_fees.remove(&key) {
+                fees.iter().for_each(|escrowed_fee| {
+                    pay_fee(
+                        &escrowed_fee.asset,
+                        &escrowed_fee.refund_account,
+                        escrowed_fee.fee.total(),
+                    )
+                });
+                refunded_sequences.push(*sequence);
+            }
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_FEES_REFUNDED","port_id":"{}","channel_id":"{}","sequences":{:?}}}"#,
+            port_id,
+            channel_id,
+            refunded_sequences
+        );
+        refunded_sequences
+    }
+    /// Parse the given acknowledgement of a packet sent in a fee enabled channel.
+    fn parse_incentivized_ack(
+        acknowledgement: &Acknowledgement,
+    ) -> Result<(Acknowledgement, String), PacketError> {
+        let ack = serde_json::from_slice::<IncentivizedAcknowledgement>(acknowledgement.as_bytes())
+            .map_err(|e| PacketError::AppModule {
+                description: format!("ERR_INVALID_INCENTIVIZED_ACKNOWLEDGEMENT: {}", e),
+            })?;
+        let app_ack = Acknowledgement::try_from(ack.app_acknowledgement.0).map_err(|e| {
+            PacketError::AppModule {
+                description: format!("ERR_INVALID_APP_ACKNOWLEDGEMENT: {}", e),
+            }
+        })?;
+        Ok((app_ack, ack.forward_relayer_address))
+    }
+}
+
+/// Get the key of the given packet on the sending chain.
+fn packet_key_of(packet: &Packet) -> PacketKey {
+    (
+        packet.port_id_on_a.clone(),
+        packet.chan_id_on_a.clone(),
+        u64::from(packet.seq_on_a),
+    )
+}
+
+impl<M: Module> Module for FeeMiddleware<M> {
+    fn on_chan_open_init_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<Version, ChannelError> {
+        match split_version(version)? {
+            Some(app_version) => self
+                .app
+                .on_chan_open_init_validate(
+                    order,
+                    connection_hops,
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    &app_version,
+                )
+                .map(|version| wrap_version(&version)),
+            None => self.app.on_chan_open_init_validate(
+                order,
+                connection_hops,
+                port_id,
+                channel_id,
+                counterparty,
+                version,
+            ),
+        }
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        match split_version(version)? {
+            Some(app_version) => {
+                let (extras, version) = self.app.on_chan_open_init_execute(
+                    order,
+                    connection_hops,
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    &app_version,
+                )?;
+                self.fee_enabled_channels
+                    .insert((port_id.clone(), channel_id.clone()));
+                Ok((extras, wrap_version(&version)))
+            }
+            None => self.app.on_chan_open_init_execute(
+                order,
+                connection_hops,
+                port_id,
+                channel_id,
+                counterparty,
+                version,
+            ),
+        }
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        match split_version(counterparty_version)? {
+            Some(app_version) => self
+                .app
+                .on_chan_open_try_validate(
+                    order,
+                    connection_hops,
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    &app_version,
+                )
+                .map(|version| wrap_version(&version)),
+            None => self.app.on_chan_open_try_validate(
+                order,
+                connection_hops,
+                port_id,
+                channel_id,
+                counterparty,
+                counterparty_version,
+            ),
+        }
+    }
+
+    fn on_chan_open_try_execute(
+        &mut self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        match split_version(counterparty_version)? {
+            Some(app_version) => {
+                let (extras, version) = self.app.on_chan_open_try_execute(
+                    order,
+                    connection_hops,
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    &app_version,
+                )?;
+                self.fee_enabled_channels
+                    .insert((port_id.clone(), channel_id.clone()));
+                Ok((extras, wrap_version(&version)))
+            }
+            None => self.app.on_chan_open_try_execute(
+                order,
+                connection_hops,
+                port_id,
+                channel_id,
+                counterparty,
+                counterparty_version,
+            ),
+        }
+    }
+
+    fn on_chan_open_ack_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        if !self.is_fee_enabled(port_id, channel_id) {
+            return self
+                .app
+                .on_chan_open_ack_validate(port_id, channel_id, counterparty_version);
+        }
+        let app_version =
+            split_version(counterparty_version)?.ok_or_else(|| ChannelError::AppModule {
+                description: format!(
+                    "ERR_COUNTERPARTY_VERSION_IS_NOT_FEE_ENABLED: {}",
+                    counterparty_version
+                ),
+            })?;
+        self.app
+            .on_chan_open_ack_validate(port_id, channel_id, &app_version)
+    }
+
+    fn on_chan_open_ack_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<ModuleExtras, ChannelError> {
+        match self.is_fee_enabled(port_id, channel_id) {
+            true => {
+                let app_version = split_version(counterparty_version)?
+                    .unwrap_or_else(|| counterparty_version.clone());
+                self.app
+                    .on_chan_open_ack_execute(port_id, channel_id, &app_version)
+            }
+            false => self
+                .app
+                .on_chan_open_ack_execute(port_id, channel_id, counterparty_version),
+        }
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        self.app.on_chan_open_confirm_validate(port_id, channel_id)
+    }
+
+    fn on_chan_open_confirm_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        self.app.on_chan_open_confirm_execute(port_id, channel_id)
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        self.app.on_chan_close_init_validate(port_id, channel_id)
+    }
+
+    fn on_chan_close_init_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let extras = self.app.on_chan_close_init_execute(port_id, channel_id)?;
+        self.on_fee_enabled_channel_closed(port_id, channel_id);
+        Ok(extras)
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        self.app.on_chan_close_confirm_validate(port_id, channel_id)
+    }
+
+    fn on_chan_close_confirm_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let extras = self
+            .app
+            .on_chan_close_confirm_execute(port_id, channel_id)?;
+        self.on_fee_enabled_channel_closed(port_id, channel_id);
+        Ok(extras)
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        let (extras, ack) = self.app.on_recv_packet_execute(packet, relayer);
+        if !self.is_fee_enabled(&packet.port_id_on_b, &packet.chan_id_on_b) {
+            return (extras, ack);
+        }
+        let forward_relayer_address = AccountId::from_str(relayer.as_ref())
+            .ok()
+            .and_then(|relayer| self.get_counterparty_payee(&packet.chan_id_on_b, &relayer))
+            .unwrap_or_default();
+        if is_async_ack_placeholder(&ack) {
+            // The acknowledgement will be wrapped when it is written.
+            async_ack_forward_relayers().insert(
+                (
+                    packet.port_id_on_b.clone(),
+                    packet.chan_id_on_b.clone(),
+                    u64::from(packet.seq_on_a),
+                ),
+                forward_relayer_address,
+            );
+            return (extras, ack);
+        }
+        (extras, incentivized_ack(ack, forward_relayer_address))
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        if !self.is_fee_enabled(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            return self
+                .app
+                .on_acknowledgement_packet_validate(packet, acknowledgement, relayer);
+        }
+        let (app_ack, _) = Self::parse_incentivized_ack(acknowledgement)?;
+        self.app
+            .on_acknowledgement_packet_validate(packet, &app_ack, relayer)
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        if !self.is_fee_enabled(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            return self
+                .app
+                .on_acknowledgement_packet_execute(packet, acknowledgement, relayer);
+        }
+        let (app_ack, forward_relayer_address) = match Self::parse_incentivized_ack(acknowledgement)
+        {
+            Ok(result) => result,
+            Err(e) => return (ModuleExtras::empty(), Err(e)),
+        };
+        let (extras, result) = self
+            .app
+            .on_acknowledgement_packet_execute(packet, &app_ack, relayer);
+        if result.is_ok() {
+            self.distribute_fees_on_ack(packet, forward_relayer_address.as_str(), relayer);
+        }
+        (extras, result)
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        self.app.on_timeout_packet_validate(packet, relayer)
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let (extras, result) = self.app.on_timeout_packet_execute(packet, relayer);
+        if result.is_ok() && self.is_fee_enabled(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            self.distribute_fees_on_timeout(packet, relayer);
+        }
+        (extras, result)
+    }
+}
+
+impl<M: Module + ChannelUpgradeModule> ChannelUpgradeModule for FeeMiddleware<M> {
+    fn on_chan_upgrade_init(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        proposed_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        match split_version(proposed_version)? {
+            Some(app_version) => self
+                .app
+                .on_chan_upgrade_init(
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    proposed_order,
+                    proposed_connection_hops,
+                    &app_version,
+                )
+                .map(|version| wrap_version(&version)),
+            None => self.app.on_chan_upgrade_init(
+                port_id,
+                channel_id,
+                counterparty,
+                proposed_order,
+                proposed_connection_hops,
+                proposed_version,
+            ),
+        }
+    }
+
+    fn on_chan_upgrade_try(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        match split_version(counterparty_version)? {
+            Some(app_version) => self
+                .app
+                .on_chan_upgrade_try(
+                    port_id,
+                    channel_id,
+                    counterparty,
+                    proposed_order,
+                    proposed_connection_hops,
+                    &app_version,
+                )
+                .map(|version| wrap_version(&version)),
+            None => self.app.on_chan_upgrade_try(
+                port_id,
+                channel_id,
+                counterparty,
+                proposed_order,
+                proposed_connection_hops,
+                counterparty_version,
+            ),
+        }
+    }
+
+    fn on_chan_upgrade_ack(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        match split_version(counterparty_version)? {
+            Some(app_version) => self
+                .app
+                .on_chan_upgrade_ack(port_id, channel_id, &app_version),
+            None => self
+                .app
+                .on_chan_upgrade_ack(port_id, channel_id, counterparty_version),
+        }
+    }
+
+    fn on_chan_upgrade_open(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        version: &Version,
+    ) {
+        // The version is validated in the previous steps of the upgrade.
+        match split_version(version).ok().flatten() {
+            Some(app_version) => {
+                self.fee_enabled_channels
+                    .insert((port_id.clone(), channel_id.clone()));
+                self.app.on_chan_upgrade_open(
+                    port_id,
+                    channel_id,
+                    order,
+                    connection_hops,
+                    &app_version,
+                )
+            }
+            None => {
+                if self.is_fee_enabled(port_id, channel_id) {
+                    self.on_fee_enabled_channel_closed(port_id, channel_id);
+                }
+                self.app
+                    .on_chan_upgrade_open(port_id, channel_id, order, connection_hops, version)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::applications::transfer::TransferModule;
+    use near_sdk::{
+        test_utils::{get_created_receipts, VMContextBuilder},
+        testing_env, PromiseResult, RuntimeFeesConfig,
+    };
+
+    fn token_asset() -> FeeAsset {
+        FeeAsset::Token {
+            escrow_account: "channel-0.ef.transfer.v1.nearibc.testnet".parse().unwrap(),
+            trace_path: String::new(),
+            base_denom: "usdt.testnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fee_version() {
+        let version = wrap_version(&Version::new("ics20-1".to_string()));
+        assert_eq!(
+            version.to_string(),
+            r#"{"fee_version":"ics29-1","app_version":"ics20-1"}"#
+        );
+        assert_eq!(
+            split_version(&version).unwrap(),
+            Some(Version::new("ics20-1".to_string()))
+        );
+        assert_eq!(
+            split_version(&Version::new("ics20-1".to_string())).unwrap(),
+            None
+        );
+        assert!(split_version(&Version::new(
+            r#"{"fee_version":"ics29-2","app_version":"ics20-1"}"#.to_string()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_incentivized_ack() {
+        let ack = serde_json::from_str::<IncentivizedAcknowledgement>(
+            r#"{"app_acknowledgement":"eyJyZXN1bHQiOiJBUT09In0=","forward_relayer_address":"relayer.near","underlying_app_success":true}"#,
+        )
+        .unwrap();
+        assert_eq!(ack.app_acknowledgement.0, br#"{"result":"AQ=="}"#.to_vec());
+        assert_eq!(ack.forward_relayer_address, "relayer.near");
+    }
+
+    #[test]
+    fn test_unpaid_token_fees() {
+        testing_env!(VMContextBuilder::new().build());
+        let relayer: AccountId = "relayer.testnet".parse().unwrap();
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 0);
+        credit_unpaid_token_fee(&relayer, &token_asset(), 100);
+        credit_unpaid_token_fee(&relayer, &token_asset(), 50);
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 150);
+        assert_eq!(unpaid_token_fee_of(&relayer, &FeeAsset::Near), 0);
+        assert_eq!(take_unpaid_token_fee(&relayer, &token_asset()), 150);
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 0);
+    }
+
+    #[test]
+    fn test_failed_token_fee_is_credited() {
+        let mut context = VMContextBuilder::new();
+        let current_account: AccountId = "v1.nearibc.testnet".parse().unwrap();
+        testing_env!(context
+            .current_account_id(current_account.clone())
+            .predecessor_account_id(current_account.clone())
+            .build());
+        let mut contract = crate::NearIbcContract::init("registry.testnet".parse().unwrap());
+        let relayer: AccountId = "relayer.testnet".parse().unwrap();
+        let on_paid = |contract: &mut crate::NearIbcContract, result: PromiseResult| {
+            testing_env!(
+                context.build(),
+                near_sdk::test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![result],
+            );
+            contract.on_token_fee_paid(token_asset(), relayer.clone(), U128(100));
+        };
+        on_paid(
+            &mut contract,
+            PromiseResult::Successful(serde_json::to_vec(&U128(100)).unwrap()),
+        );
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 0);
+        on_paid(
+            &mut contract,
+            PromiseResult::Successful(serde_json::to_vec(&U128(0)).unwrap()),
+        );
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 100);
+        on_paid(&mut contract, PromiseResult::Failed);
+        assert_eq!(unpaid_token_fee_of(&relayer, &token_asset()), 200);
+    }
+
+    #[test]
+    fn test_refund_fees_of_disabled_channel() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("v1.nearibc.testnet".parse().unwrap())
+            .build());
+        let mut fee_middleware = FeeMiddleware::new(TransferModule());
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        fee_middleware
+            .fee_enabled_channels
+            .insert((port_id.clone(), channel_id.clone()));
+        for sequence in 1..=2 {
+            fee_middleware.escrow_packet_fee(
+                (port_id.clone(), channel_id.clone(), sequence),
+                EscrowedPacketFee {
+                    fee: PacketFee {
+                        recv_fee: U128(1),
+                        ack_fee: U128(2),
+                        timeout_fee: U128(3),
+                    },
+                    asset: FeeAsset::Near,
+                    refund_account: "alice.testnet".parse().unwrap(),
+                },
+            );
+        }
+        // Closing the channel only disables the fees.
+        fee_middleware.on_fee_enabled_channel_closed(&port_id, &channel_id);
+        assert!(!fee_middleware.is_fee_enabled(&port_id, &channel_id));
+        assert!(get_created_receipts().is_empty());
+        assert_eq!(
+            fee_middleware
+                .get_escrowed_fees(&(port_id.clone(), channel_id.clone(), 1))
+                .len(),
+            1
+        );
+        // The fees are refunded by the given sequences.
+        assert_eq!(
+            fee_middleware.refund_fees_of_disabled_channel(&port_id, &channel_id, &[1, 3]),
+            vec![1]
+        );
+        assert_eq!(get_created_receipts().len(), 1);
+        assert!(fee_middleware
+            .get_escrowed_fees(&(port_id.clone(), channel_id.clone(), 1))
+            .is_empty());
+        assert_eq!(
+            fee_middleware
+                .get_escrowed_fees(&(port_id.clone(), channel_id.clone(), 2))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CHANNEL_IS_FEE_ENABLED")]
+    fn test_refund_fees_of_fee_enabled_channel() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut fee_middleware = FeeMiddleware::new(TransferModule());
+        fee_middleware
+            .fee_enabled_channels
+            .insert((PortId::transfer(), ChannelId::new(0)));
+        fee_middleware.refund_fees_of_disabled_channel(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            &[1],
+        );
+    }
+}
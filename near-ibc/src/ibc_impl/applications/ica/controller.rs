@@ -0,0 +1,403 @@
+//! The controller module of ICS-27 Interchain Accounts.
+//!
+//! Each NEAR account can register interchain accounts through its own port
+//! `icacontroller-<owner>`, one for each connection.
+use super::{IcaMetadata, CONTROLLER_PORT_ID_PREFIX, HOST_PORT_ID_STR};
+use crate::{context::NearIbcStoreHost, prelude::*, StorageKey};
+use core::{fmt::Debug, str::FromStr};
+use ibc::{
+    core::{
+        channel::types::{
+            acknowledgement::{Acknowledgement, AcknowledgementStatus, StatusValue},
+            channel::{Counterparty, Order, State},
+            error::{ChannelError, PacketError},
+            packet::Packet,
+            Version,
+        },
+        host::{
+            types::{
+                identifiers::{ChannelId, ConnectionId, PortId},
+                path::ChannelEndPath,
+            },
+            ValidationContext,
+        },
+        router::{module::Module, types::module::ModuleExtras},
+    },
+    primitives::Signer,
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json,
+    store::{LookupMap, UnorderedMap},
+    AccountId,
+};
+
+/// An interchain account registered by a NEAR account.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct InterchainAccount {
+    pub connection_id: ConnectionId,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+    /// The address of the account on the host chain,
+    /// which is available after the channel is opened.
+    pub address: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct IcaControllerModule {
+    /// The interchain accounts of owners, keyed by the connection id.
+    pub interchain_accounts: LookupMap<AccountId, UnorderedMap<ConnectionId, InterchainAccount>>,
+}
+
+impl NearIbcStoreHost for IcaControllerModule {}
+
+impl Default for IcaControllerModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IcaControllerModule {
+    pub fn new() -> Self {
+        Self {
+            interchain_accounts: LookupMap::new(StorageKey::IcaControllerAccounts),
+        }
+    }
+    /// Get the port id of ICA controller for the given owner.
+    pub fn port_id_of(owner: &AccountId) -> PortId {
+        PortId::from_str(format!("{}{}", CONTROLLER_PORT_ID_PREFIX, owner).as_str())
+            .expect("ERR_INVALID_ICA_CONTROLLER_PORT_ID")
+    }
+    /// Get the owner of the given port id of ICA controller.
+    pub fn owner_of(port_id: &PortId) -> Result<AccountId, ChannelError> {
+        port_id
+            .as_str()
+            .strip_prefix(CONTROLLER_PORT_ID_PREFIX)
+            .and_then(|owner| AccountId::from_str(owner).ok())
+            .ok_or_else(|| ChannelError::AppModule {
+                description: format!("ERR_INVALID_ICA_CONTROLLER_PORT_ID: {}", port_id),
+            })
+    }
+    /// Get the interchain account of the given owner on the given connection.
+    pub fn get_interchain_account(
+        &self,
+        owner: &AccountId,
+        connection_id: &ConnectionId,
+    ) -> Option<InterchainAccount> {
+        self.interchain_accounts
+            .get(owner)
+            .and_then(|accounts| accounts.get(connection_id).cloned())
+    }
+    /// Get all interchain accounts of the given owner.
+    pub fn get_interchain_accounts_of(&self, owner: &AccountId) -> Vec<InterchainAccount> {
+        self.interchain_accounts
+            .get(owner)
+            .map_or_else(Vec::new, |accounts| {
+                accounts
+                    .values()
+                    .cloned()
+                    .collect::<Vec<InterchainAccount>>()
+            })
+    }
+    //
+    fn find_connection_of_channel(
+        &self,
+        owner: &AccountId,
+        channel_id: &ChannelId,
+    ) -> Option<ConnectionId> {
+        self.interchain_accounts.get(owner).and_then(|accounts| {
+            accounts
+                .iter()
+                .find(|(_, account)| account.channel_id.eq(channel_id))
+                .map(|(connection_id, _)| connection_id.clone())
+        })
+    }
+    //
+    fn parse_metadata(version: &Version) -> Result<IcaMetadata, ChannelError> {
+        let metadata =
+            serde_json::from_str::<IcaMetadata>(version.to_string().as_str()).map_err(|e| {
+                ChannelError::AppModule {
+                    description: format!("ERR_INVALID_ICA_METADATA: {}", e),
+                }
+            })?;
+        metadata
+            .validate_basic()
+            .map_err(|description| ChannelError::AppModule { description })?;
+        Ok(metadata)
+    }
+}
+
+impl Module for IcaControllerModule {
+    fn on_chan_open_init_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        _channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<Version, ChannelError> {
+        let owner = Self::owner_of(port_id)?;
+        if order != Order::Ordered {
+            return Err(ChannelError::AppModule {
+                description: "ERR_ICA_CHANNEL_MUST_BE_ORDERED".to_string(),
+            });
+        }
+        if counterparty.port_id().as_str() != HOST_PORT_ID_STR {
+            return Err(ChannelError::AppModule {
+                description: format!("ERR_INVALID_ICA_HOST_PORT_ID: {}", counterparty.port_id()),
+            });
+        }
+        let metadata = Self::parse_metadata(version)?;
+        if connection_hops.len() != 1
+            || metadata.controller_connection_id != connection_hops[0].to_string()
+        {
+            return Err(ChannelError::AppModule {
+                description: "ERR_INVALID_ICA_CONTROLLER_CONNECTION_ID".to_string(),
+            });
+        }
+        // Only one active channel is allowed for an owner on a connection.
+        if let Some(account) = self.get_interchain_account(&owner, &connection_hops[0]) {
+            let near_ibc_store = Self::get_near_ibc_store();
+            if let Ok(channel_end) = near_ibc_store
+                .channel_end(&ChannelEndPath::new(&account.port_id, &account.channel_id))
+            {
+                if !channel_end.state_matches(&State::Closed) {
+                    return Err(ChannelError::AppModule {
+                        description: format!(
+                            "ERR_ACTIVE_ICA_CHANNEL_EXISTS: {}",
+                            account.channel_id
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(version.clone())
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        _order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        let owner = Self::owner_of(port_id)?;
+        let metadata = Self::parse_metadata(version)?;
+        if !self.interchain_accounts.contains_key(&owner) {
+            self.interchain_accounts.insert(
+                owner.clone(),
+                UnorderedMap::new(StorageKey::IcaControllerAccountsOfOwner {
+                    owner: owner.clone(),
+                }),
+            );
+        }
+        let accounts = self.interchain_accounts.get_mut(&owner).unwrap();
+        // Keep the address of the previous account on the same connection, as the host
+        // will reuse the account when the channel is reopened.
+        let address = accounts
+            .get(&connection_hops[0])
+            .and_then(|account| account.address.clone())
+            .or_else(|| match metadata.address.is_empty() {
+                true => None,
+                false => Some(metadata.address.clone()),
+            });
+        accounts.insert(
+            connection_hops[0].clone(),
+            InterchainAccount {
+                connection_id: connection_hops[0].clone(),
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                address,
+            },
+        );
+        log!(
+            "Interchain account of {} on {} is being registered through channel {}.",
+            owner,
+            connection_hops[0],
+            channel_id
+        );
+        Ok((ModuleExtras::empty(), version.clone()))
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_CONTROLLER_DOES_NOT_ACCEPT_CHANNEL_OPEN_TRY".to_string(),
+        })
+    }
+
+    fn on_chan_open_try_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_CONTROLLER_DOES_NOT_ACCEPT_CHANNEL_OPEN_TRY".to_string(),
+        })
+    }
+
+    fn on_chan_open_ack_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        let owner = Self::owner_of(port_id)?;
+        let metadata = Self::parse_metadata(counterparty_version)?;
+        if metadata.address.is_empty() {
+            return Err(ChannelError::AppModule {
+                description: "ERR_EMPTY_INTERCHAIN_ACCOUNT_ADDRESS".to_string(),
+            });
+        }
+        match self.find_connection_of_channel(&owner, channel_id) {
+            Some(connection_id)
+                if metadata.controller_connection_id == connection_id.to_string() =>
+            {
+                Ok(())
+            }
+            _ => Err(ChannelError::AppModule {
+                description: "ERR_INVALID_ICA_CONTROLLER_CONNECTION_ID".to_string(),
+            }),
+        }
+    }
+
+    fn on_chan_open_ack_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let owner = Self::owner_of(port_id)?;
+        let metadata = Self::parse_metadata(counterparty_version)?;
+        let connection_id = self
+            .find_connection_of_channel(&owner, channel_id)
+            .ok_or_else(|| ChannelError::AppModule {
+                description: "ERR_INTERCHAIN_ACCOUNT_NOT_FOUND".to_string(),
+            })?;
+        let accounts = self.interchain_accounts.get_mut(&owner).unwrap();
+        if let Some(account) = accounts.get_mut(&connection_id) {
+            account.address = Some(metadata.address.clone());
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"INTERCHAIN_ACCOUNT_REGISTERED","owner":"{}","connection_id":"{}","channel_id":"{}","address":"{}"}}"#,
+            owner,
+            connection_id,
+            channel_id,
+            metadata.address
+        );
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_CONTROLLER_DOES_NOT_ACCEPT_CHANNEL_OPEN_CONFIRM".to_string(),
+        })
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_CHANNEL_CAN_NOT_BE_CLOSED_BY_USER".to_string(),
+        })
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        _packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        (
+            ModuleExtras::empty(),
+            AcknowledgementStatus::error(
+                StatusValue::new("ERR_ICA_CONTROLLER_DOES_NOT_ACCEPT_PACKETS").unwrap(),
+            )
+            .into(),
+        )
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        _packet: &Packet,
+        _acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        Ok(())
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"INTERCHAIN_TX_ACKNOWLEDGED","port_id":"{}","channel_id":"{}","sequence":"{}","acknowledgement":{}}}"#,
+            packet.port_id_on_a,
+            packet.chan_id_on_a,
+            packet.seq_on_a,
+            serde_json::to_string(
+                &String::from_utf8(acknowledgement.as_bytes().to_vec()).unwrap_or_default()
+            )
+            .unwrap()
+        );
+        (ModuleExtras::empty(), Ok(()))
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        _packet: &Packet,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        Ok(())
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        // The ordered channel will be closed by IBC/TAO, the owner needs to register
+        // the interchain account again to reopen it.
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"INTERCHAIN_TX_TIMEOUT","port_id":"{}","channel_id":"{}","sequence":"{}"}}"#,
+            packet.port_id_on_a,
+            packet.chan_id_on_a,
+            packet.seq_on_a,
+        );
+        (ModuleExtras::empty(), Ok(()))
+    }
+}
@@ -0,0 +1,600 @@
+//! The host module of ICS-27 Interchain Accounts.
+//!
+//! For each controller port on each connection, a dedicated sub-account of the `near-ibc`
+//! account is created (when the ICA channel is opened) and deployed with the
+//! `interchain-account` contract. The messages in `CosmosTx` of incoming packets are
+//! NEAR actions, which will be executed by promises from that sub-account:
+//!
+//! * `/near.FunctionCall` - the value is a JSON object with fields `receiver_id`,
+//!   `method_name`, `args` (base64), `deposit` and `gas`.
+//! * `/near.Transfer` - the value is a JSON object with fields `receiver_id` and `amount`.
+//!
+//! The acknowledgement of a packet is held (refer to `transfer::async_ack` module) until
+//! the actions are executed. The result of a success acknowledgement is the base64 encoded
+//! JSON array of `InterchainAccountActionResult`, one for each message.
+use super::{
+    CosmosTx, IcaMetadata, IcaPacketData, CONTROLLER_PORT_ID_PREFIX, HOST_PORT_ID_STR,
+    TYPE_EXECUTE_TX,
+};
+use crate::{
+    context::NearIbcStoreHost,
+    ibc_impl::applications::transfer::async_ack::{async_ack_placeholder, error_ack},
+    prelude::*,
+    types::Page,
+    StorageKey,
+};
+use core::{fmt::Debug, str::FromStr};
+use ibc::{
+    core::{
+        channel::types::{
+            acknowledgement::Acknowledgement,
+            channel::{Counterparty, Order, State},
+            error::{ChannelError, PacketError},
+            packet::Packet,
+            Version,
+        },
+        host::{
+            types::{
+                identifiers::{ChannelId, ConnectionId, PortId},
+                path::ChannelEndPath,
+            },
+            ValidationContext,
+        },
+        router::{module::Module, types::module::ModuleExtras},
+    },
+    primitives::Signer,
+};
+use ibc_proto::{
+    ibc::{
+        applications::interchain_accounts::v1::CosmosTx as RawCosmosTx,
+        core::channel::v1::Packet as RawPacket,
+    },
+    Protobuf,
+};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::{Base64VecU8, U64},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json::{self, Value},
+    store::UnorderedMap,
+    AccountId, Gas, NearToken, Promise,
+};
+use utils::{interfaces::ext_interchain_account, types::InterchainAccountAction, ExtraDepositCost};
+
+/// The type url of messages for calling a function of a contract.
+pub const TYPE_URL_FUNCTION_CALL: &str = "/near.FunctionCall";
+/// The type url of messages for transferring NEAR.
+pub const TYPE_URL_TRANSFER: &str = "/near.Transfer";
+
+/// An interchain account hosted in NEAR protocol.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct HostedInterchainAccount {
+    pub connection_id: ConnectionId,
+    /// The controller port on the counterparty chain.
+    pub counterparty_port_id: PortId,
+    /// The latest ICA channel of the account.
+    pub channel_id: ChannelId,
+    pub account_id: AccountId,
+}
+
+/// The callback interface for the promise of executing interchain transactions.
+#[ext_contract(ext_ica_host_callback)]
+pub trait IcaHostCallback {
+    /// Write the acknowledgement of the given (protobuf encoded) packet,
+    /// according to the results of the actions executed by the interchain account.
+    fn on_interchain_tx_executed(&mut self, packet: Base64VecU8);
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct IcaHostModule {
+    /// The hosted interchain accounts, keyed by the connection id and the controller port id.
+    pub interchain_accounts: UnorderedMap<(ConnectionId, PortId), HostedInterchainAccount>,
+}
+
+impl NearIbcStoreHost for IcaHostModule {}
+
+impl Default for IcaHostModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IcaHostModule {
+    pub fn new() -> Self {
+        Self {
+            interchain_accounts: UnorderedMap::new(StorageKey::IcaHostAccounts),
+        }
+    }
+    /// Get the account id of the interchain account for the given controller port
+    /// on the given connection.
+    pub fn account_id_of(connection_id: &ConnectionId, counterparty_port_id: &PortId) -> AccountId {
+        let hash = env::sha256(format!("{}/{}", connection_id, counterparty_port_id).as_bytes());
+        AccountId::from_str(
+            format!(
+                "ica-{}.{}",
+                hex::encode(&hash[..16]),
+                env::current_account_id()
+            )
+            .as_str(),
+        )
+        .expect("ERR_INVALID_INTERCHAIN_ACCOUNT_ID")
+    }
+    /// Get all hosted interchain accounts.
+    pub fn get_hosted_interchain_accounts(&self) -> Vec<HostedInterchainAccount> {
+        self.interchain_accounts.values().cloned().collect()
+    }
+    /// Get a page of the hosted interchain accounts.
+    pub fn get_hosted_interchain_accounts_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<HostedInterchainAccount> {
+        Page::scan(
+            self.interchain_accounts.values(),
+            self.interchain_accounts.len().into(),
+            from_index,
+            limit,
+            |account| Some(account.clone()),
+        )
+    }
+    //
+    fn get_interchain_account_wasm() -> Option<Vec<u8>> {
+        env::storage_read(&borsh::to_vec(&StorageKey::InterchainAccountWasm).unwrap())
+    }
+    //
+    fn parse_metadata(version: &Version) -> Result<IcaMetadata, ChannelError> {
+        let metadata =
+            serde_json::from_str::<IcaMetadata>(version.to_string().as_str()).map_err(|e| {
+                ChannelError::AppModule {
+                    description: format!("ERR_INVALID_ICA_METADATA: {}", e),
+                }
+            })?;
+        metadata
+            .validate_basic()
+            .map_err(|description| ChannelError::AppModule { description })?;
+        Ok(metadata)
+    }
+    //
+    fn negotiate_version(
+        connection_hops: &[ConnectionId],
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        let metadata = Self::parse_metadata(counterparty_version)?;
+        if connection_hops.len() != 1
+            || metadata.host_connection_id != connection_hops[0].to_string()
+        {
+            return Err(ChannelError::AppModule {
+                description: "ERR_INVALID_ICA_HOST_CONNECTION_ID".to_string(),
+            });
+        }
+        let address = Self::account_id_of(&connection_hops[0], counterparty.port_id());
+        Ok(Version::new(
+            serde_json::to_string(&IcaMetadata {
+                address: address.to_string(),
+                ..metadata
+            })
+            .unwrap(),
+        ))
+    }
+    //
+    fn parse_actions(packet: &Packet) -> Result<Vec<InterchainAccountAction>, String> {
+        let packet_data = serde_json::from_slice::<IcaPacketData>(&packet.data)
+            .map_err(|e| format!("ERR_INVALID_ICA_PACKET_DATA: {}", e))?;
+        if packet_data.packet_type != TYPE_EXECUTE_TX {
+            return Err(format!(
+                "ERR_UNSUPPORTED_ICA_PACKET_TYPE: {}",
+                packet_data.packet_type
+            ));
+        }
+        let CosmosTx { messages } =
+            <CosmosTx as Protobuf<RawCosmosTx>>::decode_vec(&packet_data.data.0)
+                .map_err(|e| format!("ERR_INVALID_COSMOS_TX: {}", e))?;
+        if messages.is_empty() {
+            return Err("ERR_EMPTY_ICA_MESSAGES".to_string());
+        }
+        messages
+            .into_iter()
+            .map(|message| {
+                let variant = match message.type_url.as_str() {
+                    TYPE_URL_FUNCTION_CALL => "FunctionCall",
+                    TYPE_URL_TRANSFER => "Transfer",
+                    type_url => return Err(format!("ERR_UNSUPPORTED_ICA_MESSAGE: {}", type_url)),
+                };
+                let value = serde_json::from_slice::<Value>(&message.value)
+                    .map_err(|e| format!("ERR_INVALID_ICA_MESSAGE: {}", e))?;
+                let mut action = serde_json::Map::new();
+                action.insert(variant.to_string(), value);
+                serde_json::from_value::<InterchainAccountAction>(Value::Object(action))
+                    .map_err(|e| format!("ERR_INVALID_ICA_MESSAGE: {}", e))
+            })
+            .collect()
+    }
+    //
+    fn execute_tx(&self, packet: &Packet) -> Acknowledgement {
+        let near_ibc_store = Self::get_near_ibc_store();
+        let connection_id = match near_ibc_store.channel_end(&ChannelEndPath::new(
+            &packet.port_id_on_b,
+            &packet.chan_id_on_b,
+        )) {
+            Ok(channel_end) => channel_end.connection_hops()[0].clone(),
+            Err(e) => return error_ack(format!("ERR_ICA_CHANNEL_NOT_FOUND: {}", e)),
+        };
+        let account = match self
+            .interchain_accounts
+            .get(&(connection_id, packet.port_id_on_a.clone()))
+        {
+            Some(account) if account.channel_id == packet.chan_id_on_b => account,
+            _ => return error_ack("ERR_INTERCHAIN_ACCOUNT_NOT_FOUND".to_string()),
+        };
+        let actions = match Self::parse_actions(packet) {
+            Ok(actions) => actions,
+            Err(e) => return error_ack(e),
+        };
+        let gas_of_actions = actions
+            .iter()
+            .fold(Gas::from_gas(0), |sum, action| match action {
+                InterchainAccountAction::FunctionCall { gas, .. } => {
+                    sum.saturating_add(Gas::from_gas(gas.0))
+                }
+                InterchainAccountAction::Transfer { .. } => sum,
+            });
+        ext_interchain_account::ext(account.account_id.clone())
+            .with_static_gas(
+                gas_of_actions
+                    .saturating_add(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4)),
+            )
+            .with_unused_gas_weight(1)
+            .execute(actions)
+            .then(
+                ext_ica_host_callback::ext(env::current_account_id())
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+                    .with_unused_gas_weight(0)
+                    .on_interchain_tx_executed(Base64VecU8(Protobuf::<RawPacket>::encode_vec(
+                        packet.clone(),
+                    ))),
+            );
+        log!(
+            "Packet {}/{}/{} is being executed by interchain account {}.",
+            packet.port_id_on_b,
+            packet.chan_id_on_b,
+            packet.seq_on_a,
+            account.account_id
+        );
+        async_ack_placeholder()
+    }
+}
+
+impl Module for IcaHostModule {
+    fn on_chan_open_init_validate(
+        &self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _version: &Version,
+    ) -> Result<Version, ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_HOST_DOES_NOT_ACCEPT_CHANNEL_OPEN_INIT".to_string(),
+        })
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_HOST_DOES_NOT_ACCEPT_CHANNEL_OPEN_INIT".to_string(),
+        })
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        _channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        if port_id.as_str() != HOST_PORT_ID_STR {
+            return Err(ChannelError::AppModule {
+                description: format!("ERR_INVALID_ICA_HOST_PORT_ID: {}", port_id),
+            });
+        }
+        if order != Order::Ordered {
+            return Err(ChannelError::AppModule {
+                description: "ERR_ICA_CHANNEL_MUST_BE_ORDERED".to_string(),
+            });
+        }
+        if !counterparty
+            .port_id()
+            .as_str()
+            .starts_with(CONTROLLER_PORT_ID_PREFIX)
+        {
+            return Err(ChannelError::AppModule {
+                description: format!(
+                    "ERR_INVALID_ICA_CONTROLLER_PORT_ID: {}",
+                    counterparty.port_id()
+                ),
+            });
+        }
+        let version = Self::negotiate_version(connection_hops, counterparty, counterparty_version)?;
+        let near_ibc_store = Self::get_near_ibc_store();
+        let metadata = Self::parse_metadata(counterparty_version)?;
+        let connection_end = near_ibc_store
+            .connection_end(&connection_hops[0])
+            .map_err(|e| ChannelError::AppModule {
+                description: format!("ERR_CONNECTION_NOT_FOUND: {}", e),
+            })?;
+        if connection_end
+            .counterparty()
+            .connection_id()
+            .map_or(true, |id| {
+                id.to_string() != metadata.controller_connection_id
+            })
+        {
+            return Err(ChannelError::AppModule {
+                description: "ERR_INVALID_ICA_CONTROLLER_CONNECTION_ID".to_string(),
+            });
+        }
+        // Only one active channel is allowed for a controller port on a connection.
+        if let Some(account) = self
+            .interchain_accounts
+            .get(&(connection_hops[0].clone(), counterparty.port_id().clone()))
+        {
+            if let Ok(channel_end) =
+                near_ibc_store.channel_end(&ChannelEndPath::new(port_id, &account.channel_id))
+            {
+                if !channel_end.state_matches(&State::Closed) {
+                    return Err(ChannelError::AppModule {
+                        description: format!(
+                            "ERR_ACTIVE_ICA_CHANNEL_EXISTS: {}",
+                            account.channel_id
+                        ),
+                    });
+                }
+            }
+        } else if Self::get_interchain_account_wasm().is_none() {
+            return Err(ChannelError::AppModule {
+                description: "ERR_INTERCHAIN_ACCOUNT_WASM_NOT_STORED".to_string(),
+            });
+        }
+        Ok(version)
+    }
+
+    fn on_chan_open_try_execute(
+        &mut self,
+        _order: Order,
+        connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        let version = Self::negotiate_version(connection_hops, counterparty, counterparty_version)?;
+        let key = (connection_hops[0].clone(), counterparty.port_id().clone());
+        let account_id = Self::account_id_of(&connection_hops[0], counterparty.port_id());
+        // The account will be reused when the channel is reopened.
+        if !self.interchain_accounts.contains_key(&key) {
+            let wasm =
+                Self::get_interchain_account_wasm().ok_or_else(|| ChannelError::AppModule {
+                    description: "ERR_INTERCHAIN_ACCOUNT_WASM_NOT_STORED".to_string(),
+                })?;
+            #[derive(Serialize, Deserialize, Clone)]
+            #[serde(crate = "near_sdk::serde")]
+            struct Input {
+                near_ibc_account: AccountId,
+            }
+            let args = serde_json::to_vec(&Input {
+                near_ibc_account: env::current_account_id(),
+            })
+            .unwrap();
+            Promise::new(account_id.clone())
+                .create_account()
+                .transfer(NearToken::from_yoctonear(
+                    utils::INIT_BALANCE_FOR_INTERCHAIN_ACCOUNT_CONTRACT,
+                ))
+                .deploy_contract(wasm)
+                .function_call(
+                    "new".to_string(),
+                    args,
+                    NearToken::from_yoctonear(0),
+                    utils::GAS_FOR_SIMPLE_FUNCTION_CALL,
+                );
+            ExtraDepositCost::add(utils::INIT_BALANCE_FOR_INTERCHAIN_ACCOUNT_CONTRACT);
+        }
+        self.interchain_accounts.insert(
+            key,
+            HostedInterchainAccount {
+                connection_id: connection_hops[0].clone(),
+                counterparty_port_id: counterparty.port_id().clone(),
+                channel_id: channel_id.clone(),
+                account_id: account_id.clone(),
+            },
+        );
+        log!(
+            "Interchain account {} is being opened for {} on {} through channel {}.",
+            account_id,
+            counterparty.port_id(),
+            connection_hops[0],
+            channel_id
+        );
+        Ok((ModuleExtras::empty(), version))
+    }
+
+    fn on_chan_open_ack_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_HOST_DOES_NOT_ACCEPT_CHANNEL_OPEN_ACK".to_string(),
+        })
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_chan_open_confirm_execute(
+        &mut self,
+        _port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        if let Some(account) = self
+            .interchain_accounts
+            .values()
+            .find(|account| account.channel_id.eq(channel_id))
+        {
+            log!(
+                r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"INTERCHAIN_ACCOUNT_OPENED","connection_id":"{}","counterparty_port_id":"{}","channel_id":"{}","account_id":"{}"}}"#,
+                account.connection_id,
+                account.counterparty_port_id,
+                channel_id,
+                account.account_id
+            );
+        }
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_ICA_CHANNEL_CAN_NOT_BE_CLOSED_BY_USER".to_string(),
+        })
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        (ModuleExtras::empty(), self.execute_tx(packet))
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        _packet: &Packet,
+        _acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        Err(PacketError::AppModule {
+            description: "ERR_ICA_HOST_DOES_NOT_SEND_PACKETS".to_string(),
+        })
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        _packet: &Packet,
+        _acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        (
+            ModuleExtras::empty(),
+            Err(PacketError::AppModule {
+                description: "ERR_ICA_HOST_DOES_NOT_SEND_PACKETS".to_string(),
+            }),
+        )
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        _packet: &Packet,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        Err(PacketError::AppModule {
+            description: "ERR_ICA_HOST_DOES_NOT_SEND_PACKETS".to_string(),
+        })
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        _packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        (
+            ModuleExtras::empty(),
+            Err(PacketError::AppModule {
+                description: "ERR_ICA_HOST_DOES_NOT_SEND_PACKETS".to_string(),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ibc_proto::google::protobuf::Any;
+
+    fn packet_of(messages: Vec<Any>) -> Packet {
+        let data = IcaPacketData::execute_tx(messages, String::new());
+        Packet {
+            seq_on_a: 1u64.into(),
+            port_id_on_a: PortId::from_str("icacontroller-alice").unwrap(),
+            chan_id_on_a: ChannelId::new(0),
+            port_id_on_b: PortId::from_str(HOST_PORT_ID_STR).unwrap(),
+            chan_id_on_b: ChannelId::new(1),
+            data: serde_json::to_vec(&data).unwrap(),
+            timeout_height_on_b: ibc::core::channel::types::timeout::TimeoutHeight::Never,
+            timeout_timestamp_on_b: ibc::core::primitives::Timestamp::none(),
+        }
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let packet = packet_of(vec![
+            Any {
+                type_url: TYPE_URL_FUNCTION_CALL.to_string(),
+                value: br#"{"receiver_id":"wrap.near","method_name":"near_deposit","args":"e30=","deposit":"1","gas":"5000000000000"}"#.to_vec(),
+            },
+            Any {
+                type_url: TYPE_URL_TRANSFER.to_string(),
+                value: br#"{"receiver_id":"alice.near","amount":"100"}"#.to_vec(),
+            },
+        ]);
+        let actions = IcaHostModule::parse_actions(&packet).unwrap();
+        assert_eq!(actions.len(), 2);
+        match &actions[0] {
+            InterchainAccountAction::FunctionCall {
+                method_name, args, ..
+            } => {
+                assert_eq!(method_name, "near_deposit");
+                assert_eq!(args.0, b"{}".to_vec());
+            }
+            _ => panic!("unexpected action"),
+        }
+        let packet = packet_of(vec![Any {
+            type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+            value: vec![],
+        }]);
+        assert!(IcaHostModule::parse_actions(&packet).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Common types of ICS-27 Interchain Accounts.
+use crate::prelude::*;
+use ibc::core::host::types::identifiers::ConnectionId;
+use ibc_proto::{
+    google::protobuf::Any, ibc::applications::interchain_accounts::v1::CosmosTx as RawCosmosTx,
+    Protobuf,
+};
+use near_sdk::{
+    json_types::Base64VecU8,
+    serde::{Deserialize, Serialize},
+};
+
+pub mod controller;
+pub mod host;
+
+/// The version of ICS-27.
+pub const VERSION: &str = "ics27-1";
+/// The port id prefix of ICA controller, the full port id is `icacontroller-<owner>`.
+pub const CONTROLLER_PORT_ID_PREFIX: &str = "icacontroller-";
+/// The module id of ICA controller.
+pub const CONTROLLER_MODULE_ID_STR: &str = "icacontroller";
+/// The port id of ICA host.
+pub const HOST_PORT_ID_STR: &str = "icahost";
+/// The module id of ICA host.
+pub const HOST_MODULE_ID_STR: &str = "icahost";
+/// The encoding of the transactions in packets.
+pub const ENCODING_PROTO3: &str = "proto3";
+/// The type of the transactions in packets.
+pub const TX_TYPE_SDK_MULTI_MSG: &str = "sdk_multi_msg";
+/// The type of packet data for executing transactions.
+pub const TYPE_EXECUTE_TX: &str = "TYPE_EXECUTE_TX";
+
+/// The metadata negotiated in the version of ICA channels.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IcaMetadata {
+    pub version: String,
+    pub controller_connection_id: String,
+    pub host_connection_id: String,
+    #[serde(default)]
+    pub address: String,
+    pub encoding: String,
+    pub tx_type: String,
+}
+
+impl IcaMetadata {
+    pub fn new(
+        controller_connection_id: &ConnectionId,
+        host_connection_id: &ConnectionId,
+        address: String,
+    ) -> Self {
+        Self {
+            version: VERSION.to_string(),
+            controller_connection_id: controller_connection_id.to_string(),
+            host_connection_id: host_connection_id.to_string(),
+            address,
+            encoding: ENCODING_PROTO3.to_string(),
+            tx_type: TX_TYPE_SDK_MULTI_MSG.to_string(),
+        }
+    }
+    /// Check the fields which are not related to the connections and the address.
+    pub fn validate_basic(&self) -> Result<(), String> {
+        if self.version != VERSION {
+            return Err(format!("ERR_INVALID_ICA_VERSION: {}", self.version));
+        }
+        if self.encoding != ENCODING_PROTO3 {
+            return Err(format!("ERR_UNSUPPORTED_ICA_ENCODING: {}", self.encoding));
+        }
+        if self.tx_type != TX_TYPE_SDK_MULTI_MSG {
+            return Err(format!("ERR_UNSUPPORTED_ICA_TX_TYPE: {}", self.tx_type));
+        }
+        Ok(())
+    }
+}
+
+/// The packet data of ICS-27, in JSON format.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IcaPacketData {
+    #[serde(rename = "type")]
+    pub packet_type: String,
+    /// The protobuf encoded `CosmosTx`.
+    pub data: Base64VecU8,
+    #[serde(default)]
+    pub memo: String,
+}
+
+impl IcaPacketData {
+    pub fn execute_tx(messages: Vec<Any>, memo: String) -> Self {
+        Self {
+            packet_type: TYPE_EXECUTE_TX.to_string(),
+            data: Base64VecU8(Protobuf::<RawCosmosTx>::encode_vec(CosmosTx { messages })),
+            memo,
+        }
+    }
+}
+
+/// The messages to be executed on the host chain.
+#[derive(Clone, Debug)]
+pub struct CosmosTx {
+    pub messages: Vec<Any>,
+}
+
+impl Protobuf<RawCosmosTx> for CosmosTx {}
+
+impl TryFrom<RawCosmosTx> for CosmosTx {
+    type Error = String;
+
+    fn try_from(raw: RawCosmosTx) -> Result<Self, Self::Error> {
+        Ok(Self {
+            messages: raw.messages,
+        })
+    }
+}
+
+impl From<CosmosTx> for RawCosmosTx {
+    fn from(value: CosmosTx) -> Self {
+        Self {
+            messages: value.messages,
+        }
+    }
+}
@@ -1,2 +1,5 @@
+pub mod fee;
+pub mod ica;
+pub mod nft_transfer;
 pub mod octopus_lpos;
 pub mod transfer;
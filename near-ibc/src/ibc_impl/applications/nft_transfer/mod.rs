@@ -0,0 +1,449 @@
+//! The implementation of ICS-721 (NFT transfer).
+//!
+//! NFTs native in NEAR protocol are escrowed in the NFT escrow contract of the channel
+//! (`<channel id>.ef.nft-transfer.<near-ibc account>`), and NFTs from other chains are
+//! minted in the wrapped NFT contracts deployed by the NFT factory contract
+//! (`nf.nft-transfer.<near-ibc account>`), one for each class.
+use super::transfer::{async_ack::error_ack, escrow_account_of};
+use crate::{context::NearIbcStoreHost, prelude::*, StorageKey};
+use core::{fmt::Debug, str::FromStr};
+use ibc::{
+    apps::transfer::types::ack_success_b64,
+    core::{
+        channel::types::{
+            acknowledgement::{Acknowledgement, AcknowledgementStatus},
+            channel::{Counterparty, Order},
+            error::{ChannelError, PacketError},
+            packet::Packet,
+            Version,
+        },
+        host::types::identifiers::{ChannelId, ConnectionId, PortId},
+        router::{module::Module, types::module::ModuleExtras},
+    },
+    primitives::Signer,
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json,
+    store::UnorderedSet,
+    AccountId, NearToken,
+};
+use utils::{
+    interfaces::{ext_nft_escrow, ext_nft_factory},
+    types::NftClass,
+    ExtraDepositCost,
+};
+
+/// The version of ICS-721.
+pub const VERSION: &str = "ics721-1";
+/// The port id of ICS-721.
+pub const PORT_ID_STR: &str = utils::NFT_TRANSFER_PORT_ID_STR;
+/// The module id of ICS-721.
+pub const MODULE_ID_STR: &str = "nft-transfer";
+
+/// The packet data of ICS-721, in JSON format.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "camelCase")]
+pub struct NftPacketData {
+    pub class_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_data: Option<String>,
+    pub token_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_uris: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_data: Vec<String>,
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl NftPacketData {
+    /// Validate the basic fields of the packet data.
+    pub fn validate_basic(&self) -> Result<(), String> {
+        if self.class_id.is_empty() {
+            return Err("ERR_EMPTY_CLASS_ID".to_string());
+        }
+        if self.token_ids.is_empty() || self.token_ids.iter().any(|id| id.is_empty()) {
+            return Err("ERR_INVALID_TOKEN_IDS".to_string());
+        }
+        if !self.token_uris.is_empty() && self.token_uris.len() != self.token_ids.len() {
+            return Err("ERR_TOKEN_URIS_LENGTH_MISMATCH".to_string());
+        }
+        if !self.token_data.is_empty() && self.token_data.len() != self.token_ids.len() {
+            return Err("ERR_TOKEN_DATA_LENGTH_MISMATCH".to_string());
+        }
+        Ok(())
+    }
+    /// The class of the NFTs, with the class id prefixed by the given port and channel.
+    fn nft_class_with_prefix(&self, port_id: &PortId, channel_id: &ChannelId) -> NftClass {
+        NftClass {
+            class_id: format!("{}{}", class_prefix(port_id, channel_id), self.class_id),
+            class_uri: self.class_uri.clone(),
+            class_data: self.class_data.clone(),
+        }
+    }
+}
+
+/// Get the trace prefix of class ids for the given port and channel.
+pub fn class_prefix(port_id: &PortId, channel_id: &ChannelId) -> String {
+    format!("{}/{}/", port_id, channel_id)
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct NftTransferModule {
+    /// The (prefixed) class ids of the NFTs from other chains,
+    /// whose wrapped NFT contract is already requested to be created.
+    pub wrapped_class_id_set: UnorderedSet<String>,
+}
+
+impl NearIbcStoreHost for NftTransferModule {}
+
+impl Default for NftTransferModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NftTransferModule {
+    pub fn new() -> Self {
+        Self {
+            wrapped_class_id_set: UnorderedSet::new(StorageKey::NftTransferWrappedClassIdSet),
+        }
+    }
+    //
+    fn validate_version(version: &Version) -> Result<(), ChannelError> {
+        if version.to_string() != VERSION {
+            return Err(ChannelError::AppModule {
+                description: format!("ERR_INVALID_ICS721_VERSION: {}", version),
+            });
+        }
+        Ok(())
+    }
+    //
+    fn validate_order(order: Order) -> Result<(), ChannelError> {
+        if order != Order::Unordered {
+            return Err(ChannelError::AppModule {
+                description: "ERR_NFT_TRANSFER_CHANNEL_MUST_BE_UNORDERED".to_string(),
+            });
+        }
+        Ok(())
+    }
+    /// Send the NFT escrowed for the given channel to the given account.
+    fn unescrow_nft(
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        nft_contract: AccountId,
+        token_id: String,
+        receiver_id: AccountId,
+    ) {
+        ext_nft_escrow::ext(
+            AccountId::from_str(escrow_account_of(port_id, channel_id).as_str()).unwrap(),
+        )
+        .with_attached_deposit(NearToken::from_yoctonear(1))
+        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+        .with_unused_gas_weight(0)
+        .do_nft_transfer(nft_contract, token_id, receiver_id);
+        ExtraDepositCost::add(1);
+    }
+    /// Mint the given NFTs of the given class by the NFT factory.
+    fn mint_nfts(&mut self, nft_class: NftClass, packet_data: &NftPacketData, owner: AccountId) {
+        for (index, token_id) in packet_data.token_ids.iter().enumerate() {
+            // The first minting of a new class needs to cover the creation of
+            // the wrapped NFT contract and the storage of the class in the NFT factory.
+            let deposit = match self.wrapped_class_id_set.contains(&nft_class.class_id) {
+                true => utils::STORAGE_DEPOSIT_FOR_MINT_NFT,
+                false => {
+                    self.wrapped_class_id_set.insert(nft_class.class_id.clone());
+                    utils::INIT_BALANCE_FOR_WRAPPED_NFT_CONTRACT
+                        + utils::STORAGE_DEPOSIT_FOR_MINT_NFT * 2
+                }
+            };
+            ext_nft_factory::ext(utils::get_nft_factory_contract_id())
+                .with_attached_deposit(NearToken::from_yoctonear(deposit))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(8))
+                .with_unused_gas_weight(0)
+                .mint_nft(
+                    nft_class.clone(),
+                    token_id.clone(),
+                    packet_data.token_uris.get(index).cloned(),
+                    packet_data.token_data.get(index).cloned(),
+                    owner.clone(),
+                );
+            ExtraDepositCost::add(deposit);
+        }
+    }
+    //
+    fn on_recv_nft_packet(&mut self, packet: &Packet) -> Acknowledgement {
+        let packet_data = match serde_json::from_slice::<NftPacketData>(&packet.data) {
+            Ok(packet_data) => packet_data,
+            Err(e) => return error_ack(format!("ERR_INVALID_PACKET_DATA: {}", e)),
+        };
+        if let Err(e) = packet_data.validate_basic() {
+            return error_ack(e);
+        }
+        let receiver = match AccountId::from_str(packet_data.receiver.as_str()) {
+            Ok(receiver) => receiver,
+            Err(e) => return error_ack(format!("ERR_INVALID_RECEIVER: {}", e)),
+        };
+        let source_prefix = class_prefix(&packet.port_id_on_a, &packet.chan_id_on_a);
+        if let Some(base_class_id) = packet_data.class_id.strip_prefix(source_prefix.as_str()) {
+            // The NFTs are native in NEAR protocol and are returning from the counterparty chain.
+            let nft_contract = match AccountId::from_str(base_class_id) {
+                Ok(nft_contract) => nft_contract,
+                Err(_) => {
+                    return error_ack(format!("ERR_INVALID_NATIVE_CLASS_ID: {}", base_class_id))
+                }
+            };
+            for token_id in packet_data.token_ids.iter() {
+                Self::unescrow_nft(
+                    &packet.port_id_on_b,
+                    &packet.chan_id_on_b,
+                    nft_contract.clone(),
+                    token_id.clone(),
+                    receiver.clone(),
+                );
+            }
+        } else {
+            let nft_class =
+                packet_data.nft_class_with_prefix(&packet.port_id_on_b, &packet.chan_id_on_b);
+            self.mint_nfts(nft_class, &packet_data, receiver);
+        }
+        AcknowledgementStatus::success(ack_success_b64()).into()
+    }
+    /// Return the NFTs in the given packet to the sender.
+    fn refund_nft_packet(&mut self, packet: &Packet) -> Result<(), PacketError> {
+        let packet_data = serde_json::from_slice::<NftPacketData>(&packet.data).map_err(|e| {
+            PacketError::AppModule {
+                description: format!("ERR_INVALID_PACKET_DATA: {}", e),
+            }
+        })?;
+        let sender = AccountId::from_str(packet_data.sender.as_str()).map_err(|e| {
+            PacketError::AppModule {
+                description: format!("ERR_INVALID_SENDER: {}", e),
+            }
+        })?;
+        let source_prefix = class_prefix(&packet.port_id_on_a, &packet.chan_id_on_a);
+        if packet_data.class_id.starts_with(source_prefix.as_str()) {
+            // The NFTs from other chains were burnt when the packet was sent, mint them again.
+            let nft_class = NftClass {
+                class_id: packet_data.class_id.clone(),
+                class_uri: packet_data.class_uri.clone(),
+                class_data: packet_data.class_data.clone(),
+            };
+            self.mint_nfts(nft_class, &packet_data, sender);
+        } else {
+            let nft_contract = AccountId::from_str(packet_data.class_id.as_str()).map_err(|e| {
+                PacketError::AppModule {
+                    description: format!("ERR_INVALID_NATIVE_CLASS_ID: {}", e),
+                }
+            })?;
+            for token_id in packet_data.token_ids.iter() {
+                Self::unescrow_nft(
+                    &packet.port_id_on_a,
+                    &packet.chan_id_on_a,
+                    nft_contract.clone(),
+                    token_id.clone(),
+                    sender.clone(),
+                );
+            }
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"NFT_TRANSFER_REFUNDED","port_id":"{}","channel_id":"{}","sequence":"{}","class_id":"{}","sender":"{}"}}"#,
+            packet.port_id_on_a,
+            packet.chan_id_on_a,
+            packet.seq_on_a,
+            packet_data.class_id,
+            packet_data.sender
+        );
+        Ok(())
+    }
+}
+
+impl Module for NftTransferModule {
+    fn on_chan_open_init_validate(
+        &self,
+        order: Order,
+        _connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<Version, ChannelError> {
+        Self::validate_order(order)?;
+        if port_id.as_str() != PORT_ID_STR {
+            return Err(ChannelError::AppModule {
+                description: format!("ERR_INVALID_NFT_TRANSFER_PORT_ID: {}", port_id),
+            });
+        }
+        if !version.to_string().is_empty() {
+            Self::validate_version(version)?;
+        }
+        Ok(Version::new(VERSION.to_string()))
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Ok((ModuleExtras::empty(), Version::new(VERSION.to_string())))
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        Self::validate_order(order)?;
+        Self::validate_version(counterparty_version)?;
+        Ok(counterparty_version.clone())
+    }
+
+    fn on_chan_open_try_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Ok((ModuleExtras::empty(), counterparty_version.clone()))
+    }
+
+    fn on_chan_open_ack_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        Self::validate_version(counterparty_version)
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Err(ChannelError::AppModule {
+            description: "ERR_NFT_TRANSFER_CHANNEL_CAN_NOT_BE_CLOSED".to_string(),
+        })
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        let ack = self.on_recv_nft_packet(packet);
+        log!(
+            "Packet acknowledgement: {}",
+            String::from_utf8(ack.as_bytes().to_vec()).unwrap_or_default()
+        );
+        (ModuleExtras::empty(), ack)
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        _packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()).map_err(
+            |e| PacketError::AppModule {
+                description: format!("ERR_INVALID_ACKNOWLEDGEMENT: {}", e),
+            },
+        )?;
+        Ok(())
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let result =
+            match serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()) {
+                Ok(AcknowledgementStatus::Success(_)) => Ok(()),
+                Ok(AcknowledgementStatus::Error(_)) => self.refund_nft_packet(packet),
+                Err(e) => Err(PacketError::AppModule {
+                    description: format!("ERR_INVALID_ACKNOWLEDGEMENT: {}", e),
+                }),
+            };
+        (ModuleExtras::empty(), result)
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        _packet: &Packet,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        Ok(())
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        (ModuleExtras::empty(), self.refund_nft_packet(packet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NftPacketData;
+    use near_sdk::serde_json;
+
+    #[test]
+    fn test_nft_packet_data() {
+        let packet_data = serde_json::from_str::<NftPacketData>(
+            r#"{"classId":"nft-transfer/channel-0/cryptopunks","classUri":"https://example.com","tokenIds":["1","2"],"tokenUris":["a","b"],"sender":"cosmos1abc","receiver":"alice.near"}"#,
+        )
+        .unwrap();
+        assert!(packet_data.validate_basic().is_ok());
+        assert_eq!(packet_data.token_ids, vec!["1", "2"]);
+        assert!(packet_data.token_data.is_empty());
+        assert_eq!(
+            serde_json::to_string(&packet_data).unwrap(),
+            r#"{"classId":"nft-transfer/channel-0/cryptopunks","classUri":"https://example.com","tokenIds":["1","2"],"tokenUris":["a","b"],"sender":"cosmos1abc","receiver":"alice.near"}"#
+        );
+        let packet_data = NftPacketData {
+            token_uris: vec!["a".to_string()],
+            ..packet_data
+        };
+        assert!(packet_data.validate_basic().is_err());
+    }
+}
@@ -68,6 +68,13 @@ impl OctopusLposValidationContext for OctopusLposModule {
             AnyClientState::Tendermint(client_state) => {
                 Ok(client_state.inner().chain_id.to_string())
             }
+            AnyClientState::Grandpa(client_state) => Ok(client_state.chain_id),
+            _ => Err(OctopusLposError::Unexpected {
+                description: format!(
+                    "The client of connection {} is not a tendermint or grandpa client.",
+                    connection_id
+                ),
+            }),
         }
     }
     //
@@ -1,4 +1,4 @@
-use crate::{prelude::*, StorageKey};
+use crate::{ibc_impl::core::channel_upgrade::ChannelUpgradeModule, prelude::*, StorageKey};
 use core::fmt::Debug;
 use ibc::{
     core::{
@@ -276,3 +276,69 @@ impl Module for OctopusLposModule {
         )
     }
 }
+
+impl ChannelUpgradeModule for OctopusLposModule {
+    fn on_chan_upgrade_init(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        proposed_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        self.on_chan_open_init_validate(
+            proposed_order,
+            proposed_connection_hops,
+            port_id,
+            channel_id,
+            counterparty,
+            proposed_version,
+        )?;
+        Ok(proposed_version.clone())
+    }
+
+    fn on_chan_upgrade_try(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        self.on_chan_open_try_validate(
+            proposed_order,
+            proposed_connection_hops,
+            port_id,
+            channel_id,
+            counterparty,
+            counterparty_version,
+        )
+    }
+
+    fn on_chan_upgrade_ack(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        self.on_chan_open_ack_validate(port_id, channel_id, counterparty_version)
+    }
+
+    fn on_chan_upgrade_open(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        version: &Version,
+    ) {
+        log!(
+            "Channel {}/{} is upgraded to version {}.",
+            port_id,
+            channel_id,
+            version
+        );
+    }
+}
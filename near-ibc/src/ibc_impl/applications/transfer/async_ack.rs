@@ -0,0 +1,76 @@
+//! Asynchronous acknowledgement of ICS-20 packets.
+//!
+//! The transfer module returns a placeholder acknowledgement for the packets whose result
+//! can only be determined later (e.g. forwarded packets or packets triggering IBC hooks).
+//! The placeholder is never committed or emitted by `NearIbcStore`, the real acknowledgement
+//! is written by `NearIbcStore::write_async_acknowledgement` when the result is known.
+use crate::{context::NearIbcStore, ibc_impl::applications::fee, prelude::*};
+use ibc::core::{
+    channel::types::{
+        acknowledgement::{Acknowledgement, AcknowledgementStatus, StatusValue},
+        commitment::{compute_ack_commitment, AcknowledgementCommitment},
+        events::WriteAcknowledgement,
+        packet::Packet,
+    },
+    handler::types::events::IbcEvent,
+    host::{
+        types::path::{AckPath, ChannelEndPath},
+        ExecutionContext, ValidationContext,
+    },
+};
+use near_sdk::log;
+
+/// The acknowledgement returned by the transfer module for a packet whose
+/// acknowledgement will be written asynchronously.
+const ASYNC_ACK_PLACEHOLDER: &[u8] = br#"{"result":"async_ack_pending"}"#;
+
+/// Get the placeholder acknowledgement.
+pub fn async_ack_placeholder() -> Acknowledgement {
+    Acknowledgement::try_from(ASYNC_ACK_PLACEHOLDER.to_vec()).unwrap()
+}
+
+/// Whether the given acknowledgement is the placeholder.
+pub fn is_async_ack_placeholder(ack: &Acknowledgement) -> bool {
+    ack.as_bytes() == ASYNC_ACK_PLACEHOLDER
+}
+
+/// Whether the given commitment is the one of the placeholder.
+pub fn is_async_ack_placeholder_commitment(ack_commitment: &AcknowledgementCommitment) -> bool {
+    compute_ack_commitment(&async_ack_placeholder()).eq(ack_commitment)
+}
+
+/// Generate an error acknowledgement with the given description.
+pub fn error_ack(description: String) -> Acknowledgement {
+    AcknowledgementStatus::error(
+        StatusValue::new(description).expect("ERR_EMPTY_ACKNOWLEDGEMENT_DESCRIPTION"),
+    )
+    .into()
+}
+
+impl NearIbcStore {
+    /// Write the acknowledgement of a packet which was held by the placeholder.
+    ///
+    /// The acknowledgement is wrapped by the fee middleware if the channel is fee enabled.
+    pub fn write_async_acknowledgement(&mut self, packet: &Packet, ack: Acknowledgement) {
+        let ack = fee::wrap_async_acknowledgement(packet, ack);
+        let ack_path = AckPath::new(&packet.port_id_on_b, &packet.chan_id_on_b, packet.seq_on_a);
+        if let Err(e) = self.store_packet_acknowledgement(&ack_path, compute_ack_commitment(&ack)) {
+            log!("ERR_WRITE_ASYNC_ACKNOWLEDGEMENT: {:?}", e);
+            return;
+        }
+        match self.channel_end(&ChannelEndPath::new(
+            &packet.port_id_on_b,
+            &packet.chan_id_on_b,
+        )) {
+            Ok(channel_end) => {
+                let _ =
+                    self.emit_ibc_event(IbcEvent::WriteAcknowledgement(WriteAcknowledgement::new(
+                        packet.clone(),
+                        ack,
+                        channel_end.connection_hops[0].clone(),
+                    )));
+            }
+            Err(e) => log!("ERR_WRITE_ASYNC_ACKNOWLEDGEMENT: {:?}", e),
+        }
+    }
+}
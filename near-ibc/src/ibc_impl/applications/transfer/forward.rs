@@ -0,0 +1,779 @@
+//! Packet forward middleware (PFM) for ICS-20 transfers.
+//!
+//! When an incoming ICS-20 packet carries a memo like
+//! `{"forward":{"receiver":"...","port":"transfer","channel":"channel-1"}}`,
+//! the tokens will not be delivered to the receiver on NEAR protocol, but be re-sent to
+//! the next chain over the given channel. The acknowledgement of the original packet is
+//! held (refer to `async_ack` module) until the forwarded packet is acknowledged or timed out.
+//! The `timeout` of the forwarding can be a duration string (e.g. `"10m"`), a number of
+//! nanoseconds or a number of seconds in a string, and the `retries` are not supported.
+//!
+//! The tokens native in NEAR protocol are not moved between the channel escrows by forwarding,
+//! as the transfers between escrows are asynchronous and can fail after the forwarded packet
+//! is sent. Instead, the amount of the tokens held by the escrows of other channels on behalf
+//! of the escrow of each channel is recorded in a ledger of this contract, and the tokens are
+//! sent from the holding escrows when they are unescrowed from the channel. As the channel
+//! escrows only hold the tokens native in NEAR protocol, the vouchers of the tokens from
+//! other chains are not minted for forwarding either. Instead, the amount of the vouchers held
+//! by the escrow of each channel is recorded in another ledger of this contract. The vouchers are minted when they are received back
+//! through the channel, and burnt (debited from the ledger) when they are forwarded back
+//! to the chain they came from.
+//!
+//! Since the modules can not access `NearIbcStore` in the callbacks of `Module` trait,
+//! the module records the forwarding tasks in a transient storage, and the tasks are
+//! processed in `NearIbcContract::deliver` after all messages are dispatched.
+use super::{
+    async_ack::{async_ack_placeholder, error_ack},
+    escrow_account_of, TransferModule,
+};
+use crate::{context::NearIbcStore, prelude::*, StorageKey};
+use alloc::collections::BTreeMap;
+use core::str::FromStr;
+use ibc::{
+    apps::transfer::{
+        handler::send_transfer,
+        types::{
+            ack_success_b64, is_receiver_chain_source, msgs::transfer::MsgTransfer,
+            packet::PacketData, Memo, TracePrefix, PORT_ID_STR,
+        },
+    },
+    core::{
+        channel::types::{
+            acknowledgement::{Acknowledgement, AcknowledgementStatus},
+            packet::Packet,
+            timeout::TimeoutHeight,
+        },
+        host::{
+            types::{
+                identifiers::{ChannelId, PortId, Sequence},
+                path::SeqSendPath,
+            },
+            ValidationContext,
+        },
+    },
+    primitives::{Signer, Timestamp},
+};
+use ibc_proto::{
+    ibc::{apps::transfer::v2::FungibleTokenPacketData, core::channel::v1::Packet as RawPacket},
+    Protobuf,
+};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::LookupMap,
+    env, log,
+    serde::{de, Deserialize, Deserializer, Serialize},
+    serde_json::{self, Value},
+};
+
+const STORAGE_KEY_FOR_FORWARD_TASKS: &[u8] = b"pfm_forward_tasks";
+
+/// The metadata in the `forward` field of the memo of an ICS-20 packet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ForwardMetadata {
+    /// The receiver on the next chain.
+    pub receiver: String,
+    /// The port id to send the forwarded packet.
+    pub port: String,
+    /// The channel id to send the forwarded packet.
+    pub channel: String,
+    /// The timeout (in nanoseconds) of the forwarded packet.
+    #[serde(default, deserialize_with = "deserialize_timeout")]
+    pub timeout: Option<u64>,
+    /// The number of retries on timeout, which is not supported.
+    #[serde(default)]
+    pub retries: Option<u8>,
+    /// The memo to be attached to the forwarded packet.
+    #[serde(default)]
+    pub next: Option<Value>,
+}
+
+/// The record of a forwarded packet, stored in `NearIbcStore`.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct InFlightForward {
+    /// The protobuf encoded bytes of the original packet.
+    pub original_packet: Vec<u8>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum ForwardTask {
+    /// Forward the tokens of the given (protobuf encoded) packet.
+    Forward {
+        original_packet: Vec<u8>,
+        port_id: PortId,
+        channel_id: ChannelId,
+        receiver: String,
+        timeout_nanoseconds: u64,
+        memo: String,
+    },
+    /// The forwarded packet is acknowledged or timed out.
+    Resolve {
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+        error: Option<String>,
+    },
+}
+
+/// The transient queue of the forwarding tasks generated in a single `deliver` call.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct ForwardTasks(Vec<ForwardTask>);
+
+impl ForwardTasks {
+    /// Append a task to the queue.
+    pub fn push(task: ForwardTask) {
+        let mut tasks = match env::storage_read(STORAGE_KEY_FOR_FORWARD_TASKS) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => Self::default(),
+        };
+        tasks.0.push(task);
+        env::storage_write(
+            STORAGE_KEY_FOR_FORWARD_TASKS,
+            &borsh::to_vec(&tasks).unwrap(),
+        );
+    }
+    /// Take all tasks out of the queue.
+    pub fn take() -> Vec<ForwardTask> {
+        match env::storage_read(STORAGE_KEY_FOR_FORWARD_TASKS) {
+            Some(bytes) => {
+                env::storage_remove(STORAGE_KEY_FOR_FORWARD_TASKS);
+                Self::try_from_slice(&bytes).unwrap().0
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// Parse a duration string in the format of Go (e.g. `"10m"`, `"1h30m"` or `"1.5s"`),
+/// or a number of seconds in a string, to nanoseconds.
+pub fn parse_duration(duration: &str) -> Result<u64, String> {
+    let error = || format!("ERR_INVALID_DURATION: {}", duration);
+    if duration.is_empty() {
+        return Err(error());
+    }
+    if duration.bytes().all(|byte| byte.is_ascii_digit()) {
+        return u64::from_str(duration)
+            .ok()
+            .and_then(|seconds| seconds.checked_mul(1_000_000_000))
+            .ok_or_else(error);
+    }
+    let mut nanoseconds: u128 = 0;
+    let mut rest = duration;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let unit_len = rest[number_len..]
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len() - number_len);
+        let unit_nanoseconds: u128 = match &rest[number_len..number_len + unit_len] {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            _ => return Err(error()),
+        };
+        let (integer, fraction) = rest[..number_len]
+            .split_once('.')
+            .unwrap_or((&rest[..number_len], ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(error());
+        }
+        let mut value = match integer.is_empty() {
+            true => 0,
+            false => u128::from_str(integer)
+                .ok()
+                .and_then(|integer| integer.checked_mul(unit_nanoseconds))
+                .ok_or_else(error)?,
+        };
+        let mut scale = unit_nanoseconds;
+        for digit in fraction.bytes() {
+            if !digit.is_ascii_digit() {
+                return Err(error());
+            }
+            scale /= 10;
+            value += (digit - b'0') as u128 * scale;
+        }
+        nanoseconds = nanoseconds.checked_add(value).ok_or_else(error)?;
+        rest = &rest[number_len + unit_len..];
+    }
+    u64::try_from(nanoseconds).map_err(|_| error())
+}
+
+/// Deserialize the timeout of forwarding, which is a duration string, a number of seconds
+/// in a string, or a number of nanoseconds (the JSON format of `time.Duration` of Go).
+fn deserialize_timeout<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(nanoseconds)) => nanoseconds
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| de::Error::custom(format!("ERR_INVALID_DURATION: {}", nanoseconds))),
+        Some(Value::String(duration)) => parse_duration(duration.as_str())
+            .map(Some)
+            .map_err(de::Error::custom),
+        Some(value) => Err(de::Error::custom(format!(
+            "ERR_INVALID_DURATION: {}",
+            value
+        ))),
+    }
+}
+
+/// The vouchers held by the channel escrows for the forwarded packets,
+/// keyed by (channel id, denom).
+fn escrowed_forward_vouchers() -> LookupMap<(String, String), u128> {
+    LookupMap::new(StorageKey::EscrowedForwardVouchers)
+}
+
+/// Get the amount of the vouchers of the given denom held by the escrow of the given channel.
+pub fn escrowed_voucher_amount(channel_id: &str, denom: &str) -> u128 {
+    escrowed_forward_vouchers()
+        .get(&(channel_id.to_string(), denom.to_string()))
+        .unwrap_or(0)
+}
+
+/// Credit the vouchers of the given denom to the escrow of the given channel.
+pub fn credit_escrowed_vouchers(channel_id: &str, denom: &str, amount: u128) {
+    let key = (channel_id.to_string(), denom.to_string());
+    let mut vouchers = escrowed_forward_vouchers();
+    let balance = vouchers.get(&key).unwrap_or(0);
+    vouchers.insert(&key, &balance.saturating_add(amount));
+}
+
+/// Debit the vouchers of the given denom from the escrow of the given channel.
+pub fn debit_escrowed_vouchers(channel_id: &str, denom: &str, amount: u128) -> Result<(), String> {
+    let key = (channel_id.to_string(), denom.to_string());
+    let mut vouchers = escrowed_forward_vouchers();
+    let balance = vouchers.get(&key).unwrap_or(0);
+    if balance < amount {
+        return Err(format!(
+            "ERR_NOT_ENOUGH_ESCROWED_VOUCHERS: channel {}, denom {}",
+            channel_id, denom
+        ));
+    }
+    match balance - amount {
+        0 => vouchers.remove(&key),
+        balance => vouchers.insert(&key, &balance),
+    };
+    Ok(())
+}
+
+/// The tokens native in NEAR protocol held by the escrows of other channels on behalf of
+/// the escrow of a channel, keyed by (channel id, denom), with the amounts keyed by the channel
+/// ids of the holding escrows.
+fn escrowed_forward_tokens() -> LookupMap<(String, String), BTreeMap<String, u128>> {
+    LookupMap::new(StorageKey::EscrowedForwardTokens)
+}
+
+/// Save the holders of the tokens of the given key, or remove them if empty.
+fn save_escrowed_token_holders(key: &(String, String), holders: &BTreeMap<String, u128>) {
+    let mut tokens = escrowed_forward_tokens();
+    match holders.is_empty() {
+        true => tokens.remove(key),
+        false => tokens.insert(key, holders),
+    };
+}
+
+/// Get the channels whose escrows hold the given amount of the tokens of the given denom
+/// escrowed by the given channel, with the amounts held by them. The tokens held by
+/// the escrows of other channels are taken first, and the rest are held by the channel itself.
+pub fn holders_of_escrowed_tokens(
+    channel_id: &str,
+    denom: &str,
+    amount: u128,
+) -> Vec<(String, u128)> {
+    let holders = escrowed_forward_tokens()
+        .get(&(channel_id.to_string(), denom.to_string()))
+        .unwrap_or_default();
+    let mut result = Vec::new();
+    let mut remaining = amount;
+    for (holder, held_amount) in holders.iter() {
+        if remaining == 0 {
+            break;
+        }
+        let part = remaining.min(*held_amount);
+        result.push((holder.clone(), part));
+        remaining -= part;
+    }
+    if remaining > 0 {
+        result.push((channel_id.to_string(), remaining));
+    }
+    result
+}
+
+/// Take the given amount of the tokens of the given denom out of the escrow of the given
+/// channel, returns the channels whose escrows should send the tokens, with the amounts.
+pub fn take_escrowed_tokens(channel_id: &str, denom: &str, amount: u128) -> Vec<(String, u128)> {
+    let key = (channel_id.to_string(), denom.to_string());
+    let mut holders = escrowed_forward_tokens().get(&key).unwrap_or_default();
+    let parts = holders_of_escrowed_tokens(channel_id, denom, amount);
+    for (holder, part) in parts.iter().filter(|(holder, _)| holder != channel_id) {
+        match holders[holder] - part {
+            0 => holders.remove(holder),
+            held_amount => holders.insert(holder.clone(), held_amount),
+        };
+    }
+    save_escrowed_token_holders(&key, &holders);
+    parts
+}
+
+/// Move the given amount of the tokens of the given denom from the escrow of channel `from`
+/// to the escrow of channel `to`, by recording the escrows holding the tokens for `to`.
+pub fn move_escrowed_tokens(from_channel_id: &str, to_channel_id: &str, denom: &str, amount: u128) {
+    let key = (to_channel_id.to_string(), denom.to_string());
+    let mut holders = escrowed_forward_tokens().get(&key).unwrap_or_default();
+    for (holder, part) in take_escrowed_tokens(from_channel_id, denom, amount) {
+        // The tokens already held by the escrow of `to` are settled.
+        if holder != to_channel_id {
+            let held_amount = holders.entry(holder).or_default();
+            *held_amount = held_amount.saturating_add(part);
+        }
+    }
+    save_escrowed_token_holders(&key, &holders);
+}
+
+/// Get the denom and amount of the vouchers received by the given packet, `None` if the
+/// tokens in the packet are not vouchers of the tokens from the counterparty chain.
+fn received_vouchers_of(packet: &Packet) -> Option<(String, u128)> {
+    let mut packet_data =
+        PacketData::try_from(serde_json::from_slice::<FungibleTokenPacketData>(&packet.data).ok()?)
+            .ok()?;
+    if is_receiver_chain_source(
+        packet.port_id_on_a.clone(),
+        packet.chan_id_on_a.clone(),
+        &packet_data.token.denom,
+    ) {
+        return None;
+    }
+    packet_data.token.denom.add_trace_prefix(TracePrefix::new(
+        packet.port_id_on_b.clone(),
+        packet.chan_id_on_b.clone(),
+    ));
+    Some((
+        packet_data.token.denom.to_string(),
+        u128::from_str(packet_data.token.amount.to_string().as_str()).ok()?,
+    ))
+}
+
+/// Parse the forward metadata from the memo of an ICS-20 packet.
+///
+/// Returns `None` if the memo doesn't contain a `forward` field.
+pub fn parse_forward_metadata(memo: &str) -> Option<Result<ForwardMetadata, String>> {
+    let value = serde_json::from_str::<Value>(memo).ok()?;
+    let forward = value.get("forward")?;
+    Some(
+        serde_json::from_value::<ForwardMetadata>(forward.clone())
+            .map_err(|e| format!("ERR_INVALID_FORWARD_METADATA: {}", e)),
+    )
+}
+
+/// Whether the given packet is sent by the packet forward middleware.
+pub fn is_forwarded_packet(packet: &Packet) -> bool {
+    serde_json::from_slice::<FungibleTokenPacketData>(&packet.data).map_or(false, |data| {
+        data.sender
+            .ends_with(format!(".ef.{}.{}", PORT_ID_STR, env::current_account_id()).as_str())
+    })
+}
+
+impl TransferModule {
+    /// Check the packet to be forwarded and record the forwarding task.
+    ///
+    /// Returns the placeholder acknowledgement if the forwarding is scheduled,
+    /// otherwise returns an error acknowledgement.
+    pub fn on_recv_forward_packet(
+        &self,
+        packet: &Packet,
+        ft_packet_data: FungibleTokenPacketData,
+        metadata: Result<ForwardMetadata, String>,
+    ) -> Acknowledgement {
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => return error_ack(e),
+        };
+        let port_id = match PortId::from_str(metadata.port.as_str()) {
+            Ok(port_id) => port_id,
+            Err(e) => return error_ack(format!("ERR_INVALID_FORWARD_PORT: {}", e)),
+        };
+        let channel_id = match ChannelId::from_str(metadata.channel.as_str()) {
+            Ok(channel_id) => channel_id,
+            Err(e) => return error_ack(format!("ERR_INVALID_FORWARD_CHANNEL: {}", e)),
+        };
+        if let Err(e) = PacketData::try_from(ft_packet_data) {
+            return error_ack(format!("ERR_INVALID_PACKET_DATA: {}", e));
+        }
+        if metadata.retries.is_some_and(|retries| retries > 0) {
+            return error_ack("ERR_FORWARD_RETRIES_NOT_SUPPORTED".to_string());
+        }
+        if port_id.eq(&packet.port_id_on_b) && channel_id.eq(&packet.chan_id_on_b) {
+            return error_ack("ERR_FORWARD_TO_RECEIVING_CHANNEL".to_string());
+        }
+        let memo = metadata.next.map_or_else(String::new, |next| match next {
+            Value::String(next) => next,
+            next => next.to_string(),
+        });
+        if memo.len() > utils::MAX_LENGTH_OF_MEMO {
+            return error_ack("ERR_FORWARD_MEMO_TOO_LONG".to_string());
+        }
+        ForwardTasks::push(ForwardTask::Forward {
+            original_packet: Protobuf::<RawPacket>::encode_vec(packet.clone()),
+            port_id,
+            channel_id,
+            receiver: metadata.receiver,
+            timeout_nanoseconds: metadata
+                .timeout
+                .filter(|timeout| *timeout > 0)
+                .unwrap_or(crate::DEFAULT_TIMEOUT_SECONDS * 1_000_000_000),
+            memo,
+        });
+        log!(
+            "Packet {}/{}/{} will be forwarded.",
+            packet.port_id_on_b,
+            packet.chan_id_on_b,
+            packet.seq_on_a
+        );
+        async_ack_placeholder()
+    }
+    /// Record the result of a forwarded packet.
+    pub fn on_forwarded_packet_resolved(&self, packet: &Packet, error: Option<String>) {
+        ForwardTasks::push(ForwardTask::Resolve {
+            port_id: packet.port_id_on_a.clone(),
+            channel_id: packet.chan_id_on_a.clone(),
+            sequence: packet.seq_on_a,
+            error,
+        });
+    }
+}
+
+impl NearIbcStore {
+    /// Process all forwarding tasks generated in current transaction.
+    pub fn process_forward_tasks(&mut self) {
+        for task in ForwardTasks::take() {
+            match task {
+                ForwardTask::Forward {
+                    original_packet,
+                    port_id,
+                    channel_id,
+                    receiver,
+                    timeout_nanoseconds,
+                    memo,
+                } => self.forward_packet(
+                    original_packet,
+                    port_id,
+                    channel_id,
+                    receiver,
+                    timeout_nanoseconds,
+                    memo,
+                ),
+                ForwardTask::Resolve {
+                    port_id,
+                    channel_id,
+                    sequence,
+                    error,
+                } => self.resolve_forwarded_packet(port_id, channel_id, sequence, error),
+            }
+        }
+    }
+    //
+    fn forward_packet(
+        &mut self,
+        original_packet: Vec<u8>,
+        port_id: PortId,
+        channel_id: ChannelId,
+        receiver: String,
+        timeout_nanoseconds: u64,
+        memo: String,
+    ) {
+        let packet = Protobuf::<RawPacket>::decode_vec(&original_packet)
+            .expect("ERR_INVALID_ORIGINAL_PACKET");
+        let mut packet_data = PacketData::try_from(
+            serde_json::from_slice::<FungibleTokenPacketData>(&packet.data)
+                .expect("ERR_INVALID_ORIGINAL_PACKET_DATA"),
+        )
+        .expect("ERR_INVALID_ORIGINAL_PACKET_DATA");
+        let received_vouchers = received_vouchers_of(&packet);
+        match received_vouchers {
+            Some(_) => packet_data.token.denom.add_trace_prefix(TracePrefix::new(
+                packet.port_id_on_b.clone(),
+                packet.chan_id_on_b.clone(),
+            )),
+            None => packet_data
+                .token
+                .denom
+                .remove_trace_prefix(&TracePrefix::new(
+                    packet.port_id_on_a.clone(),
+                    packet.chan_id_on_a.clone(),
+                )),
+        }
+        let sequence = match self.get_next_sequence_send(&SeqSendPath::new(&port_id, &channel_id)) {
+            Ok(sequence) => sequence,
+            Err(e) => {
+                self.write_async_acknowledgement(
+                    &packet,
+                    error_ack(format!("ERR_FORWARD_PACKET: {}", e)),
+                );
+                return;
+            }
+        };
+        // The received vouchers are held by the escrow of the receiving channel, and then
+        // escrowed or burnt by sending the forwarded packet.
+        if let Some((denom, amount)) = received_vouchers.as_ref() {
+            credit_escrowed_vouchers(packet.chan_id_on_b.as_str(), denom, *amount);
+        }
+        let result = send_transfer(
+            self,
+            &mut TransferModule(),
+            MsgTransfer {
+                port_id_on_a: port_id.clone(),
+                chan_id_on_a: channel_id.clone(),
+                packet_data: PacketData {
+                    token: packet_data.token,
+                    sender: Signer::from(escrow_account_of(
+                        &packet.port_id_on_b,
+                        &packet.chan_id_on_b,
+                    )),
+                    receiver: Signer::from(receiver),
+                    memo: Memo::from_str(memo.as_str()).unwrap(),
+                },
+                timeout_height_on_b: TimeoutHeight::Never {},
+                timeout_timestamp_on_b: Timestamp::from_nanoseconds(
+                    env::block_timestamp().saturating_add(timeout_nanoseconds),
+                )
+                .unwrap(),
+            },
+        );
+        match result {
+            Ok(()) => {
+                log!(
+                    "Packet {}/{}/{} has been forwarded as {}/{}/{}.",
+                    packet.port_id_on_b,
+                    packet.chan_id_on_b,
+                    packet.seq_on_a,
+                    port_id,
+                    channel_id,
+                    sequence
+                );
+                self.in_flight_forwards.insert(
+                    (port_id, channel_id, sequence),
+                    InFlightForward { original_packet },
+                );
+            }
+            Err(e) => {
+                log!("ERR_FORWARD_PACKET: {:?}", e);
+                if let Some((denom, amount)) = received_vouchers {
+                    debit_escrowed_vouchers(packet.chan_id_on_b.as_str(), &denom, amount)
+                        .expect("ERR_DEBIT_CREDITED_VOUCHERS");
+                }
+                self.write_async_acknowledgement(
+                    &packet,
+                    error_ack(format!("ERR_FORWARD_PACKET: {}", e)),
+                );
+            }
+        }
+    }
+    //
+    fn resolve_forwarded_packet(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+        error: Option<String>,
+    ) {
+        let key = (port_id, channel_id, sequence);
+        if let Some(in_flight_forward) = self.in_flight_forwards.remove(&key) {
+            let packet = Protobuf::<RawPacket>::decode_vec(&in_flight_forward.original_packet)
+                .expect("ERR_INVALID_ORIGINAL_PACKET");
+            let ack = match error {
+                None => AcknowledgementStatus::success(ack_success_b64()).into(),
+                Some(e) => {
+                    // The vouchers refunded to the escrow of the receiving channel are burnt,
+                    // as the tokens will be refunded on the sending chain.
+                    if let Some((denom, amount)) = received_vouchers_of(&packet) {
+                        if let Err(err) =
+                            debit_escrowed_vouchers(packet.chan_id_on_b.as_str(), &denom, amount)
+                        {
+                            log!("{}", err);
+                        }
+                    }
+                    error_ack(format!("ERR_FORWARDED_PACKET_FAILED: {}", e))
+                }
+            };
+            self.write_async_acknowledgement(&packet, ack);
+        } else {
+            log!(
+                "No in-flight forward found for packet {}/{}/{}.",
+                key.0,
+                key.1,
+                key.2
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    #[test]
+    fn test_parse_forward_metadata() {
+        assert!(parse_forward_metadata("").is_none());
+        assert!(parse_forward_metadata("some memo").is_none());
+        assert!(parse_forward_metadata(r#"{"wasm":{}}"#).is_none());
+        assert!(parse_forward_metadata(r#"{"forward":{"receiver":"abc"}}"#)
+            .unwrap()
+            .is_err());
+        let metadata = parse_forward_metadata(
+            r#"{"forward":{"receiver":"cosmos1abc","port":"transfer","channel":"channel-1","timeout":"600","next":{"forward":{}}}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(metadata.receiver, "cosmos1abc");
+        assert_eq!(metadata.channel, "channel-1");
+        assert_eq!(metadata.timeout.unwrap(), 600_000_000_000);
+        assert!(metadata.next.is_some());
+        let metadata = parse_forward_metadata(
+            r#"{"forward":{"receiver":"cosmos1abc","port":"transfer","channel":"channel-1","timeout":"10m","retries":2}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(metadata.timeout.unwrap(), 600_000_000_000);
+        assert_eq!(metadata.retries, Some(2));
+        let metadata = parse_forward_metadata(
+            r#"{"forward":{"receiver":"cosmos1abc","port":"transfer","channel":"channel-1","timeout":90000000000}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(metadata.timeout.unwrap(), 90_000_000_000);
+        assert!(parse_forward_metadata(
+            r#"{"forward":{"receiver":"cosmos1abc","port":"transfer","channel":"channel-1","timeout":"10 minutes"}}"#,
+        )
+        .unwrap()
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10m").unwrap(), 600_000_000_000);
+        assert_eq!(parse_duration("1h30m").unwrap(), 5_400_000_000_000);
+        assert_eq!(parse_duration("1.5s").unwrap(), 1_500_000_000);
+        assert_eq!(parse_duration(".5ms").unwrap(), 500_000);
+        assert_eq!(parse_duration("300ms20us7ns").unwrap(), 300_020_007);
+        assert_eq!(parse_duration("2µs").unwrap(), 2_000);
+        assert_eq!(parse_duration("120").unwrap(), 120_000_000_000);
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_ok());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+        assert!(parse_duration("-10m").is_err());
+        assert!(parse_duration("9999999999h").is_err());
+    }
+
+    #[test]
+    fn test_escrowed_vouchers() {
+        testing_env!(VMContextBuilder::new().build());
+        let denom = "transfer/channel-0/uatom";
+        assert_eq!(escrowed_voucher_amount("channel-1", denom), 0);
+        credit_escrowed_vouchers("channel-1", denom, 100);
+        credit_escrowed_vouchers("channel-1", denom, 50);
+        assert_eq!(escrowed_voucher_amount("channel-1", denom), 150);
+        assert_eq!(escrowed_voucher_amount("channel-2", denom), 0);
+        assert!(debit_escrowed_vouchers("channel-1", denom, 151).is_err());
+        assert!(debit_escrowed_vouchers("channel-2", denom, 1).is_err());
+        assert!(debit_escrowed_vouchers("channel-1", denom, 100).is_ok());
+        assert_eq!(escrowed_voucher_amount("channel-1", denom), 50);
+        assert!(debit_escrowed_vouchers("channel-1", denom, 50).is_ok());
+        assert_eq!(escrowed_voucher_amount("channel-1", denom), 0);
+    }
+
+    #[test]
+    fn test_escrowed_tokens() {
+        testing_env!(VMContextBuilder::new().build());
+        let denom = "usdt.testnet";
+        let holders_of = |channel_id: &str| {
+            escrowed_forward_tokens()
+                .get(&(channel_id.to_string(), denom.to_string()))
+                .unwrap_or_default()
+        };
+        // The tokens received through channel-0 are forwarded through channel-1 and channel-2.
+        move_escrowed_tokens("channel-0", "channel-1", denom, 100);
+        move_escrowed_tokens("channel-0", "channel-2", denom, 30);
+        assert_eq!(
+            holders_of("channel-1"),
+            BTreeMap::from([("channel-0".to_string(), 100)])
+        );
+        // The tokens received through channel-1 are forwarded through channel-2.
+        move_escrowed_tokens("channel-1", "channel-2", denom, 120);
+        assert!(holders_of("channel-1").is_empty());
+        assert_eq!(
+            holders_of("channel-2"),
+            BTreeMap::from([
+                ("channel-0".to_string(), 130),
+                ("channel-1".to_string(), 20)
+            ])
+        );
+        // The tokens refunded to channel-0 are settled.
+        move_escrowed_tokens("channel-2", "channel-0", denom, 50);
+        assert!(holders_of("channel-0").is_empty());
+        assert_eq!(
+            holders_of("channel-2"),
+            BTreeMap::from([("channel-0".to_string(), 80), ("channel-1".to_string(), 20)])
+        );
+        // The tokens unescrowed from channel-2 are sent by the holding escrows.
+        assert_eq!(
+            holders_of_escrowed_tokens("channel-2", denom, 90),
+            vec![("channel-0".to_string(), 80), ("channel-1".to_string(), 10)]
+        );
+        assert_eq!(
+            take_escrowed_tokens("channel-2", denom, 150),
+            vec![
+                ("channel-0".to_string(), 80),
+                ("channel-1".to_string(), 20),
+                ("channel-2".to_string(), 50)
+            ]
+        );
+        assert!(holders_of("channel-2").is_empty());
+        assert_eq!(
+            take_escrowed_tokens("channel-2", denom, 10),
+            vec![("channel-2".to_string(), 10)]
+        );
+    }
+
+    #[test]
+    fn test_received_vouchers() {
+        testing_env!(VMContextBuilder::new().build());
+        let packet_of = |denom: &str| Packet {
+            seq_on_a: 1u64.into(),
+            port_id_on_a: PortId::transfer(),
+            chan_id_on_a: ChannelId::new(5),
+            port_id_on_b: PortId::transfer(),
+            chan_id_on_b: ChannelId::new(0),
+            data: serde_json::to_vec(&FungibleTokenPacketData {
+                denom: denom.to_string(),
+                amount: "100".to_string(),
+                sender: "cosmos1sender".to_string(),
+                receiver: "cosmos1receiver".to_string(),
+                memo: String::new(),
+            })
+            .unwrap(),
+            timeout_height_on_b: TimeoutHeight::Never,
+            timeout_timestamp_on_b: Timestamp::none(),
+        };
+        assert_eq!(
+            received_vouchers_of(&packet_of("uatom")),
+            Some(("transfer/channel-0/uatom".to_string(), 100))
+        );
+        assert_eq!(
+            received_vouchers_of(&packet_of("transfer/channel-5/usdt.near")),
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! IBC hooks for ICS-20 transfers.
+//!
+//! When an incoming ICS-20 packet carries a memo like
+//! `{"near":{"contract":"<contract id>","method":"ft_transfer_call","args":<msg>}}`,
+//! the tokens will not be delivered to the receiver directly, but be sent to the given
+//! contract by `ft_transfer_call` with the `args` as `msg`. The intermediate account
+//! which calls `ft_transfer_call` is the channel escrow (for tokens native in NEAR protocol)
+//! or the wrapped token contract (for tokens from other chains).
+//!
+//! The acknowledgement of the packet is held (refer to `async_ack` module) until
+//! the promise of `ft_transfer_call` is resolved:
+//!
+//! * If the contract used some of the tokens, a success acknowledgement is written.
+//!   The unused part of the tokens is sent to the receiver of the packet.
+//! * Otherwise, an error acknowledgement is written, and the tokens will be refunded
+//!   on the source chain.
+use super::{
+    async_ack::{async_ack_placeholder, error_ack},
+    escrow_account_of, forward, TransferModule,
+};
+use crate::prelude::*;
+use core::str::FromStr;
+use ibc::{
+    apps::transfer::types::{is_receiver_chain_source, packet::PacketData, TracePrefix},
+    core::channel::types::{acknowledgement::Acknowledgement, packet::Packet},
+};
+use ibc_proto::{
+    ibc::{apps::transfer::v2::FungibleTokenPacketData, core::channel::v1::Packet as RawPacket},
+    Protobuf,
+};
+use near_sdk::{
+    env, ext_contract,
+    json_types::{Base64VecU8, U128},
+    log,
+    serde::{Deserialize, Serialize},
+    serde_json::{self, Value},
+    AccountId, NearToken,
+};
+use utils::{
+    interfaces::{ext_channel_escrow, ext_token_factory},
+    ExtraDepositCost,
+};
+
+/// The only method supported by IBC hooks for now.
+const METHOD_FT_TRANSFER_CALL: &str = "ft_transfer_call";
+
+/// The metadata in the `near` field of the memo of an ICS-20 packet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HookMetadata {
+    /// The contract to receive the tokens.
+    pub contract: String,
+    /// The method to call, only `ft_transfer_call` is supported.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// The `msg` of `ft_transfer_call`.
+    #[serde(default)]
+    pub args: Option<Value>,
+}
+
+/// Parse the hook metadata from the memo of an ICS-20 packet.
+///
+/// Returns `None` if the memo doesn't contain a `near` field.
+pub fn parse_hook_metadata(memo: &str) -> Option<Result<HookMetadata, String>> {
+    let value = serde_json::from_str::<Value>(memo).ok()?;
+    let hook = value.get("near")?;
+    Some(
+        serde_json::from_value::<HookMetadata>(hook.clone())
+            .map_err(|e| format!("ERR_INVALID_HOOK_METADATA: {}", e)),
+    )
+}
+
+/// The callback interface for the promise of IBC hooks.
+#[ext_contract(ext_ibc_hooks_callback)]
+pub trait IbcHooksCallback {
+    /// Write the acknowledgement of the given (protobuf encoded) packet,
+    /// according to the amount of tokens used by the receiver contract.
+    fn on_ibc_hook_executed(&mut self, packet: Base64VecU8);
+}
+
+impl TransferModule {
+    /// Check the packet with hook metadata and schedule the `ft_transfer_call`.
+    ///
+    /// Returns the placeholder acknowledgement if the call is scheduled,
+    /// otherwise returns an error acknowledgement.
+    pub fn on_recv_hook_packet(
+        &self,
+        packet: &Packet,
+        ft_packet_data: FungibleTokenPacketData,
+        metadata: Result<HookMetadata, String>,
+    ) -> Acknowledgement {
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => return error_ack(e),
+        };
+        if let Some(method) = metadata.method.as_ref() {
+            if method != METHOD_FT_TRANSFER_CALL {
+                return error_ack(format!("ERR_UNSUPPORTED_HOOK_METHOD: {}", method));
+            }
+        }
+        let contract = match AccountId::from_str(metadata.contract.as_str()) {
+            Ok(contract) => contract,
+            Err(e) => return error_ack(format!("ERR_INVALID_HOOK_CONTRACT: {}", e)),
+        };
+        let refund_receiver = match AccountId::from_str(ft_packet_data.receiver.as_str()) {
+            Ok(receiver) => receiver,
+            Err(e) => return error_ack(format!("ERR_INVALID_RECEIVER: {}", e)),
+        };
+        let msg = metadata.args.map_or_else(String::new, |args| match args {
+            Value::String(args) => args,
+            args => args.to_string(),
+        });
+        let packet_data = match PacketData::try_from(ft_packet_data) {
+            Ok(packet_data) => packet_data,
+            Err(e) => return error_ack(format!("ERR_INVALID_PACKET_DATA: {}", e)),
+        };
+        let amount = match u128::from_str(packet_data.token.amount.to_string().as_str()) {
+            Ok(amount) => U128(amount),
+            Err(e) => return error_ack(format!("ERR_INVALID_AMOUNT: {}", e)),
+        };
+        let mut denom = packet_data.token.denom;
+        let promise = if is_receiver_chain_source(
+            packet.port_id_on_a.clone(),
+            packet.chan_id_on_a.clone(),
+            &denom,
+        ) {
+            denom.remove_trace_prefix(&TracePrefix::new(
+                packet.port_id_on_a.clone(),
+                packet.chan_id_on_a.clone(),
+            ));
+            // The tokens held by the escrows of other channels for the forwarded packets
+            // are not passed to the contract, as they can not be credited back to the escrow
+            // of the receiving channel if the contract doesn't use them.
+            if forward::holders_of_escrowed_tokens(
+                packet.chan_id_on_b.as_str(),
+                denom.to_string().as_str(),
+                amount.0,
+            )
+            .iter()
+            .any(|(holder, _)| holder != packet.chan_id_on_b.as_str())
+            {
+                return error_ack("ERR_HOOK_TOKENS_HELD_BY_OTHER_ESCROWS".to_string());
+            }
+            let escrow_account = AccountId::from_str(
+                escrow_account_of(&packet.port_id_on_b, &packet.chan_id_on_b).as_str(),
+            )
+            .unwrap();
+            ExtraDepositCost::add(1);
+            ext_channel_escrow::ext(escrow_account)
+                .with_attached_deposit(NearToken::from_yoctonear(1))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(12))
+                .with_unused_gas_weight(1)
+                .do_transfer_call(
+                    denom.trace_path.to_string(),
+                    denom.base_denom.to_string(),
+                    contract,
+                    amount,
+                    msg,
+                    refund_receiver,
+                )
+        } else {
+            denom.add_trace_prefix(TracePrefix::new(
+                packet.port_id_on_b.clone(),
+                packet.chan_id_on_b.clone(),
+            ));
+            ExtraDepositCost::add(utils::STORAGE_DEPOSIT_FOR_MINT_TOKEN);
+            ext_token_factory::ext(utils::get_token_factory_contract_id())
+                .with_attached_deposit(NearToken::from_yoctonear(
+                    utils::STORAGE_DEPOSIT_FOR_MINT_TOKEN,
+                ))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(12))
+                .with_unused_gas_weight(1)
+                .mint_asset_and_call(
+                    denom.trace_path.to_string(),
+                    denom.base_denom.to_string(),
+                    contract,
+                    amount,
+                    msg,
+                    refund_receiver,
+                )
+        };
+        promise.then(
+            ext_ibc_hooks_callback::ext(env::current_account_id())
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+                .with_unused_gas_weight(0)
+                .on_ibc_hook_executed(Base64VecU8(Protobuf::<RawPacket>::encode_vec(
+                    packet.clone(),
+                ))),
+        );
+        log!(
+            "Packet {}/{}/{} triggers IBC hook of contract {}.",
+            packet.port_id_on_b,
+            packet.chan_id_on_b,
+            packet.seq_on_a,
+            metadata.contract
+        );
+        async_ack_placeholder()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_hook_metadata;
+
+    #[test]
+    fn test_parse_hook_metadata() {
+        assert!(parse_hook_metadata("").is_none());
+        assert!(parse_hook_metadata(r#"{"forward":{}}"#).is_none());
+        assert!(
+            parse_hook_metadata(r#"{"near":{"method":"ft_transfer_call"}}"#)
+                .unwrap()
+                .is_err()
+        );
+        let metadata = parse_hook_metadata(
+            r#"{"near":{"contract":"v2.ref-finance.near","method":"ft_transfer_call","args":{"actions":[]}}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(metadata.contract, "v2.ref-finance.near");
+        assert_eq!(metadata.args.unwrap().to_string(), r#"{"actions":[]}"#);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{AccountIdConversion, TransferModule};
+use super::{forward, rate_limit, AccountIdConversion, TransferModule};
 use crate::prelude::*;
 use core::str::FromStr;
 use ibc::{
@@ -10,10 +10,57 @@ use ibc::{
 };
 use near_sdk::{env, json_types::U128, log, NearToken};
 use utils::{
-    interfaces::{ext_channel_escrow, ext_process_transfer_request_callback, ext_token_factory},
+    interfaces::{ext_channel_escrow, ext_token_factory},
     ExtraDepositCost,
 };
 
+/// Get the channel id of the given account, if it is a channel escrow.
+fn channel_of_escrow(account_id: &near_sdk::AccountId) -> Option<String> {
+    let prefixed_ef = format!(".ef.transfer.{}", env::current_account_id());
+    account_id
+        .as_str()
+        .strip_suffix(prefixed_ef.as_str())
+        .map(|channel_id| channel_id.to_string())
+}
+
+/// Get the channel id in the first trace prefix of the given coin,
+/// through which the coin is sent back when it is burnt.
+fn channel_of_burning_coin(amt: &PrefixedCoin) -> Option<String> {
+    amt.denom
+        .trace_path
+        .to_string()
+        .split('/')
+        .nth(1)
+        .map(|channel_id| channel_id.to_string())
+}
+
+/// Whether the given coin is native in NEAR protocol. The other coins held by channel escrows
+/// are the vouchers recorded for the forwarded packets (refer to `forward` module).
+fn is_native_coin(amt: &PrefixedCoin) -> bool {
+    amt.denom.trace_path.is_empty()
+}
+
+/// Get the amount of the given coin in `u128`, or `u128::MAX` if it is too large.
+fn amount_of(amt: &PrefixedCoin) -> u128 {
+    u128::from_str(amt.amount.to_string().as_str()).unwrap_or(u128::MAX)
+}
+
+/// Check whether the escrow of the given channel holds enough vouchers of the given coin.
+fn check_escrowed_vouchers(
+    channel_id: &str,
+    coin: &PrefixedCoin,
+) -> Result<(), TokenTransferError> {
+    match forward::escrowed_voucher_amount(channel_id, coin.denom.to_string().as_str())
+        >= amount_of(coin)
+    {
+        true => Ok(()),
+        false => Err(TokenTransferError::Other(format!(
+            "ERR_NOT_ENOUGH_ESCROWED_VOUCHERS: channel {}, denom {}",
+            channel_id, coin.denom
+        ))),
+    }
+}
+
 impl TokenTransferExecutionContext for TransferModule {
     fn send_coins_execute(
         &mut self,
@@ -26,29 +73,66 @@ impl TokenTransferExecutionContext for TransferModule {
         let trace_path = amt.denom.trace_path.to_string();
         let base_denom = amt.denom.base_denom.to_string();
         let prefixed_ef = format!(".ef.transfer.{}", env::current_account_id());
-        if receiver_id.ends_with(prefixed_ef.as_str()) {
-            ext_process_transfer_request_callback::ext(to.0.clone())
-                .with_attached_deposit(NearToken::from_yoctonear(0))
-                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
-                .with_unused_gas_weight(0)
-                .apply_transfer_request(
-                    trace_path,
-                    base_denom,
-                    from.0.clone(),
-                    U128(u128::from_str(amt.amount.to_string().as_str()).unwrap()),
+        if let Some(channel_id) = channel_of_escrow(&to.0) {
+            rate_limit::record_outflow(&channel_id, amt.denom.to_string().as_str(), amount_of(amt));
+        }
+        if let Some(channel_id) = channel_of_escrow(&from.0).filter(|_| !is_native_coin(amt)) {
+            // The vouchers held by the escrow are moved to the receiver, by crediting them
+            // to another escrow or minting them.
+            forward::debit_escrowed_vouchers(
+                &channel_id,
+                amt.denom.to_string().as_str(),
+                amount_of(amt),
+            )
+            .map_err(TokenTransferError::Other)?;
+            return self.mint_coins_execute(to, amt);
+        }
+        if let Some(channel_id) = channel_of_escrow(&from.0) {
+            // The sender is an escrow account when unescrowing or refunding tokens, and also
+            // when forwarding tokens to another escrow by the packet forward middleware,
+            // in which case the tokens are only recorded as held for the other escrow.
+            if let Some(to_channel_id) = channel_of_escrow(&to.0) {
+                forward::move_escrowed_tokens(
+                    &channel_id,
+                    &to_channel_id,
+                    amt.denom.to_string().as_str(),
+                    amount_of(amt),
                 );
-        } else if sender_id.ends_with(prefixed_ef.as_str()) {
-            ext_channel_escrow::ext(from.0.clone())
+                return Ok(());
+            }
+            for (holder_channel_id, amount) in forward::take_escrowed_tokens(
+                &channel_id,
+                amt.denom.to_string().as_str(),
+                amount_of(amt),
+            ) {
+                ext_channel_escrow::ext(
+                    near_sdk::AccountId::from_str(
+                        format!("{}{}", holder_channel_id, prefixed_ef).as_str(),
+                    )
+                    .unwrap(),
+                )
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(6))
                 .with_unused_gas_weight(0)
                 .do_transfer(
-                    trace_path,
-                    base_denom,
+                    trace_path.clone(),
+                    base_denom.clone(),
                     to.0.clone(),
-                    U128(u128::from_str(amt.amount.to_string().as_str()).unwrap()),
+                    U128(amount),
                 );
-            ExtraDepositCost::add(1);
+                ExtraDepositCost::add(1);
+            }
+        } else if receiver_id.ends_with(prefixed_ef.as_str()) {
+            // The tokens are already locked in the escrow contract when the transfer request
+            // is created. The pending transfer request will be applied after `send_transfer`
+            // succeeds, in `process_transfer_request`.
+            log!(
+                "Coins of trace path {}, base denom {} from {} are already in escrow {}",
+                trace_path,
+                base_denom,
+                sender_id,
+                receiver_id
+            );
         } else {
             panic!("Neither sender nor receiver is an escrow account. This should not happen.");
         }
@@ -60,6 +144,15 @@ impl TokenTransferExecutionContext for TransferModule {
         account: &Self::AccountId,
         amt: &PrefixedCoin,
     ) -> Result<(), TokenTransferError> {
+        if let Some(channel_id) = channel_of_escrow(&account.0) {
+            // The vouchers held by channel escrows are only recorded.
+            forward::credit_escrowed_vouchers(
+                &channel_id,
+                amt.denom.to_string().as_str(),
+                amount_of(amt),
+            );
+            return Ok(());
+        }
         log!(
             "Minting coins for account {}, trace path {}, base denom {}",
             account.0,
@@ -93,16 +186,21 @@ impl TokenTransferExecutionContext for TransferModule {
             amt.denom.trace_path,
             amt.denom.base_denom
         );
-        ext_process_transfer_request_callback::ext(env::predecessor_account_id())
-            .with_attached_deposit(NearToken::from_yoctonear(0))
-            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
-            .with_unused_gas_weight(0)
-            .apply_transfer_request(
-                amt.denom.trace_path.to_string(),
-                amt.denom.base_denom.to_string(),
-                account.0.clone(),
-                U128(u128::from_str(amt.amount.to_string().as_str()).unwrap()),
-            );
+        // The tokens are already locked in the wrapped token contract when the transfer
+        // request is created. They will be burnt when the pending transfer request is applied
+        // after `send_transfer` succeeds, in `process_transfer_request`.
+        if let Some(channel_id) = channel_of_burning_coin(amt) {
+            rate_limit::record_outflow(&channel_id, amt.denom.to_string().as_str(), amount_of(amt));
+        }
+        // The vouchers held by the escrow are burnt by debiting them.
+        if let Some(channel_id) = channel_of_escrow(&account.0) {
+            forward::debit_escrowed_vouchers(
+                &channel_id,
+                amt.denom.to_string().as_str(),
+                amount_of(amt),
+            )
+            .map_err(TokenTransferError::Other)?;
+        }
         Ok(())
     }
 }
@@ -131,37 +229,69 @@ impl TokenTransferValidationContext for TransferModule {
     }
 
     fn can_send_coins(&self) -> Result<(), TokenTransferError> {
-        // TODO: check if this is correct
+        // The sending is not disabled as a whole, the outflow of each channel and denom
+        // is limited in `send_coins_validate` and `burn_coins_validate`.
         Ok(())
     }
 
     fn can_receive_coins(&self) -> Result<(), TokenTransferError> {
-        // TODO: check if this is correct
+        // The receiving is not disabled as a whole, the inflow of each channel and denom
+        // is limited in `send_coins_validate` and `mint_coins_validate`.
         Ok(())
     }
 
     fn send_coins_validate(
         &self,
-        _from_account: &Self::AccountId,
-        _to_account: &Self::AccountId,
-        _coin: &PrefixedCoin,
+        from_account: &Self::AccountId,
+        to_account: &Self::AccountId,
+        coin: &PrefixedCoin,
     ) -> Result<(), TokenTransferError> {
-        Ok(())
+        if let Some(channel_id) =
+            channel_of_escrow(&from_account.0).filter(|_| !is_native_coin(coin))
+        {
+            check_escrowed_vouchers(&channel_id, coin)?;
+        }
+        if let Some(channel_id) = channel_of_escrow(&to_account.0) {
+            rate_limit::check_outflow(
+                &channel_id,
+                coin.denom.to_string().as_str(),
+                amount_of(coin),
+            )
+            .map_err(TokenTransferError::Other)?;
+        }
+        // The tokens are also sent from escrow accounts for refunding, which must not
+        // be limited, so the inflow is only checked for the unescrowing of received packets.
+        rate_limit::check_receiving_inflow(coin.denom.to_string().as_str(), amount_of(coin))
+            .map_err(TokenTransferError::Other)
     }
 
     fn mint_coins_validate(
         &self,
         _account: &Self::AccountId,
-        _coin: &PrefixedCoin,
+        coin: &PrefixedCoin,
     ) -> Result<(), TokenTransferError> {
-        Ok(())
+        // The tokens are also minted for refunding, which must not be limited,
+        // so the inflow is only checked for the minting of received packets.
+        rate_limit::check_receiving_inflow(coin.denom.to_string().as_str(), amount_of(coin))
+            .map_err(TokenTransferError::Other)
     }
 
     fn burn_coins_validate(
         &self,
-        _account: &Self::AccountId,
-        _coin: &PrefixedCoin,
+        account: &Self::AccountId,
+        coin: &PrefixedCoin,
     ) -> Result<(), TokenTransferError> {
+        if let Some(channel_id) = channel_of_escrow(&account.0) {
+            check_escrowed_vouchers(&channel_id, coin)?;
+        }
+        if let Some(channel_id) = channel_of_burning_coin(coin) {
+            rate_limit::check_outflow(
+                &channel_id,
+                coin.denom.to_string().as_str(),
+                amount_of(coin),
+            )
+            .map_err(TokenTransferError::Other)?;
+        }
         Ok(())
     }
 }
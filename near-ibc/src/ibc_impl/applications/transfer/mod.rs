@@ -1,10 +1,12 @@
-use crate::{context::NearIbcStoreHost, prelude::*};
+use crate::{
+    context::NearIbcStoreHost, ibc_impl::core::channel_upgrade::ChannelUpgradeModule, prelude::*,
+};
 use core::{fmt::Debug, str::FromStr};
 use ibc::{
     apps::transfer::types::packet::PacketData,
     core::{
         channel::types::{
-            acknowledgement::Acknowledgement,
+            acknowledgement::{Acknowledgement, AcknowledgementStatus},
             channel::{Counterparty, Order},
             error::{ChannelError, PacketError},
             packet::Packet,
@@ -18,19 +20,120 @@ use ibc::{
 use ibc_proto::ibc::apps::transfer::v2::FungibleTokenPacketData;
 use near_sdk::{
     borsh::{BorshDeserialize, BorshSerialize},
-    log, serde_json, AccountId,
+    env, log, serde_json, AccountId,
 };
 
+pub mod async_ack;
+pub mod forward;
+pub mod hooks;
 pub mod impls;
+pub mod multi_denom;
+pub mod rate_limit;
 
 pub struct AccountIdConversion(AccountId);
 
+/// Get the escrow account of the given channel, in string format.
+pub fn escrow_account_of(port_id: &PortId, channel_id: &ChannelId) -> String {
+    format!(
+        "{}.ef.{}.{}",
+        channel_id,
+        port_id,
+        env::current_account_id()
+    )
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
 #[borsh(crate = "near_sdk::borsh")]
 pub struct TransferModule();
 
 impl NearIbcStoreHost for TransferModule {}
 
+impl TransferModule {
+    /// Process the received ICS-20 packet by the packet forward middleware, IBC hooks
+    /// or the default implementation of `ibc-rs`.
+    fn on_recv_ft_packet(
+        &mut self,
+        packet: &Packet,
+        ft_packet_data: FungibleTokenPacketData,
+    ) -> (ModuleExtras, Acknowledgement) {
+        let forward_metadata = forward::parse_forward_metadata(ft_packet_data.memo.as_str());
+        let hook_metadata = hooks::parse_hook_metadata(ft_packet_data.memo.as_str());
+        if forward_metadata.is_some() || hook_metadata.is_some() {
+            // The tokens forwarded or passed to IBC hooks are not unescrowed or minted
+            // by `ibc-rs`, so the inflow of them is checked here.
+            let (denom, amount) = received_denom_and_amount(packet, &ft_packet_data);
+            if let Err(e) = rate_limit::check_inflow(packet.chan_id_on_b.as_str(), &denom, amount) {
+                log!("Packet acknowledgement: {}", e);
+                return (ModuleExtras::empty(), async_ack::error_ack(e));
+            }
+        }
+        if let Some(metadata) = forward_metadata {
+            let ack = self.on_recv_forward_packet(packet, ft_packet_data, metadata);
+            return (ModuleExtras::empty(), ack);
+        }
+        if let Some(metadata) = hook_metadata {
+            let ack = self.on_recv_hook_packet(packet, ft_packet_data, metadata);
+            return (ModuleExtras::empty(), ack);
+        }
+        let maybe_ft_packet = Packet {
+            data: serde_json::to_string(
+                &PacketData::try_from(ft_packet_data).expect("Invalid packet data"),
+            )
+            .expect("Invalid packet data")
+            .into_bytes(),
+            ..packet.clone()
+        };
+        rate_limit::set_receiving_channel(Some(packet.chan_id_on_b.as_str()));
+        let (extras, ack) =
+            ibc::apps::transfer::module::on_recv_packet_execute(self, &maybe_ft_packet);
+        rate_limit::set_receiving_channel(None);
+        let ack_status =
+            String::from_utf8(ack.as_bytes().to_vec()).expect("Invalid acknowledgement string");
+        log!("Packet acknowledgement: {}", ack_status);
+        (extras, ack)
+    }
+}
+
+/// Get the denom on this chain and the amount of the tokens in the given received packet.
+fn received_denom_and_amount(
+    packet: &Packet,
+    ft_packet_data: &FungibleTokenPacketData,
+) -> (String, u128) {
+    let source_prefix = format!("{}/{}/", packet.port_id_on_a, packet.chan_id_on_a);
+    let denom = match ft_packet_data.denom.strip_prefix(source_prefix.as_str()) {
+        Some(denom) => denom.to_string(),
+        None => format!(
+            "{}/{}/{}",
+            packet.port_id_on_b, packet.chan_id_on_b, ft_packet_data.denom
+        ),
+    };
+    (
+        denom,
+        u128::from_str(ft_packet_data.amount.as_str()).unwrap_or(u128::MAX),
+    )
+}
+
+/// Validate the order of a channel of ICS-20 v2, whose version is not supported by `ibc-rs`.
+fn validate_v2_channel_order(order: Order) -> Result<(), ChannelError> {
+    match order {
+        Order::Unordered => Ok(()),
+        _ => Err(ChannelError::AppModule {
+            description: format!("ERR_INVALID_CHANNEL_ORDER: {}", order),
+        }),
+    }
+}
+
+/// Revert the outflow of the given packet sent by this chain, as the tokens are refunded.
+fn undo_outflow_of(packet: &Packet) {
+    if let Ok(ft_packet_data) = serde_json::from_slice::<FungibleTokenPacketData>(&packet.data) {
+        rate_limit::undo_outflow(
+            packet.chan_id_on_a.as_str(),
+            &ft_packet_data.denom,
+            u128::from_str(ft_packet_data.amount.as_str()).unwrap_or(0),
+        );
+    }
+}
+
 impl Module for TransferModule {
     fn on_chan_open_init_validate(
         &self,
@@ -41,6 +144,10 @@ impl Module for TransferModule {
         counterparty: &Counterparty,
         version: &Version,
     ) -> Result<Version, ChannelError> {
+        if multi_denom::is_v2_version(version) {
+            validate_v2_channel_order(order)?;
+            return Ok(version.clone());
+        }
         ibc::apps::transfer::module::on_chan_open_init_validate(
             self,
             order,
@@ -65,6 +172,10 @@ impl Module for TransferModule {
         counterparty: &Counterparty,
         counterparty_version: &Version,
     ) -> Result<Version, ChannelError> {
+        if multi_denom::is_v2_version(counterparty_version) {
+            validate_v2_channel_order(order)?;
+            return Ok(counterparty_version.clone());
+        }
         ibc::apps::transfer::module::on_chan_open_try_validate(
             self,
             order,
@@ -86,6 +197,9 @@ impl Module for TransferModule {
         channel_id: &ChannelId,
         counterparty_version: &Version,
     ) -> Result<(), ChannelError> {
+        if multi_denom::is_v2_version(counterparty_version) {
+            return Ok(());
+        }
         ibc::apps::transfer::module::on_chan_open_ack_validate(
             self,
             port_id,
@@ -139,25 +253,24 @@ impl Module for TransferModule {
         packet: &Packet,
         _relayer: &Signer,
     ) -> (ModuleExtras, Acknowledgement) {
+        if multi_denom::is_v2_channel(&packet.port_id_on_b, &packet.chan_id_on_b) {
+            let ack = self.on_recv_multi_denom_packet(packet);
+            return (ModuleExtras::empty(), ack);
+        }
         log!(
             "Received packet: {:?}",
             String::from_utf8(packet.data.to_vec()).expect("Invalid packet data")
         );
         let ft_packet_data = serde_json::from_slice::<FungibleTokenPacketData>(&packet.data)
             .expect("Invalid packet data");
-        let maybe_ft_packet = Packet {
-            data: serde_json::to_string(
-                &PacketData::try_from(ft_packet_data).expect("Invalid packet data"),
-            )
-            .expect("Invalid packet data")
-            .into_bytes(),
-            ..packet.clone()
-        };
-        let (extras, ack) =
-            ibc::apps::transfer::module::on_recv_packet_execute(self, &maybe_ft_packet);
-        let ack_status =
-            String::from_utf8(ack.as_bytes().to_vec()).expect("Invalid acknowledgement string");
-        log!("Packet acknowledgement: {}", ack_status);
+        let (denom, amount) = received_denom_and_amount(packet, &ft_packet_data);
+        let (extras, ack) = self.on_recv_ft_packet(packet, ft_packet_data);
+        if !matches!(
+            serde_json::from_slice::<AcknowledgementStatus>(ack.as_bytes()),
+            Ok(AcknowledgementStatus::Error(_))
+        ) {
+            rate_limit::record_inflow(packet.chan_id_on_b.as_str(), &denom, amount);
+        }
         (extras, ack)
     }
 
@@ -167,6 +280,14 @@ impl Module for TransferModule {
         acknowledgement: &Acknowledgement,
         relayer: &Signer,
     ) -> Result<(), PacketError> {
+        if multi_denom::is_v2_channel(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()).map_err(
+                |e| PacketError::AppModule {
+                    description: format!("ERR_INVALID_ACKNOWLEDGEMENT: {}", e),
+                },
+            )?;
+            return multi_denom::validate_sent_packet(packet);
+        }
         ibc::apps::transfer::module::on_acknowledgement_packet_validate(
             self,
             packet,
@@ -183,6 +304,9 @@ impl Module for TransferModule {
         packet: &Packet,
         relayer: &Signer,
     ) -> Result<(), PacketError> {
+        if multi_denom::is_v2_channel(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            return multi_denom::validate_sent_packet(packet);
+        }
         ibc::apps::transfer::module::on_timeout_packet_validate(self, packet, relayer).map_err(
             |e| PacketError::AppModule {
                 description: e.to_string(),
@@ -199,6 +323,9 @@ impl Module for TransferModule {
         counterparty: &Counterparty,
         version: &Version,
     ) -> Result<(ModuleExtras, Version), ChannelError> {
+        if multi_denom::is_v2_version(version) {
+            return Ok((ModuleExtras::empty(), version.clone()));
+        }
         ibc::apps::transfer::module::on_chan_open_init_execute(
             self,
             order,
@@ -222,6 +349,9 @@ impl Module for TransferModule {
         counterparty: &Counterparty,
         counterparty_version: &Version,
     ) -> Result<(ModuleExtras, Version), ChannelError> {
+        if multi_denom::is_v2_version(counterparty_version) {
+            return Ok((ModuleExtras::empty(), counterparty_version.clone()));
+        }
         ibc::apps::transfer::module::on_chan_open_try_execute(
             self,
             order,
@@ -242,12 +372,37 @@ impl Module for TransferModule {
         acknowledgement: &Acknowledgement,
         relayer: &Signer,
     ) -> (ModuleExtras, Result<(), PacketError>) {
+        if multi_denom::is_v2_channel(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            let result =
+                match serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()) {
+                    Ok(AcknowledgementStatus::Success(_)) => Ok(()),
+                    _ => self.refund_multi_denom_packet(packet),
+                };
+            return (ModuleExtras::empty(), result);
+        }
         let result = ibc::apps::transfer::module::on_acknowledgement_packet_execute(
             self,
             packet,
             acknowledgement,
             relayer,
         );
+        if result.1.is_ok()
+            && !matches!(
+                serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()),
+                Ok(AcknowledgementStatus::Success(_))
+            )
+        {
+            undo_outflow_of(packet);
+        }
+        if forward::is_forwarded_packet(packet) {
+            let error =
+                match serde_json::from_slice::<AcknowledgementStatus>(acknowledgement.as_bytes()) {
+                    Ok(AcknowledgementStatus::Success(_)) => None,
+                    Ok(AcknowledgementStatus::Error(e)) => Some(e.to_string()),
+                    Err(e) => Some(format!("invalid acknowledgement: {}", e)),
+                };
+            self.on_forwarded_packet_resolved(packet, error);
+        }
         (
             result.0,
             result.1.map_err(|e| PacketError::AppModule {
@@ -261,7 +416,17 @@ impl Module for TransferModule {
         packet: &Packet,
         relayer: &Signer,
     ) -> (ModuleExtras, Result<(), PacketError>) {
+        if multi_denom::is_v2_channel(&packet.port_id_on_a, &packet.chan_id_on_a) {
+            let result = self.refund_multi_denom_packet(packet);
+            return (ModuleExtras::empty(), result);
+        }
         let result = ibc::apps::transfer::module::on_timeout_packet_execute(self, packet, relayer);
+        if result.1.is_ok() {
+            undo_outflow_of(packet);
+        }
+        if forward::is_forwarded_packet(packet) {
+            self.on_forwarded_packet_resolved(packet, Some("packet timed out".to_string()));
+        }
         (
             result.0,
             result.1.map_err(|e| PacketError::AppModule {
@@ -271,6 +436,77 @@ impl Module for TransferModule {
     }
 }
 
+impl ChannelUpgradeModule for TransferModule {
+    fn on_chan_upgrade_init(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        proposed_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        self.on_chan_open_init_validate(
+            proposed_order,
+            proposed_connection_hops,
+            port_id,
+            channel_id,
+            counterparty,
+            proposed_version,
+        )?;
+        match proposed_version.to_string().is_empty() {
+            true => Ok(Version::new(
+                ibc::apps::transfer::types::VERSION.to_string(),
+            )),
+            false => Ok(proposed_version.clone()),
+        }
+    }
+
+    fn on_chan_upgrade_try(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        self.on_chan_open_try_validate(
+            proposed_order,
+            proposed_connection_hops,
+            port_id,
+            channel_id,
+            counterparty,
+            counterparty_version,
+        )
+    }
+
+    fn on_chan_upgrade_ack(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        self.on_chan_open_ack_validate(port_id, channel_id, counterparty_version)
+    }
+
+    fn on_chan_upgrade_open(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        version: &Version,
+    ) {
+        log!(
+            "Channel {}/{} is upgraded to version {}.",
+            port_id,
+            channel_id,
+            version
+        );
+    }
+}
+
 impl TryFrom<Signer> for AccountIdConversion {
     type Error = &'static str;
 
@@ -0,0 +1,583 @@
+//! Multi-denom transfers of ICS-20 v2 (`ics20-2`).
+//!
+//! A v2 packet carries a list of tokens, each with the full trace of its denom. The tokens
+//! native in NEAR protocol are escrowed in the channel escrow, and the wrapped tokens are
+//! locked in their wrapped token contracts (and burnt when the transfer is applied).
+//! When the packet is timed out or acknowledged with an error, all of the tokens are
+//! refunded together.
+//!
+//! The packet forward middleware and IBC hooks are not applied to v2 packets.
+use super::{
+    async_ack::error_ack, escrow_account_of, rate_limit, AccountIdConversion, TransferModule,
+};
+use crate::{
+    context::{NearIbcStore, NearIbcStoreHost},
+    ibc_impl::applications::fee,
+    prelude::*,
+};
+use alloc::collections::BTreeMap;
+use core::str::FromStr;
+use ibc::{
+    apps::transfer::{
+        context::{TokenTransferExecutionContext, TokenTransferValidationContext},
+        types::{ack_success_b64, Amount, BaseDenom, PrefixedCoin, PrefixedDenom, TracePath},
+    },
+    core::{
+        channel::{
+            handler::send_packet,
+            types::{
+                acknowledgement::{Acknowledgement, AcknowledgementStatus},
+                error::PacketError,
+                packet::Packet,
+                timeout::TimeoutHeight,
+                Version,
+            },
+        },
+        host::{
+            types::{
+                identifiers::{ChannelId, PortId, Sequence},
+                path::{ChannelEndPath, SeqSendPath},
+            },
+            ValidationContext,
+        },
+        primitives::Timestamp,
+    },
+};
+use near_sdk::{env, log, AccountId};
+use prost::Message;
+use utils::types::{Ics20MultiTransferRequest, Ics20Token};
+
+/// The version of ICS-20 v2.
+pub const VERSION_V2: &str = "ics20-2";
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawHop {
+    #[prost(string, tag = "1")]
+    pub port_id: String,
+    #[prost(string, tag = "2")]
+    pub channel_id: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawDenom {
+    #[prost(string, tag = "1")]
+    pub base: String,
+    #[prost(message, repeated, tag = "3")]
+    pub trace: Vec<RawHop>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawToken {
+    #[prost(message, optional, tag = "1")]
+    pub denom: Option<RawDenom>,
+    #[prost(string, tag = "2")]
+    pub amount: String,
+}
+
+/// The packet data of ICS-20 v2, which is compatible with
+/// `ibc.applications.transfer.v2.FungibleTokenPacketDataV2` of `ibc-go`.
+#[derive(Clone, PartialEq, Message)]
+pub struct FungibleTokenPacketDataV2 {
+    #[prost(message, repeated, tag = "1")]
+    pub tokens: Vec<RawToken>,
+    #[prost(string, tag = "2")]
+    pub sender: String,
+    #[prost(string, tag = "3")]
+    pub receiver: String,
+    #[prost(string, tag = "4")]
+    pub memo: String,
+}
+
+/// Whether the given channel version is ICS-20 v2 (possibly wrapped by ICS-29).
+pub fn is_v2_version(version: &Version) -> bool {
+    fee::app_version_of(version).to_string() == VERSION_V2
+}
+
+/// Whether the given channel is negotiated with ICS-20 v2.
+pub fn is_v2_channel(port_id: &PortId, channel_id: &ChannelId) -> bool {
+    TransferModule::get_near_ibc_store()
+        .channel_end(&ChannelEndPath::new(port_id, channel_id))
+        .map_or(false, |channel_end| is_v2_version(channel_end.version()))
+}
+
+impl RawDenom {
+    /// The denom with the given hop prepended to its trace.
+    fn with_prefix(&self, port_id: &PortId, channel_id: &ChannelId) -> Self {
+        let mut trace = vec![RawHop {
+            port_id: port_id.to_string(),
+            channel_id: channel_id.to_string(),
+        }];
+        trace.extend(self.trace.iter().cloned());
+        Self {
+            base: self.base.clone(),
+            trace,
+        }
+    }
+    /// The denom with the first hop of its trace removed, if the first hop
+    /// is the given port and channel.
+    fn strip_prefix(&self, port_id: &PortId, channel_id: &ChannelId) -> Option<Self> {
+        match self.trace.first() {
+            Some(hop)
+                if hop.port_id == port_id.as_str() && hop.channel_id == channel_id.as_str() =>
+            {
+                Some(Self {
+                    base: self.base.clone(),
+                    trace: self.trace[1..].to_vec(),
+                })
+            }
+            _ => None,
+        }
+    }
+    ///
+    fn trace_path(&self) -> String {
+        self.trace
+            .iter()
+            .map(|hop| format!("{}/{}", hop.port_id, hop.channel_id))
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+}
+
+/// Convert the given denom and amount to a coin of `ibc-rs`.
+fn prefixed_coin_of(denom: &RawDenom, amount: &str) -> Result<PrefixedCoin, String> {
+    let trace_path = denom.trace_path();
+    Ok(PrefixedCoin {
+        denom: PrefixedDenom {
+            trace_path: TracePath::from_str(trace_path.as_str())
+                .map_err(|e| format!("ERR_INVALID_TRACE_PATH: {}", e))?,
+            base_denom: BaseDenom::from_str(denom.base.as_str())
+                .map_err(|e| format!("ERR_INVALID_BASE_DENOM: {}", e))?,
+        },
+        amount: Amount::from_str(amount).map_err(|e| format!("ERR_INVALID_AMOUNT: {}", e))?,
+    })
+}
+
+/// Get the amount of the given coin in `u128`, or `u128::MAX` if it is too large.
+fn amount_of(coin: &PrefixedCoin) -> u128 {
+    u128::from_str(coin.amount.to_string().as_str()).unwrap_or(u128::MAX)
+}
+
+/// Decode the packet data and convert the tokens in it to coins of `ibc-rs`.
+fn decode_packet_data(
+    data: &[u8],
+) -> Result<(FungibleTokenPacketDataV2, Vec<(RawDenom, PrefixedCoin)>), String> {
+    let packet_data = FungibleTokenPacketDataV2::decode(data)
+        .map_err(|e| format!("ERR_INVALID_PACKET_DATA: {}", e))?;
+    if packet_data.tokens.is_empty()
+        || packet_data.tokens.len() > utils::MAX_TOKENS_IN_MULTI_DENOM_TRANSFER
+    {
+        return Err("ERR_INVALID_NUMBER_OF_TOKENS".to_string());
+    }
+    let coins = packet_data
+        .tokens
+        .iter()
+        .map(|token| {
+            let denom = token
+                .denom
+                .clone()
+                .ok_or_else(|| "ERR_MISSING_DENOM".to_string())?;
+            let coin = prefixed_coin_of(&denom, token.amount.as_str())?;
+            match amount_of(&coin) > 0 {
+                true => Ok((denom, coin)),
+                false => Err("ERR_AMOUNT_MUST_BE_GREATER_THAN_ZERO".to_string()),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok((packet_data, coins))
+}
+
+/// Convert the given token in a transfer request to the denom in ICS-20 v2.
+fn raw_denom_of(token: &Ics20Token) -> Result<RawDenom, String> {
+    let trace_path = TracePath::from_str(token.trace_path.as_str())
+        .map_err(|e| format!("ERR_INVALID_TRACE_PATH: {}", e))?
+        .to_string();
+    let parts: Vec<&str> = trace_path.split('/').filter(|s| !s.is_empty()).collect();
+    Ok(RawDenom {
+        base: token.base_denom.clone(),
+        trace: parts
+            .chunks(2)
+            .map(|hop| RawHop {
+                port_id: hop[0].to_string(),
+                channel_id: hop[1].to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Build the ICS-20 v2 packet of the given request and send it through the channel.
+///
+/// The outflow of each of the tokens is checked and recorded for the rate limits.
+pub fn send_multi_denom_packet(
+    near_ibc_store: &mut NearIbcStore,
+    transfer_request: &Ics20MultiTransferRequest,
+) -> Result<Sequence, String> {
+    let port_id_on_a =
+        PortId::from_str(transfer_request.port_on_a.as_str()).map_err(|e| format!("{:?}", e))?;
+    let chan_id_on_a =
+        ChannelId::from_str(transfer_request.chan_on_a.as_str()).map_err(|e| format!("{:?}", e))?;
+    let channel_end = near_ibc_store
+        .channel_end(&ChannelEndPath::new(&port_id_on_a, &chan_id_on_a))
+        .map_err(|e| format!("{:?}", e))?;
+    if !is_v2_version(channel_end.version()) {
+        return Err("ERR_CHANNEL_IS_NOT_ICS20_V2".to_string());
+    }
+    let tokens = transfer_request
+        .tokens
+        .iter()
+        .map(|token| {
+            Ok(RawToken {
+                denom: Some(raw_denom_of(token)?),
+                amount: token.amount.0.to_string(),
+            })
+        })
+        .collect::<Result<Vec<RawToken>, String>>()?;
+    let packet_data = FungibleTokenPacketDataV2 {
+        tokens,
+        sender: transfer_request.sender.clone(),
+        receiver: transfer_request.receiver.clone(),
+        memo: transfer_request.memo.clone().unwrap_or_default(),
+    };
+    let data = packet_data.encode_to_vec();
+    let (_, coins) = decode_packet_data(&data)?;
+    for (_, coin) in coins.iter() {
+        rate_limit::check_outflow(
+            chan_id_on_a.as_str(),
+            coin.denom.to_string().as_str(),
+            amount_of(coin),
+        )?;
+    }
+    let sequence = near_ibc_store
+        .get_next_sequence_send(&SeqSendPath::new(&port_id_on_a, &chan_id_on_a))
+        .map_err(|e| format!("{:?}", e))?;
+    let timeout_seconds = transfer_request
+        .timeout_seconds
+        .map_or(crate::DEFAULT_TIMEOUT_SECONDS, |value| value.0);
+    let packet = Packet {
+        seq_on_a: sequence,
+        port_id_on_a,
+        chan_id_on_a: chan_id_on_a.clone(),
+        port_id_on_b: channel_end.counterparty().port_id().clone(),
+        chan_id_on_b: channel_end
+            .counterparty()
+            .channel_id()
+            .ok_or_else(|| "ERR_COUNTERPARTY_CHANNEL_NOT_FOUND".to_string())?
+            .clone(),
+        data,
+        timeout_height_on_b: TimeoutHeight::Never {},
+        timeout_timestamp_on_b: Timestamp::from_nanoseconds(
+            env::block_timestamp() + timeout_seconds * 1000000000,
+        )
+        .unwrap(),
+    };
+    send_packet(near_ibc_store, packet).map_err(|e| format!("{:?}", e))?;
+    for (_, coin) in coins.iter() {
+        rate_limit::record_outflow(
+            chan_id_on_a.as_str(),
+            coin.denom.to_string().as_str(),
+            amount_of(coin),
+        );
+    }
+    Ok(sequence)
+}
+
+/// Validate the data of the given v2 packet sent by this chain.
+pub fn validate_sent_packet(packet: &Packet) -> Result<(), PacketError> {
+    decode_packet_data(&packet.data)
+        .map(|_| ())
+        .map_err(|description| PacketError::AppModule { description })
+}
+
+impl TransferModule {
+    /// Resolve the tokens in the given received v2 packet on this chain, and validate
+    /// the unescrowing or minting of all of them, including the total inflow of each denom.
+    ///
+    /// Returns the coins to unescrow (with `true`) or mint (with `false`).
+    fn validate_received_coins(
+        &self,
+        packet: &Packet,
+        coins: &[(RawDenom, PrefixedCoin)],
+        escrow_account: &AccountIdConversion,
+        receiver: &AccountIdConversion,
+    ) -> Result<Vec<(bool, PrefixedCoin)>, String> {
+        let mut received_coins = Vec::new();
+        let mut inflows = BTreeMap::<String, u128>::new();
+        for (denom, coin) in coins.iter() {
+            let (is_source, denom) =
+                match denom.strip_prefix(&packet.port_id_on_a, &packet.chan_id_on_a) {
+                    Some(denom) => (true, denom),
+                    None => (
+                        false,
+                        denom.with_prefix(&packet.port_id_on_b, &packet.chan_id_on_b),
+                    ),
+                };
+            let coin = prefixed_coin_of(&denom, coin.amount.to_string().as_str())?;
+            match is_source {
+                true => self.send_coins_validate(escrow_account, receiver, &coin),
+                false => self.mint_coins_validate(receiver, &coin),
+            }
+            .map_err(|e| e.to_string())?;
+            let inflow = inflows.entry(coin.denom.to_string()).or_default();
+            *inflow = inflow.saturating_add(amount_of(&coin));
+            received_coins.push((is_source, coin));
+        }
+        for (denom, amount) in inflows.iter() {
+            rate_limit::check_inflow(packet.chan_id_on_b.as_str(), denom.as_str(), *amount)?;
+        }
+        Ok(received_coins)
+    }
+    /// Unescrow or mint all of the tokens in the given received v2 packet.
+    ///
+    /// All of the tokens are validated before unescrowing or minting any, and the execution
+    /// of them can not fail after the validation, so that either all or none of the tokens
+    /// are received.
+    pub fn on_recv_multi_denom_packet(&mut self, packet: &Packet) -> Acknowledgement {
+        let (packet_data, coins) = match decode_packet_data(&packet.data) {
+            Ok(result) => result,
+            Err(e) => return error_ack(e),
+        };
+        let receiver = match AccountId::from_str(packet_data.receiver.as_str()) {
+            Ok(receiver) => AccountIdConversion(receiver),
+            Err(e) => return error_ack(format!("ERR_INVALID_RECEIVER: {}", e)),
+        };
+        let escrow_account = AccountIdConversion(
+            AccountId::from_str(
+                escrow_account_of(&packet.port_id_on_b, &packet.chan_id_on_b).as_str(),
+            )
+            .unwrap(),
+        );
+        let received_coins =
+            match self.validate_received_coins(packet, &coins, &escrow_account, &receiver) {
+                Ok(received_coins) => received_coins,
+                Err(e) => return error_ack(e),
+            };
+        for (is_source, coin) in received_coins.iter() {
+            match is_source {
+                true => self.send_coins_execute(&escrow_account, &receiver, coin),
+                false => self.mint_coins_execute(&receiver, coin),
+            }
+            .expect("ERR_RECEIVING_VALIDATED_COIN");
+            rate_limit::record_inflow(
+                packet.chan_id_on_b.as_str(),
+                coin.denom.to_string().as_str(),
+                amount_of(coin),
+            );
+        }
+        AcknowledgementStatus::success(ack_success_b64()).into()
+    }
+    /// Refund all of the tokens in the given v2 packet sent by this chain to the sender.
+    ///
+    /// All of the tokens are validated before refunding any, and the execution of them
+    /// can not fail after the validation, so that either all or none of the tokens
+    /// are refunded.
+    pub fn refund_multi_denom_packet(&mut self, packet: &Packet) -> Result<(), PacketError> {
+        let (packet_data, coins) = decode_packet_data(&packet.data)
+            .map_err(|description| PacketError::AppModule { description })?;
+        let sender = AccountIdConversion(
+            AccountId::from_str(packet_data.sender.as_str()).map_err(|e| {
+                PacketError::AppModule {
+                    description: format!("ERR_INVALID_SENDER: {}", e),
+                }
+            })?,
+        );
+        let escrow_account = AccountIdConversion(
+            AccountId::from_str(
+                escrow_account_of(&packet.port_id_on_a, &packet.chan_id_on_a).as_str(),
+            )
+            .unwrap(),
+        );
+        // The tokens whose trace starts with the sending channel were burnt,
+        // and the other ones were escrowed.
+        let refunded_coins = coins
+            .iter()
+            .map(|(denom, coin)| {
+                let is_burnt = denom
+                    .strip_prefix(&packet.port_id_on_a, &packet.chan_id_on_a)
+                    .is_some();
+                match is_burnt {
+                    true => self.mint_coins_validate(&sender, coin),
+                    false => self.send_coins_validate(&escrow_account, &sender, coin),
+                }
+                .map(|_| (is_burnt, coin))
+                .map_err(|e| PacketError::AppModule {
+                    description: e.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, PacketError>>()?;
+        for (is_burnt, coin) in refunded_coins {
+            match is_burnt {
+                true => self.mint_coins_execute(&sender, coin),
+                false => self.send_coins_execute(&escrow_account, &sender, coin),
+            }
+            .expect("ERR_REFUNDING_VALIDATED_COIN");
+            rate_limit::undo_outflow(
+                packet.chan_id_on_a.as_str(),
+                coin.denom.to_string().as_str(),
+                amount_of(coin),
+            );
+        }
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"MULTI_DENOM_TRANSFER_REFUNDED","port_id":"{}","channel_id":"{}","sequence":"{}","sender":"{}","number_of_tokens":"{}"}}"#,
+            packet.port_id_on_a,
+            packet.chan_id_on_a,
+            packet.seq_on_a,
+            packet_data.sender,
+            coins.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::applications::transfer::rate_limit::{FlowQuota, RateLimitConfig};
+    use near_sdk::{
+        json_types::{U128, U64},
+        test_utils::{get_created_receipts, VMContextBuilder},
+        testing_env,
+    };
+
+    fn raw_token_of(base: &str, amount: u128) -> RawToken {
+        RawToken {
+            denom: Some(RawDenom {
+                base: base.to_string(),
+                trace: vec![],
+            }),
+            amount: amount.to_string(),
+        }
+    }
+
+    fn received_packet_of(tokens: Vec<RawToken>) -> Packet {
+        Packet {
+            seq_on_a: 1u64.into(),
+            port_id_on_a: PortId::transfer(),
+            chan_id_on_a: ChannelId::new(1),
+            port_id_on_b: PortId::transfer(),
+            chan_id_on_b: ChannelId::new(0),
+            data: FungibleTokenPacketDataV2 {
+                tokens,
+                sender: "cosmos1sender".to_string(),
+                receiver: "alice.near".to_string(),
+                memo: String::new(),
+            }
+            .encode_to_vec(),
+            timeout_height_on_b: TimeoutHeight::Never,
+            timeout_timestamp_on_b: Timestamp::none(),
+        }
+    }
+
+    fn set_inflow_limit(base_denom: &str, max_inflow: u128) {
+        rate_limit::set_rate_limit(
+            "channel-0".to_string(),
+            format!("transfer/channel-0/{}", base_denom),
+            RateLimitConfig {
+                max_inflow: Some(FlowQuota::Absolute(U128(max_inflow))),
+                max_outflow: None,
+                window_seconds: U64(100),
+                supply: U128(0),
+            },
+        );
+    }
+
+    fn current_inflow_of(base_denom: &str) -> u128 {
+        let denom = format!("transfer/channel-0/{}", base_denom);
+        rate_limit::get_rate_limits()
+            .into_iter()
+            .find(|status| status.denom == denom)
+            .unwrap()
+            .current_inflow
+            .0
+    }
+
+    #[test]
+    fn test_recv_multi_denom_packet_is_all_or_nothing() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .current_account_id("v1.nearibc.near".parse().unwrap())
+            .block_timestamp(100_000_000_000)
+            .build());
+        set_inflow_limit("uatom", 1000);
+        set_inflow_limit("uosmo", 10);
+        let mut transfer_module = TransferModule();
+        // The second token exceeds its inflow rate limit.
+        let ack = transfer_module.on_recv_multi_denom_packet(&received_packet_of(vec![
+            raw_token_of("uatom", 100),
+            raw_token_of("uosmo", 100),
+        ]));
+        assert!(String::from_utf8(ack.as_bytes().to_vec())
+            .unwrap()
+            .contains("ERR_INFLOW_RATE_LIMIT_EXCEEDED"));
+        assert!(get_created_receipts().is_empty());
+        assert_eq!(current_inflow_of("uatom"), 0);
+        // The total inflow of the same denom is checked.
+        let ack = transfer_module.on_recv_multi_denom_packet(&received_packet_of(vec![
+            raw_token_of("uosmo", 6),
+            raw_token_of("uatom", 100),
+            raw_token_of("uosmo", 6),
+        ]));
+        assert!(String::from_utf8(ack.as_bytes().to_vec())
+            .unwrap()
+            .contains("ERR_INFLOW_RATE_LIMIT_EXCEEDED"));
+        assert!(get_created_receipts().is_empty());
+        assert_eq!(current_inflow_of("uatom"), 0);
+        // All of the tokens are minted when all of them are valid.
+        let ack = transfer_module.on_recv_multi_denom_packet(&received_packet_of(vec![
+            raw_token_of("uatom", 100),
+            raw_token_of("uosmo", 5),
+        ]));
+        assert_eq!(
+            ack.as_bytes(),
+            Acknowledgement::from(AcknowledgementStatus::success(ack_success_b64())).as_bytes()
+        );
+        assert_eq!(get_created_receipts().len(), 2);
+        assert_eq!(current_inflow_of("uatom"), 100);
+        assert_eq!(current_inflow_of("uosmo"), 5);
+    }
+
+    #[test]
+    fn test_multi_denom_packet_data() {
+        let denom = raw_denom_of(&Ics20Token {
+            trace_path: "transfer/channel-1/transfer/channel-5".to_string(),
+            base_denom: "uatom".to_string(),
+            amount: U128(100),
+        })
+        .unwrap();
+        assert_eq!(denom.trace.len(), 2);
+        assert_eq!(denom.trace_path(), "transfer/channel-1/transfer/channel-5");
+        let port_id = PortId::transfer();
+        let stripped = denom.strip_prefix(&port_id, &ChannelId::new(1)).unwrap();
+        assert_eq!(stripped.trace_path(), "transfer/channel-5");
+        assert!(denom.strip_prefix(&port_id, &ChannelId::new(5)).is_none());
+        assert_eq!(stripped.with_prefix(&port_id, &ChannelId::new(1)), denom);
+        let packet_data = FungibleTokenPacketDataV2 {
+            tokens: vec![
+                RawToken {
+                    denom: Some(denom),
+                    amount: "100".to_string(),
+                },
+                RawToken {
+                    denom: Some(RawDenom {
+                        base: "usdt.near".to_string(),
+                        trace: vec![],
+                    }),
+                    amount: "0".to_string(),
+                },
+            ],
+            sender: "alice.near".to_string(),
+            receiver: "cosmos1receiver".to_string(),
+            memo: String::new(),
+        };
+        assert_eq!(
+            decode_packet_data(&packet_data.encode_to_vec()).unwrap_err(),
+            "ERR_AMOUNT_MUST_BE_GREATER_THAN_ZERO"
+        );
+        let mut packet_data = packet_data;
+        packet_data.tokens[1].amount = "5".to_string();
+        let (_, coins) = decode_packet_data(&packet_data.encode_to_vec()).unwrap();
+        assert_eq!(
+            coins[0].1.denom.to_string(),
+            "transfer/channel-1/transfer/channel-5/uatom"
+        );
+        assert_eq!(coins[1].1.denom.to_string(), "usdt.near");
+    }
+}
@@ -0,0 +1,615 @@
+//! Rate limiting of ICS-20 flows.
+//!
+//! Governance can limit the inflow and outflow of a denom through a channel, as an absolute
+//! amount or a percentage of the supply of the denom, over a rolling window. The flow in the
+//! rolling window is estimated by the flow in the current fixed window plus the weighted flow
+//! in the previous fixed window.
+//!
+//! The rate limits are stored in a `LazyOption` which is loaded only when needed,
+//! as the transfer module is stateless.
+use crate::{prelude::*, StorageKey};
+use alloc::collections::BTreeMap;
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    collections::LazyOption,
+    env,
+    json_types::{U128, U64},
+    log,
+    serde::{Deserialize, Serialize},
+};
+
+/// The max amount of a flow in a window.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum FlowQuota {
+    /// An absolute amount.
+    Absolute(U128),
+    /// A percentage of the supply of the denom, in basis points.
+    Percentage(u32),
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateLimitConfig {
+    /// The max amount received from the channel in a window, unlimited if `None`.
+    pub max_inflow: Option<FlowQuota>,
+    /// The max amount sent through the channel in a window, unlimited if `None`.
+    pub max_outflow: Option<FlowQuota>,
+    /// The length of the window.
+    pub window_seconds: U64,
+    /// The supply of the denom, which is the base of the percentage quotas.
+    /// It should be updated by governance when the supply changes significantly.
+    #[serde(default)]
+    pub supply: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlowUsage {
+    /// The start time of the current window, in nanoseconds.
+    pub window_start: U64,
+    pub inflow: U128,
+    pub outflow: U128,
+    pub previous_inflow: U128,
+    pub previous_outflow: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateLimit {
+    pub config: RateLimitConfig,
+    pub usage: FlowUsage,
+}
+
+/// The status of a rate limit, for view functions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RateLimitStatus {
+    pub channel_id: String,
+    pub denom: String,
+    pub config: RateLimitConfig,
+    /// The max inflow amount in the rolling window, unlimited if `None`.
+    pub max_inflow: Option<U128>,
+    /// The max outflow amount in the rolling window, unlimited if `None`.
+    pub max_outflow: Option<U128>,
+    /// The inflow amount in the rolling window.
+    pub current_inflow: U128,
+    /// The outflow amount in the rolling window.
+    pub current_outflow: U128,
+}
+
+/// The rate limits, keyed by (channel id, denom).
+type RateLimits = BTreeMap<(String, String), RateLimit>;
+
+impl FlowQuota {
+    fn max_amount(&self, supply: u128) -> u128 {
+        match self {
+            FlowQuota::Absolute(amount) => amount.0,
+            FlowQuota::Percentage(basis_points) => {
+                supply.saturating_mul(*basis_points as u128) / 10000
+            }
+        }
+    }
+}
+
+impl RateLimit {
+    fn window_nanoseconds(&self) -> u64 {
+        self.config.window_seconds.0.saturating_mul(1_000_000_000)
+    }
+    /// Move the current window forward, if it has passed.
+    fn roll_window(&mut self) {
+        let window = self.window_nanoseconds();
+        let now = env::block_timestamp();
+        if window == 0 || now < self.usage.window_start.0.saturating_add(window) {
+            return;
+        }
+        let passed_windows = (now - self.usage.window_start.0) / window;
+        let usage = &mut self.usage;
+        match passed_windows {
+            1 => {
+                usage.previous_inflow = usage.inflow;
+                usage.previous_outflow = usage.outflow;
+            }
+            _ => {
+                usage.previous_inflow = U128(0);
+                usage.previous_outflow = U128(0);
+            }
+        }
+        usage.inflow = U128(0);
+        usage.outflow = U128(0);
+        usage.window_start = U64(usage.window_start.0 + passed_windows * window);
+    }
+    /// Estimate the flow in the rolling window ending at the current time.
+    fn rolling_flow(&self, current: u128, previous: u128) -> u128 {
+        let window = self.window_nanoseconds();
+        let elapsed = env::block_timestamp().saturating_sub(self.usage.window_start.0);
+        if window == 0 || elapsed >= window {
+            return current;
+        }
+        current.saturating_add(previous.saturating_mul((window - elapsed) as u128) / window as u128)
+    }
+    fn current_inflow(&self) -> u128 {
+        self.rolling_flow(self.usage.inflow.0, self.usage.previous_inflow.0)
+    }
+    fn current_outflow(&self) -> u128 {
+        self.rolling_flow(self.usage.outflow.0, self.usage.previous_outflow.0)
+    }
+}
+
+fn rate_limits_storage() -> LazyOption<RateLimits> {
+    LazyOption::new(StorageKey::RateLimits, None)
+}
+
+fn load_rate_limits() -> RateLimits {
+    rate_limits_storage().get().unwrap_or_default()
+}
+
+fn save_rate_limits(rate_limits: &RateLimits) {
+    rate_limits_storage().set(rate_limits);
+}
+
+/// Set the rate limit of the given channel and denom, the current usage is kept.
+///
+/// The supply must be set if any of the quotas is a percentage, otherwise the quota
+/// would be zero and block all the flows.
+pub fn set_rate_limit(channel_id: String, denom: String, config: RateLimitConfig) {
+    assert!(config.window_seconds.0 > 0, "ERR_INVALID_WINDOW_SECONDS");
+    assert!(
+        config.supply.0 > 0
+            || ![&config.max_inflow, &config.max_outflow]
+                .iter()
+                .any(|quota| matches!(quota, Some(FlowQuota::Percentage(_)))),
+        "ERR_SUPPLY_REQUIRED_FOR_PERCENTAGE_QUOTA"
+    );
+    let mut rate_limits = load_rate_limits();
+    let usage = rate_limits
+        .get(&(channel_id.clone(), denom.clone()))
+        .map_or_else(
+            || FlowUsage {
+                window_start: U64(env::block_timestamp()),
+                ..Default::default()
+            },
+            |rate_limit| rate_limit.usage.clone(),
+        );
+    rate_limits.insert((channel_id, denom), RateLimit { config, usage });
+    save_rate_limits(&rate_limits);
+}
+
+/// Remove the rate limit of the given channel and denom.
+pub fn remove_rate_limit(channel_id: String, denom: String) {
+    let mut rate_limits = load_rate_limits();
+    assert!(
+        rate_limits.remove(&(channel_id, denom)).is_some(),
+        "ERR_RATE_LIMIT_NOT_FOUND"
+    );
+    save_rate_limits(&rate_limits);
+}
+
+/// Reset the usage of the rate limit of the given channel and denom.
+pub fn reset_rate_limit_usage(channel_id: String, denom: String) {
+    let mut rate_limits = load_rate_limits();
+    let rate_limit = rate_limits
+        .get_mut(&(channel_id, denom))
+        .expect("ERR_RATE_LIMIT_NOT_FOUND");
+    rate_limit.usage = FlowUsage {
+        window_start: U64(env::block_timestamp()),
+        ..Default::default()
+    };
+    save_rate_limits(&rate_limits);
+}
+
+/// Get the status of all rate limits.
+pub fn get_rate_limits() -> Vec<RateLimitStatus> {
+    load_rate_limits()
+        .into_iter()
+        .map(|((channel_id, denom), mut rate_limit)| {
+            rate_limit.roll_window();
+            RateLimitStatus {
+                channel_id,
+                denom,
+                max_inflow: rate_limit
+                    .config
+                    .max_inflow
+                    .as_ref()
+                    .map(|quota| U128(quota.max_amount(rate_limit.config.supply.0))),
+                max_outflow: rate_limit
+                    .config
+                    .max_outflow
+                    .as_ref()
+                    .map(|quota| U128(quota.max_amount(rate_limit.config.supply.0))),
+                current_inflow: U128(rate_limit.current_inflow()),
+                current_outflow: U128(rate_limit.current_outflow()),
+                config: rate_limit.config,
+            }
+        })
+        .collect()
+}
+
+/// Check whether the given inflow exceeds the rate limit of the given channel and denom.
+pub fn check_inflow(channel_id: &str, denom: &str, amount: u128) -> Result<(), String> {
+    let rate_limits = load_rate_limits();
+    match rate_limits.get(&(channel_id.to_string(), denom.to_string())) {
+        Some(rate_limit) => {
+            let mut rate_limit = rate_limit.clone();
+            rate_limit.roll_window();
+            match &rate_limit.config.max_inflow {
+                Some(quota)
+                    if rate_limit.current_inflow().saturating_add(amount)
+                        > quota.max_amount(rate_limit.config.supply.0) =>
+                {
+                    Err(format!(
+                        "ERR_INFLOW_RATE_LIMIT_EXCEEDED: channel {}, denom {}",
+                        channel_id, denom
+                    ))
+                }
+                _ => Ok(()),
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+/// Check whether the given outflow exceeds the rate limit of the given channel and denom.
+pub fn check_outflow(channel_id: &str, denom: &str, amount: u128) -> Result<(), String> {
+    let rate_limits = load_rate_limits();
+    match rate_limits.get(&(channel_id.to_string(), denom.to_string())) {
+        Some(rate_limit) => {
+            let mut rate_limit = rate_limit.clone();
+            rate_limit.roll_window();
+            match &rate_limit.config.max_outflow {
+                Some(quota)
+                    if rate_limit.current_outflow().saturating_add(amount)
+                        > quota.max_amount(rate_limit.config.supply.0) =>
+                {
+                    Err(format!(
+                        "ERR_OUTFLOW_RATE_LIMIT_EXCEEDED: channel {}, denom {}",
+                        channel_id, denom
+                    ))
+                }
+                _ => Ok(()),
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+/// The storage key of the channel of the packet being received, which is only set while
+/// `ibc-rs` unescrows or mints the tokens of the packet, so that the tokens sent from
+/// escrows or minted for refunding are not limited by the inflow.
+const STORAGE_KEY_FOR_RECEIVING_CHANNEL: &[u8] = b"rate_limit_receiving_channel";
+
+/// Set the channel (on this chain) of the packet being received, or clear it by `None`.
+pub fn set_receiving_channel(channel_id: Option<&str>) {
+    match channel_id {
+        Some(channel_id) => {
+            env::storage_write(STORAGE_KEY_FOR_RECEIVING_CHANNEL, channel_id.as_bytes());
+        }
+        None => {
+            env::storage_remove(STORAGE_KEY_FOR_RECEIVING_CHANNEL);
+        }
+    }
+}
+
+/// Check whether the given inflow exceeds the rate limit of the channel of the packet
+/// being received, if any.
+pub fn check_receiving_inflow(denom: &str, amount: u128) -> Result<(), String> {
+    match env::storage_read(STORAGE_KEY_FOR_RECEIVING_CHANNEL) {
+        Some(channel_id) => check_inflow(
+            String::from_utf8(channel_id).unwrap().as_str(),
+            denom,
+            amount,
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Update the usage of the rate limit of the given channel and denom, if any.
+fn update_usage(channel_id: &str, denom: &str, f: impl FnOnce(&mut FlowUsage)) {
+    let mut rate_limits = load_rate_limits();
+    if let Some(rate_limit) = rate_limits.get_mut(&(channel_id.to_string(), denom.to_string())) {
+        rate_limit.roll_window();
+        f(&mut rate_limit.usage);
+        save_rate_limits(&rate_limits);
+    }
+}
+
+/// Record the given inflow.
+pub fn record_inflow(channel_id: &str, denom: &str, amount: u128) {
+    update_usage(channel_id, denom, |usage| {
+        usage.inflow = U128(usage.inflow.0.saturating_add(amount))
+    });
+}
+
+/// Record the given outflow.
+pub fn record_outflow(channel_id: &str, denom: &str, amount: u128) {
+    update_usage(channel_id, denom, |usage| {
+        usage.outflow = U128(usage.outflow.0.saturating_add(amount))
+    });
+}
+
+/// Revert the given outflow, when the sent tokens are refunded.
+pub fn undo_outflow(channel_id: &str, denom: &str, amount: u128) {
+    update_usage(channel_id, denom, |usage| {
+        usage.outflow = U128(usage.outflow.0.saturating_sub(amount));
+        log!(
+            "Outflow of denom {} through channel {} is reverted by {}.",
+            denom,
+            channel_id,
+            amount
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::applications::transfer::{
+        async_ack::error_ack, AccountIdConversion, TransferModule,
+    };
+    use core::str::FromStr;
+    use ibc::apps::transfer::{
+        context::TokenTransferValidationContext,
+        types::{Amount, PrefixedCoin, PrefixedDenom},
+    };
+    use ibc::core::{
+        channel::types::{packet::Packet, timeout::TimeoutHeight},
+        host::types::identifiers::{ChannelId, PortId},
+        primitives::Timestamp,
+        router::module::Module,
+    };
+    use ibc::primitives::Signer;
+    use ibc_proto::ibc::apps::transfer::v2::FungibleTokenPacketData;
+    use near_sdk::{serde_json, test_utils::VMContextBuilder, testing_env};
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn config_of(max_inflow: Option<FlowQuota>, max_outflow: Option<FlowQuota>) -> RateLimitConfig {
+        RateLimitConfig {
+            max_inflow,
+            max_outflow,
+            window_seconds: U64(100),
+            supply: U128(10000),
+        }
+    }
+
+    fn status_of(channel_id: &str, denom: &str) -> RateLimitStatus {
+        get_rate_limits()
+            .into_iter()
+            .find(|status| status.channel_id == channel_id && status.denom == denom)
+            .unwrap()
+    }
+
+    fn sent_packet_of(denom: &str, amount: u128) -> Packet {
+        Packet {
+            seq_on_a: 1u64.into(),
+            port_id_on_a: PortId::transfer(),
+            chan_id_on_a: ChannelId::new(0),
+            port_id_on_b: PortId::transfer(),
+            chan_id_on_b: ChannelId::new(1),
+            data: serde_json::to_vec(&FungibleTokenPacketData {
+                denom: denom.to_string(),
+                amount: amount.to_string(),
+                sender: "alice.testnet".to_string(),
+                receiver: "cosmos1receiver".to_string(),
+                memo: String::new(),
+            })
+            .unwrap(),
+            timeout_height_on_b: TimeoutHeight::Never,
+            timeout_timestamp_on_b: Timestamp::none(),
+        }
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(100_000_000_000).build());
+        let mut rate_limit = RateLimit {
+            config: RateLimitConfig {
+                max_inflow: Some(FlowQuota::Percentage(1000)),
+                max_outflow: Some(FlowQuota::Absolute(U128(500))),
+                window_seconds: U64(100),
+                supply: U128(10000),
+            },
+            usage: FlowUsage {
+                window_start: U64(0),
+                inflow: U128(800),
+                outflow: U128(400),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            rate_limit
+                .config
+                .max_inflow
+                .as_ref()
+                .unwrap()
+                .max_amount(rate_limit.config.supply.0),
+            1000
+        );
+        // A quarter of the next window has passed.
+        testing_env!(context.block_timestamp(125_000_000_000).build());
+        rate_limit.roll_window();
+        assert_eq!(rate_limit.usage.window_start, U64(100_000_000_000));
+        assert_eq!(rate_limit.current_inflow(), 600);
+        assert_eq!(rate_limit.current_outflow(), 300);
+        // More than one window has passed.
+        testing_env!(context.block_timestamp(350_000_000_000).build());
+        rate_limit.roll_window();
+        assert_eq!(rate_limit.usage.window_start, U64(300_000_000_000));
+        assert_eq!(rate_limit.current_inflow(), 0);
+        assert_eq!(rate_limit.current_outflow(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SUPPLY_REQUIRED_FOR_PERCENTAGE_QUOTA")]
+    fn test_set_percentage_rate_limit_without_supply() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut config = config_of(None, Some(FlowQuota::Percentage(1000)));
+        config.supply = U128(0);
+        set_rate_limit("channel-0".to_string(), "usdt.testnet".to_string(), config);
+    }
+
+    #[test]
+    fn test_quota_types() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(100 * SECOND).build());
+        // 10% of the supply of 10000.
+        set_rate_limit(
+            "channel-0".to_string(),
+            "usdt.testnet".to_string(),
+            config_of(
+                Some(FlowQuota::Percentage(1000)),
+                Some(FlowQuota::Absolute(U128(500))),
+            ),
+        );
+        assert!(check_inflow("channel-0", "usdt.testnet", 1000).is_ok());
+        assert!(check_inflow("channel-0", "usdt.testnet", 1001).is_err());
+        assert!(check_outflow("channel-0", "usdt.testnet", 500).is_ok());
+        assert!(check_outflow("channel-0", "usdt.testnet", 501).is_err());
+        record_inflow("channel-0", "usdt.testnet", 600);
+        record_outflow("channel-0", "usdt.testnet", 200);
+        assert!(check_inflow("channel-0", "usdt.testnet", 400).is_ok());
+        assert!(check_inflow("channel-0", "usdt.testnet", 401).is_err());
+        assert!(check_outflow("channel-0", "usdt.testnet", 300).is_ok());
+        assert!(check_outflow("channel-0", "usdt.testnet", 301).is_err());
+        // The other channels and denoms are not limited.
+        assert!(check_inflow("channel-1", "usdt.testnet", 100000).is_ok());
+        assert!(check_inflow("channel-0", "wrap.testnet", 100000).is_ok());
+        let status = status_of("channel-0", "usdt.testnet");
+        assert_eq!(status.max_inflow, Some(U128(1000)));
+        assert_eq!(status.max_outflow, Some(U128(500)));
+    }
+
+    #[test]
+    fn test_window_rollover() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.block_timestamp(100 * SECOND).build());
+        set_rate_limit(
+            "channel-0".to_string(),
+            "usdt.testnet".to_string(),
+            config_of(None, Some(FlowQuota::Absolute(U128(1000)))),
+        );
+        record_outflow("channel-0", "usdt.testnet", 800);
+        assert!(check_outflow("channel-0", "usdt.testnet", 201).is_err());
+        // Half of the next window has passed, half of the previous outflow is counted.
+        testing_env!(context.block_timestamp(250 * SECOND).build());
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(400)
+        );
+        assert!(check_outflow("channel-0", "usdt.testnet", 600).is_ok());
+        assert!(check_outflow("channel-0", "usdt.testnet", 601).is_err());
+        record_outflow("channel-0", "usdt.testnet", 100);
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(500)
+        );
+        // The outflow of the previous window fades out along the window.
+        testing_env!(context.block_timestamp(275 * SECOND).build());
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(300)
+        );
+        // More than one window has passed, all the outflow is dropped.
+        testing_env!(context.block_timestamp(400 * SECOND).build());
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(0)
+        );
+        assert!(check_outflow("channel-0", "usdt.testnet", 1000).is_ok());
+        // Resetting the usage starts a new window.
+        record_outflow("channel-0", "usdt.testnet", 1000);
+        reset_rate_limit_usage("channel-0".to_string(), "usdt.testnet".to_string());
+        assert!(check_outflow("channel-0", "usdt.testnet", 1000).is_ok());
+    }
+
+    #[test]
+    fn test_check_inflow_of_received_coins() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .current_account_id("v1.nearibc.testnet".parse().unwrap())
+            .block_timestamp(100 * SECOND)
+            .build());
+        set_rate_limit(
+            "channel-1".to_string(),
+            "transfer/channel-1/uatom".to_string(),
+            config_of(Some(FlowQuota::Absolute(U128(1000))), None),
+        );
+        set_rate_limit(
+            "channel-1".to_string(),
+            "usdt.testnet".to_string(),
+            config_of(Some(FlowQuota::Absolute(U128(1000))), None),
+        );
+        let transfer_module = TransferModule();
+        let receiver = AccountIdConversion("alice.testnet".parse().unwrap());
+        let escrow_account = transfer_module
+            .get_escrow_account(&PortId::transfer(), &ChannelId::new(1))
+            .unwrap();
+        let coin_of = |denom: &str, amount: u64| PrefixedCoin {
+            denom: PrefixedDenom::from_str(denom).unwrap(),
+            amount: Amount::from(amount),
+        };
+        // The minting and unescrowing of received packets are limited by the inflow.
+        set_receiving_channel(Some("channel-1"));
+        assert!(transfer_module
+            .mint_coins_validate(&receiver, &coin_of("transfer/channel-1/uatom", 1000))
+            .is_ok());
+        assert!(transfer_module
+            .mint_coins_validate(&receiver, &coin_of("transfer/channel-1/uatom", 1001))
+            .is_err());
+        assert!(transfer_module
+            .send_coins_validate(&escrow_account, &receiver, &coin_of("usdt.testnet", 1001))
+            .is_err());
+        // The minting and unescrowing for refunding are not limited.
+        set_receiving_channel(None);
+        assert!(transfer_module
+            .mint_coins_validate(&receiver, &coin_of("transfer/channel-1/uatom", 1001))
+            .is_ok());
+        assert!(transfer_module
+            .send_coins_validate(&escrow_account, &receiver, &coin_of("usdt.testnet", 1001))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_undo_outflow_on_refund() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .current_account_id("v1.nearibc.testnet".parse().unwrap())
+            .predecessor_account_id("v1.nearibc.testnet".parse().unwrap())
+            .block_timestamp(100 * SECOND)
+            .build());
+        crate::NearIbcContract::init("registry.testnet".parse().unwrap());
+        set_rate_limit(
+            "channel-0".to_string(),
+            "usdt.testnet".to_string(),
+            config_of(None, Some(FlowQuota::Absolute(U128(1000)))),
+        );
+        record_outflow("channel-0", "usdt.testnet", 300);
+        record_outflow("channel-0", "usdt.testnet", 200);
+        let mut transfer_module = TransferModule();
+        let relayer = Signer::from("relayer.testnet".to_string());
+        // The outflow of a packet is reverted when it is acknowledged with an error.
+        let (_, result) = transfer_module.on_acknowledgement_packet_execute(
+            &sent_packet_of("usdt.testnet", 300),
+            &error_ack("ERR_RECEIVING".to_string()),
+            &relayer,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(200)
+        );
+        // The outflow of a packet is reverted when it is timed out.
+        let (_, result) = transfer_module
+            .on_timeout_packet_execute(&sent_packet_of("usdt.testnet", 200), &relayer);
+        assert!(result.is_ok());
+        assert_eq!(
+            status_of("channel-0", "usdt.testnet").current_outflow,
+            U128(0)
+        );
+    }
+}
@@ -0,0 +1,905 @@
+use super::{
+    consensus_state::GrandpaConsensusState, decode_header, decode_justification,
+    read_child_proof_check, read_timestamp, scheduled_change_of, verify_justification,
+    GrandpaAuthority, PendingAuthoritySetChange, SubstrateHeader, GRANDPA_CLIENT_TYPE,
+    GRANDPA_HEADER_TYPE_URL, GRANDPA_MISBEHAVIOUR_TYPE_URL,
+};
+use crate::{
+    context::NearIbcStore,
+    ibc_impl::core::{client_state::AnyClientState, consensus_state::AnyConsensusState},
+    prelude::*,
+};
+use core::str::FromStr;
+use ibc::core::{
+    client::context::{
+        client_state::{ClientStateCommon, ClientStateExecution, ClientStateValidation},
+        types::{error::ClientError, Height, Status, UpdateKind},
+        ClientExecutionContext,
+    },
+    commitment_types::commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
+    handler::types::error::ContextError,
+    host::{
+        types::{
+            identifiers::{ClientId, ClientType},
+            path::{ClientConsensusStatePath, ClientStatePath, Path},
+        },
+        ValidationContext,
+    },
+};
+use ibc_proto::{google::protobuf::Any, ibc::core::client::v1::Height as RawHeight, Protobuf};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrandpaClientState {
+    pub chain_id: String,
+    /// The revision height is the number of the latest finalized block.
+    pub latest_height: Height,
+    pub frozen_height: Option<Height>,
+    /// The id of the current authority set.
+    pub authority_set_id: u64,
+    /// The current authority set.
+    pub authorities: Vec<GrandpaAuthority>,
+    /// The authority set change which is scheduled but not enacted yet.
+    pub pending_change: Option<PendingAuthoritySetChange>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaClientState {
+    #[prost(string, tag = "1")]
+    pub chain_id: String,
+    #[prost(message, optional, tag = "2")]
+    pub latest_height: Option<RawHeight>,
+    #[prost(message, optional, tag = "3")]
+    pub frozen_height: Option<RawHeight>,
+    #[prost(uint64, tag = "4")]
+    pub authority_set_id: u64,
+    #[prost(message, repeated, tag = "5")]
+    pub authorities: Vec<RawGrandpaAuthority>,
+    #[prost(message, optional, tag = "6")]
+    pub pending_change: Option<RawPendingAuthoritySetChange>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaAuthority {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub weight: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawPendingAuthoritySetChange {
+    #[prost(uint32, tag = "1")]
+    pub enact_at: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub next_authorities: Vec<RawGrandpaAuthority>,
+}
+
+/// The header of the client, with the SCALE-encoded Substrate header and GRANDPA
+/// justification, and the trie nodes proving `Timestamp::Now` under the state root.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaHeader {
+    #[prost(bytes = "vec", tag = "1")]
+    pub header: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub justification: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub timestamp_proof: Vec<Vec<u8>>,
+}
+
+/// Two headers of the same number which are both finalized by the current authority set.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaMisbehaviour {
+    #[prost(message, optional, tag = "1")]
+    pub header_one: Option<RawGrandpaHeader>,
+    #[prost(message, optional, tag = "2")]
+    pub header_two: Option<RawGrandpaHeader>,
+}
+
+/// The proof of membership or non-membership, which is the set of trie nodes.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaStorageProof {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub trie_nodes: Vec<Vec<u8>>,
+}
+
+fn client_error(description: &str) -> ClientError {
+    ClientError::ClientSpecific {
+        description: description.to_string(),
+    }
+}
+
+fn context_error(e: ContextError) -> ClientError {
+    ClientError::Other {
+        description: e.to_string(),
+    }
+}
+
+impl GrandpaClientState {
+    //
+    fn decode_header(client_message: Any) -> Result<RawGrandpaHeader, ClientError> {
+        if client_message.type_url != GRANDPA_HEADER_TYPE_URL {
+            return Err(ClientError::InvalidUpdateClientMessage);
+        }
+        RawGrandpaHeader::decode(client_message.value.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))
+    }
+    //
+    fn decode_misbehaviour(client_message: Any) -> Result<RawGrandpaMisbehaviour, ClientError> {
+        if client_message.type_url != GRANDPA_MISBEHAVIOUR_TYPE_URL {
+            return Err(ClientError::InvalidUpdateClientMessage);
+        }
+        RawGrandpaMisbehaviour::decode(client_message.value.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))
+    }
+    //
+    fn height_of(&self, header: &SubstrateHeader) -> Height {
+        Height::new(self.latest_height.revision_number(), header.number as u64)
+            .expect("block number must not be zero")
+    }
+    /// Verify the header is finalized by the current authority set, and return
+    /// the decoded header with its hash.
+    fn verify_finality(
+        &self,
+        header: &RawGrandpaHeader,
+    ) -> Result<(SubstrateHeader, [u8; 32]), ClientError> {
+        let (substrate_header, hash) = decode_header(&header.header)?;
+        if substrate_header.number == 0 {
+            return Err(client_error("genesis header can not be submitted"));
+        }
+        if let Some(pending_change) = self.pending_change.as_ref() {
+            if substrate_header.number > pending_change.enact_at {
+                return Err(client_error(
+                    format!(
+                        "header at the authority set change {} must be submitted first",
+                        pending_change.enact_at
+                    )
+                    .as_str(),
+                ));
+            }
+        }
+        verify_justification(
+            &decode_justification(&header.justification)?,
+            &hash,
+            substrate_header.number,
+            self.authority_set_id,
+            &self.authorities,
+        )?;
+        Ok((substrate_header, hash))
+    }
+    /// Get the consensus state in the header, whose finality is already verified.
+    fn consensus_state_of(
+        header: &SubstrateHeader,
+        timestamp_proof: &[Vec<u8>],
+    ) -> Result<GrandpaConsensusState, ClientError> {
+        Ok(GrandpaConsensusState::new(
+            CommitmentRoot::from_bytes(&header.state_root),
+            read_timestamp(&header.state_root, timestamp_proof)?,
+        ))
+    }
+    /// Get the new client state after the given header is finalized, which enacts
+    /// the pending authority set change and schedules the new one in the header.
+    fn next_client_state(&self, header: &SubstrateHeader) -> Result<Self, ClientError> {
+        let mut client_state = GrandpaClientState {
+            latest_height: core::cmp::max(self.latest_height, self.height_of(header)),
+            ..self.clone()
+        };
+        if client_state
+            .pending_change
+            .as_ref()
+            .is_some_and(|change| change.enact_at == header.number)
+        {
+            client_state.enact_pending_change();
+        }
+        if let Some(change) = scheduled_change_of(header)? {
+            let enact_at = change.enact_at;
+            client_state.pending_change = Some(change);
+            if enact_at == header.number {
+                client_state.enact_pending_change();
+            }
+        }
+        Ok(client_state)
+    }
+    //
+    fn enact_pending_change(&mut self) {
+        if let Some(change) = self.pending_change.take() {
+            self.authority_set_id += 1;
+            self.authorities = change.next_authorities;
+        }
+    }
+    /// Verify the proof of the value (`None` for absence) of the given path
+    /// in the child trie named by the prefix.
+    fn verify_storage_proof(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        root: &CommitmentRoot,
+        path: Path,
+        value: Option<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let proof: Vec<u8> = proof.clone().into();
+        let proof = RawGrandpaStorageProof::decode(proof.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))?;
+        let state_root: [u8; 32] = root
+            .as_bytes()
+            .try_into()
+            .map_err(|_| client_error("invalid state root"))?;
+        let stored_value = read_child_proof_check(
+            &state_root,
+            &proof.trie_nodes,
+            prefix.as_bytes(),
+            path.to_string().as_bytes(),
+        )?;
+        if stored_value != value {
+            return Err(client_error(
+                format!("value of path {} does not match the storage proof", path).as_str(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Protobuf<RawGrandpaClientState> for GrandpaClientState {}
+
+impl TryFrom<RawGrandpaAuthority> for GrandpaAuthority {
+    type Error = ClientError;
+
+    fn try_from(raw: RawGrandpaAuthority) -> Result<Self, Self::Error> {
+        if raw.public_key.len() != 32 {
+            return Err(client_error("invalid ed25519 public key"));
+        }
+        Ok(Self {
+            public_key: raw.public_key,
+            weight: raw.weight,
+        })
+    }
+}
+
+impl From<GrandpaAuthority> for RawGrandpaAuthority {
+    fn from(value: GrandpaAuthority) -> Self {
+        RawGrandpaAuthority {
+            public_key: value.public_key,
+            weight: value.weight,
+        }
+    }
+}
+
+impl TryFrom<RawGrandpaClientState> for GrandpaClientState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawGrandpaClientState) -> Result<Self, Self::Error> {
+        if raw.chain_id.is_empty() {
+            return Err(client_error("chain id must not be empty"));
+        }
+        if raw.authorities.is_empty() {
+            return Err(client_error("authority set must not be empty"));
+        }
+        Ok(Self {
+            chain_id: raw.chain_id,
+            latest_height: raw
+                .latest_height
+                .ok_or_else(|| client_error("missing latest height"))?
+                .try_into()?,
+            frozen_height: raw
+                .frozen_height
+                .map(|height| height.try_into())
+                .transpose()?,
+            authority_set_id: raw.authority_set_id,
+            authorities: raw
+                .authorities
+                .into_iter()
+                .map(GrandpaAuthority::try_from)
+                .collect::<Result<_, _>>()?,
+            pending_change: raw
+                .pending_change
+                .map(|change| {
+                    Ok::<_, ClientError>(PendingAuthoritySetChange {
+                        enact_at: change.enact_at,
+                        next_authorities: change
+                            .next_authorities
+                            .into_iter()
+                            .map(GrandpaAuthority::try_from)
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl From<GrandpaClientState> for RawGrandpaClientState {
+    fn from(value: GrandpaClientState) -> Self {
+        RawGrandpaClientState {
+            chain_id: value.chain_id,
+            latest_height: Some(value.latest_height.into()),
+            frozen_height: value.frozen_height.map(|height| height.into()),
+            authority_set_id: value.authority_set_id,
+            authorities: value.authorities.into_iter().map(Into::into).collect(),
+            pending_change: value
+                .pending_change
+                .map(|change| RawPendingAuthoritySetChange {
+                    enact_at: change.enact_at,
+                    next_authorities: change
+                        .next_authorities
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                }),
+        }
+    }
+}
+
+impl ClientStateValidation<NearIbcStore> for GrandpaClientState {
+    fn verify_client_message(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        client_message: Any,
+        update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        match update_kind {
+            UpdateKind::UpdateClient => {
+                let header = Self::decode_header(client_message)?;
+                let (substrate_header, _) = self.verify_finality(&header)?;
+                Self::consensus_state_of(&substrate_header, &header.timestamp_proof).map(|_| ())
+            }
+            UpdateKind::SubmitMisbehaviour => {
+                let misbehaviour = Self::decode_misbehaviour(client_message)?;
+                let (header_one, hash_one) = self.verify_finality(
+                    misbehaviour
+                        .header_one
+                        .as_ref()
+                        .ok_or_else(|| client_error("missing header one"))?,
+                )?;
+                let (header_two, hash_two) = self.verify_finality(
+                    misbehaviour
+                        .header_two
+                        .as_ref()
+                        .ok_or_else(|| client_error("missing header two"))?,
+                )?;
+                if header_one.number != header_two.number || hash_one == hash_two {
+                    return Err(client_error(
+                        "headers of misbehaviour must be different blocks of the same number",
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_for_misbehaviour(
+        &self,
+        ctx: &NearIbcStore,
+        client_id: &ClientId,
+        client_message: Any,
+        update_kind: &UpdateKind,
+    ) -> Result<bool, ClientError> {
+        match update_kind {
+            UpdateKind::UpdateClient => {
+                let header = Self::decode_header(client_message)?;
+                let (substrate_header, _) = decode_header(&header.header)?;
+                let height = self.height_of(&substrate_header);
+                // A different block finalized at a height which is already stored.
+                match ValidationContext::consensus_state(
+                    ctx,
+                    &ClientConsensusStatePath::new(
+                        client_id.clone(),
+                        height.revision_number(),
+                        height.revision_height(),
+                    ),
+                ) {
+                    Ok(AnyConsensusState::Grandpa(consensus_state)) => {
+                        Ok(consensus_state.root().as_bytes() != substrate_header.state_root)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            UpdateKind::SubmitMisbehaviour => Ok(true),
+        }
+    }
+
+    fn status(&self, _ctx: &NearIbcStore, _client_id: &ClientId) -> Result<Status, ClientError> {
+        if self.frozen_height.is_some() {
+            Ok(Status::Frozen)
+        } else {
+            Ok(Status::Active)
+        }
+    }
+}
+
+impl ClientStateCommon for GrandpaClientState {
+    fn verify_consensus_state(&self, consensus_state: Any) -> Result<(), ClientError> {
+        GrandpaConsensusState::try_from(consensus_state).map(|_| ())
+    }
+
+    fn client_type(&self) -> ClientType {
+        ClientType::from_str(GRANDPA_CLIENT_TYPE).expect("Invalid client type.")
+    }
+
+    fn latest_height(&self) -> Height {
+        self.latest_height
+    }
+
+    fn validate_proof_height(&self, proof_height: Height) -> Result<(), ClientError> {
+        if proof_height > self.latest_height {
+            return Err(ClientError::InvalidProofHeight {
+                latest_height: self.latest_height,
+                proof_height,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_upgrade_client(
+        &self,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+        _proof_upgrade_client: CommitmentProofBytes,
+        _proof_upgrade_consensus_state: CommitmentProofBytes,
+        _root: &CommitmentRoot,
+    ) -> Result<(), ClientError> {
+        Err(client_error("grandpa client can not be upgraded"))
+    }
+
+    fn verify_membership(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        root: &CommitmentRoot,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        self.verify_storage_proof(prefix, proof, root, path, Some(value))
+    }
+
+    fn verify_non_membership(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        root: &CommitmentRoot,
+        path: Path,
+    ) -> Result<(), ClientError> {
+        self.verify_storage_proof(prefix, proof, root, path, None)
+    }
+}
+
+impl ClientStateExecution<NearIbcStore> for GrandpaClientState {
+    fn initialise(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        consensus_state: Any,
+    ) -> Result<(), ClientError> {
+        let consensus_state = GrandpaConsensusState::try_from(consensus_state)?;
+        store_states(
+            ctx,
+            client_id,
+            self.clone(),
+            self.latest_height,
+            consensus_state,
+        )
+    }
+
+    fn update_state(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        header: Any,
+    ) -> Result<Vec<Height>, ClientError> {
+        // The finality of the header is verified in `verify_client_message`.
+        let header = Self::decode_header(header)?;
+        let (substrate_header, _) = decode_header(&header.header)?;
+        let consensus_state = Self::consensus_state_of(&substrate_header, &header.timestamp_proof)?;
+        let height = self.height_of(&substrate_header);
+        let new_client_state = self.next_client_state(&substrate_header)?;
+        store_states(ctx, client_id, new_client_state, height, consensus_state)?;
+        Ok(vec![height])
+    }
+
+    fn update_state_on_misbehaviour(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        let frozen_client_state = GrandpaClientState {
+            frozen_height: Some(
+                Height::new(self.latest_height.revision_number(), 1)
+                    .expect("revision height is not zero"),
+            ),
+            ..self.clone()
+        };
+        ctx.store_client_state(
+            ClientStatePath(client_id.clone()),
+            AnyClientState::Grandpa(frozen_client_state),
+        )
+        .map_err(context_error)
+    }
+
+    fn update_state_on_upgrade(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+    ) -> Result<Height, ClientError> {
+        Err(client_error("grandpa client can not be upgraded"))
+    }
+}
+
+/// Store the client state and the consensus state at the given height.
+fn store_states(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    client_state: GrandpaClientState,
+    height: Height,
+    consensus_state: GrandpaConsensusState,
+) -> Result<(), ClientError> {
+    ctx.store_client_state(
+        ClientStatePath(client_id.clone()),
+        AnyClientState::Grandpa(client_state),
+    )
+    .map_err(context_error)?;
+    ctx.store_consensus_state(
+        ClientConsensusStatePath::new(
+            client_id.clone(),
+            height.revision_number(),
+            height.revision_height(),
+        ),
+        AnyConsensusState::Grandpa(consensus_state),
+    )
+    .map_err(context_error)?;
+    let host_timestamp = ValidationContext::host_timestamp(ctx).map_err(context_error)?;
+    let host_height = ValidationContext::host_height(ctx).map_err(context_error)?;
+    ctx.store_update_time(client_id.clone(), height, host_timestamp)
+        .map_err(context_error)?;
+    ctx.store_update_height(client_id.clone(), height, host_height)
+        .map_err(context_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            blake2_256,
+            trie::tests::{branch, leaf, nibbles_of},
+            DigestItem, Precommit, DEFAULT_CHILD_STORAGE_KEY_PREFIX, GRANDPA_ENGINE_ID,
+            TIMESTAMP_NOW_KEY,
+        },
+        *,
+    };
+    use ed25519_consensus::SigningKey;
+    use ibc::core::host::types::{
+        identifiers::{ChannelId, PortId, Sequence},
+        path::CommitmentPath,
+    };
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+    use parity_scale_codec::Encode;
+
+    const TIMESTAMP_MILLIS: u64 = 1_700_000_000_000;
+
+    fn setup() -> (NearIbcStore, GrandpaClientState) {
+        testing_env!(VMContextBuilder::new()
+            .block_height(100)
+            .block_timestamp(1_000_000_000)
+            .build());
+        let client_state = GrandpaClientState {
+            chain_id: "appchain".to_string(),
+            latest_height: Height::new(0, 10).unwrap(),
+            frozen_height: None,
+            authority_set_id: 0,
+            authorities: authorities(&signing_keys()),
+            pending_change: None,
+        };
+        (NearIbcStore::new(), client_state)
+    }
+
+    fn client_id() -> ClientId {
+        ClientId::new(ClientType::from_str(GRANDPA_CLIENT_TYPE).unwrap(), 0).unwrap()
+    }
+
+    fn signing_keys() -> Vec<SigningKey> {
+        (1..=4).map(|seed| SigningKey::from([seed; 32])).collect()
+    }
+
+    fn authorities(keys: &[SigningKey]) -> Vec<GrandpaAuthority> {
+        keys.iter()
+            .map(|key| GrandpaAuthority {
+                public_key: key.verification_key().to_bytes().to_vec(),
+                weight: 1,
+            })
+            .collect()
+    }
+
+    fn prefix() -> CommitmentPrefix {
+        CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap()
+    }
+
+    fn commitment_path() -> Path {
+        Path::Commitment(CommitmentPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(1),
+        ))
+    }
+
+    /// Build the state trie with `Timestamp::Now` and the child trie `ibc`, in which
+    /// the commitment path is stored with the given value. Returns the state root and
+    /// all of the trie nodes.
+    fn state_trie(value: &[u8]) -> ([u8; 32], Vec<Vec<u8>>) {
+        let child_leaf = leaf(&nibbles_of(commitment_path().to_string().as_bytes()), value);
+        let child_root_key = [DEFAULT_CHILD_STORAGE_KEY_PREFIX, b"ibc"].concat();
+        let child_root_leaf = leaf(&nibbles_of(&child_root_key)[1..], &blake2_256(&child_leaf));
+        let timestamp_leaf = leaf(
+            &nibbles_of(&TIMESTAMP_NOW_KEY)[1..],
+            &TIMESTAMP_MILLIS.encode(),
+        );
+        let root_node = branch(
+            &[],
+            &[(3, child_root_leaf.clone()), (15, timestamp_leaf.clone())],
+        );
+        (
+            blake2_256(&root_node),
+            vec![root_node, child_root_leaf, timestamp_leaf, child_leaf],
+        )
+    }
+
+    fn substrate_header(
+        number: u32,
+        state_root: [u8; 32],
+        digest: Vec<DigestItem>,
+    ) -> SubstrateHeader {
+        SubstrateHeader {
+            parent_hash: [0; 32],
+            number,
+            state_root,
+            extrinsics_root: [0; 32],
+            digest,
+        }
+    }
+
+    /// Encode the justification of the given header, signed by the given keys.
+    fn justification_of(
+        header: &SubstrateHeader,
+        authority_set_id: u64,
+        keys: &[SigningKey],
+    ) -> Vec<u8> {
+        let round = 1u64;
+        let precommit = Precommit {
+            target_hash: blake2_256(&header.encode()),
+            target_number: header.number,
+        };
+        let precommits: Vec<(Precommit, [u8; 64], [u8; 32])> = keys
+            .iter()
+            .map(|key| {
+                let mut message = vec![1u8];
+                message.extend(precommit.encode());
+                message.extend(round.to_le_bytes());
+                message.extend(authority_set_id.to_le_bytes());
+                (
+                    precommit.clone(),
+                    key.sign(&message).to_bytes(),
+                    key.verification_key().to_bytes(),
+                )
+            })
+            .collect();
+        (
+            round,
+            precommit.target_hash,
+            precommit.target_number,
+            precommits,
+            Vec::<SubstrateHeader>::new(),
+        )
+            .encode()
+    }
+
+    fn raw_header_of(
+        header: &SubstrateHeader,
+        authority_set_id: u64,
+        keys: &[SigningKey],
+    ) -> RawGrandpaHeader {
+        RawGrandpaHeader {
+            header: header.encode(),
+            justification: justification_of(header, authority_set_id, keys),
+            timestamp_proof: state_trie(b"commitment").1,
+        }
+    }
+
+    fn header_any(header: RawGrandpaHeader) -> Any {
+        Any {
+            type_url: GRANDPA_HEADER_TYPE_URL.to_string(),
+            value: header.encode_to_vec(),
+        }
+    }
+
+    fn proof_of(trie_nodes: Vec<Vec<u8>>) -> CommitmentProofBytes {
+        CommitmentProofBytes::try_from(RawGrandpaStorageProof { trie_nodes }.encode_to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let (mut store, client_state) = setup();
+        let (state_root, _) = state_trie(b"commitment");
+        let header = substrate_header(11, state_root, vec![]);
+        let keys = signing_keys();
+        let verify = |raw_header: RawGrandpaHeader| {
+            client_state.verify_client_message(
+                &store,
+                &client_id(),
+                header_any(raw_header),
+                &UpdateKind::UpdateClient,
+            )
+        };
+        assert!(verify(raw_header_of(&header, 0, &keys[..3])).is_ok());
+        // The precommit weight is not more than 2/3 of the authority set.
+        assert!(verify(raw_header_of(&header, 0, &keys[..2])).is_err());
+        // The precommits are signed for another authority set.
+        assert!(verify(raw_header_of(&header, 1, &keys[..3])).is_err());
+        // The precommits are signed by an unknown authority.
+        assert!(verify(raw_header_of(
+            &header,
+            0,
+            &[SigningKey::from([9; 32]), keys[0].clone(), keys[1].clone()]
+        ))
+        .is_err());
+        // The header is tampered after the justification is signed.
+        let mut raw_header = raw_header_of(&header, 0, &keys[..3]);
+        raw_header.header = substrate_header(11, [1; 32], vec![]).encode();
+        assert!(verify(raw_header).is_err());
+        // The timestamp is not proved under the state root.
+        let mut raw_header = raw_header_of(&header, 0, &keys[..3]);
+        raw_header.timestamp_proof = state_trie(b"commitment").1[1..].to_vec();
+        assert!(verify(raw_header).is_err());
+
+        let heights = client_state
+            .update_state(
+                &mut store,
+                &client_id(),
+                header_any(raw_header_of(&header, 0, &keys[..3])),
+            )
+            .unwrap();
+        assert_eq!(heights, vec![Height::new(0, 11).unwrap()]);
+        let AnyConsensusState::Grandpa(consensus_state) = ValidationContext::consensus_state(
+            &store,
+            &ClientConsensusStatePath::new(client_id(), 0, 11),
+        )
+        .unwrap() else {
+            panic!("not a grandpa consensus state");
+        };
+        assert_eq!(consensus_state.root().as_bytes(), state_root);
+        assert_eq!(consensus_state.timestamp, TIMESTAMP_MILLIS * 1_000_000);
+    }
+
+    #[test]
+    fn test_verify_misbehaviour() {
+        let (store, client_state) = setup();
+        let keys = signing_keys();
+        let header_one = substrate_header(11, [1; 32], vec![]);
+        let header_two = substrate_header(11, [2; 32], vec![]);
+        let verify = |header_one: &SubstrateHeader, header_two: &SubstrateHeader| {
+            client_state.verify_client_message(
+                &store,
+                &client_id(),
+                Any {
+                    type_url: GRANDPA_MISBEHAVIOUR_TYPE_URL.to_string(),
+                    value: RawGrandpaMisbehaviour {
+                        header_one: Some(raw_header_of(header_one, 0, &keys[..3])),
+                        header_two: Some(raw_header_of(header_two, 0, &keys[..3])),
+                    }
+                    .encode_to_vec(),
+                },
+                &UpdateKind::SubmitMisbehaviour,
+            )
+        };
+        assert!(verify(&header_one, &header_two).is_ok());
+        assert!(verify(&header_one, &header_one).is_err());
+        assert!(verify(&header_one, &substrate_header(12, [2; 32], vec![])).is_err());
+    }
+
+    #[test]
+    fn test_authority_set_change() {
+        let (_, client_state) = setup();
+        let next_keys: Vec<SigningKey> = (5..=7).map(|seed| SigningKey::from([seed; 32])).collect();
+        let scheduled_change = |delay: u32| {
+            let next_authorities: Vec<([u8; 32], u64)> = next_keys
+                .iter()
+                .map(|key| (key.verification_key().to_bytes(), 1))
+                .collect();
+            DigestItem::Consensus(GRANDPA_ENGINE_ID, (1u8, next_authorities, delay).encode())
+        };
+        // The change without delay is enacted by the header itself.
+        let next_client_state = client_state
+            .next_client_state(&substrate_header(11, [0; 32], vec![scheduled_change(0)]))
+            .unwrap();
+        assert_eq!(next_client_state.authority_set_id, 1);
+        assert_eq!(next_client_state.authorities, authorities(&next_keys));
+        assert!(next_client_state.pending_change.is_none());
+        // The delayed change is enacted at the block `number + delay`.
+        let next_client_state = client_state
+            .next_client_state(&substrate_header(11, [0; 32], vec![scheduled_change(2)]))
+            .unwrap();
+        assert_eq!(next_client_state.authority_set_id, 0);
+        assert_eq!(
+            next_client_state.pending_change.as_ref().unwrap().enact_at,
+            13
+        );
+        let header = substrate_header(14, [0; 32], vec![]);
+        assert!(next_client_state
+            .verify_finality(&raw_header_of(&header, 0, &signing_keys()[..3]))
+            .is_err());
+        let next_client_state = next_client_state
+            .next_client_state(&substrate_header(13, [0; 32], vec![]))
+            .unwrap();
+        assert_eq!(next_client_state.authority_set_id, 1);
+        assert_eq!(next_client_state.authorities, authorities(&next_keys));
+        assert!(next_client_state
+            .verify_finality(&raw_header_of(&header, 1, &next_keys))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let (_, client_state) = setup();
+        let (state_root, trie_nodes) = state_trie(b"commitment");
+        let root = CommitmentRoot::from_bytes(&state_root);
+        let absent_path = Path::Commitment(CommitmentPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(2),
+        ));
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof_of(trie_nodes.clone()),
+                &root,
+                commitment_path(),
+                b"commitment".to_vec()
+            )
+            .is_ok());
+        assert!(client_state
+            .verify_non_membership(&prefix(), &proof_of(trie_nodes.clone()), &root, absent_path)
+            .is_ok());
+        // The value is tampered.
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof_of(trie_nodes.clone()),
+                &root,
+                commitment_path(),
+                b"tampered".to_vec()
+            )
+            .is_err());
+        assert!(client_state
+            .verify_non_membership(
+                &prefix(),
+                &proof_of(trie_nodes.clone()),
+                &root,
+                commitment_path()
+            )
+            .is_err());
+        // The value is not in the child trie named by another prefix.
+        assert!(client_state
+            .verify_membership(
+                &CommitmentPrefix::try_from(b"other".to_vec()).unwrap(),
+                &proof_of(trie_nodes.clone()),
+                &root,
+                commitment_path(),
+                b"commitment".to_vec()
+            )
+            .is_err());
+        // The trie nodes of another state are not valid under the root.
+        let (_, tampered_trie_nodes) = state_trie(b"tampered");
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof_of(tampered_trie_nodes),
+                &root,
+                commitment_path(),
+                b"tampered".to_vec()
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use super::GRANDPA_CONSENSUS_STATE_TYPE_URL;
+use crate::prelude::*;
+use ibc::{
+    core::{client::types::error::ClientError, commitment_types::commitment::CommitmentRoot},
+    primitives::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, Protobuf};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrandpaConsensusState {
+    /// The state root of the finalized block.
+    pub root: CommitmentRoot,
+    /// The timestamp of the finalized block, in nanoseconds.
+    pub timestamp: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawGrandpaConsensusState {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+}
+
+impl GrandpaConsensusState {
+    pub fn new(root: CommitmentRoot, timestamp: u64) -> Self {
+        Self { root, timestamp }
+    }
+    ///
+    pub fn root(&self) -> &CommitmentRoot {
+        &self.root
+    }
+    ///
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::from_nanoseconds(self.timestamp).unwrap_or(Timestamp::none())
+    }
+}
+
+impl Protobuf<RawGrandpaConsensusState> for GrandpaConsensusState {}
+
+impl TryFrom<RawGrandpaConsensusState> for GrandpaConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawGrandpaConsensusState) -> Result<Self, Self::Error> {
+        if raw.root.len() != 32 {
+            return Err(ClientError::ClientSpecific {
+                description: "invalid state root".to_string(),
+            });
+        }
+        if raw.timestamp == 0 {
+            return Err(ClientError::ClientSpecific {
+                description: "timestamp must not be zero".to_string(),
+            });
+        }
+        Ok(Self::new(
+            CommitmentRoot::from_bytes(&raw.root),
+            raw.timestamp,
+        ))
+    }
+}
+
+impl From<GrandpaConsensusState> for RawGrandpaConsensusState {
+    fn from(value: GrandpaConsensusState) -> Self {
+        RawGrandpaConsensusState {
+            root: value.root.into_vec(),
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+impl Protobuf<Any> for GrandpaConsensusState {}
+
+impl TryFrom<Any> for GrandpaConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: Any) -> Result<Self, Self::Error> {
+        match raw.type_url.as_str() {
+            GRANDPA_CONSENSUS_STATE_TYPE_URL => {
+                Protobuf::<RawGrandpaConsensusState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })
+            }
+            _ => Err(ClientError::UnknownConsensusStateType {
+                consensus_state_type: raw.type_url,
+            }),
+        }
+    }
+}
+
+impl From<GrandpaConsensusState> for Any {
+    fn from(value: GrandpaConsensusState) -> Self {
+        Any {
+            type_url: GRANDPA_CONSENSUS_STATE_TYPE_URL.to_string(),
+            value: Protobuf::<RawGrandpaConsensusState>::encode_vec(value),
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! The GRANDPA light client, for Substrate-based chains finalized by GRANDPA
+//! (e.g. Octopus appchains without a Tendermint layer).
+//!
+//! The client tracks the current GRANDPA authority set of the counterparty chain, and the
+//! height of the client is `(revision_number, block_number)`. A header of the client contains
+//! the SCALE-encoded Substrate header, the GRANDPA justification which finalizes it, and the
+//! storage proof of `Timestamp::Now` under the state root of the header.
+//!
+//! The changes of the authority set are read from the GRANDPA consensus logs in the digest of
+//! the finalized headers. A scheduled change is enacted when the block at `number + delay`
+//! is finalized, so the relayer must update the client with that block before any later one.
+//! Forced changes are not supported.
+//!
+//! The IBC state of the counterparty chain is expected to be stored in the default child trie
+//! named by the commitment prefix, keyed by the IBC paths, as `pallet-ibc` does. The proofs of
+//! membership and non-membership are the trie nodes proving the child trie root under the state
+//! root and the value of the path under the child trie root.
+use crate::prelude::*;
+use alloc::collections::{BTreeMap, BTreeSet};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use ibc::core::client::types::error::ClientError;
+use near_sdk::env;
+use parity_scale_codec::{Decode, DecodeAll, Encode};
+use serde::{Deserialize, Serialize};
+
+pub mod client_state;
+pub mod consensus_state;
+pub mod trie;
+
+pub const GRANDPA_CLIENT_TYPE: &str = "10-grandpa";
+pub const GRANDPA_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.grandpa.v1.ClientState";
+pub const GRANDPA_CONSENSUS_STATE_TYPE_URL: &str = "/ibc.lightclients.grandpa.v1.ConsensusState";
+pub const GRANDPA_HEADER_TYPE_URL: &str = "/ibc.lightclients.grandpa.v1.Header";
+pub const GRANDPA_MISBEHAVIOUR_TYPE_URL: &str = "/ibc.lightclients.grandpa.v1.Misbehaviour";
+/// The consensus engine id of GRANDPA in the digest of Substrate headers.
+const GRANDPA_ENGINE_ID: [u8; 4] = *b"FRNK";
+/// The storage key of `Timestamp::Now`, which is `twox_128("Timestamp") ++ twox_128("Now")`.
+const TIMESTAMP_NOW_KEY: [u8; 32] = [
+    0xf0, 0xc3, 0x65, 0xc3, 0xcf, 0x59, 0xd6, 0x71, 0xeb, 0x72, 0xda, 0x0e, 0x7a, 0x41, 0x13, 0xc4,
+    0x9f, 0x1f, 0x05, 0x15, 0xf4, 0x62, 0xcd, 0xcf, 0x84, 0xe0, 0xf1, 0xd6, 0x04, 0x5d, 0xfc, 0xbb,
+];
+/// The prefix of the storage keys of the roots of default child tries.
+const DEFAULT_CHILD_STORAGE_KEY_PREFIX: &[u8] = b":child_storage:default:";
+
+/// A GRANDPA authority with its voting weight.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrandpaAuthority {
+    /// The ed25519 public key of the authority.
+    pub public_key: Vec<u8>,
+    pub weight: u64,
+}
+
+/// The header of Substrate chains (with `u32` block number and `BlakeTwo256` hashing).
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct SubstrateHeader {
+    pub parent_hash: [u8; 32],
+    #[codec(compact)]
+    pub number: u32,
+    pub state_root: [u8; 32],
+    pub extrinsics_root: [u8; 32],
+    pub digest: Vec<DigestItem>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum DigestItem {
+    #[codec(index = 0)]
+    Other(Vec<u8>),
+    #[codec(index = 4)]
+    Consensus([u8; 4], Vec<u8>),
+    #[codec(index = 5)]
+    Seal([u8; 4], Vec<u8>),
+    #[codec(index = 6)]
+    PreRuntime([u8; 4], Vec<u8>),
+    #[codec(index = 8)]
+    RuntimeEnvironmentUpdated,
+}
+
+/// The consensus logs of GRANDPA in the digest of Substrate headers.
+#[derive(Clone, Debug, Decode)]
+pub enum GrandpaConsensusLog {
+    #[codec(index = 1)]
+    ScheduledChange(ScheduledChange),
+    #[codec(index = 2)]
+    ForcedChange(u32, ScheduledChange),
+    #[codec(index = 3)]
+    OnDisabled(u64),
+    #[codec(index = 4)]
+    Pause(u32),
+    #[codec(index = 5)]
+    Resume(u32),
+}
+
+#[derive(Clone, Debug, Decode)]
+pub struct ScheduledChange {
+    pub next_authorities: Vec<([u8; 32], u64)>,
+    pub delay: u32,
+}
+
+/// The justification of GRANDPA which proves the finality of a block.
+#[derive(Clone, Debug, Decode)]
+pub struct GrandpaJustification {
+    pub round: u64,
+    pub commit: Commit,
+    pub votes_ancestries: Vec<SubstrateHeader>,
+}
+
+#[derive(Clone, Debug, Decode)]
+pub struct Commit {
+    pub target_hash: [u8; 32],
+    pub target_number: u32,
+    pub precommits: Vec<SignedPrecommit>,
+}
+
+#[derive(Clone, Debug, Decode)]
+pub struct SignedPrecommit {
+    pub precommit: Precommit,
+    pub signature: [u8; 64],
+    pub id: [u8; 32],
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct Precommit {
+    pub target_hash: [u8; 32],
+    pub target_number: u32,
+}
+
+/// A change of the authority set which is scheduled in a finalized header.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingAuthoritySetChange {
+    /// The block number at which the change is enacted.
+    pub enact_at: u32,
+    pub next_authorities: Vec<GrandpaAuthority>,
+}
+
+fn client_error(description: String) -> ClientError {
+    ClientError::ClientSpecific { description }
+}
+
+pub fn blake2_256(data: &[u8]) -> [u8; 32] {
+    Blake2b::<U32>::digest(data).into()
+}
+
+/// Decode the given SCALE-encoded header, and return it with its hash.
+pub fn decode_header(encoded_header: &[u8]) -> Result<(SubstrateHeader, [u8; 32]), ClientError> {
+    let header = SubstrateHeader::decode_all(&mut &encoded_header[..])
+        .map_err(|e| client_error(format!("invalid substrate header: {}", e)))?;
+    Ok((header, blake2_256(encoded_header)))
+}
+
+/// Decode the given SCALE-encoded justification.
+pub fn decode_justification(
+    encoded_justification: &[u8],
+) -> Result<GrandpaJustification, ClientError> {
+    GrandpaJustification::decode_all(&mut &encoded_justification[..])
+        .map_err(|e| client_error(format!("invalid grandpa justification: {}", e)))
+}
+
+/// Verify the given justification finalizes the block with the given hash and number,
+/// and is signed by more than 2/3 of the weight of the given authority set.
+pub fn verify_justification(
+    justification: &GrandpaJustification,
+    block_hash: &[u8; 32],
+    block_number: u32,
+    authority_set_id: u64,
+    authorities: &[GrandpaAuthority],
+) -> Result<(), ClientError> {
+    let commit = &justification.commit;
+    if commit.target_hash != *block_hash || commit.target_number != block_number {
+        return Err(client_error(
+            "justification does not target the header".to_string(),
+        ));
+    }
+    let ancestry: BTreeMap<[u8; 32], ([u8; 32], u32)> = justification
+        .votes_ancestries
+        .iter()
+        .map(|header| {
+            (
+                blake2_256(&header.encode()),
+                (header.parent_hash, header.number),
+            )
+        })
+        .collect();
+    let weights: BTreeMap<&[u8], u64> = authorities
+        .iter()
+        .map(|authority| (authority.public_key.as_slice(), authority.weight))
+        .collect();
+    let mut signers = BTreeSet::new();
+    let mut signed_weight: u64 = 0;
+    for signed_precommit in commit.precommits.iter() {
+        let weight = weights
+            .get(signed_precommit.id.as_slice())
+            .ok_or_else(|| client_error("precommit of unknown authority".to_string()))?;
+        if !is_descendant_of(
+            &ancestry,
+            &signed_precommit.precommit,
+            &commit.target_hash,
+            commit.target_number,
+        ) {
+            return Err(client_error(
+                "precommit target is not a descendant of the commit target".to_string(),
+            ));
+        }
+        // The encoded `(Message::Precommit(precommit), round, set_id)`.
+        let mut message = vec![1u8];
+        message.extend(signed_precommit.precommit.encode());
+        message.extend(justification.round.to_le_bytes());
+        message.extend(authority_set_id.to_le_bytes());
+        if !env::ed25519_verify(&signed_precommit.signature, &message, &signed_precommit.id) {
+            return Err(client_error("invalid precommit signature".to_string()));
+        }
+        // Equivocations of an authority are only counted once.
+        if signers.insert(signed_precommit.id) {
+            signed_weight = signed_weight.saturating_add(*weight);
+        }
+    }
+    let total_weight = authorities.iter().fold(0u64, |total, authority| {
+        total.saturating_add(authority.weight)
+    });
+    let threshold = total_weight - total_weight.saturating_sub(1) / 3;
+    if signed_weight < threshold {
+        return Err(client_error(format!(
+            "insufficient precommit weight: {} < {}",
+            signed_weight, threshold
+        )));
+    }
+    Ok(())
+}
+
+/// Check the precommit target is the commit target or one of its descendants
+/// in the votes ancestries.
+fn is_descendant_of(
+    ancestry: &BTreeMap<[u8; 32], ([u8; 32], u32)>,
+    precommit: &Precommit,
+    target_hash: &[u8; 32],
+    target_number: u32,
+) -> bool {
+    let mut current_hash = precommit.target_hash;
+    let mut current_number = precommit.target_number;
+    loop {
+        if current_hash == *target_hash {
+            return true;
+        }
+        if current_number <= target_number {
+            return false;
+        }
+        match ancestry.get(&current_hash) {
+            Some((parent_hash, number)) if *number == current_number => {
+                current_hash = *parent_hash;
+                current_number -= 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Get the authority set change scheduled in the digest of the given header.
+pub fn scheduled_change_of(
+    header: &SubstrateHeader,
+) -> Result<Option<PendingAuthoritySetChange>, ClientError> {
+    let mut pending_change = None;
+    for item in header.digest.iter() {
+        let DigestItem::Consensus(engine_id, data) = item else {
+            continue;
+        };
+        if *engine_id != GRANDPA_ENGINE_ID {
+            continue;
+        }
+        let log = GrandpaConsensusLog::decode(&mut data.as_slice())
+            .map_err(|e| client_error(format!("invalid grandpa consensus log: {}", e)))?;
+        match log {
+            GrandpaConsensusLog::ScheduledChange(change) => {
+                pending_change = Some(PendingAuthoritySetChange {
+                    enact_at: header.number.saturating_add(change.delay),
+                    next_authorities: change
+                        .next_authorities
+                        .into_iter()
+                        .map(|(public_key, weight)| GrandpaAuthority {
+                            public_key: public_key.to_vec(),
+                            weight,
+                        })
+                        .collect(),
+                });
+            }
+            GrandpaConsensusLog::ForcedChange(..) => {
+                return Err(client_error(
+                    "forced authority set change is not supported".to_string(),
+                ))
+            }
+            _ => (),
+        }
+    }
+    Ok(pending_change)
+}
+
+/// Read the timestamp (in nanoseconds) of the block with the given state root
+/// from the storage proof of `Timestamp::Now`.
+pub fn read_timestamp(state_root: &[u8; 32], proof: &[Vec<u8>]) -> Result<u64, ClientError> {
+    let value = trie::read_proof_check(state_root, proof, &TIMESTAMP_NOW_KEY)?
+        .ok_or_else(|| client_error("timestamp is not found in storage proof".to_string()))?;
+    let millis = u64::decode_all(&mut value.as_slice())
+        .map_err(|e| client_error(format!("invalid timestamp: {}", e)))?;
+    millis
+        .checked_mul(1_000_000)
+        .filter(|nanos| *nanos > 0)
+        .ok_or_else(|| client_error("invalid timestamp".to_string()))
+}
+
+/// Read the value of the given key in the default child trie with the given name, from
+/// the storage proof under the given state root.
+pub fn read_child_proof_check(
+    state_root: &[u8; 32],
+    proof: &[Vec<u8>],
+    child_trie_name: &[u8],
+    key: &[u8],
+) -> Result<Option<Vec<u8>>, ClientError> {
+    let child_root_key = [DEFAULT_CHILD_STORAGE_KEY_PREFIX, child_trie_name].concat();
+    match trie::read_proof_check(state_root, proof, &child_root_key)? {
+        Some(child_root) => {
+            let child_root: [u8; 32] = child_root
+                .as_slice()
+                .try_into()
+                .map_err(|_| client_error("invalid child trie root".to_string()))?;
+            trie::read_proof_check(&child_root, proof, key)
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,385 @@
+//! The verification of storage proofs of the base-16 Patricia-Merkle trie of Substrate,
+//! with `BlakeTwo256` hashing and the node codec of both state versions.
+use super::blake2_256;
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use ibc::core::client::types::error::ClientError;
+use parity_scale_codec::{Compact, Decode};
+
+const EMPTY_TRIE: u8 = 0;
+const LEAF_PREFIX_MASK: u8 = 0b01 << 6;
+const BRANCH_WITHOUT_VALUE_MASK: u8 = 0b10 << 6;
+const BRANCH_WITH_VALUE_MASK: u8 = 0b11 << 6;
+const ALT_HASHING_LEAF_PREFIX_MASK: u8 = 0b001 << 5;
+const ALT_HASHING_BRANCH_WITH_MASK: u8 = 0b0001 << 4;
+
+enum NodeValue<'a> {
+    Inline(&'a [u8]),
+    Hashed([u8; 32]),
+}
+
+enum NodeChild<'a> {
+    Inline(&'a [u8]),
+    Hashed([u8; 32]),
+}
+
+enum Node<'a> {
+    Empty,
+    Leaf {
+        partial_key: Vec<u8>,
+        value: NodeValue<'a>,
+    },
+    Branch {
+        partial_key: Vec<u8>,
+        children: [Option<NodeChild<'a>>; 16],
+        value: Option<NodeValue<'a>>,
+    },
+}
+
+fn trie_error(description: &str) -> ClientError {
+    ClientError::ClientSpecific {
+        description: format!("invalid storage proof: {}", description),
+    }
+}
+
+/// Read the value of the given key from the storage proof (the encoded trie nodes)
+/// under the given root.
+///
+/// Returns `Ok(None)` if the key is proved to be absent.
+pub fn read_proof_check(
+    root: &[u8; 32],
+    proof: &[Vec<u8>],
+    key: &[u8],
+) -> Result<Option<Vec<u8>>, ClientError> {
+    let db: BTreeMap<[u8; 32], &[u8]> = proof
+        .iter()
+        .map(|node| (blake2_256(node), node.as_slice()))
+        .collect();
+    let lookup = |hash: &[u8; 32]| {
+        db.get(hash)
+            .copied()
+            .ok_or_else(|| trie_error("missing trie node"))
+    };
+    let nibbles: Vec<u8> = key
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect();
+    let mut node_data = lookup(root)?;
+    let mut position = 0;
+    loop {
+        let value = match decode_node(node_data)? {
+            Node::Empty => return Ok(None),
+            Node::Leaf { partial_key, value } => {
+                if nibbles[position..] != partial_key[..] {
+                    return Ok(None);
+                }
+                value
+            }
+            Node::Branch {
+                partial_key,
+                mut children,
+                value,
+            } => {
+                if !nibbles[position..].starts_with(&partial_key) {
+                    return Ok(None);
+                }
+                position += partial_key.len();
+                if position == nibbles.len() {
+                    match value {
+                        Some(value) => value,
+                        None => return Ok(None),
+                    }
+                } else {
+                    let index = nibbles[position] as usize;
+                    position += 1;
+                    node_data = match children[index].take() {
+                        Some(NodeChild::Inline(data)) => data,
+                        Some(NodeChild::Hashed(hash)) => lookup(&hash)?,
+                        None => return Ok(None),
+                    };
+                    continue;
+                }
+            }
+        };
+        return match value {
+            NodeValue::Inline(value) => Ok(Some(value.to_vec())),
+            NodeValue::Hashed(hash) => lookup(&hash).map(|value| Some(value.to_vec())),
+        };
+    }
+}
+
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    //
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ClientError> {
+        if self.data.len() < length {
+            return Err(trie_error("unexpected end of trie node"));
+        }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+    //
+    fn take_byte(&mut self) -> Result<u8, ClientError> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+    //
+    fn take_hash(&mut self) -> Result<[u8; 32], ClientError> {
+        self.take(32)
+            .map(|bytes| bytes.try_into().expect("length is checked"))
+    }
+    /// Take the bytes prefixed with a SCALE compact length.
+    fn take_prefixed(&mut self) -> Result<&'a [u8], ClientError> {
+        let length = <Compact<u32>>::decode(&mut self.data)
+            .map_err(|_| trie_error("invalid length prefix"))?
+            .0;
+        self.take(length as usize)
+    }
+}
+
+fn decode_node(data: &[u8]) -> Result<Node<'_>, ClientError> {
+    let mut input = Input { data };
+    let first = input.take_byte()?;
+    if first == EMPTY_TRIE {
+        return Ok(Node::Empty);
+    }
+    // The kind of the node, whether the value is hashed and the bits of the prefix.
+    let (is_leaf, has_value, is_value_hashed, prefix_bits) = match first & (0b11 << 6) {
+        LEAF_PREFIX_MASK => (true, true, false, 2),
+        BRANCH_WITHOUT_VALUE_MASK => (false, false, false, 2),
+        BRANCH_WITH_VALUE_MASK => (false, true, false, 2),
+        _ => match first & (0b111 << 5) {
+            ALT_HASHING_LEAF_PREFIX_MASK => (true, true, true, 3),
+            _ => match first & (0b1111 << 4) {
+                ALT_HASHING_BRANCH_WITH_MASK => (false, true, true, 4),
+                _ => return Err(trie_error("unknown node header")),
+            },
+        },
+    };
+    let nibble_count = decode_size(first, &mut input, prefix_bits)?;
+    let partial_key = decode_partial_key(&mut input, nibble_count)?;
+    if is_leaf {
+        let value = decode_value(&mut input, is_value_hashed)?;
+        return Ok(Node::Leaf { partial_key, value });
+    }
+    let bitmap = u16::from_le_bytes(input.take(2)?.try_into().expect("length is checked"));
+    let value = match has_value {
+        true => Some(decode_value(&mut input, is_value_hashed)?),
+        false => None,
+    };
+    let mut children: [Option<NodeChild<'_>>; 16] = Default::default();
+    for (index, child) in children.iter_mut().enumerate() {
+        if bitmap & (1 << index) != 0 {
+            let data = input.take_prefixed()?;
+            *child = Some(match data.len() {
+                32 => NodeChild::Hashed(data.try_into().expect("length is checked")),
+                _ => NodeChild::Inline(data),
+            });
+        }
+    }
+    Ok(Node::Branch {
+        partial_key,
+        children,
+        value,
+    })
+}
+
+fn decode_value<'a>(
+    input: &mut Input<'a>,
+    is_value_hashed: bool,
+) -> Result<NodeValue<'a>, ClientError> {
+    match is_value_hashed {
+        true => input.take_hash().map(NodeValue::Hashed),
+        false => input.take_prefixed().map(NodeValue::Inline),
+    }
+}
+
+/// Decode the nibble count of the partial key in the node header.
+fn decode_size(first: u8, input: &mut Input<'_>, prefix_bits: u8) -> Result<usize, ClientError> {
+    let max_value = 255u8 >> prefix_bits;
+    let mut result = (first & max_value) as usize;
+    if result < max_value as usize {
+        return Ok(result);
+    }
+    result -= 1;
+    loop {
+        let n = input.take_byte()? as usize;
+        if n < 255 {
+            return Ok(result + n + 1);
+        }
+        result += 255;
+    }
+}
+
+/// Decode the partial key with the given count of nibbles, which is left-padded
+/// to whole bytes.
+fn decode_partial_key(input: &mut Input<'_>, nibble_count: usize) -> Result<Vec<u8>, ClientError> {
+    let bytes = input.take((nibble_count + 1) / 2)?;
+    let nibbles: Vec<u8> = bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect();
+    Ok(nibbles[nibbles.len() - nibble_count..].to_vec())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use parity_scale_codec::Encode;
+
+    pub(crate) fn nibbles_of(key: &[u8]) -> Vec<u8> {
+        key.iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0f])
+            .collect()
+    }
+
+    /// Encode the node header with the nibble count, and the left-padded partial key.
+    fn encode_partial_key(mask: u8, prefix_bits: u8, nibbles: &[u8]) -> Vec<u8> {
+        let max_value = (255u8 >> prefix_bits) as usize;
+        let mut encoded = vec![];
+        if nibbles.len() < max_value {
+            encoded.push(mask | nibbles.len() as u8);
+        } else {
+            encoded.push(mask | max_value as u8);
+            let mut rest = nibbles.len() - max_value;
+            while rest >= 255 {
+                encoded.push(255);
+                rest -= 255;
+            }
+            encoded.push(rest as u8);
+        }
+        if nibbles.len() % 2 == 1 {
+            encoded.push(nibbles[0]);
+        }
+        nibbles[nibbles.len() % 2..]
+            .chunks(2)
+            .for_each(|pair| encoded.push(pair[0] << 4 | pair[1]));
+        encoded
+    }
+
+    pub(crate) fn leaf(nibbles: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut node = encode_partial_key(LEAF_PREFIX_MASK, 2, nibbles);
+        node.extend(Compact(value.len() as u32).encode());
+        node.extend(value);
+        node
+    }
+
+    pub(crate) fn hashed_value_leaf(nibbles: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut node = encode_partial_key(ALT_HASHING_LEAF_PREFIX_MASK, 3, nibbles);
+        node.extend(blake2_256(value));
+        node
+    }
+
+    /// Encode a branch without value, with the children in ascending order of index.
+    /// The children shorter than a hash are inlined.
+    pub(crate) fn branch(nibbles: &[u8], children: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut node = encode_partial_key(BRANCH_WITHOUT_VALUE_MASK, 2, nibbles);
+        let bitmap = children
+            .iter()
+            .fold(0u16, |bitmap, (index, _)| bitmap | 1 << index);
+        node.extend(bitmap.to_le_bytes());
+        children.iter().for_each(|(_, child)| {
+            let reference = match child.len() < 32 {
+                true => child.clone(),
+                false => blake2_256(child).to_vec(),
+            };
+            node.extend(Compact(reference.len() as u32).encode());
+            node.extend(reference);
+        });
+        node
+    }
+
+    #[test]
+    fn test_read_leaf() {
+        let node = leaf(&nibbles_of(&[0x12, 0x34]), b"value");
+        let root = blake2_256(&node);
+        let proof = vec![node];
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x12, 0x34]).unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x12, 0x35]).unwrap(),
+            None
+        );
+        assert_eq!(read_proof_check(&root, &proof, &[0x12]).unwrap(), None);
+        // The proof does not match the root with a tampered value.
+        let tampered_root = blake2_256(&leaf(&nibbles_of(&[0x12, 0x34]), b"valuf"));
+        assert!(read_proof_check(&tampered_root, &proof, &[0x12, 0x34]).is_err());
+    }
+
+    #[test]
+    fn test_read_branch() {
+        let hashed_leaf = leaf(&[3, 4], &[7; 40]);
+        let inline_leaf = leaf(&[6, 7], b"v");
+        let node = branch(&[1], &[(2, hashed_leaf.clone()), (5, inline_leaf)]);
+        let root = blake2_256(&node);
+        let proof = vec![node.clone(), hashed_leaf];
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x12, 0x34]).unwrap(),
+            Some(vec![7; 40])
+        );
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x15, 0x67]).unwrap(),
+            Some(b"v".to_vec())
+        );
+        // There is no child at index 3, or the partial key of the branch does not match.
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x13, 0x34]).unwrap(),
+            None
+        );
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x22, 0x34]).unwrap(),
+            None
+        );
+        // The hashed child is missing in the proof, while the inlined one is still readable.
+        let proof = vec![node];
+        assert!(read_proof_check(&root, &proof, &[0x12, 0x34]).is_err());
+        assert_eq!(
+            read_proof_check(&root, &proof, &[0x15, 0x67]).unwrap(),
+            Some(b"v".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_read_hashed_value() {
+        let node = hashed_value_leaf(&nibbles_of(&[0xab]), &[9; 64]);
+        let root = blake2_256(&node);
+        assert_eq!(
+            read_proof_check(&root, &[node.clone(), vec![9; 64]], &[0xab]).unwrap(),
+            Some(vec![9; 64])
+        );
+        assert!(read_proof_check(&root, &[node.clone()], &[0xab]).is_err());
+        assert!(read_proof_check(&root, &[node, vec![8; 64]], &[0xab]).is_err());
+    }
+
+    #[test]
+    fn test_read_long_partial_key() {
+        // The nibble counts which need one and two extra bytes in the node header.
+        for key in [vec![0xcd; 40], vec![0xef; 160]] {
+            let node = leaf(&nibbles_of(&key), b"value");
+            let root = blake2_256(&node);
+            assert_eq!(
+                read_proof_check(&root, &[node], &key).unwrap(),
+                Some(b"value".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_invalid_node() {
+        let unknown_header = vec![0b0000_0001];
+        assert!(read_proof_check(&blake2_256(&unknown_header), &[unknown_header], &[0]).is_err());
+        let mut truncated = leaf(&nibbles_of(&[0x12, 0x34]), b"value");
+        truncated.pop();
+        assert!(read_proof_check(&blake2_256(&truncated), &[truncated], &[0x12, 0x34]).is_err());
+        let empty = vec![EMPTY_TRIE];
+        assert_eq!(
+            read_proof_check(&blake2_256(&empty), &[empty], &[0x12]).unwrap(),
+            None
+        );
+    }
+}
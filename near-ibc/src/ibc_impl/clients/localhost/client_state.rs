@@ -0,0 +1,342 @@
+use super::{stored_value_of, LOCALHOST_CLIENT_TYPE};
+use crate::{context::NearIbcStore, prelude::*};
+use core::str::FromStr;
+use ibc::core::{
+    client::context::{
+        client_state::{ClientStateCommon, ClientStateExecution, ClientStateValidation},
+        types::{error::ClientError, Height, Status, UpdateKind},
+    },
+    commitment_types::commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
+    host::types::{
+        identifiers::{ClientId, ClientType},
+        path::Path,
+    },
+};
+use ibc_proto::{google::protobuf::Any, ibc::core::client::v1::Height as RawHeight, Protobuf};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+/// The client state of the localhost client, which is built from the current
+/// height of the host chain and is never stored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalhostClientState {
+    pub latest_height: Height,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawLocalhostClientState {
+    #[prost(message, optional, tag = "1")]
+    pub latest_height: Option<RawHeight>,
+}
+
+fn client_error(description: &str) -> ClientError {
+    ClientError::ClientSpecific {
+        description: description.to_string(),
+    }
+}
+
+impl LocalhostClientState {
+    pub fn new(latest_height: Height) -> Self {
+        Self { latest_height }
+    }
+}
+
+impl Protobuf<RawLocalhostClientState> for LocalhostClientState {}
+
+impl TryFrom<RawLocalhostClientState> for LocalhostClientState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawLocalhostClientState) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            raw.latest_height
+                .ok_or_else(|| client_error("missing latest height"))?
+                .try_into()?,
+        ))
+    }
+}
+
+impl From<LocalhostClientState> for RawLocalhostClientState {
+    fn from(value: LocalhostClientState) -> Self {
+        RawLocalhostClientState {
+            latest_height: Some(value.latest_height.into()),
+        }
+    }
+}
+
+impl ClientStateValidation<NearIbcStore> for LocalhostClientState {
+    fn verify_client_message(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        Err(client_error("localhost client does not need to be updated"))
+    }
+
+    fn check_for_misbehaviour(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<bool, ClientError> {
+        Ok(false)
+    }
+
+    fn status(&self, _ctx: &NearIbcStore, _client_id: &ClientId) -> Result<Status, ClientError> {
+        Ok(Status::Active)
+    }
+}
+
+impl ClientStateCommon for LocalhostClientState {
+    fn verify_consensus_state(&self, _consensus_state: Any) -> Result<(), ClientError> {
+        Err(client_error("localhost client can not be created"))
+    }
+
+    fn client_type(&self) -> ClientType {
+        ClientType::from_str(LOCALHOST_CLIENT_TYPE).expect("Invalid client type.")
+    }
+
+    fn latest_height(&self) -> Height {
+        self.latest_height
+    }
+
+    fn validate_proof_height(&self, proof_height: Height) -> Result<(), ClientError> {
+        if proof_height > self.latest_height {
+            return Err(ClientError::InvalidProofHeight {
+                latest_height: self.latest_height,
+                proof_height,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_upgrade_client(
+        &self,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+        _proof_upgrade_client: CommitmentProofBytes,
+        _proof_upgrade_consensus_state: CommitmentProofBytes,
+        _root: &CommitmentRoot,
+    ) -> Result<(), ClientError> {
+        Err(client_error("localhost client can not be upgraded"))
+    }
+
+    fn verify_membership(
+        &self,
+        _prefix: &CommitmentPrefix,
+        _proof: &CommitmentProofBytes,
+        _root: &CommitmentRoot,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        match stored_value_of(&path) {
+            Some(stored_value) if stored_value == value => Ok(()),
+            Some(_) => Err(client_error(
+                format!("value of path {} does not match the stored value", path).as_str(),
+            )),
+            None => Err(client_error(
+                format!("value of path {} is not found in storage", path).as_str(),
+            )),
+        }
+    }
+
+    fn verify_non_membership(
+        &self,
+        _prefix: &CommitmentPrefix,
+        _proof: &CommitmentProofBytes,
+        _root: &CommitmentRoot,
+        path: Path,
+    ) -> Result<(), ClientError> {
+        match stored_value_of(&path) {
+            Some(_) => Err(client_error(
+                format!("value of path {} exists in storage", path).as_str(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ClientStateExecution<NearIbcStore> for LocalhostClientState {
+    fn initialise(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _consensus_state: Any,
+    ) -> Result<(), ClientError> {
+        Err(client_error("localhost client can not be created"))
+    }
+
+    fn update_state(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _header: Any,
+    ) -> Result<Vec<Height>, ClientError> {
+        Err(client_error("localhost client does not need to be updated"))
+    }
+
+    fn update_state_on_misbehaviour(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        Err(client_error("localhost client can not be frozen"))
+    }
+
+    fn update_state_on_upgrade(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+    ) -> Result<Height, ClientError> {
+        Err(client_error("localhost client can not be upgraded"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ibc::core::host::types::{
+        identifiers::{ChannelId, PortId, Sequence},
+        path::{CommitmentPath, ReceiptPath, SeqSendPath},
+    };
+    use near_sdk::{env, test_utils::VMContextBuilder, testing_env};
+
+    fn setup() -> LocalhostClientState {
+        testing_env!(VMContextBuilder::new().block_height(100).build());
+        LocalhostClientState::new(Height::new(0, 100).unwrap())
+    }
+
+    fn prefix() -> CommitmentPrefix {
+        CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap()
+    }
+
+    fn proof() -> CommitmentProofBytes {
+        CommitmentProofBytes::try_from(vec![0]).unwrap()
+    }
+
+    fn root() -> CommitmentRoot {
+        CommitmentRoot::from_bytes(&[])
+    }
+
+    fn commitment_path(sequence: u64) -> Path {
+        Path::Commitment(CommitmentPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(sequence),
+        ))
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let client_state = setup();
+        env::storage_write(&commitment_path(1).to_string().into_bytes(), &[1; 32]);
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof(),
+                &root(),
+                commitment_path(1),
+                vec![1; 32]
+            )
+            .is_ok());
+        // The value does not match the stored value.
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof(),
+                &root(),
+                commitment_path(1),
+                vec![2; 32]
+            )
+            .is_err());
+        // The path is not in storage.
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof(),
+                &root(),
+                commitment_path(2),
+                vec![1; 32]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_sequence_membership() {
+        let client_state = setup();
+        let path = Path::SeqSend(SeqSendPath::new(&PortId::transfer(), &ChannelId::new(0)));
+        env::storage_write(
+            &path.to_string().into_bytes(),
+            &near_sdk::borsh::to_vec(&Sequence::from(5)).unwrap(),
+        );
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof(),
+                &root(),
+                path.clone(),
+                5u64.to_be_bytes().to_vec()
+            )
+            .is_ok());
+        // The sequences are proved in big endian bytes rather than the stored borsh bytes.
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof(),
+                &root(),
+                path,
+                5u64.to_le_bytes().to_vec()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_non_membership() {
+        let client_state = setup();
+        let receipt_path = Path::Receipt(ReceiptPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(1),
+        ));
+        assert!(client_state
+            .verify_non_membership(&prefix(), &proof(), &root(), receipt_path.clone())
+            .is_ok());
+        env::storage_write(&receipt_path.to_string().into_bytes(), &[0]);
+        assert!(client_state
+            .verify_non_membership(&prefix(), &proof(), &root(), receipt_path)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_proof_height() {
+        let client_state = setup();
+        assert!(client_state
+            .validate_proof_height(Height::new(0, 100).unwrap())
+            .is_ok());
+        assert!(client_state
+            .validate_proof_height(Height::new(0, 101).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_localhost_client_can_not_be_updated() {
+        let client_state = setup();
+        let mut store = NearIbcStore::new();
+        assert!(client_state
+            .update_state(
+                &mut store,
+                &super::super::localhost_client_id(),
+                Any {
+                    type_url: String::new(),
+                    value: vec![],
+                }
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use super::LOCALHOST_CONSENSUS_STATE_TYPE_URL;
+use crate::prelude::*;
+use ibc::{
+    core::{client::types::error::ClientError, commitment_types::commitment::CommitmentRoot},
+    primitives::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, Protobuf};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+/// The consensus state of the localhost client, which is built from the current
+/// timestamp of the host chain and is never stored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalhostConsensusState {
+    /// The timestamp of the host chain, in nanoseconds.
+    pub timestamp: u64,
+    /// The commitment root is not used by the localhost client.
+    root: CommitmentRoot,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawLocalhostConsensusState {
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+}
+
+impl LocalhostConsensusState {
+    pub fn new(timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            root: CommitmentRoot::from_bytes(&[]),
+        }
+    }
+    ///
+    pub fn root(&self) -> &CommitmentRoot {
+        &self.root
+    }
+    ///
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::from_nanoseconds(self.timestamp).unwrap_or(Timestamp::none())
+    }
+}
+
+impl Protobuf<RawLocalhostConsensusState> for LocalhostConsensusState {}
+
+impl TryFrom<RawLocalhostConsensusState> for LocalhostConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawLocalhostConsensusState) -> Result<Self, Self::Error> {
+        Ok(Self::new(raw.timestamp))
+    }
+}
+
+impl From<LocalhostConsensusState> for RawLocalhostConsensusState {
+    fn from(value: LocalhostConsensusState) -> Self {
+        RawLocalhostConsensusState {
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+impl From<LocalhostConsensusState> for Any {
+    fn from(value: LocalhostConsensusState) -> Self {
+        Any {
+            type_url: LOCALHOST_CONSENSUS_STATE_TYPE_URL.to_string(),
+            value: Protobuf::<RawLocalhostConsensusState>::encode_vec(value),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! The localhost client (ICS-09).
+//!
+//! The localhost client is used for the connections between the modules on NEAR protocol,
+//! e.g. sending tokens from the transfer module to the interchain account host module,
+//! without a relayer proving the state against a remote chain.
+//!
+//! The client state, consensus state and the sentinel connection `connection-localhost`
+//! of the localhost client are not stored, they are built by the validation context
+//! from the current state of the host chain. And the proofs are verified by reading
+//! the IBC state in storage of this contract directly.
+use crate::prelude::*;
+use core::{str::FromStr, time::Duration};
+use ibc::core::{
+    commitment_types::commitment::CommitmentPrefix,
+    connection::types::{
+        version::get_compatible_versions, ConnectionEnd, Counterparty, State as ConnectionState,
+    },
+    host::types::{
+        identifiers::{ClientId, ConnectionId, Sequence},
+        path::Path,
+    },
+};
+use near_sdk::{borsh::BorshDeserialize, env};
+
+pub mod client_state;
+pub mod consensus_state;
+
+pub const LOCALHOST_CLIENT_TYPE: &str = "09-localhost";
+/// The client id of the localhost client, which is the same as the client type.
+pub const LOCALHOST_CLIENT_ID: &str = "09-localhost";
+pub const LOCALHOST_CONNECTION_ID: &str = "connection-localhost";
+pub const LOCALHOST_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.localhost.v2.ClientState";
+pub const LOCALHOST_CONSENSUS_STATE_TYPE_URL: &str =
+    "/ibc.lightclients.localhost.v2.ConsensusState";
+
+pub fn localhost_client_id() -> ClientId {
+    ClientId::from_str(LOCALHOST_CLIENT_ID).unwrap()
+}
+
+pub fn localhost_connection_id() -> ConnectionId {
+    ConnectionId::from_str(LOCALHOST_CONNECTION_ID).unwrap()
+}
+
+/// Get the sentinel connection end of the localhost client, which is always open
+/// and its counterparty is itself.
+pub fn localhost_connection_end(prefix: CommitmentPrefix) -> ConnectionEnd {
+    ConnectionEnd::new(
+        ConnectionState::Open,
+        localhost_client_id(),
+        Counterparty::new(
+            localhost_client_id(),
+            Some(localhost_connection_id()),
+            prefix,
+        ),
+        get_compatible_versions(),
+        Duration::ZERO,
+    )
+    .expect("Invalid localhost connection end.")
+}
+
+/// Get the value of the given path in storage, in the format which is used
+/// in the proof verification of `ibc-rs`.
+pub fn stored_value_of(path: &Path) -> Option<Vec<u8>> {
+    let value = env::storage_read(&path.to_string().into_bytes())?;
+    match path {
+        // The sequences are stored in borsh format, but proved in big endian bytes.
+        Path::SeqSend(_) | Path::SeqRecv(_) | Path::SeqAck(_) => Sequence::try_from_slice(&value)
+            .ok()
+            .map(|sequence| u64::from(sequence).to_be_bytes().to_vec()),
+        _ => Some(value),
+    }
+}
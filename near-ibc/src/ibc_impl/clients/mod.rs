@@ -0,0 +1,4 @@
+pub mod grandpa;
+pub mod localhost;
+pub mod solomachine;
+pub mod wasm;
@@ -0,0 +1,779 @@
+use super::{
+    consensus_state::SoloMachineConsensusState, decode_public_key, record_verified_proof,
+    take_verified_proofs, verified_proof_count, verify_signature, SENTINEL_HEADER_PATH,
+    SOLO_MACHINE_CLIENT_TYPE, SOLO_MACHINE_HEADER_TYPE_URL, SOLO_MACHINE_MISBEHAVIOUR_TYPE_URL,
+};
+use crate::{
+    context::NearIbcStore,
+    ibc_impl::core::{client_state::AnyClientState, consensus_state::AnyConsensusState},
+    prelude::*,
+};
+use core::str::FromStr;
+use ibc::core::{
+    client::context::{
+        client_state::{ClientStateCommon, ClientStateExecution, ClientStateValidation},
+        types::{error::ClientError, Height, Status, UpdateKind},
+        ClientExecutionContext,
+    },
+    commitment_types::commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
+    handler::types::error::ContextError,
+    host::{
+        types::{
+            identifiers::{ClientId, ClientType},
+            path::{ClientConsensusStatePath, ClientStatePath, Path},
+        },
+        ValidationContext,
+    },
+};
+use ibc_proto::{
+    google::protobuf::Any,
+    ibc::lightclients::solomachine::v3::{
+        ClientState as RawSmClientState, Header as RawSmHeader, HeaderData as RawSmHeaderData,
+        Misbehaviour as RawSmMisbehaviour, SignBytes, SignatureAndData, TimestampedSignatureData,
+    },
+    Protobuf,
+};
+use near_sdk::log;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoloMachineClientState {
+    /// The sequence of the solo machine, which is the revision height of the client.
+    pub sequence: u64,
+    pub is_frozen: bool,
+    pub consensus_state: SoloMachineConsensusState,
+    /// The id of the client, which is set when the client state is loaded from the store,
+    /// for recording the verified proofs of the client.
+    #[serde(skip)]
+    pub client_id: Option<ClientId>,
+}
+
+fn client_error(description: &str) -> ClientError {
+    ClientError::ClientSpecific {
+        description: description.to_string(),
+    }
+}
+
+fn context_error(e: ContextError) -> ClientError {
+    ClientError::Other {
+        description: e.to_string(),
+    }
+}
+
+impl SoloMachineClientState {
+    //
+    fn decode_header(client_message: Any) -> Result<RawSmHeader, ClientError> {
+        if client_message.type_url != SOLO_MACHINE_HEADER_TYPE_URL {
+            return Err(ClientError::InvalidUpdateClientMessage);
+        }
+        RawSmHeader::decode(client_message.value.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))
+    }
+    //
+    fn decode_misbehaviour(client_message: Any) -> Result<RawSmMisbehaviour, ClientError> {
+        if client_message.type_url != SOLO_MACHINE_MISBEHAVIOUR_TYPE_URL {
+            return Err(ClientError::InvalidUpdateClientMessage);
+        }
+        RawSmMisbehaviour::decode(client_message.value.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))
+    }
+    /// Verify the header is signed by the current public key and return
+    /// the new consensus state in it.
+    fn verify_header(&self, header: RawSmHeader) -> Result<SoloMachineConsensusState, ClientError> {
+        if header.timestamp < self.consensus_state.timestamp {
+            return Err(client_error(
+                "header timestamp is less than the consensus state timestamp",
+            ));
+        }
+        let new_public_key = header
+            .new_public_key
+            .ok_or_else(|| client_error("missing new public key"))?;
+        let sign_bytes = SignBytes {
+            sequence: self.sequence,
+            timestamp: header.timestamp,
+            diversifier: self.consensus_state.diversifier.clone(),
+            path: SENTINEL_HEADER_PATH.as_bytes().to_vec(),
+            data: RawSmHeaderData {
+                new_pub_key: Some(new_public_key.clone()),
+                new_diversifier: header.new_diversifier.clone(),
+            }
+            .encode_to_vec(),
+        };
+        verify_signature(
+            &self.consensus_state.public_key,
+            &sign_bytes,
+            &header.signature,
+        )?;
+        Ok(SoloMachineConsensusState::new(
+            decode_public_key(&new_public_key)?,
+            header.new_diversifier,
+            header.timestamp,
+        ))
+    }
+    /// Verify the misbehaviour contains two different messages signed by the current
+    /// public key at the same sequence.
+    fn verify_misbehaviour(&self, misbehaviour: RawSmMisbehaviour) -> Result<(), ClientError> {
+        if misbehaviour.sequence == 0 {
+            return Err(client_error("sequence of misbehaviour must not be zero"));
+        }
+        let signature_one = misbehaviour
+            .signature_one
+            .ok_or_else(|| client_error("missing signature one"))?;
+        let signature_two = misbehaviour
+            .signature_two
+            .ok_or_else(|| client_error("missing signature two"))?;
+        if signature_one.signature == signature_two.signature {
+            return Err(client_error(
+                "signatures of misbehaviour must not be the same",
+            ));
+        }
+        for signature_and_data in [signature_one, signature_two] {
+            self.verify_signature_and_data(misbehaviour.sequence, signature_and_data)?;
+        }
+        Ok(())
+    }
+    //
+    fn verify_signature_and_data(
+        &self,
+        sequence: u64,
+        signature_and_data: SignatureAndData,
+    ) -> Result<(), ClientError> {
+        let sign_bytes = SignBytes {
+            sequence,
+            timestamp: signature_and_data.timestamp,
+            diversifier: self.consensus_state.diversifier.clone(),
+            path: signature_and_data.path,
+            data: signature_and_data.data,
+        };
+        verify_signature(
+            &self.consensus_state.public_key,
+            &sign_bytes,
+            &signature_and_data.signature,
+        )
+    }
+    /// Verify the proof is a signature of the current public key over the given path
+    /// and value (empty for non-membership), at the current sequence plus the number of
+    /// the proofs verified before in the current message.
+    ///
+    /// The proof height is checked by `validate_proof_height` before the verification,
+    /// and the verified proof is recorded for increasing the sequence.
+    fn verify_signed_value(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        if self.is_frozen {
+            return Err(client_error("solo machine client is frozen"));
+        }
+        let client_id = self
+            .client_id
+            .as_ref()
+            .ok_or_else(|| client_error("unknown client id of solo machine client state"))?;
+        let proof: Vec<u8> = proof.clone().into();
+        let timestamped_signature = TimestampedSignatureData::decode(proof.as_slice())
+            .map_err(|e| client_error(e.to_string().as_str()))?;
+        if timestamped_signature.timestamp < self.consensus_state.timestamp {
+            return Err(client_error(
+                "proof timestamp is less than the consensus state timestamp",
+            ));
+        }
+        let sign_bytes = SignBytes {
+            sequence: self.sequence + verified_proof_count(client_id),
+            timestamp: timestamped_signature.timestamp,
+            diversifier: self.consensus_state.diversifier.clone(),
+            path: merkle_path_string(prefix, &path).into_bytes(),
+            data: value,
+        };
+        verify_signature(
+            &self.consensus_state.public_key,
+            &sign_bytes,
+            &timestamped_signature.signature_data,
+        )?;
+        record_verified_proof(client_id, timestamped_signature.timestamp);
+        Ok(())
+    }
+}
+
+/// The string format of the merkle path of `ibc-go`, in which every key is escaped.
+fn merkle_path_string(prefix: &CommitmentPrefix, path: &Path) -> String {
+    format!(
+        "/{}/{}",
+        String::from_utf8_lossy(prefix.as_bytes()).replace('/', "%2F"),
+        path.to_string().replace('/', "%2F")
+    )
+}
+
+impl Protobuf<RawSmClientState> for SoloMachineClientState {}
+
+impl TryFrom<RawSmClientState> for SoloMachineClientState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawSmClientState) -> Result<Self, Self::Error> {
+        if raw.sequence == 0 {
+            return Err(client_error("sequence must not be zero"));
+        }
+        Ok(Self {
+            sequence: raw.sequence,
+            is_frozen: raw.is_frozen,
+            consensus_state: raw
+                .consensus_state
+                .ok_or_else(|| client_error("missing consensus state"))?
+                .try_into()?,
+            client_id: None,
+        })
+    }
+}
+
+impl From<SoloMachineClientState> for RawSmClientState {
+    fn from(value: SoloMachineClientState) -> Self {
+        RawSmClientState {
+            sequence: value.sequence,
+            is_frozen: value.is_frozen,
+            consensus_state: Some(value.consensus_state.into()),
+        }
+    }
+}
+
+impl ClientStateValidation<NearIbcStore> for SoloMachineClientState {
+    fn verify_client_message(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        client_message: Any,
+        update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        match update_kind {
+            UpdateKind::UpdateClient => self
+                .verify_header(Self::decode_header(client_message)?)
+                .map(|_| ()),
+            UpdateKind::SubmitMisbehaviour => {
+                self.verify_misbehaviour(Self::decode_misbehaviour(client_message)?)
+            }
+        }
+    }
+
+    fn check_for_misbehaviour(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        update_kind: &UpdateKind,
+    ) -> Result<bool, ClientError> {
+        // The misbehaviour is already verified in `verify_client_message`.
+        Ok(matches!(update_kind, UpdateKind::SubmitMisbehaviour))
+    }
+
+    fn status(&self, _ctx: &NearIbcStore, _client_id: &ClientId) -> Result<Status, ClientError> {
+        if self.is_frozen {
+            Ok(Status::Frozen)
+        } else {
+            Ok(Status::Active)
+        }
+    }
+}
+
+impl ClientStateCommon for SoloMachineClientState {
+    fn verify_consensus_state(&self, consensus_state: Any) -> Result<(), ClientError> {
+        SoloMachineConsensusState::try_from(consensus_state).map(|_| ())
+    }
+
+    fn client_type(&self) -> ClientType {
+        ClientType::from_str(SOLO_MACHINE_CLIENT_TYPE).expect("Invalid client type.")
+    }
+
+    fn latest_height(&self) -> Height {
+        Height::new(0, self.sequence).expect("Invalid sequence of solo machine.")
+    }
+
+    fn validate_proof_height(&self, proof_height: Height) -> Result<(), ClientError> {
+        if proof_height != self.latest_height() {
+            return Err(ClientError::InvalidProofHeight {
+                latest_height: self.latest_height(),
+                proof_height,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_upgrade_client(
+        &self,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+        _proof_upgrade_client: CommitmentProofBytes,
+        _proof_upgrade_consensus_state: CommitmentProofBytes,
+        _root: &CommitmentRoot,
+    ) -> Result<(), ClientError> {
+        Err(client_error("solo machine client can not be upgraded"))
+    }
+
+    fn verify_membership(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        _root: &CommitmentRoot,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        self.verify_signed_value(prefix, proof, path, value)
+    }
+
+    fn verify_non_membership(
+        &self,
+        prefix: &CommitmentPrefix,
+        proof: &CommitmentProofBytes,
+        _root: &CommitmentRoot,
+        path: Path,
+    ) -> Result<(), ClientError> {
+        self.verify_signed_value(prefix, proof, path, Vec::new())
+    }
+}
+
+impl ClientStateExecution<NearIbcStore> for SoloMachineClientState {
+    fn initialise(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        consensus_state: Any,
+    ) -> Result<(), ClientError> {
+        let consensus_state = SoloMachineConsensusState::try_from(consensus_state)?;
+        let height = self.latest_height();
+        ctx.store_client_state(
+            ClientStatePath(client_id.clone()),
+            AnyClientState::SoloMachine(self.clone()),
+        )
+        .map_err(context_error)?;
+        ctx.store_consensus_state(
+            ClientConsensusStatePath::new(
+                client_id.clone(),
+                height.revision_number(),
+                height.revision_height(),
+            ),
+            AnyConsensusState::SoloMachine(consensus_state),
+        )
+        .map_err(context_error)?;
+        store_update_time_and_height(ctx, client_id, height)
+    }
+
+    fn update_state(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        header: Any,
+    ) -> Result<Vec<Height>, ClientError> {
+        let consensus_state = self.verify_header(Self::decode_header(header)?)?;
+        let new_client_state = SoloMachineClientState {
+            sequence: self.sequence + 1,
+            is_frozen: false,
+            consensus_state,
+            client_id: None,
+        };
+        let height = new_client_state.latest_height();
+        store_client_and_consensus_state(ctx, client_id, new_client_state)?;
+        Ok(vec![height])
+    }
+
+    fn update_state_on_misbehaviour(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        let frozen_client_state = SoloMachineClientState {
+            is_frozen: true,
+            ..self.clone()
+        };
+        ctx.store_client_state(
+            ClientStatePath(client_id.clone()),
+            AnyClientState::SoloMachine(frozen_client_state),
+        )
+        .map_err(context_error)
+    }
+
+    fn update_state_on_upgrade(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+    ) -> Result<Height, ClientError> {
+        Err(client_error("solo machine client can not be upgraded"))
+    }
+}
+
+/// Store the given client state, and the consensus state in it at the latest height.
+fn store_client_and_consensus_state(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    client_state: SoloMachineClientState,
+) -> Result<(), ClientError> {
+    let height = client_state.latest_height();
+    let consensus_state = client_state.consensus_state.clone();
+    ctx.store_client_state(
+        ClientStatePath(client_id.clone()),
+        AnyClientState::SoloMachine(client_state),
+    )
+    .map_err(context_error)?;
+    ctx.store_consensus_state(
+        ClientConsensusStatePath::new(
+            client_id.clone(),
+            height.revision_number(),
+            height.revision_height(),
+        ),
+        AnyConsensusState::SoloMachine(consensus_state),
+    )
+    .map_err(context_error)?;
+    store_update_time_and_height(ctx, client_id, height)
+}
+
+impl NearIbcStore {
+    /// Increase the sequences of the solo machine clients by the number of the proofs
+    /// verified in processing the last message if it succeeded, otherwise discard them.
+    pub fn commit_solo_machine_proofs(&mut self, succeeded: bool) {
+        for verified_proofs in take_verified_proofs() {
+            if !succeeded {
+                continue;
+            }
+            let client_id = verified_proofs.client_id;
+            let mut client_state = match self.client_state(&client_id) {
+                Ok(AnyClientState::SoloMachine(client_state)) => client_state,
+                _ => continue,
+            };
+            client_state.sequence += verified_proofs.count;
+            client_state.consensus_state.timestamp = client_state
+                .consensus_state
+                .timestamp
+                .max(verified_proofs.timestamp);
+            client_state.client_id = None;
+            let sequence = client_state.sequence;
+            match store_client_and_consensus_state(self, &client_id, client_state) {
+                Ok(()) => log!(
+                    "Sequence of solo machine client '{}' is increased to {}.",
+                    client_id,
+                    sequence
+                ),
+                Err(e) => log!(
+                    "Failed to increase sequence of solo machine client '{}': {}",
+                    client_id,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+fn store_update_time_and_height(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    height: Height,
+) -> Result<(), ClientError> {
+    let host_timestamp = ValidationContext::host_timestamp(ctx).map_err(context_error)?;
+    let host_height = ValidationContext::host_height(ctx).map_err(context_error)?;
+    ctx.store_update_time(client_id.clone(), height, host_timestamp)
+        .map_err(context_error)?;
+    ctx.store_update_height(client_id.clone(), height, host_height)
+        .map_err(context_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{encode_public_key, RawSignatureData, RawSingleSignature},
+        *,
+    };
+    use ed25519_consensus::SigningKey;
+    use ibc::core::host::types::{
+        identifiers::{ChannelId, PortId, Sequence},
+        path::CommitmentPath,
+    };
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    const DIVERSIFIER: &str = "diversifier";
+    const TIMESTAMP: u64 = 1_000_000_000;
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from([seed; 32])
+    }
+
+    fn public_key_of(key: &SigningKey) -> Vec<u8> {
+        key.verification_key().to_bytes().to_vec()
+    }
+
+    fn sign(key: &SigningKey, sign_bytes: &SignBytes) -> Vec<u8> {
+        RawSignatureData {
+            single: Some(RawSingleSignature {
+                mode: 1,
+                signature: key.sign(&sign_bytes.encode_to_vec()).to_bytes().to_vec(),
+            }),
+        }
+        .encode_to_vec()
+    }
+
+    fn client_id() -> ClientId {
+        ClientId::new(ClientType::from_str(SOLO_MACHINE_CLIENT_TYPE).unwrap(), 0).unwrap()
+    }
+
+    fn client_state_of(key: &SigningKey, sequence: u64) -> SoloMachineClientState {
+        SoloMachineClientState {
+            sequence,
+            is_frozen: false,
+            consensus_state: SoloMachineConsensusState::new(
+                public_key_of(key),
+                DIVERSIFIER.to_string(),
+                TIMESTAMP,
+            ),
+            client_id: Some(client_id()),
+        }
+    }
+
+    fn header_of(
+        key: &SigningKey,
+        sequence: u64,
+        timestamp: u64,
+        new_key: &SigningKey,
+    ) -> RawSmHeader {
+        let new_public_key = encode_public_key(&public_key_of(new_key));
+        let sign_bytes = SignBytes {
+            sequence,
+            timestamp,
+            diversifier: DIVERSIFIER.to_string(),
+            path: SENTINEL_HEADER_PATH.as_bytes().to_vec(),
+            data: RawSmHeaderData {
+                new_pub_key: Some(new_public_key.clone()),
+                new_diversifier: DIVERSIFIER.to_string(),
+            }
+            .encode_to_vec(),
+        };
+        RawSmHeader {
+            timestamp,
+            signature: sign(key, &sign_bytes),
+            new_public_key: Some(new_public_key),
+            new_diversifier: DIVERSIFIER.to_string(),
+        }
+    }
+
+    fn signature_and_data_of(key: &SigningKey, sequence: u64, data: &[u8]) -> SignatureAndData {
+        let path = b"/ibc/commitments%2Fports%2Ftransfer".to_vec();
+        let sign_bytes = SignBytes {
+            sequence,
+            timestamp: TIMESTAMP,
+            diversifier: DIVERSIFIER.to_string(),
+            path: path.clone(),
+            data: data.to_vec(),
+        };
+        SignatureAndData {
+            signature: sign(key, &sign_bytes),
+            path,
+            data: data.to_vec(),
+            timestamp: TIMESTAMP,
+        }
+    }
+
+    fn prefix() -> CommitmentPrefix {
+        CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap()
+    }
+
+    fn commitment_path() -> Path {
+        Path::Commitment(CommitmentPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(1),
+        ))
+    }
+
+    fn proof_of(
+        key: &SigningKey,
+        sequence: u64,
+        path: &Path,
+        value: &[u8],
+    ) -> CommitmentProofBytes {
+        let sign_bytes = SignBytes {
+            sequence,
+            timestamp: TIMESTAMP,
+            diversifier: DIVERSIFIER.to_string(),
+            path: merkle_path_string(&prefix(), path).into_bytes(),
+            data: value.to_vec(),
+        };
+        CommitmentProofBytes::try_from(
+            TimestampedSignatureData {
+                signature_data: sign(key, &sign_bytes),
+                timestamp: TIMESTAMP,
+            }
+            .encode_to_vec(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_header() {
+        testing_env!(VMContextBuilder::new().build());
+        let (key, new_key) = (signing_key(1), signing_key(2));
+        let client_state = client_state_of(&key, 5);
+        let consensus_state = client_state
+            .verify_header(header_of(&key, 5, TIMESTAMP + 1, &new_key))
+            .unwrap();
+        assert_eq!(consensus_state.public_key, public_key_of(&new_key));
+        assert_eq!(consensus_state.timestamp, TIMESTAMP + 1);
+        // Signed at another sequence.
+        assert!(client_state
+            .verify_header(header_of(&key, 4, TIMESTAMP + 1, &new_key))
+            .is_err());
+        // Signed by another key.
+        assert!(client_state
+            .verify_header(header_of(&new_key, 5, TIMESTAMP + 1, &new_key))
+            .is_err());
+        // The signed timestamp is tampered.
+        let mut header = header_of(&key, 5, TIMESTAMP + 1, &new_key);
+        header.timestamp = TIMESTAMP + 2;
+        assert!(client_state.verify_header(header).is_err());
+        // The timestamp is less than the consensus state.
+        assert!(client_state
+            .verify_header(header_of(&key, 5, TIMESTAMP - 1, &new_key))
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_misbehaviour() {
+        testing_env!(VMContextBuilder::new().build());
+        let key = signing_key(1);
+        let client_state = client_state_of(&key, 5);
+        let misbehaviour = RawSmMisbehaviour {
+            sequence: 8,
+            signature_one: Some(signature_and_data_of(&key, 8, b"one")),
+            signature_two: Some(signature_and_data_of(&key, 8, b"two")),
+        };
+        assert!(client_state
+            .verify_misbehaviour(misbehaviour.clone())
+            .is_ok());
+        // The same signature.
+        let mut same = misbehaviour.clone();
+        same.signature_two = same.signature_one.clone();
+        assert!(client_state.verify_misbehaviour(same).is_err());
+        // The signed data is tampered.
+        let mut tampered = misbehaviour.clone();
+        tampered.signature_two.as_mut().unwrap().data = b"three".to_vec();
+        assert!(client_state.verify_misbehaviour(tampered).is_err());
+        // Signed at another sequence.
+        let mut other_sequence = misbehaviour.clone();
+        other_sequence.signature_two = Some(signature_and_data_of(&key, 9, b"two"));
+        assert!(client_state.verify_misbehaviour(other_sequence).is_err());
+        // Signed by another key.
+        let mut other_key = misbehaviour;
+        other_key.signature_two = Some(signature_and_data_of(&signing_key(2), 8, b"two"));
+        assert!(client_state.verify_misbehaviour(other_key).is_err());
+    }
+
+    #[test]
+    fn test_verify_membership_and_increase_sequence() {
+        testing_env!(VMContextBuilder::new().build());
+        let key = signing_key(1);
+        let mut store = NearIbcStore::new();
+        let client_state = client_state_of(&key, 1);
+        client_state
+            .initialise(
+                &mut store,
+                &client_id(),
+                client_state.consensus_state.clone().into(),
+            )
+            .unwrap();
+        let load = |store: &NearIbcStore| match ValidationContext::client_state(store, &client_id())
+            .unwrap()
+        {
+            AnyClientState::SoloMachine(client_state) => client_state,
+            _ => panic!("unexpected client state"),
+        };
+        let client_state = load(&store);
+        assert_eq!(client_state.client_id, Some(client_id()));
+        let root = client_state.consensus_state.root().clone();
+        let path = commitment_path();
+        // The tampered value, the other sequence and the other key are rejected.
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof_of(&key, 1, &path, b"value"),
+                &root,
+                path.clone(),
+                b"other".to_vec()
+            )
+            .is_err());
+        assert!(client_state
+            .verify_membership(
+                &prefix(),
+                &proof_of(&key, 2, &path, b"value"),
+                &root,
+                path.clone(),
+                b"value".to_vec()
+            )
+            .is_err());
+        assert!(client_state
+            .verify_non_membership(
+                &prefix(),
+                &proof_of(&signing_key(2), 1, &path, b""),
+                &root,
+                path.clone(),
+            )
+            .is_err());
+        assert!(solomachine_verified_proofs().is_empty());
+        // The proofs of membership and non-membership at consecutive sequences.
+        let proof = proof_of(&key, 1, &path, b"value");
+        client_state
+            .verify_membership(&prefix(), &proof, &root, path.clone(), b"value".to_vec())
+            .unwrap();
+        assert!(client_state
+            .verify_non_membership(
+                &prefix(),
+                &proof_of(&key, 1, &path, b""),
+                &root,
+                path.clone(),
+            )
+            .is_err());
+        client_state
+            .verify_non_membership(
+                &prefix(),
+                &proof_of(&key, 2, &path, b""),
+                &root,
+                path.clone(),
+            )
+            .unwrap();
+        assert!(client_state
+            .validate_proof_height(Height::new(0, 2).unwrap())
+            .is_err());
+        // The proofs of a failed message are discarded.
+        store.commit_solo_machine_proofs(false);
+        assert_eq!(load(&store).sequence, 1);
+        // The sequence is increased by the number of verified proofs.
+        client_state
+            .verify_membership(&prefix(), &proof, &root, path.clone(), b"value".to_vec())
+            .unwrap();
+        client_state
+            .verify_non_membership(
+                &prefix(),
+                &proof_of(&key, 2, &path, b""),
+                &root,
+                path.clone(),
+            )
+            .unwrap();
+        store.commit_solo_machine_proofs(true);
+        let client_state = load(&store);
+        assert_eq!(client_state.sequence, 3);
+        assert!(ValidationContext::consensus_state(
+            &store,
+            &ClientConsensusStatePath::new(client_id(), 0, 3)
+        )
+        .is_ok());
+        // The proof can not be replayed.
+        assert!(client_state
+            .validate_proof_height(Height::new(0, 1).unwrap())
+            .is_err());
+        assert!(client_state
+            .verify_membership(&prefix(), &proof, &root, path, b"value".to_vec())
+            .is_err());
+    }
+
+    fn solomachine_verified_proofs() -> Vec<super::super::VerifiedProofs> {
+        super::super::get_verified_proofs()
+    }
+}
@@ -0,0 +1,103 @@
+use super::{decode_public_key, encode_public_key, SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL};
+use crate::prelude::*;
+use ibc::{
+    core::{client::types::error::ClientError, commitment_types::commitment::CommitmentRoot},
+    primitives::Timestamp,
+};
+use ibc_proto::{
+    google::protobuf::Any,
+    ibc::lightclients::solomachine::v3::ConsensusState as RawSmConsensusState, Protobuf,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoloMachineConsensusState {
+    /// The ed25519 public key of the solo machine.
+    pub public_key: Vec<u8>,
+    /// The diversifier which allows the same public key to be re-used across
+    /// different solo machine clients.
+    pub diversifier: String,
+    /// The timestamp of the solo machine, in nanoseconds.
+    pub timestamp: u64,
+    /// The commitment root is not used by the proofs of solo machine,
+    /// it is the public key for the interface of `ConsensusState`.
+    root: CommitmentRoot,
+}
+
+impl SoloMachineConsensusState {
+    pub fn new(public_key: Vec<u8>, diversifier: String, timestamp: u64) -> Self {
+        Self {
+            root: CommitmentRoot::from_bytes(&public_key),
+            public_key,
+            diversifier,
+            timestamp,
+        }
+    }
+    ///
+    pub fn root(&self) -> &CommitmentRoot {
+        &self.root
+    }
+    ///
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::from_nanoseconds(self.timestamp).unwrap_or(Timestamp::none())
+    }
+}
+
+impl Protobuf<RawSmConsensusState> for SoloMachineConsensusState {}
+
+impl TryFrom<RawSmConsensusState> for SoloMachineConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawSmConsensusState) -> Result<Self, Self::Error> {
+        let public_key =
+            decode_public_key(&raw.public_key.ok_or_else(|| ClientError::ClientSpecific {
+                description: "missing public key".to_string(),
+            })?)?;
+        if raw.timestamp == 0 {
+            return Err(ClientError::ClientSpecific {
+                description: "timestamp must not be zero".to_string(),
+            });
+        }
+        Ok(Self::new(public_key, raw.diversifier, raw.timestamp))
+    }
+}
+
+impl From<SoloMachineConsensusState> for RawSmConsensusState {
+    fn from(value: SoloMachineConsensusState) -> Self {
+        RawSmConsensusState {
+            public_key: Some(encode_public_key(&value.public_key)),
+            diversifier: value.diversifier,
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+impl Protobuf<Any> for SoloMachineConsensusState {}
+
+impl TryFrom<Any> for SoloMachineConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: Any) -> Result<Self, Self::Error> {
+        match raw.type_url.as_str() {
+            SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL => {
+                Protobuf::<RawSmConsensusState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })
+            }
+            _ => Err(ClientError::UnknownConsensusStateType {
+                consensus_state_type: raw.type_url,
+            }),
+        }
+    }
+}
+
+impl From<SoloMachineConsensusState> for Any {
+    fn from(value: SoloMachineConsensusState) -> Self {
+        Any {
+            type_url: SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL.to_string(),
+            value: Protobuf::<RawSmConsensusState>::encode_vec(value),
+        }
+    }
+}
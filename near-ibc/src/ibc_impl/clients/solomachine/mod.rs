@@ -0,0 +1,175 @@
+//! The solo machine light client (ICS-06).
+//!
+//! A solo machine is a standalone machine (e.g. a wallet or an off-chain process) which
+//! proves its state by signing it with an ed25519 key. The client tracks the current
+//! public key, diversifier and sequence of the solo machine, and the height of the client
+//! is `(0, sequence)`.
+//!
+//! The proofs of membership and non-membership are signatures of the current public key
+//! over the sequence, timestamp, diversifier, path and value, and the proof height must be
+//! the latest height of the client. As the interfaces of proof verification in `ibc-rs` can not
+//! change the client state, the verified proofs are recorded temporarily, and the sequence of
+//! the client is increased by the number of them after the message is processed successfully
+//! (refer to `NearIbcStore::commit_solo_machine_proofs`), so a signature can not be replayed.
+//! As in `ibc-go`, every verified proof takes a sequence, so the proofs in a message are signed
+//! at consecutive sequences from the current one, in the order they are verified.
+use crate::prelude::*;
+use ibc::core::{client::types::error::ClientError, host::types::identifiers::ClientId};
+use ibc_proto::{google::protobuf::Any, ibc::lightclients::solomachine::v3::SignBytes};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+};
+use prost::Message;
+
+pub mod client_state;
+pub mod consensus_state;
+
+pub const SOLO_MACHINE_CLIENT_TYPE: &str = "06-solomachine";
+pub const SOLO_MACHINE_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.solomachine.v3.ClientState";
+pub const SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL: &str =
+    "/ibc.lightclients.solomachine.v3.ConsensusState";
+pub const SOLO_MACHINE_HEADER_TYPE_URL: &str = "/ibc.lightclients.solomachine.v3.Header";
+pub const SOLO_MACHINE_MISBEHAVIOUR_TYPE_URL: &str =
+    "/ibc.lightclients.solomachine.v3.Misbehaviour";
+pub const ED25519_PUBLIC_KEY_TYPE_URL: &str = "/cosmos.crypto.ed25519.PubKey";
+/// The path in the sign bytes of a header.
+pub const SENTINEL_HEADER_PATH: &str = "solomachine:header";
+/// The storage key of the proofs verified in processing the current message.
+const STORAGE_KEY_FOR_VERIFIED_PROOFS: &[u8] = b"solo_machine_verified_proofs";
+
+/// The number of the proofs verified for a client in processing the current message,
+/// and the max timestamp of them.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct VerifiedProofs {
+    pub client_id: ClientId,
+    pub count: u64,
+    pub timestamp: u64,
+}
+
+/// Record a proof verified for the given client.
+pub fn record_verified_proof(client_id: &ClientId, timestamp: u64) {
+    let mut verified_proofs = get_verified_proofs();
+    match verified_proofs
+        .iter_mut()
+        .find(|proofs| proofs.client_id == *client_id)
+    {
+        Some(proofs) => {
+            proofs.count += 1;
+            proofs.timestamp = proofs.timestamp.max(timestamp);
+        }
+        None => verified_proofs.push(VerifiedProofs {
+            client_id: client_id.clone(),
+            count: 1,
+            timestamp,
+        }),
+    }
+    env::storage_write(
+        STORAGE_KEY_FOR_VERIFIED_PROOFS,
+        &borsh::to_vec(&verified_proofs).unwrap(),
+    );
+}
+
+/// Get the number of the proofs verified for the given client in processing the current message.
+pub fn verified_proof_count(client_id: &ClientId) -> u64 {
+    get_verified_proofs()
+        .iter()
+        .find(|proofs| proofs.client_id == *client_id)
+        .map_or(0, |proofs| proofs.count)
+}
+
+/// Get the proofs verified in processing the current message.
+pub fn get_verified_proofs() -> Vec<VerifiedProofs> {
+    env::storage_read(STORAGE_KEY_FOR_VERIFIED_PROOFS).map_or(vec![], |bytes| {
+        Vec::<VerifiedProofs>::try_from_slice(&bytes).unwrap()
+    })
+}
+
+/// Remove the records of the verified proofs, returns them.
+pub fn take_verified_proofs() -> Vec<VerifiedProofs> {
+    let verified_proofs = get_verified_proofs();
+    env::storage_remove(STORAGE_KEY_FOR_VERIFIED_PROOFS);
+    verified_proofs
+}
+
+/// The ed25519 public key of cosmos-sdk, `cosmos.crypto.ed25519.PubKey`.
+#[derive(Clone, PartialEq, Message)]
+struct RawEd25519PubKey {
+    #[prost(bytes = "vec", tag = "1")]
+    key: Vec<u8>,
+}
+
+/// The single signature variant of `cosmos.tx.signing.v1beta1.SignatureDescriptor.Data`,
+/// which is the only variant supported by this client.
+#[derive(Clone, PartialEq, Message)]
+struct RawSignatureData {
+    #[prost(message, optional, tag = "1")]
+    single: Option<RawSingleSignature>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct RawSingleSignature {
+    #[prost(int32, tag = "1")]
+    mode: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    signature: Vec<u8>,
+}
+
+fn client_error(description: String) -> ClientError {
+    ClientError::ClientSpecific { description }
+}
+
+/// Decode the ed25519 public key in the given `Any`.
+pub fn decode_public_key(raw: &Any) -> Result<Vec<u8>, ClientError> {
+    if raw.type_url != ED25519_PUBLIC_KEY_TYPE_URL {
+        return Err(client_error(format!(
+            "unsupported public key type: {}",
+            raw.type_url
+        )));
+    }
+    let public_key =
+        RawEd25519PubKey::decode(raw.value.as_slice()).map_err(|e| client_error(e.to_string()))?;
+    if public_key.key.len() != 32 {
+        return Err(client_error("invalid ed25519 public key".to_string()));
+    }
+    Ok(public_key.key)
+}
+
+/// Encode the given ed25519 public key to `Any`.
+pub fn encode_public_key(public_key: &[u8]) -> Any {
+    Any {
+        type_url: ED25519_PUBLIC_KEY_TYPE_URL.to_string(),
+        value: RawEd25519PubKey {
+            key: public_key.to_vec(),
+        }
+        .encode_to_vec(),
+    }
+}
+
+/// Verify the given signature data (encoded `SignatureDescriptor.Data`) over the sign bytes.
+pub fn verify_signature(
+    public_key: &[u8],
+    sign_bytes: &SignBytes,
+    signature_data: &[u8],
+) -> Result<(), ClientError> {
+    let signature = RawSignatureData::decode(signature_data)
+        .map_err(|e| client_error(e.to_string()))?
+        .single
+        .ok_or_else(|| client_error("only single signature is supported".to_string()))?
+        .signature;
+    let signature: &[u8; 64] = signature
+        .as_slice()
+        .try_into()
+        .map_err(|_| client_error("invalid ed25519 signature".to_string()))?;
+    let public_key: &[u8; 32] = public_key
+        .try_into()
+        .map_err(|_| client_error("invalid ed25519 public key".to_string()))?;
+    if env::ed25519_verify(signature, &sign_bytes.encode_to_vec(), public_key) {
+        Ok(())
+    } else {
+        Err(client_error(
+            "failed to verify solo machine signature".to_string(),
+        ))
+    }
+}
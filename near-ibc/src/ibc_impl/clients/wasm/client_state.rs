@@ -0,0 +1,251 @@
+use super::{
+    commitment_root_of, consensus_state::WasmConsensusState, get_light_client_contract,
+    get_verified_value, store_states, WASM_CLIENT_TYPE,
+};
+use crate::{context::NearIbcStore, prelude::*};
+use core::str::FromStr;
+use ibc::core::{
+    client::context::{
+        client_state::{ClientStateCommon, ClientStateExecution, ClientStateValidation},
+        types::{error::ClientError, Height, Status, UpdateKind},
+    },
+    commitment_types::commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
+    host::types::{
+        identifiers::{ClientId, ClientType},
+        path::Path,
+    },
+};
+use ibc_proto::{google::protobuf::Any, ibc::core::client::v1::Height as RawHeight, Protobuf};
+use near_sdk::env;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmClientState {
+    /// The opaque data of the client state, which is interpreted
+    /// by the light client contract.
+    pub data: Vec<u8>,
+    /// The code hash of the light client contract.
+    pub checksum: Vec<u8>,
+    pub latest_height: Height,
+    /// Whether the client is frozen by the light client contract.
+    pub is_frozen: bool,
+}
+
+/// The client state of `08-wasm`, with the frozen flag which is needed by this contract.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawWasmClientState {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub checksum: Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub latest_height: Option<RawHeight>,
+    #[prost(bool, tag = "4")]
+    pub is_frozen: bool,
+}
+
+pub fn client_type() -> ClientType {
+    ClientType::from_str(WASM_CLIENT_TYPE).expect("Invalid client type.")
+}
+
+fn client_error(description: &str) -> ClientError {
+    ClientError::ClientSpecific {
+        description: description.to_string(),
+    }
+}
+
+impl Protobuf<RawWasmClientState> for WasmClientState {}
+
+impl TryFrom<RawWasmClientState> for WasmClientState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawWasmClientState) -> Result<Self, Self::Error> {
+        if raw.checksum.len() != 32 {
+            return Err(client_error("invalid checksum"));
+        }
+        Ok(Self {
+            data: raw.data,
+            checksum: raw.checksum,
+            latest_height: raw
+                .latest_height
+                .ok_or_else(|| client_error("missing latest height"))?
+                .try_into()?,
+            is_frozen: raw.is_frozen,
+        })
+    }
+}
+
+impl From<WasmClientState> for RawWasmClientState {
+    fn from(value: WasmClientState) -> Self {
+        RawWasmClientState {
+            data: value.data,
+            checksum: value.checksum,
+            latest_height: Some(value.latest_height.into()),
+            is_frozen: value.is_frozen,
+        }
+    }
+}
+
+impl ClientStateValidation<NearIbcStore> for WasmClientState {
+    fn verify_client_message(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        Err(client_error(
+            "08-wasm clients are updated by their light client contracts",
+        ))
+    }
+
+    fn check_for_misbehaviour(
+        &self,
+        _ctx: &NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<bool, ClientError> {
+        Ok(false)
+    }
+
+    fn status(&self, _ctx: &NearIbcStore, _client_id: &ClientId) -> Result<Status, ClientError> {
+        if self.is_frozen {
+            Ok(Status::Frozen)
+        } else {
+            Ok(Status::Active)
+        }
+    }
+}
+
+impl ClientStateCommon for WasmClientState {
+    fn verify_consensus_state(&self, consensus_state: Any) -> Result<(), ClientError> {
+        WasmConsensusState::try_from(consensus_state).map(|_| ())
+    }
+
+    fn client_type(&self) -> ClientType {
+        client_type()
+    }
+
+    fn latest_height(&self) -> Height {
+        self.latest_height
+    }
+
+    fn validate_proof_height(&self, proof_height: Height) -> Result<(), ClientError> {
+        if proof_height > self.latest_height {
+            return Err(ClientError::InvalidProofHeight {
+                latest_height: self.latest_height,
+                proof_height,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_upgrade_client(
+        &self,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+        _proof_upgrade_client: CommitmentProofBytes,
+        _proof_upgrade_consensus_state: CommitmentProofBytes,
+        _root: &CommitmentRoot,
+    ) -> Result<(), ClientError> {
+        Err(client_error("08-wasm clients can not be upgraded"))
+    }
+
+    fn verify_membership(
+        &self,
+        _prefix: &CommitmentPrefix,
+        _proof: &CommitmentProofBytes,
+        root: &CommitmentRoot,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        match get_verified_value(root, path.to_string().as_str()) {
+            Some(Some(value_hash)) if value_hash == env::sha256(&value) => Ok(()),
+            Some(_) => Err(client_error(
+                format!("value of path {} does not match the verified value", path).as_str(),
+            )),
+            None => Err(client_error(
+                format!("path {} is not verified by the light client contract", path).as_str(),
+            )),
+        }
+    }
+
+    fn verify_non_membership(
+        &self,
+        _prefix: &CommitmentPrefix,
+        _proof: &CommitmentProofBytes,
+        root: &CommitmentRoot,
+        path: Path,
+    ) -> Result<(), ClientError> {
+        match get_verified_value(root, path.to_string().as_str()) {
+            Some(None) => Ok(()),
+            Some(Some(_)) => Err(client_error(
+                format!("path {} is verified to exist", path).as_str(),
+            )),
+            None => Err(client_error(
+                format!("path {} is not verified by the light client contract", path).as_str(),
+            )),
+        }
+    }
+}
+
+impl ClientStateExecution<NearIbcStore> for WasmClientState {
+    fn initialise(
+        &self,
+        ctx: &mut NearIbcStore,
+        client_id: &ClientId,
+        consensus_state: Any,
+    ) -> Result<(), ClientError> {
+        if get_light_client_contract(&self.checksum).is_none() {
+            return Err(client_error("light client contract is not registered"));
+        }
+        let consensus_state = WasmConsensusState::try_from(consensus_state)?;
+        let consensus_state = WasmConsensusState::new(
+            consensus_state.data,
+            consensus_state.timestamp,
+            commitment_root_of(client_id, &self.latest_height),
+        );
+        store_states(
+            ctx,
+            client_id,
+            self.clone(),
+            self.latest_height,
+            consensus_state,
+        )
+    }
+
+    fn update_state(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _header: Any,
+    ) -> Result<Vec<Height>, ClientError> {
+        Err(client_error(
+            "08-wasm clients are updated by their light client contracts",
+        ))
+    }
+
+    fn update_state_on_misbehaviour(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _client_message: Any,
+        _update_kind: &UpdateKind,
+    ) -> Result<(), ClientError> {
+        Err(client_error(
+            "08-wasm clients are frozen by their light client contracts",
+        ))
+    }
+
+    fn update_state_on_upgrade(
+        &self,
+        _ctx: &mut NearIbcStore,
+        _client_id: &ClientId,
+        _upgraded_client_state: Any,
+        _upgraded_consensus_state: Any,
+    ) -> Result<Height, ClientError> {
+        Err(client_error("08-wasm clients can not be upgraded"))
+    }
+}
@@ -0,0 +1,109 @@
+use super::WASM_CONSENSUS_STATE_TYPE_URL;
+use crate::prelude::*;
+use ibc::{
+    core::{client::types::error::ClientError, commitment_types::commitment::CommitmentRoot},
+    primitives::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, Protobuf};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmConsensusState {
+    /// The opaque data of the consensus state, which is interpreted
+    /// by the light client contract.
+    pub data: Vec<u8>,
+    /// The timestamp of the counterparty chain, in nanoseconds.
+    pub timestamp: u64,
+    /// The root identifying this consensus state in the keys of verified values.
+    root: CommitmentRoot,
+}
+
+/// The consensus state of `08-wasm`, with the timestamp and root which are needed
+/// by this contract.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawWasmConsensusState {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub root: Vec<u8>,
+}
+
+impl WasmConsensusState {
+    pub fn new(data: Vec<u8>, timestamp: u64, root: CommitmentRoot) -> Self {
+        Self {
+            data,
+            timestamp,
+            root,
+        }
+    }
+    ///
+    pub fn root(&self) -> &CommitmentRoot {
+        &self.root
+    }
+    ///
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::from_nanoseconds(self.timestamp).unwrap_or(Timestamp::none())
+    }
+}
+
+impl Protobuf<RawWasmConsensusState> for WasmConsensusState {}
+
+impl TryFrom<RawWasmConsensusState> for WasmConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawWasmConsensusState) -> Result<Self, Self::Error> {
+        if raw.timestamp == 0 {
+            return Err(ClientError::ClientSpecific {
+                description: "timestamp must not be zero".to_string(),
+            });
+        }
+        Ok(Self::new(
+            raw.data,
+            raw.timestamp,
+            CommitmentRoot::from_bytes(&raw.root),
+        ))
+    }
+}
+
+impl From<WasmConsensusState> for RawWasmConsensusState {
+    fn from(value: WasmConsensusState) -> Self {
+        RawWasmConsensusState {
+            data: value.data,
+            timestamp: value.timestamp,
+            root: value.root.into_vec(),
+        }
+    }
+}
+
+impl Protobuf<Any> for WasmConsensusState {}
+
+impl TryFrom<Any> for WasmConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: Any) -> Result<Self, Self::Error> {
+        match raw.type_url.as_str() {
+            WASM_CONSENSUS_STATE_TYPE_URL => {
+                Protobuf::<RawWasmConsensusState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })
+            }
+            _ => Err(ClientError::UnknownConsensusStateType {
+                consensus_state_type: raw.type_url,
+            }),
+        }
+    }
+}
+
+impl From<WasmConsensusState> for Any {
+    fn from(value: WasmConsensusState) -> Self {
+        Any {
+            type_url: WASM_CONSENSUS_STATE_TYPE_URL.to_string(),
+            value: Protobuf::<RawWasmConsensusState>::encode_vec(value),
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! The pluggable light client, in the style of `08-wasm` of `ibc-go`.
+//!
+//! The verification logic of a client of this type lives in a separately deployed
+//! light client contract. The governance account registers the code hash of the contract
+//! (which is also the checksum in the client state) with the account it is deployed to.
+//!
+//! As the cross-contract calls on NEAR protocol are asynchronous, the light client contract
+//! verifies the headers and proofs submitted by relayers itself, and then pushes the results
+//! to this contract by the functions of `WasmLightClientActions`:
+//!
+//! * `update_wasm_client` stores a new consensus state of the client.
+//! * `submit_wasm_client_proofs` records the verified values (or absence) of IBC paths
+//!   of the counterparty chain under a consensus state.
+//! * `freeze_wasm_client` freezes the client when misbehaviour is detected.
+//!
+//! The proofs in IBC messages are then verified against the recorded results, so relayers
+//! should submit the proofs to the light client contract before delivering the messages.
+use crate::{
+    context::NearIbcStore,
+    ibc_impl::core::{client_state::AnyClientState, consensus_state::AnyConsensusState},
+    prelude::*,
+    StorageKey,
+};
+use alloc::collections::BTreeMap;
+use client_state::WasmClientState;
+use consensus_state::WasmConsensusState;
+use ibc::core::{
+    client::{
+        context::{types::error::ClientError, ClientExecutionContext},
+        types::{events::UpdateClient, Height},
+    },
+    commitment_types::commitment::CommitmentRoot,
+    handler::types::{error::ContextError, events::IbcEvent},
+    host::{
+        types::{
+            identifiers::ClientId,
+            path::{ClientConsensusStatePath, ClientStatePath},
+        },
+        ExecutionContext, ValidationContext,
+    },
+};
+use near_sdk::{collections::LazyOption, env, AccountId, CryptoHash};
+
+pub mod client_state;
+pub mod consensus_state;
+
+pub const WASM_CLIENT_TYPE: &str = "08-wasm";
+pub const WASM_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.wasm.v1.ClientState";
+pub const WASM_CONSENSUS_STATE_TYPE_URL: &str = "/ibc.lightclients.wasm.v1.ConsensusState";
+/// The prefix of the storage keys of the verified values submitted by light client contracts.
+const VERIFIED_VALUE_KEY_PREFIX: &str = "wasmclientproofs";
+
+/// The registered light client contracts, keyed by their code hash.
+type LightClientContracts = BTreeMap<CryptoHash, AccountId>;
+
+fn light_client_contracts_storage() -> LazyOption<LightClientContracts> {
+    LazyOption::new(StorageKey::WasmLightClientContracts, None)
+}
+
+/// Get all of the registered light client contracts.
+pub fn get_light_client_contracts() -> LightClientContracts {
+    light_client_contracts_storage().get().unwrap_or_default()
+}
+
+/// Get the light client contract of the given code hash.
+pub fn get_light_client_contract(code_hash: &[u8]) -> Option<AccountId> {
+    let code_hash: CryptoHash = code_hash.try_into().ok()?;
+    get_light_client_contracts().get(&code_hash).cloned()
+}
+
+/// Register the light client contract with the given code hash.
+pub fn register_light_client_contract(code_hash: CryptoHash, contract_id: AccountId) {
+    let mut contracts = get_light_client_contracts();
+    contracts.insert(code_hash, contract_id);
+    light_client_contracts_storage().set(&contracts);
+}
+
+/// Unregister the light client contract with the given code hash.
+pub fn unregister_light_client_contract(code_hash: &CryptoHash) {
+    let mut contracts = get_light_client_contracts();
+    assert!(
+        contracts.remove(code_hash).is_some(),
+        "ERR_LIGHT_CLIENT_NOT_REGISTERED"
+    );
+    light_client_contracts_storage().set(&contracts);
+}
+
+/// Get the commitment root of the consensus state of the given client at the given height,
+/// which identifies the consensus state in the keys of verified values.
+pub fn commitment_root_of(client_id: &ClientId, height: &Height) -> CommitmentRoot {
+    CommitmentRoot::from_bytes(&env::sha256(format!("{}/{}", client_id, height).as_bytes()))
+}
+
+fn verified_value_key(root: &CommitmentRoot, path: &str) -> Vec<u8> {
+    format!(
+        "{}/{}/{}",
+        VERIFIED_VALUE_KEY_PREFIX,
+        hex::encode(root.as_bytes()),
+        path
+    )
+    .into_bytes()
+}
+
+/// Get the verified value of the given path under the consensus state with the given root.
+///
+/// Returns `Some(None)` if the path is verified to be absent.
+pub fn get_verified_value(root: &CommitmentRoot, path: &str) -> Option<Option<Vec<u8>>> {
+    env::storage_read(&verified_value_key(root, path)).map(|hash| match hash.is_empty() {
+        true => None,
+        false => Some(hash),
+    })
+}
+
+/// Get the 08-wasm client state of the given client, and check that the caller is
+/// the light client contract of it.
+pub fn assert_light_client_contract_of(
+    ctx: &NearIbcStore,
+    client_id: &ClientId,
+) -> WasmClientState {
+    let client_state = match ValidationContext::client_state(ctx, client_id) {
+        Ok(AnyClientState::Wasm(client_state)) => client_state,
+        Ok(_) => panic!("ERR_NOT_WASM_CLIENT"),
+        Err(e) => panic!("ERR_CLIENT_NOT_FOUND: {:?}", e),
+    };
+    assert_eq!(
+        get_light_client_contract(&client_state.checksum),
+        Some(env::predecessor_account_id()),
+        "ERR_NOT_LIGHT_CLIENT_CONTRACT"
+    );
+    client_state
+}
+
+/// Store the new consensus state of the given client, which is verified by
+/// the light client contract.
+pub fn update_client(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    client_state: WasmClientState,
+    height: Height,
+    consensus_state_data: Vec<u8>,
+    timestamp: u64,
+    client_state_data: Option<Vec<u8>>,
+) -> Result<(), ClientError> {
+    if client_state.is_frozen {
+        return Err(ClientError::ClientSpecific {
+            description: format!("client {} is frozen", client_id),
+        });
+    }
+    let consensus_state = WasmConsensusState::new(
+        consensus_state_data,
+        timestamp,
+        commitment_root_of(client_id, &height),
+    );
+    let new_client_state = WasmClientState {
+        data: client_state_data.unwrap_or(client_state.data),
+        latest_height: core::cmp::max(client_state.latest_height, height),
+        ..client_state
+    };
+    store_states(ctx, client_id, new_client_state, height, consensus_state)?;
+    ctx.emit_ibc_event(IbcEvent::UpdateClient(UpdateClient::new(
+        client_id.clone(),
+        client_state::client_type(),
+        height,
+        vec![height],
+        Vec::new(),
+    )))
+    .map_err(context_error)
+}
+
+/// Freeze the given client, as misbehaviour is detected by the light client contract.
+pub fn freeze_client(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    client_state: WasmClientState,
+) -> Result<(), ClientError> {
+    ctx.store_client_state(
+        ClientStatePath(client_id.clone()),
+        AnyClientState::Wasm(WasmClientState {
+            is_frozen: true,
+            ..client_state
+        }),
+    )
+    .map_err(context_error)
+}
+
+/// Record the verified values (`None` for absence) of the given paths under the consensus
+/// state of the given client at the given height.
+pub fn record_verified_values(
+    ctx: &NearIbcStore,
+    client_id: &ClientId,
+    height: &Height,
+    values: Vec<(String, Option<Vec<u8>>)>,
+) -> Result<(), ClientError> {
+    // Make sure the consensus state exists.
+    ValidationContext::consensus_state(
+        ctx,
+        &ClientConsensusStatePath::new(
+            client_id.clone(),
+            height.revision_number(),
+            height.revision_height(),
+        ),
+    )
+    .map_err(context_error)?;
+    let root = commitment_root_of(client_id, height);
+    values.into_iter().for_each(|(path, value)| {
+        env::storage_write(
+            &verified_value_key(&root, &path),
+            &value.map_or(Vec::new(), |value| env::sha256(&value)),
+        );
+    });
+    Ok(())
+}
+
+/// Store the client state and the consensus state at the given height.
+pub fn store_states(
+    ctx: &mut NearIbcStore,
+    client_id: &ClientId,
+    client_state: WasmClientState,
+    height: Height,
+    consensus_state: WasmConsensusState,
+) -> Result<(), ClientError> {
+    ctx.store_client_state(
+        ClientStatePath(client_id.clone()),
+        AnyClientState::Wasm(client_state),
+    )
+    .map_err(context_error)?;
+    ctx.store_consensus_state(
+        ClientConsensusStatePath::new(
+            client_id.clone(),
+            height.revision_number(),
+            height.revision_height(),
+        ),
+        AnyConsensusState::Wasm(consensus_state),
+    )
+    .map_err(context_error)?;
+    let host_timestamp = ValidationContext::host_timestamp(ctx).map_err(context_error)?;
+    let host_height = ValidationContext::host_height(ctx).map_err(context_error)?;
+    ctx.store_update_time(client_id.clone(), height, host_timestamp)
+        .map_err(context_error)?;
+    ctx.store_update_height(client_id.clone(), height, host_height)
+        .map_err(context_error)
+}
+
+fn context_error(e: ContextError) -> ClientError {
+    ClientError::Other {
+        description: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ibc::core::{
+        client::context::client_state::{ClientStateCommon, ClientStateExecution},
+        commitment_types::commitment::{CommitmentPrefix, CommitmentProofBytes},
+        host::types::{
+            identifiers::{ChannelId, PortId, Sequence},
+            path::{CommitmentPath, Path, ReceiptPath},
+        },
+    };
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    fn light_client_contract() -> AccountId {
+        "light-client.testnet".parse().unwrap()
+    }
+
+    fn context(predecessor: AccountId) -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id("near-ibc.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor)
+            .block_height(100)
+            .block_timestamp(1_000_000_000);
+        context
+    }
+
+    fn client_id() -> ClientId {
+        ClientId::new(client_state::client_type(), 0).unwrap()
+    }
+
+    fn height(revision_height: u64) -> Height {
+        Height::new(0, revision_height).unwrap()
+    }
+
+    fn commitment_path(sequence: u64) -> Path {
+        Path::Commitment(CommitmentPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(sequence),
+        ))
+    }
+
+    fn receipt_path(sequence: u64) -> Path {
+        Path::Receipt(ReceiptPath::new(
+            &PortId::transfer(),
+            &ChannelId::new(0),
+            Sequence::from(sequence),
+        ))
+    }
+
+    /// Set up an 08-wasm client at height 10, whose light client contract is registered.
+    fn setup() -> (NearIbcStore, WasmClientState) {
+        testing_env!(context(light_client_contract()).build());
+        register_light_client_contract([1; 32], light_client_contract());
+        let mut store = NearIbcStore::new();
+        let client_state = WasmClientState {
+            data: vec![],
+            checksum: vec![1; 32],
+            latest_height: height(10),
+            is_frozen: false,
+        };
+        client_state
+            .initialise(
+                &mut store,
+                &client_id(),
+                WasmConsensusState::new(vec![], 1, CommitmentRoot::from_bytes(&[])).into(),
+            )
+            .unwrap();
+        (store, client_state)
+    }
+
+    fn verify_membership(
+        client_state: &WasmClientState,
+        height: Height,
+        path: Path,
+        value: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        client_state.verify_membership(
+            &CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap(),
+            &CommitmentProofBytes::try_from(vec![0]).unwrap(),
+            &commitment_root_of(&client_id(), &height),
+            path,
+            value,
+        )
+    }
+
+    fn verify_non_membership(
+        client_state: &WasmClientState,
+        height: Height,
+        path: Path,
+    ) -> Result<(), ClientError> {
+        client_state.verify_non_membership(
+            &CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap(),
+            &CommitmentProofBytes::try_from(vec![0]).unwrap(),
+            &commitment_root_of(&client_id(), &height),
+            path,
+        )
+    }
+
+    #[test]
+    fn test_verify_recorded_values() {
+        let (store, client_state) = setup();
+        record_verified_values(
+            &store,
+            &client_id(),
+            &height(10),
+            vec![
+                (commitment_path(1).to_string(), Some(vec![1; 32])),
+                (receipt_path(1).to_string(), None),
+            ],
+        )
+        .unwrap();
+        assert!(
+            verify_membership(&client_state, height(10), commitment_path(1), vec![1; 32]).is_ok()
+        );
+        assert!(verify_non_membership(&client_state, height(10), receipt_path(1)).is_ok());
+        // The value is tampered.
+        assert!(
+            verify_membership(&client_state, height(10), commitment_path(1), vec![2; 32]).is_err()
+        );
+        // The path is not verified by the light client contract.
+        assert!(
+            verify_membership(&client_state, height(10), commitment_path(2), vec![1; 32]).is_err()
+        );
+        assert!(verify_non_membership(&client_state, height(10), receipt_path(2)).is_err());
+        // The absence of a path is not a proof of its value, and vice versa.
+        assert!(verify_membership(&client_state, height(10), receipt_path(1), vec![]).is_err());
+        assert!(verify_non_membership(&client_state, height(10), commitment_path(1)).is_err());
+        // The values are only valid under the consensus state they are recorded with.
+        assert!(
+            verify_membership(&client_state, height(9), commitment_path(1), vec![1; 32]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_record_values_without_consensus_state() {
+        let (store, _) = setup();
+        assert!(record_verified_values(
+            &store,
+            &client_id(),
+            &height(11),
+            vec![(commitment_path(1).to_string(), Some(vec![1; 32]))],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_update_and_freeze_client() {
+        let (mut store, client_state) = setup();
+        update_client(
+            &mut store,
+            &client_id(),
+            client_state.clone(),
+            height(11),
+            vec![],
+            2,
+            None,
+        )
+        .unwrap();
+        let client_state = assert_light_client_contract_of(&store, &client_id());
+        assert_eq!(client_state.latest_height, height(11));
+        assert!(client_state.validate_proof_height(height(11)).is_ok());
+        assert!(client_state.validate_proof_height(height(12)).is_err());
+        freeze_client(&mut store, &client_id(), client_state.clone()).unwrap();
+        let client_state = assert_light_client_contract_of(&store, &client_id());
+        assert!(client_state.is_frozen);
+        assert!(update_client(
+            &mut store,
+            &client_id(),
+            client_state,
+            height(12),
+            vec![],
+            3,
+            None,
+        )
+        .is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_LIGHT_CLIENT_CONTRACT")]
+    fn test_assert_light_client_contract_by_other_account() {
+        let (store, _) = setup();
+        testing_env!(context("relayer.testnet".parse().unwrap()).build());
+        assert_light_client_contract_of(&store, &client_id());
+    }
+}
@@ -0,0 +1,1419 @@
+//! The channel upgrade handshake of ICS-04, which is compatible with `ibc-go` v8.1.
+//!
+//! An upgrade of a channel is initialized by the governance account of this contract, and
+//! the counterparty chain must have initialized the same upgrade by its own authority. The
+//! upgrade handshake (try/ack/confirm/open) and the timeout and cancellation of upgrades are
+//! then relayed by function `deliver`.
+//!
+//! As `ibc-rs` doesn't support the flushing states of channels, the upgrades are only allowed
+//! for the channels without in-flight packets sent by this chain, and the channel on this
+//! chain is not usable by `ibc-rs` (the messages of it will fail) until the upgrade is
+//! completed, timed out or cancelled.
+use super::client_state::AnyClientState;
+use crate::{context::NearIbcStore, module_holder::ModuleHolder, prelude::*};
+use core::{fmt::Debug, str::FromStr};
+use ibc::core::{
+    channel::types::{
+        channel::{ChannelEnd, Counterparty, Order},
+        error::ChannelError,
+        Version,
+    },
+    client::{
+        context::{
+            client_state::{ClientStateCommon, ClientStateValidation},
+            consensus_state::ConsensusState,
+        },
+        types::{Height, Status},
+    },
+    commitment_types::{
+        commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
+        merkle::{apply_prefix, MerkleProof},
+    },
+    connection::types::ConnectionEnd,
+    handler::types::events::IbcEvent,
+    host::{
+        types::{
+            identifiers::{ChannelId, ConnectionId, PortId},
+            path::{ChannelEndPath, ClientConsensusStatePath, Path, SeqSendPath},
+        },
+        ExecutionContext, ValidationContext,
+    },
+    router::types::event::{ModuleEvent, ModuleEventAttribute},
+};
+use ibc_proto::{
+    google::protobuf::Any,
+    ibc::core::{
+        channel::v1::{Channel as RawChannelEnd, Counterparty as RawCounterparty},
+        client::v1::Height as RawHeight,
+        commitment::v1::MerkleProof as RawMerkleProof,
+    },
+};
+use msgs::*;
+use near_sdk::{env, log};
+use prost::Message;
+
+pub mod msgs;
+
+/// The states of channel ends of `ibc-go`, including the ones added by channel upgrades.
+const STATE_OPEN: i32 = 3;
+const STATE_FLUSHING: i32 = 5;
+const STATE_FLUSHCOMPLETE: i32 = 6;
+
+/// The timeout of the upgrades agreed on by this chain, 10 minutes in nanoseconds.
+const UPGRADE_TIMEOUT_NANOS: u64 = 600_000_000_000;
+
+/// The callbacks of the application modules for channel upgrades.
+///
+/// The callbacks except `on_chan_upgrade_open` must not change the state of the modules.
+pub trait ChannelUpgradeModule {
+    /// Validate the upgrade fields proposed by this chain, and return the version to propose.
+    fn on_chan_upgrade_init(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        proposed_version: &Version,
+    ) -> Result<Version, ChannelError>;
+    /// Validate the upgrade fields proposed by the counterparty chain,
+    /// and return the version to agree on.
+    fn on_chan_upgrade_try(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        proposed_order: Order,
+        proposed_connection_hops: &[ConnectionId],
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError>;
+    /// Validate the version agreed on by the counterparty chain.
+    fn on_chan_upgrade_ack(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError>;
+    /// Apply the upgraded fields of the channel.
+    fn on_chan_upgrade_open(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        version: &Version,
+    );
+}
+
+/// Whether the given type url is one of the channel upgrade messages relayed by `deliver`.
+pub fn is_channel_upgrade_message(type_url: &str) -> bool {
+    [
+        MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+        MSG_CHANNEL_UPGRADE_ACK_TYPE_URL,
+        MSG_CHANNEL_UPGRADE_CONFIRM_TYPE_URL,
+        MSG_CHANNEL_UPGRADE_OPEN_TYPE_URL,
+        MSG_CHANNEL_UPGRADE_TIMEOUT_TYPE_URL,
+        MSG_CHANNEL_UPGRADE_CANCEL_TYPE_URL,
+    ]
+    .contains(&type_url)
+}
+
+/// Get the port id and channel id on this chain of the given channel upgrade message.
+pub fn port_and_channel_of(msg: &Any) -> Result<(PortId, ChannelId), String> {
+    let (port_id, channel_id) = match msg.type_url.as_str() {
+        MSG_CHANNEL_UPGRADE_TRY_TYPE_URL => {
+            decode::<MsgChannelUpgradeTry>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        MSG_CHANNEL_UPGRADE_ACK_TYPE_URL => {
+            decode::<MsgChannelUpgradeAck>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        MSG_CHANNEL_UPGRADE_CONFIRM_TYPE_URL => {
+            decode::<MsgChannelUpgradeConfirm>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        MSG_CHANNEL_UPGRADE_OPEN_TYPE_URL => {
+            decode::<MsgChannelUpgradeOpen>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        MSG_CHANNEL_UPGRADE_TIMEOUT_TYPE_URL => {
+            decode::<MsgChannelUpgradeTimeout>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        MSG_CHANNEL_UPGRADE_CANCEL_TYPE_URL => {
+            decode::<MsgChannelUpgradeCancel>(msg).map(|msg| (msg.port_id, msg.channel_id))?
+        }
+        type_url => return Err(format!("ERR_UNKNOWN_CHANNEL_UPGRADE_MESSAGE: {}", type_url)),
+    };
+    Ok((parse(&port_id)?, parse(&channel_id)?))
+}
+
+/// Encode the given channel end for storing at the given path, with the upgrade sequence
+/// of the stored channel end, which is not a field of `ChannelEnd` of `ibc-rs`.
+pub fn encode_channel_end(channel_end_path: &ChannelEndPath, channel_end: ChannelEnd) -> Vec<u8> {
+    let mut channel = RawChannel::from(RawChannelEnd::from(channel_end));
+    channel.upgrade_sequence = read_proto::<RawChannel>(&channel_end_path.to_string())
+        .map_or(0, |stored| stored.upgrade_sequence);
+    channel.encode_to_vec()
+}
+
+/// Get the raw upgrade of the given channel, which is empty if there is no upgrade.
+pub fn get_upgrade(port_id: &PortId, channel_id: &ChannelId) -> Vec<u8> {
+    env::storage_read(upgrade_path(port_id, channel_id).as_bytes()).unwrap_or_default()
+}
+
+/// Get the raw error receipt of the last failed upgrade of the given channel,
+/// which is empty if there is no error receipt.
+pub fn get_upgrade_error(port_id: &PortId, channel_id: &ChannelId) -> Vec<u8> {
+    env::storage_read(upgrade_error_path(port_id, channel_id).as_bytes()).unwrap_or_default()
+}
+
+fn upgrade_path(port_id: &PortId, channel_id: &ChannelId) -> String {
+    format!(
+        "channelUpgrades/upgrades/ports/{}/channels/{}",
+        port_id, channel_id
+    )
+}
+
+fn upgrade_error_path(port_id: &PortId, channel_id: &ChannelId) -> String {
+    format!(
+        "channelUpgrades/upgradeError/ports/{}/channels/{}",
+        port_id, channel_id
+    )
+}
+
+fn counterparty_upgrade_path(port_id: &PortId, channel_id: &ChannelId) -> String {
+    format!(
+        "channelUpgrades/counterpartyUpgrade/ports/{}/channels/{}",
+        port_id, channel_id
+    )
+}
+
+fn read_proto<T: Message + Default>(key: &str) -> Option<T> {
+    env::storage_read(key.as_bytes()).and_then(|data| T::decode(data.as_slice()).ok())
+}
+
+fn write_proto<T: Message>(key: &str, value: &T) {
+    env::storage_write(key.as_bytes(), &value.encode_to_vec());
+}
+
+fn decode<T: Message + Default>(msg: &Any) -> Result<T, String> {
+    T::decode(msg.value.as_slice()).map_err(|e| format!("ERR_INVALID_MESSAGE: {}", e))
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: Debug,
+{
+    T::from_str(value).map_err(|e| format!("ERR_INVALID_IDENTIFIER: {}, {:?}", value, e))
+}
+
+fn to_error<E: Debug>(e: E) -> String {
+    format!("{:?}", e)
+}
+
+fn order_of(ordering: i32) -> Result<Order, String> {
+    match ordering {
+        1 => Ok(Order::Unordered),
+        2 => Ok(Order::Ordered),
+        _ => Err(format!("ERR_INVALID_ORDERING: {}", ordering)),
+    }
+}
+
+fn proof_height_of(height: Option<RawHeight>) -> Result<Height, String> {
+    height
+        .ok_or_else(|| "ERR_MISSING_PROOF_HEIGHT".to_string())?
+        .try_into()
+        .map_err(to_error)
+}
+
+fn zero_height() -> RawHeight {
+    RawHeight {
+        revision_number: 0,
+        revision_height: 0,
+    }
+}
+
+/// Whether the given upgrade timeout has elapsed at the given height and timestamp.
+fn timeout_elapsed(timeout: &RawUpgradeTimeout, height: Height, timestamp: u64) -> bool {
+    let height_elapsed = timeout.height.as_ref().is_some_and(|timeout_height| {
+        timeout_height.revision_height > 0
+            && (height.revision_number(), height.revision_height())
+                >= (
+                    timeout_height.revision_number,
+                    timeout_height.revision_height,
+                )
+    });
+    height_elapsed || (timeout.timestamp > 0 && timestamp >= timeout.timestamp)
+}
+
+/// A channel on this chain with its counterparty.
+struct UpgradingChannel {
+    port_id: PortId,
+    channel_id: ChannelId,
+    channel: RawChannel,
+    counterparty_port_id: PortId,
+    counterparty_channel_id: ChannelId,
+}
+
+impl UpgradingChannel {
+    //
+    fn load(port_id: &PortId, channel_id: &ChannelId) -> Result<Self, String> {
+        let channel =
+            read_proto::<RawChannel>(&ChannelEndPath::new(port_id, channel_id).to_string())
+                .ok_or_else(|| format!("ERR_CHANNEL_NOT_FOUND: {}/{}", port_id, channel_id))?;
+        let counterparty = channel
+            .counterparty
+            .clone()
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY".to_string())?;
+        Ok(Self {
+            port_id: port_id.clone(),
+            channel_id: channel_id.clone(),
+            counterparty_port_id: parse(&counterparty.port_id)?,
+            counterparty_channel_id: parse(&counterparty.channel_id)?,
+            channel,
+        })
+    }
+    //
+    fn save(&self) {
+        write_proto(
+            &ChannelEndPath::new(&self.port_id, &self.channel_id).to_string(),
+            &self.channel,
+        );
+    }
+    //
+    fn assert_state(&self, states: &[i32]) -> Result<(), String> {
+        match states.contains(&self.channel.state) {
+            true => Ok(()),
+            false => Err(format!(
+                "ERR_INVALID_CHANNEL_STATE: {}/{} is in state {}",
+                self.port_id, self.channel_id, self.channel.state
+            )),
+        }
+    }
+    //
+    fn counterparty(&self) -> Counterparty {
+        Counterparty::new(
+            self.counterparty_port_id.clone(),
+            Some(self.counterparty_channel_id.clone()),
+        )
+    }
+    //
+    fn upgrade(&self) -> Result<RawUpgrade, String> {
+        read_proto(&upgrade_path(&self.port_id, &self.channel_id)).ok_or_else(|| {
+            format!(
+                "ERR_UPGRADE_NOT_FOUND: {}/{}",
+                self.port_id, self.channel_id
+            )
+        })
+    }
+    //
+    fn counterparty_upgrade(&self) -> Result<RawUpgrade, String> {
+        read_proto(&counterparty_upgrade_path(&self.port_id, &self.channel_id)).ok_or_else(|| {
+            format!(
+                "ERR_COUNTERPARTY_UPGRADE_NOT_FOUND: {}/{}",
+                self.port_id, self.channel_id
+            )
+        })
+    }
+    /// The channel end of the counterparty chain which is expected by this chain.
+    fn expected_counterparty_channel(
+        &self,
+        state: i32,
+        ordering: i32,
+        connection: &ConnectionEnd,
+        version: String,
+        upgrade_sequence: u64,
+    ) -> Result<RawChannel, String> {
+        let counterparty_connection_id = connection
+            .counterparty()
+            .connection_id()
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY_CONNECTION_ID".to_string())?;
+        Ok(RawChannel {
+            state,
+            ordering,
+            counterparty: Some(RawCounterparty {
+                port_id: self.port_id.to_string(),
+                channel_id: self.channel_id.to_string(),
+            }),
+            connection_hops: vec![counterparty_connection_id.to_string()],
+            version,
+            upgrade_sequence,
+        })
+    }
+}
+
+/// The context of the channel upgrade handshake.
+pub struct ChannelUpgradeContext<'a> {
+    pub store: &'a mut NearIbcStore,
+    pub modules: &'a mut ModuleHolder,
+}
+
+impl ChannelUpgradeContext<'_> {
+    /// Process the given channel upgrade message relayed by function `deliver`.
+    pub fn dispatch(&mut self, msg: Any) -> Result<(), String> {
+        match msg.type_url.as_str() {
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL => self.upgrade_try(decode(&msg)?),
+            MSG_CHANNEL_UPGRADE_ACK_TYPE_URL => self.upgrade_ack(decode(&msg)?),
+            MSG_CHANNEL_UPGRADE_CONFIRM_TYPE_URL => self.upgrade_confirm(decode(&msg)?),
+            MSG_CHANNEL_UPGRADE_OPEN_TYPE_URL => self.upgrade_open(decode(&msg)?),
+            MSG_CHANNEL_UPGRADE_TIMEOUT_TYPE_URL => self.upgrade_timeout(decode(&msg)?),
+            MSG_CHANNEL_UPGRADE_CANCEL_TYPE_URL => self.upgrade_cancel(decode(&msg)?),
+            type_url => Err(format!("ERR_UNKNOWN_CHANNEL_UPGRADE_MESSAGE: {}", type_url)),
+        }
+    }
+    /// Initialize an upgrade of the given open channel, with the given version and
+    /// connection hops (the current ones if not specified). The ordering can not be changed.
+    ///
+    /// An upgrade which is initialized but not yet agreed on by the counterparty chain
+    /// is replaced.
+    pub fn upgrade_init(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        version: Version,
+        connection_hops: Option<Vec<ConnectionId>>,
+    ) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(port_id, channel_id)?;
+        uc.assert_state(&[STATE_OPEN])?;
+        let connection_hops = match connection_hops {
+            Some(connection_hops) => connection_hops,
+            None => parse_connection_hops(&uc.channel.connection_hops)?,
+        };
+        self.open_connection_of(&connection_hops)?;
+        let module = self
+            .modules
+            .get_upgrade_module(port_id)
+            .ok_or_else(|| format!("ERR_UPGRADE_NOT_SUPPORTED_BY_PORT: {}", port_id))?;
+        let version = module
+            .on_chan_upgrade_init(
+                port_id,
+                channel_id,
+                &uc.counterparty(),
+                order_of(uc.channel.ordering)?,
+                &connection_hops,
+                &version,
+            )
+            .map_err(to_error)?;
+        let fields = RawUpgradeFields {
+            ordering: uc.channel.ordering,
+            connection_hops: connection_hops.iter().map(|id| id.to_string()).collect(),
+            version: version.to_string(),
+        };
+        if fields.version == uc.channel.version
+            && fields.connection_hops == uc.channel.connection_hops
+        {
+            return Err("ERR_UPGRADE_FIELDS_NOT_CHANGED".to_string());
+        }
+        if uc.upgrade().is_ok() {
+            write_error_receipt(&uc, uc.channel.upgrade_sequence, "upgrade replaced");
+        }
+        uc.channel.upgrade_sequence += 1;
+        uc.save();
+        write_proto(
+            &upgrade_path(port_id, channel_id),
+            &RawUpgrade {
+                fields: Some(fields),
+                timeout: Some(RawUpgradeTimeout {
+                    height: Some(zero_height()),
+                    timestamp: 0,
+                }),
+                next_sequence_send: 0,
+            },
+        );
+        self.emit_event("channel_upgrade_init", &uc, vec![])
+    }
+    /// Cancel the upgrade of the given channel, which has not yet completed flushing.
+    pub fn upgrade_cancel_by_governance(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(port_id, channel_id)?;
+        uc.upgrade()?;
+        uc.assert_state(&[STATE_OPEN, STATE_FLUSHING])?;
+        let sequence = uc.channel.upgrade_sequence;
+        restore_channel(&mut uc, sequence, "upgrade cancelled by governance");
+        self.emit_event("channel_upgrade_cancelled", &uc, vec![])
+    }
+    //
+    fn upgrade_try(&mut self, msg: MsgChannelUpgradeTry) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.assert_state(&[STATE_OPEN])?;
+        let mut upgrade = uc.upgrade()?;
+        let fields = upgrade.fields.clone().unwrap_or_default();
+        if msg.proposed_upgrade_connection_hops != fields.connection_hops {
+            return Err("ERR_PROPOSED_CONNECTION_HOPS_MISMATCH".to_string());
+        }
+        let counterparty_fields = msg
+            .counterparty_upgrade_fields
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY_UPGRADE_FIELDS".to_string())?;
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+        let proof_height = proof_height_of(msg.proof_height)?;
+        let expected_channel = uc.expected_counterparty_channel(
+            STATE_OPEN,
+            uc.channel.ordering,
+            &connection,
+            uc.channel.version.clone(),
+            msg.counterparty_upgrade_sequence,
+        )?;
+        self.verify_channel(
+            &uc,
+            &connection,
+            &msg.proof_channel,
+            proof_height,
+            expected_channel,
+        )?;
+        self.verify_upgrade(
+            &uc,
+            &connection,
+            &msg.proof_upgrade,
+            proof_height,
+            RawUpgrade {
+                fields: Some(counterparty_fields.clone()),
+                timeout: Some(RawUpgradeTimeout {
+                    height: Some(zero_height()),
+                    timestamp: 0,
+                }),
+                next_sequence_send: 0,
+            },
+        )?;
+        if msg.counterparty_upgrade_sequence < uc.channel.upgrade_sequence {
+            let sequence = uc.channel.upgrade_sequence.saturating_sub(1);
+            return self.abort_upgrade(
+                &mut uc,
+                sequence,
+                "counterparty upgrade sequence is outdated",
+            );
+        }
+        uc.channel.upgrade_sequence = msg.counterparty_upgrade_sequence;
+        let sequence = uc.channel.upgrade_sequence;
+        if let Err(e) = self.check_upgrade_compatibility(&fields, &counterparty_fields) {
+            return self.abort_upgrade(&mut uc, sequence, &e);
+        }
+        if self.has_in_flight_packets(&uc) {
+            return self.abort_upgrade(&mut uc, sequence, "channel has in-flight packets");
+        }
+        let version = self.modules.get_upgrade_module(&uc.port_id).map_or(
+            Err("ERR_UPGRADE_NOT_SUPPORTED_BY_PORT".to_string()),
+            |module| {
+                module
+                    .on_chan_upgrade_try(
+                        &uc.port_id,
+                        &uc.channel_id,
+                        &uc.counterparty(),
+                        order_of(fields.ordering)?,
+                        &parse_connection_hops(&fields.connection_hops)?,
+                        &Version::new(counterparty_fields.version.clone()),
+                    )
+                    .map_err(to_error)
+            },
+        );
+        match version {
+            Ok(version) if version.to_string() == fields.version => (),
+            Ok(version) => {
+                return self.abort_upgrade(
+                    &mut uc,
+                    sequence,
+                    &format!("version {} is not agreed on by the application", version),
+                )
+            }
+            Err(e) => return self.abort_upgrade(&mut uc, sequence, &e),
+        }
+        uc.channel.state = STATE_FLUSHING;
+        uc.save();
+        upgrade.timeout = Some(RawUpgradeTimeout {
+            height: Some(zero_height()),
+            timestamp: env::block_timestamp() + UPGRADE_TIMEOUT_NANOS,
+        });
+        upgrade.next_sequence_send = self.next_sequence_send(&uc)?;
+        write_proto(&upgrade_path(&uc.port_id, &uc.channel_id), &upgrade);
+        self.emit_event("channel_upgrade_try", &uc, vec![])
+    }
+    //
+    fn upgrade_ack(&mut self, msg: MsgChannelUpgradeAck) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.assert_state(&[STATE_OPEN, STATE_FLUSHING])?;
+        let upgrade = uc.upgrade()?;
+        let fields = upgrade.fields.clone().unwrap_or_default();
+        let counterparty_upgrade = msg
+            .counterparty_upgrade
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY_UPGRADE".to_string())?;
+        let counterparty_fields = counterparty_upgrade.fields.clone().unwrap_or_default();
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+        let proof_height = proof_height_of(msg.proof_height)?;
+        let expected_channel = uc.expected_counterparty_channel(
+            STATE_FLUSHING,
+            uc.channel.ordering,
+            &connection,
+            uc.channel.version.clone(),
+            uc.channel.upgrade_sequence,
+        )?;
+        self.verify_channel(
+            &uc,
+            &connection,
+            &msg.proof_channel,
+            proof_height,
+            expected_channel,
+        )?;
+        self.verify_upgrade(
+            &uc,
+            &connection,
+            &msg.proof_upgrade,
+            proof_height,
+            counterparty_upgrade.clone(),
+        )?;
+        let sequence = uc.channel.upgrade_sequence;
+        if let Err(e) = self.check_upgrade_compatibility(&fields, &counterparty_fields) {
+            return self.abort_upgrade(&mut uc, sequence, &e);
+        }
+        if timeout_elapsed(
+            &counterparty_upgrade.timeout.clone().unwrap_or_default(),
+            self.store.host_height().map_err(to_error)?,
+            env::block_timestamp(),
+        ) {
+            return self.abort_upgrade(&mut uc, sequence, "counterparty upgrade timeout elapsed");
+        }
+        if self.has_in_flight_packets(&uc) {
+            return self.abort_upgrade(&mut uc, sequence, "channel has in-flight packets");
+        }
+        let result = self.modules.get_upgrade_module(&uc.port_id).map_or(
+            Err("ERR_UPGRADE_NOT_SUPPORTED_BY_PORT".to_string()),
+            |module| {
+                module
+                    .on_chan_upgrade_ack(
+                        &uc.port_id,
+                        &uc.channel_id,
+                        &Version::new(counterparty_fields.version.clone()),
+                    )
+                    .map_err(to_error)
+            },
+        );
+        if let Err(e) = result {
+            return self.abort_upgrade(&mut uc, sequence, &e);
+        }
+        // There is no packet to flush on this chain.
+        uc.channel.state = STATE_FLUSHCOMPLETE;
+        uc.save();
+        write_proto(
+            &counterparty_upgrade_path(&uc.port_id, &uc.channel_id),
+            &counterparty_upgrade,
+        );
+        self.emit_event("channel_upgrade_ack", &uc, vec![])
+    }
+    //
+    fn upgrade_confirm(&mut self, msg: MsgChannelUpgradeConfirm) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.assert_state(&[STATE_FLUSHING])?;
+        let upgrade = uc.upgrade()?;
+        if ![STATE_FLUSHING, STATE_FLUSHCOMPLETE].contains(&msg.counterparty_channel_state) {
+            return Err(format!(
+                "ERR_INVALID_COUNTERPARTY_CHANNEL_STATE: {}",
+                msg.counterparty_channel_state
+            ));
+        }
+        let counterparty_upgrade = msg
+            .counterparty_upgrade
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY_UPGRADE".to_string())?;
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+        let proof_height = proof_height_of(msg.proof_height)?;
+        let expected_channel = uc.expected_counterparty_channel(
+            msg.counterparty_channel_state,
+            uc.channel.ordering,
+            &connection,
+            uc.channel.version.clone(),
+            uc.channel.upgrade_sequence,
+        )?;
+        self.verify_channel(
+            &uc,
+            &connection,
+            &msg.proof_channel,
+            proof_height,
+            expected_channel,
+        )?;
+        self.verify_upgrade(
+            &uc,
+            &connection,
+            &msg.proof_upgrade,
+            proof_height,
+            counterparty_upgrade.clone(),
+        )?;
+        if timeout_elapsed(
+            &counterparty_upgrade.timeout.clone().unwrap_or_default(),
+            self.store.host_height().map_err(to_error)?,
+            env::block_timestamp(),
+        ) {
+            let sequence = uc.channel.upgrade_sequence;
+            return self.abort_upgrade(&mut uc, sequence, "counterparty upgrade timeout elapsed");
+        }
+        // The packets can not be sent during the upgrade, so there is no packet to flush.
+        uc.channel.state = STATE_FLUSHCOMPLETE;
+        uc.save();
+        write_proto(
+            &counterparty_upgrade_path(&uc.port_id, &uc.channel_id),
+            &counterparty_upgrade,
+        );
+        self.emit_event("channel_upgrade_confirm", &uc, vec![])?;
+        if msg.counterparty_channel_state == STATE_FLUSHCOMPLETE {
+            self.open_channel(&mut uc, upgrade)?;
+        }
+        Ok(())
+    }
+    //
+    fn upgrade_open(&mut self, msg: MsgChannelUpgradeOpen) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.assert_state(&[STATE_FLUSHCOMPLETE])?;
+        let upgrade = uc.upgrade()?;
+        let fields = upgrade.fields.clone().unwrap_or_default();
+        let proof_height = proof_height_of(msg.proof_height)?;
+        let (connection, expected_channel) = match msg.counterparty_channel_state {
+            STATE_OPEN => {
+                if msg.counterparty_upgrade_sequence < uc.channel.upgrade_sequence {
+                    return Err("ERR_INVALID_COUNTERPARTY_UPGRADE_SEQUENCE".to_string());
+                }
+                // The counterparty channel is already upgraded.
+                let connection =
+                    self.open_connection_of(&parse_connection_hops(&fields.connection_hops)?)?;
+                let expected_channel = uc.expected_counterparty_channel(
+                    STATE_OPEN,
+                    fields.ordering,
+                    &connection,
+                    fields.version.clone(),
+                    msg.counterparty_upgrade_sequence,
+                )?;
+                (connection, expected_channel)
+            }
+            STATE_FLUSHCOMPLETE => {
+                let connection =
+                    self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+                let expected_channel = uc.expected_counterparty_channel(
+                    STATE_FLUSHCOMPLETE,
+                    uc.channel.ordering,
+                    &connection,
+                    uc.channel.version.clone(),
+                    uc.channel.upgrade_sequence,
+                )?;
+                (connection, expected_channel)
+            }
+            state => return Err(format!("ERR_INVALID_COUNTERPARTY_CHANNEL_STATE: {}", state)),
+        };
+        self.verify_channel(
+            &uc,
+            &connection,
+            &msg.proof_channel,
+            proof_height,
+            expected_channel,
+        )?;
+        self.open_channel(&mut uc, upgrade)
+    }
+    //
+    fn upgrade_timeout(&mut self, msg: MsgChannelUpgradeTimeout) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.assert_state(&[STATE_FLUSHING, STATE_FLUSHCOMPLETE])?;
+        let counterparty_upgrade = uc.counterparty_upgrade()?;
+        let counterparty_channel = msg
+            .counterparty_channel
+            .ok_or_else(|| "ERR_MISSING_COUNTERPARTY_CHANNEL".to_string())?;
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+        let proof_height = proof_height_of(msg.proof_height)?;
+        let consensus_state = self
+            .store
+            .consensus_state(&ClientConsensusStatePath::new(
+                connection.client_id().clone(),
+                proof_height.revision_number(),
+                proof_height.revision_height(),
+            ))
+            .map_err(to_error)?;
+        if !timeout_elapsed(
+            &counterparty_upgrade.timeout.clone().unwrap_or_default(),
+            proof_height,
+            consensus_state.timestamp().nanoseconds(),
+        ) {
+            return Err("ERR_UPGRADE_TIMEOUT_NOT_ELAPSED".to_string());
+        }
+        match counterparty_channel.state {
+            STATE_OPEN if counterparty_channel.upgrade_sequence >= uc.channel.upgrade_sequence => {
+                return Err("ERR_COUNTERPARTY_CHANNEL_ALREADY_UPGRADED".to_string())
+            }
+            STATE_FLUSHCOMPLETE => {
+                return Err("ERR_COUNTERPARTY_CHANNEL_FLUSH_COMPLETED".to_string())
+            }
+            _ => (),
+        }
+        self.verify_channel(
+            &uc,
+            &connection,
+            &msg.proof_channel,
+            proof_height,
+            counterparty_channel,
+        )?;
+        let sequence = uc.channel.upgrade_sequence;
+        restore_channel(&mut uc, sequence, "upgrade timed out");
+        self.emit_event("channel_upgrade_timeout", &uc, vec![])
+    }
+    //
+    fn upgrade_cancel(&mut self, msg: MsgChannelUpgradeCancel) -> Result<(), String> {
+        let mut uc = UpgradingChannel::load(&parse(&msg.port_id)?, &parse(&msg.channel_id)?)?;
+        uc.upgrade()?;
+        let error_receipt = msg
+            .error_receipt
+            .ok_or_else(|| "ERR_MISSING_ERROR_RECEIPT".to_string())?;
+        if error_receipt.sequence < uc.channel.upgrade_sequence {
+            return Err("ERR_INVALID_ERROR_RECEIPT_SEQUENCE".to_string());
+        }
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&uc.channel.connection_hops)?)?;
+        let proof_height = proof_height_of(msg.proof_height)?;
+        self.verify_membership(
+            &connection,
+            &msg.proof_error_receipt,
+            proof_height,
+            CounterpartyPath::Raw(upgrade_error_path(
+                &uc.counterparty_port_id,
+                &uc.counterparty_channel_id,
+            )),
+            error_receipt.encode_to_vec(),
+        )?;
+        restore_channel(&mut uc, error_receipt.sequence, "upgrade cancelled");
+        self.emit_event("channel_upgrade_cancelled", &uc, vec![])
+    }
+    /// Apply the upgrade to the channel and the application module.
+    fn open_channel(
+        &mut self,
+        uc: &mut UpgradingChannel,
+        upgrade: RawUpgrade,
+    ) -> Result<(), String> {
+        let fields = upgrade.fields.unwrap_or_default();
+        let connection_hops = parse_connection_hops(&fields.connection_hops)?;
+        let order = order_of(fields.ordering)?;
+        uc.channel.state = STATE_OPEN;
+        uc.channel.ordering = fields.ordering;
+        uc.channel.connection_hops = fields.connection_hops;
+        uc.channel.version = fields.version.clone();
+        uc.save();
+        env::storage_remove(upgrade_path(&uc.port_id, &uc.channel_id).as_bytes());
+        env::storage_remove(counterparty_upgrade_path(&uc.port_id, &uc.channel_id).as_bytes());
+        if let Some(module) = self.modules.get_upgrade_module_mut(&uc.port_id) {
+            module.on_chan_upgrade_open(
+                &uc.port_id,
+                &uc.channel_id,
+                order,
+                &connection_hops,
+                &Version::new(fields.version.clone()),
+            );
+        }
+        self.emit_event(
+            "channel_upgrade_open",
+            uc,
+            vec![("upgrade_version", fields.version)],
+        )
+    }
+    /// Abort the upgrade with an error receipt, which is not an error of the message,
+    /// so that the counterparty chain can cancel the upgrade with it.
+    fn abort_upgrade(
+        &mut self,
+        uc: &mut UpgradingChannel,
+        sequence: u64,
+        message: &str,
+    ) -> Result<(), String> {
+        log!(
+            "Channel upgrade of {}/{} is aborted: {}",
+            uc.port_id,
+            uc.channel_id,
+            message
+        );
+        restore_channel(uc, sequence, message);
+        self.emit_event(
+            "channel_upgrade_error",
+            uc,
+            vec![("upgrade_error_receipt", message.to_string())],
+        )
+    }
+    //
+    fn check_upgrade_compatibility(
+        &self,
+        fields: &RawUpgradeFields,
+        counterparty_fields: &RawUpgradeFields,
+    ) -> Result<(), String> {
+        if fields.ordering != counterparty_fields.ordering {
+            return Err("proposed ordering does not match the counterparty".to_string());
+        }
+        if fields.version != counterparty_fields.version {
+            return Err("proposed version does not match the counterparty".to_string());
+        }
+        let connection =
+            self.open_connection_of(&parse_connection_hops(&fields.connection_hops)?)?;
+        match connection.counterparty().connection_id() {
+            Some(connection_id)
+                if counterparty_fields.connection_hops == vec![connection_id.to_string()] =>
+            {
+                Ok(())
+            }
+            _ => Err("proposed connection hops do not match the counterparty".to_string()),
+        }
+    }
+    /// Get the connection of the given connection hops, which must be open.
+    fn open_connection_of(
+        &self,
+        connection_hops: &[ConnectionId],
+    ) -> Result<ConnectionEnd, String> {
+        if connection_hops.len() != 1 {
+            return Err("ERR_INVALID_CONNECTION_HOPS_LENGTH".to_string());
+        }
+        let connection = self
+            .store
+            .connection_end(&connection_hops[0])
+            .map_err(to_error)?;
+        match connection.is_open() {
+            true => Ok(connection),
+            false => Err(format!("ERR_CONNECTION_NOT_OPEN: {}", connection_hops[0])),
+        }
+    }
+    //
+    fn has_in_flight_packets(&self, uc: &UpgradingChannel) -> bool {
+        self.store
+            .packet_commitment_sequence_sets
+            .get(&(uc.port_id.clone(), uc.channel_id.clone()))
+            .is_some_and(|sequences| !sequences.is_empty())
+    }
+    //
+    fn next_sequence_send(&self, uc: &UpgradingChannel) -> Result<u64, String> {
+        self.store
+            .get_next_sequence_send(&SeqSendPath::new(&uc.port_id, &uc.channel_id))
+            .map(u64::from)
+            .map_err(to_error)
+    }
+    //
+    fn verify_channel(
+        &self,
+        uc: &UpgradingChannel,
+        connection: &ConnectionEnd,
+        proof: &[u8],
+        proof_height: Height,
+        expected_channel: RawChannel,
+    ) -> Result<(), String> {
+        self.verify_membership(
+            connection,
+            proof,
+            proof_height,
+            CounterpartyPath::ChannelEnd(ChannelEndPath::new(
+                &uc.counterparty_port_id,
+                &uc.counterparty_channel_id,
+            )),
+            expected_channel.encode_to_vec(),
+        )
+    }
+    //
+    fn verify_upgrade(
+        &self,
+        uc: &UpgradingChannel,
+        connection: &ConnectionEnd,
+        proof: &[u8],
+        proof_height: Height,
+        expected_upgrade: RawUpgrade,
+    ) -> Result<(), String> {
+        self.verify_membership(
+            connection,
+            proof,
+            proof_height,
+            CounterpartyPath::Raw(upgrade_path(
+                &uc.counterparty_port_id,
+                &uc.counterparty_channel_id,
+            )),
+            expected_upgrade.encode_to_vec(),
+        )
+    }
+    /// Verify the given value of the given path on the counterparty chain of the connection.
+    fn verify_membership(
+        &self,
+        connection: &ConnectionEnd,
+        proof: &[u8],
+        proof_height: Height,
+        path: CounterpartyPath,
+        value: Vec<u8>,
+    ) -> Result<(), String> {
+        let client_id = connection.client_id();
+        let client_state = self.store.client_state(client_id).map_err(to_error)?;
+        match client_state
+            .status(self.store, client_id)
+            .map_err(to_error)?
+        {
+            Status::Active => (),
+            status => return Err(format!("ERR_CLIENT_NOT_ACTIVE: {:?}", status)),
+        }
+        client_state
+            .validate_proof_height(proof_height)
+            .map_err(to_error)?;
+        let consensus_state = self
+            .store
+            .consensus_state(&ClientConsensusStatePath::new(
+                client_id.clone(),
+                proof_height.revision_number(),
+                proof_height.revision_height(),
+            ))
+            .map_err(to_error)?;
+        let proof = CommitmentProofBytes::try_from(proof.to_vec()).map_err(to_error)?;
+        let prefix = connection.counterparty().prefix();
+        let root = consensus_state.root();
+        match path {
+            CounterpartyPath::ChannelEnd(path) => client_state
+                .verify_membership(prefix, &proof, root, Path::ChannelEnd(path), value)
+                .map_err(to_error),
+            CounterpartyPath::Raw(path) => {
+                verify_raw_path_membership(&client_state, prefix, &proof, root, path, value)
+            }
+        }
+    }
+    //
+    fn emit_event(
+        &mut self,
+        kind: &str,
+        uc: &UpgradingChannel,
+        extra_attributes: Vec<(&str, String)>,
+    ) -> Result<(), String> {
+        let mut attributes: Vec<ModuleEventAttribute> = vec![
+            ("port_id", uc.port_id.to_string()).into(),
+            ("channel_id", uc.channel_id.to_string()).into(),
+            ("counterparty_port_id", uc.counterparty_port_id.to_string()).into(),
+            (
+                "counterparty_channel_id",
+                uc.counterparty_channel_id.to_string(),
+            )
+                .into(),
+            ("upgrade_sequence", uc.channel.upgrade_sequence.to_string()).into(),
+        ];
+        attributes.extend(extra_attributes.into_iter().map(ModuleEventAttribute::from));
+        self.store
+            .emit_ibc_event(IbcEvent::Module(ModuleEvent {
+                kind: kind.to_string(),
+                attributes,
+            }))
+            .map_err(to_error)
+    }
+}
+
+/// The paths on the counterparty chain to verify.
+enum CounterpartyPath {
+    ChannelEnd(ChannelEndPath),
+    /// The paths of channel upgrades, which are not defined in `ibc-rs`.
+    Raw(String),
+}
+
+/// Verify the value of the given path which is not defined in `ibc-rs`,
+/// only the tendermint clients and the localhost client are supported.
+fn verify_raw_path_membership(
+    client_state: &AnyClientState,
+    prefix: &CommitmentPrefix,
+    proof: &CommitmentProofBytes,
+    root: &CommitmentRoot,
+    path: String,
+    value: Vec<u8>,
+) -> Result<(), String> {
+    match client_state {
+        AnyClientState::Tendermint(client_state) => {
+            let merkle_proof: MerkleProof = RawMerkleProof::try_from(proof.clone())
+                .map_err(to_error)?
+                .into();
+            merkle_proof
+                .verify_membership(
+                    &client_state.inner().proof_specs,
+                    root.clone().into(),
+                    apply_prefix(prefix, vec![path]),
+                    value,
+                    0,
+                )
+                .map_err(to_error)
+        }
+        AnyClientState::Localhost(_) => match env::storage_read(path.as_bytes()) {
+            Some(stored_value) if stored_value == value => Ok(()),
+            _ => Err(format!("ERR_INVALID_PROOF: {}", path)),
+        },
+        _ => Err("ERR_CHANNEL_UPGRADE_NOT_SUPPORTED_BY_CLIENT".to_string()),
+    }
+}
+
+/// Restore the channel to the state before the upgrade, with an error receipt
+/// of the given upgrade sequence.
+fn restore_channel(uc: &mut UpgradingChannel, sequence: u64, message: &str) {
+    uc.channel.state = STATE_OPEN;
+    uc.channel.upgrade_sequence = uc.channel.upgrade_sequence.max(sequence);
+    uc.save();
+    env::storage_remove(upgrade_path(&uc.port_id, &uc.channel_id).as_bytes());
+    env::storage_remove(counterparty_upgrade_path(&uc.port_id, &uc.channel_id).as_bytes());
+    write_error_receipt(uc, sequence, message);
+}
+
+fn write_error_receipt(uc: &UpgradingChannel, sequence: u64, message: &str) {
+    write_proto(
+        &upgrade_error_path(&uc.port_id, &uc.channel_id),
+        &RawErrorReceipt {
+            sequence,
+            message: message.to_string(),
+        },
+    );
+}
+
+fn parse_connection_hops(connection_hops: &[String]) -> Result<Vec<ConnectionId>, String> {
+    connection_hops.iter().map(|id| parse(id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::{
+        applications::fee::{FeeMetadata, FEE_VERSION},
+        clients::localhost::localhost_connection_id,
+    };
+    use ibc::core::{
+        channel::types::{channel::State, commitment::PacketCommitment},
+        host::types::{
+            identifiers::Sequence,
+            path::{CommitmentPath, SeqSendPath},
+        },
+    };
+    use near_sdk::{serde_json, test_utils::VMContextBuilder, testing_env, AccountId};
+
+    const BLOCK_TIMESTAMP: u64 = 1_000_000_000_000;
+
+    fn context(block_timestamp: u64) -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id("near-ibc.testnet".parse().unwrap())
+            .predecessor_account_id("relayer.testnet".parse().unwrap())
+            .block_height(100)
+            .block_timestamp(block_timestamp);
+        context
+    }
+
+    fn channel_id(index: u64) -> ChannelId {
+        ChannelId::new(index)
+    }
+
+    fn fee_version() -> String {
+        serde_json::to_string(&FeeMetadata {
+            fee_version: FEE_VERSION.to_string(),
+            app_version: "ics20-1".to_string(),
+        })
+        .unwrap()
+    }
+
+    /// Set up the open transfer channels `channel-0` and `channel-1` on the localhost
+    /// connection, which are the counterparty of each other, and initialize the upgrades
+    /// of them to enable fees.
+    fn setup() -> (NearIbcStore, ModuleHolder) {
+        testing_env!(context(BLOCK_TIMESTAMP).build());
+        let mut store = NearIbcStore::new();
+        let mut modules = ModuleHolder::new(AccountId::from_str("registry.testnet").unwrap());
+        for (index, counterparty_index) in [(0, 1), (1, 0)] {
+            store
+                .store_channel(
+                    &ChannelEndPath::new(&PortId::transfer(), &channel_id(index)),
+                    ChannelEnd::new(
+                        State::Open,
+                        Order::Unordered,
+                        Counterparty::new(PortId::transfer(), Some(channel_id(counterparty_index))),
+                        vec![localhost_connection_id()],
+                        Version::new("ics20-1".to_string()),
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+            store
+                .store_next_sequence_send(
+                    &SeqSendPath::new(&PortId::transfer(), &channel_id(index)),
+                    Sequence::from(1),
+                )
+                .unwrap();
+            ChannelUpgradeContext {
+                store: &mut store,
+                modules: &mut modules,
+            }
+            .upgrade_init(
+                &PortId::transfer(),
+                &channel_id(index),
+                Version::new(fee_version()),
+                None,
+            )
+            .unwrap();
+        }
+        (store, modules)
+    }
+
+    fn deliver<T: Message>(
+        store: &mut NearIbcStore,
+        modules: &mut ModuleHolder,
+        type_url: &str,
+        msg: T,
+    ) -> Result<(), String> {
+        ChannelUpgradeContext { store, modules }.dispatch(Any {
+            type_url: type_url.to_string(),
+            value: msg.encode_to_vec(),
+        })
+    }
+
+    fn channel_of(index: u64) -> RawChannel {
+        read_proto(&ChannelEndPath::new(&PortId::transfer(), &channel_id(index)).to_string())
+            .unwrap()
+    }
+
+    fn upgrade_of(index: u64) -> RawUpgrade {
+        RawUpgrade::decode(get_upgrade(&PortId::transfer(), &channel_id(index)).as_slice()).unwrap()
+    }
+
+    fn error_receipt_of(index: u64) -> RawErrorReceipt {
+        RawErrorReceipt::decode(
+            get_upgrade_error(&PortId::transfer(), &channel_id(index)).as_slice(),
+        )
+        .unwrap()
+    }
+
+    fn proof_height() -> Option<RawHeight> {
+        Some(RawHeight {
+            revision_number: 0,
+            revision_height: 100,
+        })
+    }
+
+    fn try_msg(index: u64, counterparty_upgrade_sequence: u64) -> MsgChannelUpgradeTry {
+        MsgChannelUpgradeTry {
+            port_id: PortId::transfer().to_string(),
+            channel_id: channel_id(index).to_string(),
+            proposed_upgrade_connection_hops: vec![localhost_connection_id().to_string()],
+            counterparty_upgrade_fields: upgrade_of(1 - index).fields,
+            counterparty_upgrade_sequence,
+            proof_channel: vec![0],
+            proof_upgrade: vec![0],
+            proof_height: proof_height(),
+            signer: "relayer.testnet".to_string(),
+        }
+    }
+
+    fn ack_msg(index: u64) -> MsgChannelUpgradeAck {
+        MsgChannelUpgradeAck {
+            port_id: PortId::transfer().to_string(),
+            channel_id: channel_id(index).to_string(),
+            counterparty_upgrade: Some(upgrade_of(1 - index)),
+            proof_channel: vec![0],
+            proof_upgrade: vec![0],
+            proof_height: proof_height(),
+            signer: "relayer.testnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_handshake() {
+        let (mut store, mut modules) = setup();
+        assert_eq!(channel_of(0).upgrade_sequence, 1);
+        assert_eq!(channel_of(1).upgrade_sequence, 1);
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            try_msg(1, 1),
+        )
+        .unwrap();
+        assert_eq!(channel_of(1).state, STATE_FLUSHING);
+        assert_eq!(
+            upgrade_of(1).timeout.unwrap().timestamp,
+            BLOCK_TIMESTAMP + UPGRADE_TIMEOUT_NANOS
+        );
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_ACK_TYPE_URL,
+            ack_msg(0),
+        )
+        .unwrap();
+        assert_eq!(channel_of(0).state, STATE_FLUSHCOMPLETE);
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_CONFIRM_TYPE_URL,
+            MsgChannelUpgradeConfirm {
+                port_id: PortId::transfer().to_string(),
+                channel_id: channel_id(1).to_string(),
+                counterparty_channel_state: STATE_FLUSHCOMPLETE,
+                counterparty_upgrade: Some(upgrade_of(0)),
+                proof_channel: vec![0],
+                proof_upgrade: vec![0],
+                proof_height: proof_height(),
+                signer: "relayer.testnet".to_string(),
+            },
+        )
+        .unwrap();
+        // The channel is opened as the counterparty channel has completed flushing.
+        assert_eq!(channel_of(1).state, STATE_OPEN);
+        assert_eq!(channel_of(1).version, fee_version());
+        assert!(get_upgrade(&PortId::transfer(), &channel_id(1)).is_empty());
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_OPEN_TYPE_URL,
+            MsgChannelUpgradeOpen {
+                port_id: PortId::transfer().to_string(),
+                channel_id: channel_id(0).to_string(),
+                counterparty_channel_state: STATE_OPEN,
+                counterparty_upgrade_sequence: 1,
+                proof_channel: vec![0],
+                proof_height: proof_height(),
+                signer: "relayer.testnet".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(channel_of(0).state, STATE_OPEN);
+        assert_eq!(channel_of(0).version, fee_version());
+        assert!(get_upgrade(&PortId::transfer(), &channel_id(0)).is_empty());
+        assert!(modules
+            .transfer_module
+            .is_fee_enabled(&PortId::transfer(), &channel_id(0)));
+        assert!(modules
+            .transfer_module
+            .is_fee_enabled(&PortId::transfer(), &channel_id(1)));
+    }
+
+    #[test]
+    fn test_upgrade_try_with_invalid_proofs() {
+        let (mut store, mut modules) = setup();
+        // The proposed upgrade of the counterparty channel is tampered.
+        let mut msg = try_msg(1, 1);
+        msg.counterparty_upgrade_fields.as_mut().unwrap().version = "ics20-1".to_string();
+        assert!(deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            msg
+        )
+        .is_err());
+        // The upgrade sequence of the counterparty channel is not the proved one.
+        assert!(deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            try_msg(1, 2)
+        )
+        .is_err());
+        // The proof height is greater than the latest height of the client.
+        let mut msg = try_msg(1, 1);
+        msg.proof_height = Some(RawHeight {
+            revision_number: 0,
+            revision_height: 101,
+        });
+        assert!(deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            msg
+        )
+        .is_err());
+        // The upgrade can not be acknowledged before the counterparty channel is flushing.
+        assert!(deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_ACK_TYPE_URL,
+            ack_msg(0)
+        )
+        .is_err());
+        assert_eq!(channel_of(0).state, STATE_OPEN);
+        assert_eq!(channel_of(1).state, STATE_OPEN);
+        assert_eq!(upgrade_of(1).fields.unwrap().version, fee_version());
+    }
+
+    #[test]
+    fn test_upgrade_aborted_and_cancelled() {
+        let (mut store, mut modules) = setup();
+        store
+            .store_packet_commitment(
+                &CommitmentPath::new(&PortId::transfer(), &channel_id(1), Sequence::from(1)),
+                PacketCommitment::from(vec![1; 32]),
+            )
+            .unwrap();
+        // The upgrade is aborted with an error receipt, as there is an in-flight packet.
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            try_msg(1, 1),
+        )
+        .unwrap();
+        assert_eq!(channel_of(1).state, STATE_OPEN);
+        assert!(get_upgrade(&PortId::transfer(), &channel_id(1)).is_empty());
+        let error_receipt = error_receipt_of(1);
+        assert_eq!(error_receipt.sequence, 1);
+        let cancel_msg = |error_receipt: RawErrorReceipt| MsgChannelUpgradeCancel {
+            port_id: PortId::transfer().to_string(),
+            channel_id: channel_id(0).to_string(),
+            error_receipt: Some(error_receipt),
+            proof_error_receipt: vec![0],
+            proof_height: proof_height(),
+            signer: "relayer.testnet".to_string(),
+        };
+        let mut tampered_receipt = error_receipt.clone();
+        tampered_receipt.message = "tampered".to_string();
+        assert!(deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_CANCEL_TYPE_URL,
+            cancel_msg(tampered_receipt)
+        )
+        .is_err());
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_CANCEL_TYPE_URL,
+            cancel_msg(error_receipt),
+        )
+        .unwrap();
+        assert_eq!(channel_of(0).state, STATE_OPEN);
+        assert_eq!(channel_of(0).version, "ics20-1");
+        assert!(get_upgrade(&PortId::transfer(), &channel_id(0)).is_empty());
+        assert_eq!(error_receipt_of(0).sequence, 1);
+    }
+
+    #[test]
+    fn test_upgrade_timeout() {
+        let (mut store, mut modules) = setup();
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TRY_TYPE_URL,
+            try_msg(1, 1),
+        )
+        .unwrap();
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_ACK_TYPE_URL,
+            ack_msg(0),
+        )
+        .unwrap();
+        let timeout_msg = || MsgChannelUpgradeTimeout {
+            port_id: PortId::transfer().to_string(),
+            channel_id: channel_id(0).to_string(),
+            counterparty_channel: Some(channel_of(1)),
+            proof_channel: vec![0],
+            proof_height: proof_height(),
+            signer: "relayer.testnet".to_string(),
+        };
+        assert_eq!(
+            deliver(
+                &mut store,
+                &mut modules,
+                MSG_CHANNEL_UPGRADE_TIMEOUT_TYPE_URL,
+                timeout_msg()
+            ),
+            Err("ERR_UPGRADE_TIMEOUT_NOT_ELAPSED".to_string())
+        );
+        testing_env!(context(BLOCK_TIMESTAMP + UPGRADE_TIMEOUT_NANOS).build());
+        deliver(
+            &mut store,
+            &mut modules,
+            MSG_CHANNEL_UPGRADE_TIMEOUT_TYPE_URL,
+            timeout_msg(),
+        )
+        .unwrap();
+        assert_eq!(channel_of(0).state, STATE_OPEN);
+        assert_eq!(channel_of(0).version, "ics20-1");
+        assert!(get_upgrade(&PortId::transfer(), &channel_id(0)).is_empty());
+        assert_eq!(error_receipt_of(0).message, "upgrade timed out");
+    }
+}
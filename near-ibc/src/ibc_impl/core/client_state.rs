@@ -1,6 +1,23 @@
 use super::consensus_state::AnyConsensusState;
 use crate::context::NearEd25519Verifier;
-use crate::{context::NearIbcStore, prelude::*};
+use crate::ibc_impl::clients::{
+    grandpa::{
+        client_state::{GrandpaClientState, RawGrandpaClientState},
+        GRANDPA_CLIENT_STATE_TYPE_URL,
+    },
+    localhost::{
+        self,
+        client_state::{LocalhostClientState, RawLocalhostClientState},
+        LOCALHOST_CLIENT_STATE_TYPE_URL,
+    },
+    solomachine::{client_state::SoloMachineClientState, SOLO_MACHINE_CLIENT_STATE_TYPE_URL},
+    wasm::{
+        client_state::{RawWasmClientState, WasmClientState},
+        WASM_CLIENT_STATE_TYPE_URL,
+    },
+};
+use crate::{collections::IndexedAscendingQueueViewer, context::NearIbcStore, prelude::*};
+use core::time::Duration;
 use ibc::{
     clients::tendermint::client_state::ClientState as TmClientState,
     core::{
@@ -22,7 +39,11 @@ use ibc::{
     primitives::Timestamp,
 };
 use ibc_proto::{
-    google::protobuf::Any, ibc::lightclients::tendermint::v1::ClientState as RawTmClientState,
+    google::protobuf::Any,
+    ibc::lightclients::{
+        solomachine::v3::ClientState as RawSmClientState,
+        tendermint::v1::ClientState as RawTmClientState,
+    },
     Protobuf,
 };
 use serde::{Deserialize, Serialize};
@@ -32,6 +53,12 @@ const TENDERMINT_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum AnyClientState {
     Tendermint(TmClientState<NearEd25519Verifier>),
+    SoloMachine(SoloMachineClientState),
+    /// The localhost client is built by the validation context and is never stored,
+    /// so it can not be decoded from `Any`.
+    Localhost(LocalhostClientState),
+    Wasm(WasmClientState),
+    Grandpa(GrandpaClientState),
 }
 
 impl Protobuf<Any> for AnyClientState {}
@@ -48,6 +75,27 @@ impl TryFrom<Any> for AnyClientState {
                     }
                 })?,
             )),
+            SOLO_MACHINE_CLIENT_STATE_TYPE_URL => Ok(AnyClientState::SoloMachine(
+                Protobuf::<RawSmClientState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
+            WASM_CLIENT_STATE_TYPE_URL => Ok(AnyClientState::Wasm(
+                Protobuf::<RawWasmClientState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
+            GRANDPA_CLIENT_STATE_TYPE_URL => Ok(AnyClientState::Grandpa(
+                Protobuf::<RawGrandpaClientState>::decode_vec(&raw.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
             _ => Err(ClientError::UnknownClientStateType {
                 client_state_type: raw.type_url,
             }),
@@ -62,6 +110,22 @@ impl From<AnyClientState> for Any {
                 type_url: TENDERMINT_CLIENT_STATE_TYPE_URL.to_string(),
                 value: Protobuf::<RawTmClientState>::encode_vec(client_state),
             },
+            AnyClientState::SoloMachine(client_state) => Any {
+                type_url: SOLO_MACHINE_CLIENT_STATE_TYPE_URL.to_string(),
+                value: Protobuf::<RawSmClientState>::encode_vec(client_state),
+            },
+            AnyClientState::Localhost(client_state) => Any {
+                type_url: LOCALHOST_CLIENT_STATE_TYPE_URL.to_string(),
+                value: Protobuf::<RawLocalhostClientState>::encode_vec(client_state),
+            },
+            AnyClientState::Wasm(client_state) => Any {
+                type_url: WASM_CLIENT_STATE_TYPE_URL.to_string(),
+                value: Protobuf::<RawWasmClientState>::encode_vec(client_state),
+            },
+            AnyClientState::Grandpa(client_state) => Any {
+                type_url: GRANDPA_CLIENT_STATE_TYPE_URL.to_string(),
+                value: Protobuf::<RawGrandpaClientState>::encode_vec(client_state),
+            },
         }
     }
 }
@@ -78,6 +142,18 @@ impl ClientStateValidation<NearIbcStore> for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.verify_client_message(ctx, client_id, client_message, update_kind)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.verify_client_message(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.verify_client_message(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.verify_client_message(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.verify_client_message(ctx, client_id, client_message, update_kind)
+            }
         }
     }
 
@@ -92,11 +168,27 @@ impl ClientStateValidation<NearIbcStore> for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.check_for_misbehaviour(ctx, client_id, client_message, update_kind)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.check_for_misbehaviour(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.check_for_misbehaviour(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.check_for_misbehaviour(ctx, client_id, client_message, update_kind)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.check_for_misbehaviour(ctx, client_id, client_message, update_kind)
+            }
         }
     }
     fn status(&self, ctx: &NearIbcStore, client_id: &ClientId) -> Result<Status, ClientError> {
         match self {
             AnyClientState::Tendermint(client_state) => client_state.status(ctx, client_id),
+            AnyClientState::SoloMachine(client_state) => client_state.status(ctx, client_id),
+            AnyClientState::Wasm(client_state) => client_state.status(ctx, client_id),
+            AnyClientState::Grandpa(client_state) => client_state.status(ctx, client_id),
+            AnyClientState::Localhost(client_state) => client_state.status(ctx, client_id),
         }
     }
 }
@@ -107,18 +199,38 @@ impl ClientStateCommon for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.verify_consensus_state(consensus_state)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.verify_consensus_state(consensus_state)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.verify_consensus_state(consensus_state)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.verify_consensus_state(consensus_state)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.verify_consensus_state(consensus_state)
+            }
         }
     }
 
     fn client_type(&self) -> ClientType {
         match self {
             AnyClientState::Tendermint(client_state) => client_state.client_type(),
+            AnyClientState::SoloMachine(client_state) => client_state.client_type(),
+            AnyClientState::Wasm(client_state) => client_state.client_type(),
+            AnyClientState::Grandpa(client_state) => client_state.client_type(),
+            AnyClientState::Localhost(client_state) => client_state.client_type(),
         }
     }
 
     fn latest_height(&self) -> Height {
         match self {
             AnyClientState::Tendermint(client_state) => client_state.latest_height(),
+            AnyClientState::SoloMachine(client_state) => client_state.latest_height(),
+            AnyClientState::Wasm(client_state) => client_state.latest_height(),
+            AnyClientState::Grandpa(client_state) => client_state.latest_height(),
+            AnyClientState::Localhost(client_state) => client_state.latest_height(),
         }
     }
 
@@ -127,6 +239,16 @@ impl ClientStateCommon for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.validate_proof_height(proof_height)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.validate_proof_height(proof_height)
+            }
+            AnyClientState::Wasm(client_state) => client_state.validate_proof_height(proof_height),
+            AnyClientState::Grandpa(client_state) => {
+                client_state.validate_proof_height(proof_height)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.validate_proof_height(proof_height)
+            }
         }
     }
 
@@ -146,6 +268,34 @@ impl ClientStateCommon for AnyClientState {
                 proof_upgrade_consensus_state,
                 root,
             ),
+            AnyClientState::SoloMachine(client_state) => client_state.verify_upgrade_client(
+                upgraded_client_state,
+                upgraded_consensus_state,
+                proof_upgrade_client,
+                proof_upgrade_consensus_state,
+                root,
+            ),
+            AnyClientState::Wasm(client_state) => client_state.verify_upgrade_client(
+                upgraded_client_state,
+                upgraded_consensus_state,
+                proof_upgrade_client,
+                proof_upgrade_consensus_state,
+                root,
+            ),
+            AnyClientState::Grandpa(client_state) => client_state.verify_upgrade_client(
+                upgraded_client_state,
+                upgraded_consensus_state,
+                proof_upgrade_client,
+                proof_upgrade_consensus_state,
+                root,
+            ),
+            AnyClientState::Localhost(client_state) => client_state.verify_upgrade_client(
+                upgraded_client_state,
+                upgraded_consensus_state,
+                proof_upgrade_client,
+                proof_upgrade_consensus_state,
+                root,
+            ),
         }
     }
 
@@ -161,6 +311,18 @@ impl ClientStateCommon for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.verify_membership(prefix, proof, root, path, value)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.verify_membership(prefix, proof, root, path, value)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.verify_membership(prefix, proof, root, path, value)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.verify_membership(prefix, proof, root, path, value)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.verify_membership(prefix, proof, root, path, value)
+            }
         }
     }
 
@@ -175,6 +337,18 @@ impl ClientStateCommon for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.verify_non_membership(prefix, proof, root, path)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.verify_non_membership(prefix, proof, root, path)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.verify_non_membership(prefix, proof, root, path)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.verify_non_membership(prefix, proof, root, path)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.verify_non_membership(prefix, proof, root, path)
+            }
         }
     }
 }
@@ -185,6 +359,34 @@ impl From<TmClientState<NearEd25519Verifier>> for AnyClientState {
     }
 }
 
+impl From<SoloMachineClientState> for AnyClientState {
+    fn from(value: SoloMachineClientState) -> Self {
+        AnyClientState::SoloMachine(value)
+    }
+}
+
+impl From<WasmClientState> for AnyClientState {
+    fn from(value: WasmClientState) -> Self {
+        AnyClientState::Wasm(value)
+    }
+}
+
+impl From<GrandpaClientState> for AnyClientState {
+    fn from(value: GrandpaClientState) -> Self {
+        AnyClientState::Grandpa(value)
+    }
+}
+
+impl AnyClientState {
+    /// The trusting period of the client, `None` if the consensus states of it never expire.
+    pub fn trusting_period(&self) -> Option<Duration> {
+        match self {
+            AnyClientState::Tendermint(client_state) => Some(client_state.inner().trusting_period),
+            _ => None,
+        }
+    }
+}
+
 impl ClientStateExecution<NearIbcStore> for AnyClientState {
     fn initialise(
         &self,
@@ -196,6 +398,18 @@ impl ClientStateExecution<NearIbcStore> for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.initialise(ctx, client_id, consensus_state)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.initialise(ctx, client_id, consensus_state)
+            }
+            AnyClientState::Wasm(client_state) => {
+                client_state.initialise(ctx, client_id, consensus_state)
+            }
+            AnyClientState::Grandpa(client_state) => {
+                client_state.initialise(ctx, client_id, consensus_state)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.initialise(ctx, client_id, consensus_state)
+            }
         }
     }
 
@@ -209,6 +423,16 @@ impl ClientStateExecution<NearIbcStore> for AnyClientState {
             AnyClientState::Tendermint(client_state) => {
                 client_state.update_state(ctx, client_id, header)
             }
+            AnyClientState::SoloMachine(client_state) => {
+                client_state.update_state(ctx, client_id, header)
+            }
+            AnyClientState::Wasm(client_state) => client_state.update_state(ctx, client_id, header),
+            AnyClientState::Grandpa(client_state) => {
+                client_state.update_state(ctx, client_id, header)
+            }
+            AnyClientState::Localhost(client_state) => {
+                client_state.update_state(ctx, client_id, header)
+            }
         }
     }
 
@@ -226,6 +450,30 @@ impl ClientStateExecution<NearIbcStore> for AnyClientState {
                 client_message,
                 update_kind,
             ),
+            AnyClientState::SoloMachine(client_state) => client_state.update_state_on_misbehaviour(
+                ctx,
+                client_id,
+                client_message,
+                update_kind,
+            ),
+            AnyClientState::Wasm(client_state) => client_state.update_state_on_misbehaviour(
+                ctx,
+                client_id,
+                client_message,
+                update_kind,
+            ),
+            AnyClientState::Grandpa(client_state) => client_state.update_state_on_misbehaviour(
+                ctx,
+                client_id,
+                client_message,
+                update_kind,
+            ),
+            AnyClientState::Localhost(client_state) => client_state.update_state_on_misbehaviour(
+                ctx,
+                client_id,
+                client_message,
+                update_kind,
+            ),
         }
     }
 
@@ -243,6 +491,30 @@ impl ClientStateExecution<NearIbcStore> for AnyClientState {
                 upgraded_client_state,
                 upgraded_consensus_state,
             ),
+            AnyClientState::SoloMachine(client_state) => client_state.update_state_on_upgrade(
+                ctx,
+                client_id,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            ),
+            AnyClientState::Wasm(client_state) => client_state.update_state_on_upgrade(
+                ctx,
+                client_id,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            ),
+            AnyClientState::Grandpa(client_state) => client_state.update_state_on_upgrade(
+                ctx,
+                client_id,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            ),
+            AnyClientState::Localhost(client_state) => client_state.update_state_on_upgrade(
+                ctx,
+                client_id,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            ),
         }
     }
 }
@@ -260,13 +532,7 @@ impl ibc::clients::tendermint::context::CommonContext for NearIbcStore {
     }
 
     fn consensus_state_heights(&self, client_id: &ClientId) -> Result<Vec<Height>, ContextError> {
-        Ok(self
-            .client_consensus_state_height_sets
-            .get(&client_id)
-            .map_or_else(
-                || Vec::new(),
-                |heights| heights.iter().map(|height| height.clone()).collect(),
-            ))
+        Ok(self.consensus_state_heights_of(client_id))
     }
 
     fn host_height(&self) -> Result<Height, ContextError> {
@@ -284,19 +550,17 @@ impl ibc::clients::tendermint::context::ValidationContext for NearIbcStore {
         client_id: &ClientId,
         height: &Height,
     ) -> Result<Option<Self::AnyConsensusState>, ContextError> {
-        if let Some(consensus_state_keys) = self.client_consensus_state_height_sets.get(client_id) {
-            get_next_height(
-                height,
-                consensus_state_keys.iter().map(|h| h.clone()).collect(),
-            )
-            .map(|next_height| {
-                self.consensus_state(&ClientConsensusStatePath::new(
-                    client_id.clone(),
-                    next_height.revision_number(),
-                    next_height.revision_height(),
-                ))
-            })
-            .map_or_else(|| Ok(None), |cs| Ok(Some(cs.unwrap())))
+        if let Some(consensus_state_heights) = self.client_consensus_state_heights.get(client_id) {
+            consensus_state_heights
+                .get_next_key_by_key(height)
+                .map(|next_height| {
+                    self.consensus_state(&ClientConsensusStatePath::new(
+                        client_id.clone(),
+                        next_height.revision_number(),
+                        next_height.revision_height(),
+                    ))
+                })
+                .map_or_else(|| Ok(None), |cs| Ok(Some(cs.unwrap())))
         } else {
             Err(ContextError::ClientError(
                 ClientError::MissingRawConsensusState,
@@ -309,19 +573,17 @@ impl ibc::clients::tendermint::context::ValidationContext for NearIbcStore {
         client_id: &ClientId,
         height: &Height,
     ) -> Result<Option<Self::AnyConsensusState>, ContextError> {
-        if let Some(consensus_state_keys) = self.client_consensus_state_height_sets.get(client_id) {
-            get_previous_height(
-                height,
-                consensus_state_keys.iter().map(|h| h.clone()).collect(),
-            )
-            .map(|next_height| {
-                self.consensus_state(&ClientConsensusStatePath::new(
-                    client_id.clone(),
-                    next_height.revision_number(),
-                    next_height.revision_height(),
-                ))
-            })
-            .map_or_else(|| Ok(None), |cs| Ok(Some(cs.unwrap())))
+        if let Some(consensus_state_heights) = self.client_consensus_state_heights.get(client_id) {
+            consensus_state_heights
+                .get_previous_key_by_key(height)
+                .map(|next_height| {
+                    self.consensus_state(&ClientConsensusStatePath::new(
+                        client_id.clone(),
+                        next_height.revision_number(),
+                        next_height.revision_height(),
+                    ))
+                })
+                .map_or_else(|| Ok(None), |cs| Ok(Some(cs.unwrap())))
         } else {
             Err(ContextError::ClientError(
                 ClientError::MissingRawConsensusState,
@@ -336,6 +598,9 @@ impl ClientValidationContext for NearIbcStore {
         client_id: &ClientId,
         height: &Height,
     ) -> Result<Timestamp, ContextError> {
+        if *client_id == localhost::localhost_client_id() {
+            return ValidationContext::host_timestamp(self);
+        }
         self.client_processed_times
             .get(client_id)
             .and_then(|processed_times| processed_times.get(height))
@@ -355,6 +620,9 @@ impl ClientValidationContext for NearIbcStore {
         client_id: &ClientId,
         height: &Height,
     ) -> Result<Height, ContextError> {
+        if *client_id == localhost::localhost_client_id() {
+            return ValidationContext::host_height(self);
+        }
         self.client_processed_heights
             .get(client_id)
             .and_then(|processed_heights| processed_heights.get(height))
@@ -370,46 +638,37 @@ impl ClientValidationContext for NearIbcStore {
     }
 }
 
-fn get_previous_height(height: &Height, heights: Vec<Height>) -> Option<Height> {
-    let mut heights = heights;
-    heights.sort();
-    heights.reverse();
-    heights
-        .iter()
-        .find(|h| **h < *height)
-        .and_then(|h| Some(h.clone()))
-}
-
-fn get_next_height(height: &Height, heights: Vec<Height>) -> Option<Height> {
-    let mut heights = heights;
-    heights.sort();
-    heights
-        .iter()
-        .find(|h| **h > *height)
-        .and_then(|h| Some(h.clone()))
-}
-
 #[cfg(test)]
 mod tests {
-    use ibc::core::client::types::Height;
+    use crate::{
+        collections::IndexedAscendingQueueViewer,
+        ibc_impl::core::consensus_state_retention::ConsensusStateHeights, StorageKey,
+    };
+    use core::str::FromStr;
+    use ibc::core::{
+        client::types::Height,
+        host::types::identifiers::{ClientId, ClientType},
+    };
 
     #[test]
     fn test_get_previous_next_height() {
-        let heights = vec![
-            Height::new(0, 6).unwrap(),
-            Height::new(0, 1).unwrap(),
-            Height::new(0, 2).unwrap(),
-            Height::new(0, 3).unwrap(),
-            Height::new(0, 4).unwrap(),
-            Height::new(0, 5).unwrap(),
-        ];
-        let height = Height::new(0, 3).unwrap();
-        assert!(
-            super::get_previous_height(&height, heights.clone()).unwrap()
-                == Height::new(0, 2).unwrap()
-        );
-        assert!(
-            super::get_next_height(&height, heights.clone()).unwrap() == Height::new(0, 4).unwrap()
+        let client_id = ClientId::new(ClientType::from_str("07-tendermint").unwrap(), 0).unwrap();
+        let mut heights = ConsensusStateHeights::new(
+            StorageKey::ClientConsensusStateHeightsIndexMap {
+                client_id: client_id.clone(),
+            },
+            StorageKey::ClientConsensusStateHeightsValueMap { client_id },
+            u64::MAX,
         );
+        [6, 1, 2, 4, 5, 3].into_iter().for_each(|height| {
+            heights.insert((Height::new(0, height).unwrap(), height));
+        });
+        let height = Height::new(0, 3).unwrap();
+        assert!(heights.get_previous_key_by_key(&height).unwrap() == &Height::new(0, 2).unwrap());
+        assert!(heights.get_next_key_by_key(&height).unwrap() == &Height::new(0, 4).unwrap());
+        heights.remove(&Height::new(0, 4).unwrap());
+        assert!(heights.get_next_key_by_key(&height).unwrap() == &Height::new(0, 5).unwrap());
+        assert!(heights.first_key().unwrap() == &Height::new(0, 1).unwrap());
+        assert!(heights.last_key().unwrap() == &Height::new(0, 6).unwrap());
     }
 }
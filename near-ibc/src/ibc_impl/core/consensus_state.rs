@@ -1,4 +1,21 @@
-use crate::prelude::*;
+use super::host::NearConsensusState;
+use crate::{
+    ibc_impl::clients::{
+        grandpa::{
+            consensus_state::{GrandpaConsensusState, RawGrandpaConsensusState},
+            GRANDPA_CONSENSUS_STATE_TYPE_URL,
+        },
+        localhost::consensus_state::LocalhostConsensusState,
+        solomachine::{
+            consensus_state::SoloMachineConsensusState, SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL,
+        },
+        wasm::{
+            consensus_state::{RawWasmConsensusState, WasmConsensusState},
+            WASM_CONSENSUS_STATE_TYPE_URL,
+        },
+    },
+    prelude::*,
+};
 use ibc::{
     clients::tendermint::consensus_state::ConsensusState as TmConsensusState,
     core::{
@@ -9,7 +26,11 @@ use ibc::{
 };
 use ibc_proto::{
     google::protobuf::Any,
-    ibc::lightclients::tendermint::v1::ConsensusState as RawTmConsensusState, Protobuf,
+    ibc::lightclients::{
+        solomachine::v3::ConsensusState as RawSmConsensusState,
+        tendermint::v1::ConsensusState as RawTmConsensusState,
+    },
+    Protobuf,
 };
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +40,15 @@ const TENDERMINT_CONSENSUS_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.
 #[serde(tag = "type")]
 pub enum AnyConsensusState {
     Tendermint(TmConsensusState),
+    SoloMachine(SoloMachineConsensusState),
+    /// The consensus state of the localhost client is built by the validation context
+    /// and is never stored, so it can not be decoded from `Any`.
+    Localhost(LocalhostConsensusState),
+    Wasm(WasmConsensusState),
+    Grandpa(GrandpaConsensusState),
+    /// The consensus state of NEAR protocol (the host chain) recorded by this contract,
+    /// which is never stored as a consensus state of a client.
+    Near(NearConsensusState),
 }
 
 impl Protobuf<Any> for AnyConsensusState {}
@@ -35,6 +65,27 @@ impl TryFrom<Any> for AnyConsensusState {
                     }
                 })?,
             )),
+            SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL => Ok(AnyConsensusState::SoloMachine(
+                Protobuf::<RawSmConsensusState>::decode_vec(&value.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
+            WASM_CONSENSUS_STATE_TYPE_URL => Ok(AnyConsensusState::Wasm(
+                Protobuf::<RawWasmConsensusState>::decode_vec(&value.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
+            GRANDPA_CONSENSUS_STATE_TYPE_URL => Ok(AnyConsensusState::Grandpa(
+                Protobuf::<RawGrandpaConsensusState>::decode_vec(&value.value).map_err(|e| {
+                    ClientError::ClientSpecific {
+                        description: e.to_string(),
+                    }
+                })?,
+            )),
             _ => Err(ClientError::UnknownConsensusStateType {
                 consensus_state_type: value.type_url.clone(),
             }),
@@ -49,6 +100,14 @@ impl From<AnyConsensusState> for Any {
                 type_url: TENDERMINT_CONSENSUS_STATE_TYPE_URL.to_string(),
                 value: Protobuf::<RawTmConsensusState>::encode_vec(value),
             },
+            AnyConsensusState::SoloMachine(value) => Any {
+                type_url: SOLO_MACHINE_CONSENSUS_STATE_TYPE_URL.to_string(),
+                value: Protobuf::<RawSmConsensusState>::encode_vec(value),
+            },
+            AnyConsensusState::Localhost(value) => value.into(),
+            AnyConsensusState::Wasm(value) => value.into(),
+            AnyConsensusState::Grandpa(value) => value.into(),
+            AnyConsensusState::Near(value) => value.into(),
         }
     }
 }
@@ -59,16 +118,44 @@ impl From<TmConsensusState> for AnyConsensusState {
     }
 }
 
+impl From<SoloMachineConsensusState> for AnyConsensusState {
+    fn from(value: SoloMachineConsensusState) -> Self {
+        AnyConsensusState::SoloMachine(value)
+    }
+}
+
+impl From<WasmConsensusState> for AnyConsensusState {
+    fn from(value: WasmConsensusState) -> Self {
+        AnyConsensusState::Wasm(value)
+    }
+}
+
+impl From<GrandpaConsensusState> for AnyConsensusState {
+    fn from(value: GrandpaConsensusState) -> Self {
+        AnyConsensusState::Grandpa(value)
+    }
+}
+
 impl ConsensusState for AnyConsensusState {
     fn root(&self) -> &CommitmentRoot {
         match self {
             AnyConsensusState::Tendermint(value) => value.root(),
+            AnyConsensusState::SoloMachine(value) => value.root(),
+            AnyConsensusState::Localhost(value) => value.root(),
+            AnyConsensusState::Wasm(value) => value.root(),
+            AnyConsensusState::Grandpa(value) => value.root(),
+            AnyConsensusState::Near(value) => value.root(),
         }
     }
 
     fn timestamp(&self) -> Timestamp {
         match self {
             AnyConsensusState::Tendermint(value) => value.timestamp().into(),
+            AnyConsensusState::SoloMachine(value) => value.timestamp(),
+            AnyConsensusState::Localhost(value) => value.timestamp(),
+            AnyConsensusState::Wasm(value) => value.timestamp(),
+            AnyConsensusState::Grandpa(value) => value.timestamp(),
+            AnyConsensusState::Near(value) => value.timestamp(),
         }
     }
 
@@ -77,6 +164,15 @@ impl ConsensusState for AnyConsensusState {
             AnyConsensusState::Tendermint(value) => {
                 ibc::core::client::context::consensus_state::ConsensusState::encode_vec(value)
             }
+            AnyConsensusState::SoloMachine(value) => Protobuf::<Any>::encode_vec(value),
+            AnyConsensusState::Localhost(value) => {
+                Protobuf::<Any>::encode_vec(AnyConsensusState::Localhost(value))
+            }
+            AnyConsensusState::Wasm(value) => Protobuf::<Any>::encode_vec(value),
+            AnyConsensusState::Grandpa(value) => Protobuf::<Any>::encode_vec(value),
+            AnyConsensusState::Near(value) => {
+                Protobuf::<Any>::encode_vec(AnyConsensusState::Near(value))
+            }
         }
     }
 }
@@ -89,6 +185,9 @@ impl TryInto<ibc::clients::tendermint::consensus_state::ConsensusState> for AnyC
     ) -> Result<ibc::clients::tendermint::consensus_state::ConsensusState, Self::Error> {
         match self {
             AnyConsensusState::Tendermint(value) => Ok(value),
+            _ => Err(ClientError::ClientSpecific {
+                description: "not a tendermint consensus state".to_string(),
+            }),
         }
     }
 }
@@ -0,0 +1,218 @@
+//! Retention of the consensus states of the light clients.
+//!
+//! The heights of the consensus states of a client are kept in ascending order in a queue,
+//! along with the timestamps of the consensus states. When a consensus state is stored,
+//! the oldest consensus states of the client are pruned (along with the processed times and
+//! processed heights of them) while the number of the consensus states exceeds the `max_count`
+//! of the retention of the client, or while they are expired (the timestamp plus the trusting
+//! period of the client is not after the current block timestamp) if `prune_expired` is set,
+//! which follows the semantics of pruning expired consensus states in ibc-go.
+//! The latest consensus state of a client is always kept.
+use crate::{
+    collections::{IndexedAscendingLookupQueue, IndexedAscendingQueueViewer},
+    context::{NearIbcStore, NearTimeStamp},
+    prelude::*,
+    types::ProcessingResult,
+    StorageKey,
+};
+use ibc::core::{
+    client::types::Height,
+    host::{
+        types::{identifiers::ClientId, path::ClientConsensusStatePath},
+        ValidationContext,
+    },
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    env,
+    json_types::U64,
+    log,
+    serde::{Deserialize, Serialize},
+};
+
+/// The default max number of the consensus states kept for a client.
+pub const DEFAULT_MAX_CONSENSUS_STATES: u64 = 50;
+
+/// The heights of the consensus states of a client in ascending order,
+/// with the timestamps (in nanoseconds) of the consensus states.
+pub type ConsensusStateHeights = IndexedAscendingLookupQueue<Height, NearTimeStamp>;
+
+/// The retention of the consensus states of a client.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConsensusStateRetention {
+    /// The max number of the consensus states kept for the client.
+    pub max_count: U64,
+    /// Whether to prune the consensus states which are expired by the trusting period
+    /// of the client. Only applies to the clients with a trusting period.
+    pub prune_expired: bool,
+}
+
+impl Default for ConsensusStateRetention {
+    fn default() -> Self {
+        Self {
+            max_count: U64(DEFAULT_MAX_CONSENSUS_STATES),
+            prune_expired: true,
+        }
+    }
+}
+
+impl NearIbcStore {
+    /// Get the retention of the consensus states of the given client.
+    pub fn consensus_state_retention(&self, client_id: &ClientId) -> ConsensusStateRetention {
+        self.consensus_state_retentions
+            .get(client_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// Set the retention of the consensus states of the given client,
+    /// or reset it to the default by `None`.
+    pub fn set_consensus_state_retention(
+        &mut self,
+        client_id: &ClientId,
+        retention: Option<ConsensusStateRetention>,
+    ) {
+        match retention {
+            Some(retention) => {
+                assert!(
+                    retention.max_count.0 > 0,
+                    "ERR_INVALID_CONSENSUS_STATE_RETENTION"
+                );
+                self.consensus_state_retentions
+                    .insert(client_id.clone(), retention);
+            }
+            None => {
+                self.consensus_state_retentions.remove(client_id);
+            }
+        }
+        self.consensus_state_retentions.flush();
+    }
+    /// Get the heights of the consensus states of the given client, in ascending order.
+    pub fn consensus_state_heights_of(&self, client_id: &ClientId) -> Vec<Height> {
+        self.client_consensus_state_heights
+            .get(client_id)
+            .map_or(vec![], |heights| {
+                heights.keys().into_iter().flatten().cloned().collect()
+            })
+    }
+    /// Whether there is a consensus state of the given client at the given height.
+    pub fn has_consensus_state_height(&self, client_id: &ClientId, height: &Height) -> bool {
+        self.client_consensus_state_heights
+            .get(client_id)
+            .is_some_and(|heights| heights.contains_key(height))
+    }
+    /// Record the height and timestamp of a consensus state of the given client.
+    pub fn record_consensus_state_height(
+        &mut self,
+        client_id: &ClientId,
+        height: &Height,
+        timestamp: NearTimeStamp,
+    ) {
+        if !self.client_consensus_state_heights.contains_key(client_id) {
+            self.client_consensus_state_heights.insert(
+                client_id.clone(),
+                ConsensusStateHeights::new(
+                    StorageKey::ClientConsensusStateHeightsIndexMap {
+                        client_id: client_id.clone(),
+                    },
+                    StorageKey::ClientConsensusStateHeightsValueMap {
+                        client_id: client_id.clone(),
+                    },
+                    u64::MAX,
+                ),
+            );
+        }
+        self.client_consensus_state_heights
+            .get_mut(client_id)
+            .map(|heights| {
+                heights.insert((*height, timestamp));
+                heights.flush();
+            });
+    }
+    /// Remove the height of a consensus state of the given client from the record.
+    pub fn unrecord_consensus_state_height(&mut self, client_id: &ClientId, height: &Height) {
+        self.client_consensus_state_heights
+            .get_mut(client_id)
+            .map(|heights| {
+                heights.remove(height);
+                heights.flush();
+            });
+    }
+    /// Remove the consensus state of the given client at the given height,
+    /// along with the processed time and processed height of it.
+    pub(crate) fn remove_consensus_state_at(&mut self, client_id: &ClientId, height: &Height) {
+        self.remove_with_storage_payer(
+            &ClientConsensusStatePath::new(
+                client_id.clone(),
+                height.revision_number(),
+                height.revision_height(),
+            )
+            .to_string()
+            .into_bytes(),
+        );
+        self.unrecord_consensus_state_height(client_id, height);
+        self.client_processed_times
+            .get_mut(client_id)
+            .map(|processed_times| processed_times.remove(height));
+        self.client_processed_heights
+            .get_mut(client_id)
+            .map(|processed_heights| processed_heights.remove(height));
+    }
+    /// Prune the oldest consensus states of the given client by the retention of it.
+    ///
+    /// Returns `ProcessingResult::NeedMoreGas` if the pruning is stopped for running out
+    /// of gas, in which case the rest of the consensus states exceeding the retention
+    /// are pruned by the next call.
+    pub fn prune_consensus_states(&mut self, client_id: &ClientId) -> ProcessingResult {
+        let retention = self.consensus_state_retention(client_id);
+        let trusting_period = match retention.prune_expired {
+            true => self
+                .client_state(client_id)
+                .ok()
+                .and_then(|client_state| client_state.trusting_period()),
+            false => None,
+        };
+        let now = env::block_timestamp();
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        let mut pruned_heights = vec![];
+        let mut result = ProcessingResult::Ok;
+        while let Some((height, timestamp, count)) = self
+            .client_consensus_state_heights
+            .get(client_id)
+            .filter(|heights| heights.len() > 1)
+            .and_then(|heights| {
+                heights.first_key().map(|height| {
+                    (
+                        *height,
+                        heights.get_value_by_key(height).cloned(),
+                        heights.len(),
+                    )
+                })
+            })
+        {
+            let expired = trusting_period.is_some_and(|trusting_period| {
+                timestamp.is_some_and(|timestamp| {
+                    timestamp.saturating_add(trusting_period.as_nanos() as u64) <= now
+                })
+            });
+            if count <= retention.max_count.0 && !expired {
+                break;
+            }
+            if env::used_gas() >= max_gas {
+                result = ProcessingResult::NeedMoreGas;
+                break;
+            }
+            self.remove_consensus_state_at(client_id, &height);
+            pruned_heights.push(height);
+        }
+        if !pruned_heights.is_empty() {
+            log!(
+                "Consensus states of client '{}' have been pruned: {:?}",
+                client_id,
+                pruned_heights
+            );
+        }
+        result
+    }
+}
@@ -1,7 +1,7 @@
-use super::{client_state::AnyClientState, consensus_state::AnyConsensusState};
+use super::{channel_upgrade, client_state::AnyClientState, consensus_state::AnyConsensusState};
 use crate::{
     collections::IndexedAscendingQueueViewer, context::NearIbcStore, events::EventEmitter,
-    prelude::*, StorageKey,
+    ibc_impl::applications::transfer::async_ack, prelude::*, StorageKey,
 };
 use core::fmt::Debug;
 use ibc::{
@@ -11,7 +11,10 @@ use ibc::{
             commitment::{AcknowledgementCommitment, PacketCommitment},
             packet::Receipt,
         },
-        client::{context::ClientExecutionContext, types::Height},
+        client::{
+            context::{consensus_state::ConsensusState, ClientExecutionContext},
+            types::Height,
+        },
         connection::types::{error::ConnectionError, ConnectionEnd},
         handler::types::{error::ContextError, events::IbcEvent},
         host::{
@@ -35,8 +38,6 @@ use near_sdk::{
     store::{LookupMap, UnorderedMap, UnorderedSet},
 };
 
-const MAX_STATE_HISTORY_SIZE: u32 = 50;
-
 impl ClientExecutionContext for NearIbcStore {
     type V = Self;
 
@@ -72,44 +73,19 @@ impl ClientExecutionContext for NearIbcStore {
             consensus_state_path,
             consensus_state
         );
+        let height = Height::new(
+            consensus_state_path.revision_number,
+            consensus_state_path.revision_height,
+        )
+        .unwrap();
+        let timestamp = consensus_state.timestamp().nanoseconds();
         let data = Protobuf::encode_vec(consensus_state);
         let key = consensus_state_path.to_string().into_bytes();
-        env::storage_write(&key, &data);
+        self.write_with_storage_payer(&key, &data);
         //
-        if !self
-            .client_consensus_state_height_sets
-            .contains_key(&consensus_state_path.client_id)
-        {
-            self.client_consensus_state_height_sets.insert(
-                consensus_state_path.client_id.clone(),
-                UnorderedSet::new(StorageKey::ClientConsensusStateHeightSet {
-                    client_id: consensus_state_path.client_id.clone(),
-                }),
-            );
-        }
-        self.client_consensus_state_height_sets
-            .get_mut(&consensus_state_path.client_id)
-            .map(|heights| {
-                heights.insert(
-                    Height::new(
-                        consensus_state_path.revision_number,
-                        consensus_state_path.revision_height,
-                    )
-                    .unwrap(),
-                );
-                if heights.len() > MAX_STATE_HISTORY_SIZE {
-                    let mut all_heights: Vec<Height> =
-                        heights.into_iter().map(|h| h.clone()).collect();
-                    all_heights.sort();
-                    let first_cs_path = ClientConsensusStatePath {
-                        client_id: consensus_state_path.client_id.clone(),
-                        revision_number: all_heights[0].revision_number(),
-                        revision_height: all_heights[0].revision_height(),
-                    };
-                    env::storage_remove(&first_cs_path.to_string().into_bytes());
-                    heights.remove(&all_heights[0]);
-                }
-            });
+        self.record_consensus_state_height(&consensus_state_path.client_id, &height, timestamp);
+        // The rest of the states exceeding the retention (if any) are pruned by the next update.
+        self.prune_consensus_states(&consensus_state_path.client_id);
         Ok(())
     }
 
@@ -125,6 +101,10 @@ impl ClientExecutionContext for NearIbcStore {
             height,
             timestamp
         );
+        // The processed times are only kept for the consensus states which are not pruned.
+        if !self.has_consensus_state_height(&client_id, &height) {
+            return Ok(());
+        }
         if !self.client_processed_times.contains_key(&client_id) {
             self.client_processed_times.insert(
                 client_id.clone(),
@@ -135,18 +115,7 @@ impl ClientExecutionContext for NearIbcStore {
         }
         self.client_processed_times
             .get_mut(&client_id)
-            .map(|processed_times| {
-                processed_times.insert(height, timestamp.nanoseconds());
-                if processed_times.len() > MAX_STATE_HISTORY_SIZE {
-                    let mut all_heights: Vec<Height> = processed_times
-                        .keys()
-                        .into_iter()
-                        .map(|k| k.clone())
-                        .collect();
-                    all_heights.sort();
-                    processed_times.remove(&all_heights[0]);
-                }
-            });
+            .map(|processed_times| processed_times.insert(height, timestamp.nanoseconds()));
         Ok(())
     }
 
@@ -162,6 +131,10 @@ impl ClientExecutionContext for NearIbcStore {
             height,
             host_height
         );
+        // The processed heights are only kept for the consensus states which are not pruned.
+        if !self.has_consensus_state_height(&client_id, &height) {
+            return Ok(());
+        }
         if !self.client_processed_heights.contains_key(&client_id) {
             self.client_processed_heights.insert(
                 client_id.clone(),
@@ -172,18 +145,7 @@ impl ClientExecutionContext for NearIbcStore {
         }
         self.client_processed_heights
             .get_mut(&client_id)
-            .map(|processed_heights| {
-                processed_heights.insert(height, host_height);
-                if processed_heights.len() > MAX_STATE_HISTORY_SIZE {
-                    let mut all_heights: Vec<Height> = processed_heights
-                        .keys()
-                        .into_iter()
-                        .map(|k| k.clone())
-                        .collect();
-                    all_heights.sort();
-                    processed_heights.remove(&all_heights[0]);
-                }
-            });
+            .map(|processed_heights| processed_heights.insert(height, host_height));
         Ok(())
     }
 
@@ -193,19 +155,16 @@ impl ClientExecutionContext for NearIbcStore {
     ) -> Result<(), ContextError> {
         log!("delete_consensus_state - path: {}", consensus_state_path,);
         let key = consensus_state_path.to_string().into_bytes();
-        env::storage_remove(&key);
+        self.remove_with_storage_payer(&key);
         //
-        self.client_consensus_state_height_sets
-            .get_mut(&consensus_state_path.client_id)
-            .map(|heights| {
-                heights.remove(
-                    &Height::new(
-                        consensus_state_path.revision_number,
-                        consensus_state_path.revision_height,
-                    )
-                    .unwrap(),
-                );
-            });
+        self.unrecord_consensus_state_height(
+            &consensus_state_path.client_id,
+            &Height::new(
+                consensus_state_path.revision_number,
+                consensus_state_path.revision_height,
+            )
+            .unwrap(),
+        );
         Ok(())
     }
 
@@ -378,7 +337,7 @@ impl ExecutionContext for NearIbcStore {
         );
         let data = borsh::to_vec(&receipt).unwrap();
         let key = receipt_path.to_string().into_bytes();
-        env::storage_write(&key, &data);
+        self.write_with_storage_payer(&key, &data);
         //
         record_packet_sequence(
             &mut self.packet_receipt_sequence_sets,
@@ -403,9 +362,13 @@ impl ExecutionContext for NearIbcStore {
             ack_path,
             ack_commitment
         );
+        // The acknowledgement of the packet will be written asynchronously.
+        if async_ack::is_async_ack_placeholder_commitment(&ack_commitment) {
+            return Ok(());
+        }
         let data = ack_commitment.into_vec();
         let key = ack_path.to_string().into_bytes();
-        env::storage_write(&key, &data);
+        self.write_with_storage_payer(&key, &data);
         //
         record_packet_sequence(
             &mut self.packet_acknowledgement_sequence_sets,
@@ -423,7 +386,7 @@ impl ExecutionContext for NearIbcStore {
     fn delete_packet_acknowledgement(&mut self, ack_path: &AckPath) -> Result<(), ContextError> {
         log!("delete_packet_acknowledgement: path: {}", ack_path,);
         let key = ack_path.to_string().into_bytes();
-        env::storage_remove(&key);
+        self.remove_with_storage_payer(&key);
         //
         self.packet_acknowledgement_sequence_sets
             .get_mut(&(ack_path.port_id.clone(), ack_path.channel_id.clone()))
@@ -444,7 +407,7 @@ impl ExecutionContext for NearIbcStore {
             channel_end_path,
             channel_end
         );
-        let data = channel_end.encode_vec();
+        let data = channel_upgrade::encode_channel_end(channel_end_path, channel_end);
         let key = channel_end_path.to_string().into_bytes();
         env::storage_write(&key, &data);
         //
@@ -508,20 +471,33 @@ impl ExecutionContext for NearIbcStore {
     }
 
     fn emit_ibc_event(&mut self, event: IbcEvent) -> Result<(), ContextError> {
+        if let IbcEvent::WriteAcknowledgement(write_ack) = &event {
+            if async_ack::is_async_ack_placeholder(write_ack.acknowledgement()) {
+                return Ok(());
+            }
+        }
         let height = self.host_height().unwrap();
+        self.record_host_consensus_state(height);
         if self.ibc_events_history.contains_key(&height) {
             self.ibc_events_history
                 .get_value_by_key_mut(&height)
                 .map(|events| events.push(event.clone()));
         } else {
+            // Remove the oldest events before the queue does so, to keep the index consistent.
+            if self.ibc_events_history.len() >= self.ibc_events_history.max_length() {
+                self.pop_front_ibc_events();
+            }
             self.ibc_events_history
                 .push_back((height, vec![event.clone()]));
         }
+        self.index_packet_event(&height, &event);
         while let Some(first_height) = self.ibc_events_history.first_key() {
             if first_height.revision_height()
-                < height.revision_height() - self.max_ibc_events_height_difference
+                < height
+                    .revision_height()
+                    .saturating_sub(self.max_ibc_events_height_difference)
             {
-                self.ibc_events_history.pop_front();
+                self.pop_front_ibc_events();
             } else {
                 break;
             }
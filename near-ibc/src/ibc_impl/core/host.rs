@@ -0,0 +1,461 @@
+//! The client state and consensus state of NEAR protocol (the host chain), which are
+//! validated and provided for the connection handshakes with counterparty chains.
+//!
+//! The messages are defined by this contract (there is no upstream definition of them), as:
+//!
+//! ```proto
+//! package ibc.lightclients.near.v1;
+//!
+//! message ClientState {
+//!   string chain_id = 1;
+//!   ibc.core.client.v1.Height latest_height = 2;
+//!   ibc.core.client.v1.Height frozen_height = 3;
+//!   uint64 epoch_length = 4;
+//!   uint64 latest_epoch_height = 5;
+//! }
+//!
+//! message ConsensusState {
+//!   bytes root = 1;
+//!   uint64 timestamp = 2;
+//!   uint64 epoch_height = 3;
+//! }
+//! ```
+//!
+//! As the block hash and state root are not accessible to contracts, the consensus state
+//! recorded by this contract is a compact one, with the random seed of the receipt as the root,
+//! the timestamp and the epoch height of the block. The root is NOT a commitment of the state,
+//! so the NEAR light clients on counterparty chains must store the same compact consensus
+//! states for the proofs of them to be verified.
+//!
+//! The consensus states are recorded at each height at which the contract delivers messages
+//! or emits IBC events, and pruned along with the IBC events history. The consensus state at
+//! a height without a record is the one recorded at the latest height before it, as the state
+//! of this contract doesn't change between the two heights.
+use super::consensus_state::AnyConsensusState;
+use crate::{
+    collections::IndexedAscendingQueueViewer, context::NearIbcStore, prelude::*, StorageKey,
+};
+use ibc::{
+    core::{
+        client::types::{error::ClientError, Height},
+        commitment_types::commitment::CommitmentRoot,
+        connection::types::error::ConnectionError,
+        handler::types::error::ContextError,
+    },
+    primitives::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, ibc::core::client::v1::Height as RawHeight, Protobuf};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    collections::LazyOption,
+    env,
+    json_types::U64,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+pub const NEAR_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.near.v1.ClientState";
+pub const NEAR_CONSENSUS_STATE_TYPE_URL: &str = "/ibc.lightclients.near.v1.ConsensusState";
+/// The epoch length of both mainnet and testnet of NEAR protocol, in blocks.
+pub const NEAR_EPOCH_LENGTH: u64 = 43200;
+
+/// The parameters of NEAR protocol which the client states of this chain
+/// on counterparty chains must match.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct HostClientParams {
+    /// The chain id of NEAR protocol, e.g. `mainnet` or `testnet`.
+    pub chain_id: String,
+    /// The length of an epoch, in blocks.
+    pub epoch_length: U64,
+}
+
+/// The client state of NEAR protocol on counterparty chains, with the fields
+/// which are validated by this contract.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawNearClientState {
+    #[prost(string, tag = "1")]
+    pub chain_id: String,
+    #[prost(message, optional, tag = "2")]
+    pub latest_height: Option<RawHeight>,
+    #[prost(message, optional, tag = "3")]
+    pub frozen_height: Option<RawHeight>,
+    #[prost(uint64, tag = "4")]
+    pub epoch_length: u64,
+    /// The epoch height of the block at the latest height.
+    #[prost(uint64, tag = "5")]
+    pub latest_epoch_height: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NearConsensusState {
+    /// The random seed of the receipt which recorded the consensus state.
+    pub root: CommitmentRoot,
+    /// The timestamp of the block, in nanoseconds.
+    pub timestamp: u64,
+    pub epoch_height: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawNearConsensusState {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(uint64, tag = "3")]
+    pub epoch_height: u64,
+}
+
+impl NearConsensusState {
+    /// The consensus state of the current block, with the random seed as the root.
+    pub fn current() -> Self {
+        Self {
+            root: CommitmentRoot::from_bytes(&env::random_seed()),
+            timestamp: env::block_timestamp(),
+            epoch_height: env::epoch_height(),
+        }
+    }
+    ///
+    pub fn root(&self) -> &CommitmentRoot {
+        &self.root
+    }
+    ///
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::from_nanoseconds(self.timestamp).unwrap_or(Timestamp::none())
+    }
+}
+
+impl Protobuf<RawNearConsensusState> for NearConsensusState {}
+
+impl TryFrom<RawNearConsensusState> for NearConsensusState {
+    type Error = ClientError;
+
+    fn try_from(raw: RawNearConsensusState) -> Result<Self, Self::Error> {
+        Ok(Self {
+            root: CommitmentRoot::from_bytes(&raw.root),
+            timestamp: raw.timestamp,
+            epoch_height: raw.epoch_height,
+        })
+    }
+}
+
+impl From<NearConsensusState> for RawNearConsensusState {
+    fn from(value: NearConsensusState) -> Self {
+        RawNearConsensusState {
+            root: value.root.into_vec(),
+            timestamp: value.timestamp,
+            epoch_height: value.epoch_height,
+        }
+    }
+}
+
+impl From<NearConsensusState> for Any {
+    fn from(value: NearConsensusState) -> Self {
+        Any {
+            type_url: NEAR_CONSENSUS_STATE_TYPE_URL.to_string(),
+            value: Protobuf::<RawNearConsensusState>::encode_vec(value),
+        }
+    }
+}
+
+fn host_client_params_storage() -> LazyOption<HostClientParams> {
+    LazyOption::new(StorageKey::HostClientParams, None)
+}
+
+/// Get the parameters of NEAR protocol set by governance.
+pub fn get_host_client_params() -> Option<HostClientParams> {
+    host_client_params_storage().get()
+}
+
+/// Set the parameters of NEAR protocol.
+pub fn set_host_client_params(params: HostClientParams) {
+    assert!(!params.chain_id.is_empty(), "ERR_INVALID_CHAIN_ID");
+    assert!(params.epoch_length.0 > 0, "ERR_INVALID_EPOCH_LENGTH");
+    host_client_params_storage().set(&params);
+}
+
+/// The default parameters of the network which the current account belongs to,
+/// by the top level account of it.
+pub fn default_host_client_params() -> Option<HostClientParams> {
+    let account_id = env::current_account_id();
+    let chain_id = if account_id.as_str().ends_with(".near") {
+        "mainnet"
+    } else if account_id.as_str().ends_with(".testnet") {
+        "testnet"
+    } else {
+        return None;
+    };
+    Some(HostClientParams {
+        chain_id: chain_id.to_string(),
+        epoch_length: U64(NEAR_EPOCH_LENGTH),
+    })
+}
+
+/// Set the default parameters of NEAR protocol, if they are not set yet.
+pub fn init_host_client_params() {
+    if get_host_client_params().is_none() {
+        if let Some(params) = default_host_client_params() {
+            host_client_params_storage().set(&params);
+        }
+    }
+}
+
+fn invalid_client_state(reason: String) -> ContextError {
+    ContextError::ConnectionError(ConnectionError::InvalidClientState { reason })
+}
+
+/// Validate the client state of NEAR protocol on the counterparty chain.
+pub fn validate_self_client(client_state: Any) -> Result<(), ContextError> {
+    if client_state.type_url != NEAR_CLIENT_STATE_TYPE_URL {
+        return Err(invalid_client_state(format!(
+            "unexpected client state type: {}",
+            client_state.type_url
+        )));
+    }
+    let client_state = RawNearClientState::decode(client_state.value.as_slice())
+        .map_err(|e| invalid_client_state(e.to_string()))?;
+    let params = get_host_client_params()
+        .ok_or_else(|| invalid_client_state("host client params are not set".to_string()))?;
+    if client_state
+        .frozen_height
+        .is_some_and(|height| height.revision_height > 0)
+    {
+        return Err(invalid_client_state("client is frozen".to_string()));
+    }
+    if client_state.chain_id != params.chain_id {
+        return Err(invalid_client_state(format!(
+            "invalid chain id: expected {}, got {}",
+            params.chain_id, client_state.chain_id
+        )));
+    }
+    let latest_height: Height = client_state
+        .latest_height
+        .ok_or_else(|| invalid_client_state("missing latest height".to_string()))?
+        .try_into()
+        .map_err(|e: ClientError| invalid_client_state(e.to_string()))?;
+    if latest_height.revision_number() != 0 {
+        return Err(invalid_client_state(format!(
+            "invalid revision number: expected 0, got {}",
+            latest_height.revision_number()
+        )));
+    }
+    if latest_height.revision_height() > env::block_height() {
+        return Err(invalid_client_state(format!(
+            "latest height {} is greater than host height {}",
+            latest_height,
+            env::block_height()
+        )));
+    }
+    if client_state.epoch_length != params.epoch_length.0 {
+        return Err(invalid_client_state(format!(
+            "invalid epoch length: expected {}, got {}",
+            params.epoch_length.0, client_state.epoch_length
+        )));
+    }
+    if client_state.latest_epoch_height > env::epoch_height() {
+        return Err(invalid_client_state(format!(
+            "latest epoch height {} is greater than host epoch height {}",
+            client_state.latest_epoch_height,
+            env::epoch_height()
+        )));
+    }
+    Ok(())
+}
+
+impl NearIbcStore {
+    /// Record the consensus state of the current block, and prune the ones older than
+    /// the max height difference of the IBC events history.
+    pub fn record_host_consensus_state(&mut self, height: Height) {
+        if self.host_consensus_states.contains_key(&height) {
+            return;
+        }
+        self.host_consensus_states.push_back((
+            height,
+            Protobuf::<RawNearConsensusState>::encode_vec(NearConsensusState::current()),
+        ));
+        while let Some(first_height) = self.host_consensus_states.first_key() {
+            if first_height.revision_height()
+                < height
+                    .revision_height()
+                    .saturating_sub(self.max_ibc_events_height_difference)
+            {
+                self.host_consensus_states.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+    /// Get the consensus state of NEAR protocol at the given height, which is the one
+    /// recorded at the latest height not greater than the given height.
+    pub fn get_host_consensus_state(
+        &self,
+        height: &Height,
+    ) -> Result<AnyConsensusState, ContextError> {
+        let missing =
+            ContextError::ClientError(ClientError::MissingLocalConsensusState { height: *height });
+        if height.revision_number() != 0
+            || height.revision_height() > env::block_height()
+            || self
+                .host_consensus_states
+                .first_key()
+                .map_or(true, |first_height| height < first_height)
+        {
+            return Err(missing);
+        }
+        let data = match self.host_consensus_states.get_value_by_key(height) {
+            Some(data) => data,
+            None => self
+                .host_consensus_states
+                .get_previous_value_by_key(height)
+                .ok_or(missing)?,
+        };
+        Protobuf::<RawNearConsensusState>::decode_vec(data)
+            .map(AnyConsensusState::Near)
+            .map_err(|e| {
+                ContextError::ClientError(ClientError::Other {
+                    description: format!("Decode host consensus state failed: {:?}", e),
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    fn set_context(account_id: &str, block_height: u64, epoch_height: u64) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_id.parse().unwrap())
+            .block_height(block_height)
+            .block_timestamp(block_height * 1_000_000_000)
+            .epoch_height(epoch_height)
+            .build());
+    }
+
+    fn height(revision_height: u64) -> Height {
+        Height::new(0, revision_height).unwrap()
+    }
+
+    fn client_state(chain_id: &str, latest_height: u64, epoch_length: u64) -> RawNearClientState {
+        RawNearClientState {
+            chain_id: chain_id.to_string(),
+            latest_height: Some(RawHeight {
+                revision_number: 0,
+                revision_height: latest_height,
+            }),
+            frozen_height: None,
+            epoch_length,
+            latest_epoch_height: 10,
+        }
+    }
+
+    fn any_of(client_state: RawNearClientState) -> Any {
+        Any {
+            type_url: NEAR_CLIENT_STATE_TYPE_URL.to_string(),
+            value: client_state.encode_to_vec(),
+        }
+    }
+
+    fn timestamp_of(store: &NearIbcStore, revision_height: u64) -> Option<u64> {
+        match store.get_host_consensus_state(&height(revision_height)) {
+            Ok(AnyConsensusState::Near(consensus_state)) => Some(consensus_state.timestamp),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_default_host_client_params() {
+        set_context("near-ibc.near", 100, 10);
+        assert_eq!(
+            default_host_client_params().map(|params| params.chain_id),
+            Some("mainnet".to_string())
+        );
+        set_context("near-ibc.testnet", 100, 10);
+        init_host_client_params();
+        assert_eq!(
+            get_host_client_params(),
+            Some(HostClientParams {
+                chain_id: "testnet".to_string(),
+                epoch_length: U64(NEAR_EPOCH_LENGTH),
+            })
+        );
+        set_context("near-ibc.test.near", 100, 10);
+        set_host_client_params(HostClientParams {
+            chain_id: "localnet".to_string(),
+            epoch_length: U64(500),
+        });
+        init_host_client_params();
+        assert_eq!(get_host_client_params().unwrap().chain_id, "localnet");
+        set_context("near-ibc.sandbox", 100, 10);
+        assert!(default_host_client_params().is_none());
+    }
+
+    #[test]
+    fn test_validate_self_client() {
+        set_context("near-ibc.testnet", 100, 10);
+        init_host_client_params();
+        assert!(
+            validate_self_client(any_of(client_state("testnet", 90, NEAR_EPOCH_LENGTH))).is_ok()
+        );
+        // Wrong type url.
+        let mut any = any_of(client_state("testnet", 90, NEAR_EPOCH_LENGTH));
+        any.type_url = "/ibc.lightclients.tendermint.v1.ClientState".to_string();
+        assert!(validate_self_client(any).is_err());
+        // Wrong chain id.
+        assert!(
+            validate_self_client(any_of(client_state("mainnet", 90, NEAR_EPOCH_LENGTH))).is_err()
+        );
+        // Wrong epoch length.
+        assert!(validate_self_client(any_of(client_state("testnet", 90, 500))).is_err());
+        // Latest height in the future.
+        assert!(
+            validate_self_client(any_of(client_state("testnet", 101, NEAR_EPOCH_LENGTH))).is_err()
+        );
+        // Latest epoch height in the future.
+        let mut raw = client_state("testnet", 90, NEAR_EPOCH_LENGTH);
+        raw.latest_epoch_height = 11;
+        assert!(validate_self_client(any_of(raw)).is_err());
+        // Frozen.
+        let mut raw = client_state("testnet", 90, NEAR_EPOCH_LENGTH);
+        raw.frozen_height = Some(RawHeight {
+            revision_number: 0,
+            revision_height: 80,
+        });
+        assert!(validate_self_client(any_of(raw)).is_err());
+        // Missing latest height.
+        let mut raw = client_state("testnet", 90, NEAR_EPOCH_LENGTH);
+        raw.latest_height = None;
+        assert!(validate_self_client(any_of(raw)).is_err());
+    }
+
+    #[test]
+    fn test_host_consensus_states() {
+        set_context("near-ibc.testnet", 10, 1);
+        let mut store = NearIbcStore::new();
+        store.max_ibc_events_height_difference = 100;
+        assert!(timestamp_of(&store, 10).is_none());
+        store.record_host_consensus_state(height(10));
+        set_context("near-ibc.testnet", 20, 1);
+        store.record_host_consensus_state(height(20));
+        set_context("near-ibc.testnet", 30, 1);
+        // The heights without a record use the latest recorded one before them.
+        assert_eq!(timestamp_of(&store, 10), Some(10_000_000_000));
+        assert_eq!(timestamp_of(&store, 15), Some(10_000_000_000));
+        assert_eq!(timestamp_of(&store, 20), Some(20_000_000_000));
+        assert_eq!(timestamp_of(&store, 25), Some(20_000_000_000));
+        // The heights before the first record or after the current height are missing.
+        assert!(timestamp_of(&store, 5).is_none());
+        assert!(timestamp_of(&store, 31).is_none());
+        assert!(store
+            .get_host_consensus_state(&Height::new(1, 15).unwrap())
+            .is_err());
+        // The records older than the max height difference are pruned.
+        set_context("near-ibc.testnet", 115, 1);
+        store.record_host_consensus_state(height(115));
+        assert!(timestamp_of(&store, 15).is_none());
+        assert_eq!(timestamp_of(&store, 20), Some(20_000_000_000));
+        assert_eq!(timestamp_of(&store, 115), Some(115_000_000_000));
+    }
+}
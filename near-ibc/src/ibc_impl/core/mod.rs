@@ -1,5 +1,10 @@
+pub mod channel_upgrade;
 pub mod client_state;
 pub mod consensus_state;
+pub mod consensus_state_retention;
 mod execution_context;
+pub mod host;
+pub mod packet_events;
+pub mod packet_pruning;
 mod router;
 mod validation_context;
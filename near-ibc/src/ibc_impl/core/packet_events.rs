@@ -0,0 +1,316 @@
+//! The index of the packet events in the IBC events history.
+//!
+//! The index maps the port id, channel id and sequence of a packet (on the sending chain)
+//! and the type of a packet event to the height at which the event is emitted, so that
+//! the events of a set of sequences can be queried without scanning the whole history.
+//! The entries are removed along with the IBC events history.
+//!
+//! The events emitted before the index is introduced (below `packet_events_indexed_since`)
+//! are not indexed, and are still found by scanning the heights of them in the history.
+use crate::{
+    collections::IndexedAscendingQueueViewer, context::NearIbcStore, prelude::*,
+    types::ProcessingResult,
+};
+use alloc::collections::BTreeSet;
+use ibc::core::{
+    client::types::Height,
+    handler::types::events::IbcEvent,
+    host::types::identifiers::{ChannelId, PortId, Sequence},
+};
+use near_sdk::env;
+
+/// The key of the packet event index, which is the port id, channel id (on the sending chain),
+/// sequence of the packet and the type of the event.
+pub type PacketEventKey = (PortId, ChannelId, Sequence, String);
+
+/// The types of the packet events which are indexed.
+const INDEXED_EVENT_TYPES: [&str; 3] = ["send_packet", "recv_packet", "write_acknowledgement"];
+
+/// Whether the events of the given type are indexed.
+pub fn is_indexed_event_type(event_type: &str) -> bool {
+    INDEXED_EVENT_TYPES.contains(&event_type)
+}
+
+/// Get the key of the given event in the index, which is `None` if the event is not indexed.
+pub fn packet_event_key_of(event: &IbcEvent) -> Option<PacketEventKey> {
+    let (port_id, channel_id, sequence) = match event {
+        IbcEvent::SendPacket(send_packet) => (
+            send_packet.port_id_on_a(),
+            send_packet.chan_id_on_a(),
+            send_packet.seq_on_a(),
+        ),
+        IbcEvent::ReceivePacket(receive_packet) => (
+            receive_packet.port_id_on_a(),
+            receive_packet.chan_id_on_a(),
+            receive_packet.seq_on_b(),
+        ),
+        IbcEvent::WriteAcknowledgement(write_ack) => (
+            write_ack.port_id_on_a(),
+            write_ack.chan_id_on_a(),
+            write_ack.seq_on_a(),
+        ),
+        _ => return None,
+    };
+    Some((
+        port_id.clone(),
+        channel_id.clone(),
+        *sequence,
+        event.event_type().to_string(),
+    ))
+}
+
+impl NearIbcStore {
+    /// Add the given event emitted at the given height to the index.
+    pub fn index_packet_event(&mut self, height: &Height, event: &IbcEvent) {
+        if let Some(key) = packet_event_key_of(event) {
+            self.packet_event_heights.insert(key, *height);
+        }
+    }
+    /// Remove the given event emitted at the given height from the index.
+    fn unindex_packet_event(&mut self, height: &Height, event: &IbcEvent) {
+        if let Some(key) = packet_event_key_of(event) {
+            if self
+                .packet_event_heights
+                .get(&key)
+                .is_some_and(|indexed_height| indexed_height == height)
+            {
+                self.packet_event_heights.remove(&key);
+            }
+        }
+    }
+    /// Remove the oldest IBC events from the history, along with their index entries.
+    pub fn pop_front_ibc_events(&mut self) {
+        if let Some((Some(height), Some(events))) = self.ibc_events_history.pop_front() {
+            events
+                .iter()
+                .for_each(|event| self.unindex_packet_event(&height, event));
+        }
+    }
+    /// Remove the IBC events at the heights less than the given height (or all of them)
+    /// from the history, along with their index entries.
+    pub fn clear_ibc_events_history(
+        &mut self,
+        less_than_height: Option<&Height>,
+    ) -> ProcessingResult {
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        while let Some(first_height) = self.ibc_events_history.first_key() {
+            if less_than_height.is_some_and(|height| first_height >= height) {
+                break;
+            }
+            self.pop_front_ibc_events();
+            if env::used_gas() >= max_gas {
+                self.flush();
+                return ProcessingResult::NeedMoreGas;
+            }
+        }
+        if self.ibc_events_history.is_empty() {
+            self.ibc_events_history.clear(None);
+        }
+        self.flush();
+        ProcessingResult::Ok
+    }
+    /// Set the max length of the IBC events history, and remove the oldest events
+    /// (along with their index entries) which exceed the max length.
+    pub fn set_max_length_of_ibc_events_history(&mut self, max_length: u64) -> ProcessingResult {
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        while self.ibc_events_history.len() > max_length {
+            self.pop_front_ibc_events();
+            if env::used_gas() >= max_gas {
+                self.flush();
+                return ProcessingResult::NeedMoreGas;
+            }
+        }
+        let result = self.ibc_events_history.set_max_length(max_length);
+        self.flush();
+        result
+    }
+    /// Get the heights of the packet events of the given type, port id, channel id
+    /// (on the sending chain) and sequences, which are not greater than (or equal to)
+    /// the given height, in ascending order.
+    pub fn get_packet_event_heights(
+        &self,
+        event_type: &str,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        sequences: &[Sequence],
+        target_height: &Height,
+        search_in_range: bool,
+    ) -> Vec<Height> {
+        let matches_target = |height: &Height| match search_in_range {
+            true => height <= target_height,
+            false => height == target_height,
+        };
+        let mut heights: BTreeSet<Height> = sequences
+            .iter()
+            .filter_map(|sequence| {
+                self.packet_event_heights
+                    .get(&(
+                        port_id.clone(),
+                        channel_id.clone(),
+                        *sequence,
+                        event_type.to_string(),
+                    ))
+                    .cloned()
+            })
+            .filter(|height| matches_target(height))
+            .collect();
+        // The heights of the events which are not indexed.
+        for index in self.ibc_events_history.start_index()..self.ibc_events_history.end_index() + 1
+        {
+            match self.ibc_events_history.get_key_by_index(&index) {
+                Some(height) if height.revision_height() < self.packet_events_indexed_since => {
+                    if matches_target(height) {
+                        heights.insert(*height);
+                    }
+                }
+                _ => break,
+            }
+        }
+        heights.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::setup_store;
+    use ibc::core::{
+        channel::types::{
+            channel::Order,
+            events::{ReceivePacket, SendPacket},
+            packet::Packet,
+            timeout::TimeoutHeight,
+        },
+        host::{types::identifiers::ConnectionId, ExecutionContext},
+        primitives::Timestamp,
+    };
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    fn height(revision_height: u64) -> Height {
+        Height::new(0, revision_height).unwrap()
+    }
+
+    fn packet_of(channel_index: u64, sequence: u64) -> Packet {
+        Packet {
+            seq_on_a: sequence.into(),
+            port_id_on_a: PortId::transfer(),
+            chan_id_on_a: ChannelId::new(channel_index),
+            port_id_on_b: PortId::transfer(),
+            chan_id_on_b: ChannelId::new(channel_index + 1),
+            data: vec![],
+            timeout_height_on_b: TimeoutHeight::Never,
+            timeout_timestamp_on_b: Timestamp::none(),
+        }
+    }
+
+    fn send_packet_event(channel_index: u64, sequence: u64) -> IbcEvent {
+        IbcEvent::SendPacket(SendPacket::new(
+            packet_of(channel_index, sequence),
+            Order::Unordered,
+            ConnectionId::new(0),
+        ))
+    }
+
+    fn recv_packet_event(channel_index: u64, sequence: u64) -> IbcEvent {
+        IbcEvent::ReceivePacket(ReceivePacket::new(
+            packet_of(channel_index, sequence),
+            Order::Unordered,
+            ConnectionId::new(0),
+        ))
+    }
+
+    fn emit_at(store: &mut NearIbcStore, block_height: u64, event: IbcEvent) {
+        testing_env!(VMContextBuilder::new().block_height(block_height).build());
+        store.emit_ibc_event(event).unwrap();
+    }
+
+    fn heights_of(
+        store: &NearIbcStore,
+        event_type: &str,
+        sequences: &[u64],
+        target_height: u64,
+        search_in_range: bool,
+    ) -> Vec<u64> {
+        let sequences: Vec<Sequence> = sequences.iter().map(|seq| (*seq).into()).collect();
+        store
+            .get_packet_event_heights(
+                event_type,
+                &PortId::transfer(),
+                &ChannelId::new(0),
+                &sequences,
+                &height(target_height),
+                search_in_range,
+            )
+            .iter()
+            .map(|height| height.revision_height())
+            .collect()
+    }
+
+    /// Set up a store with `send_packet` events of sequence 1 to 3 of `channel-0`
+    /// at height 10 to 12, and a `recv_packet` event of sequence 1 at height 13.
+    fn setup() -> NearIbcStore {
+        let mut store = setup_store();
+        for sequence in 1..4 {
+            emit_at(&mut store, 9 + sequence, send_packet_event(0, sequence));
+        }
+        emit_at(&mut store, 13, send_packet_event(2, 1));
+        emit_at(&mut store, 13, recv_packet_event(0, 1));
+        store
+    }
+
+    #[test]
+    fn test_get_packet_event_heights() {
+        let store = setup();
+        assert_eq!(
+            heights_of(&store, "send_packet", &[1, 3], 13, true),
+            [10, 12]
+        );
+        assert_eq!(heights_of(&store, "send_packet", &[1, 3], 11, true), [10]);
+        assert_eq!(heights_of(&store, "send_packet", &[1, 3], 12, false), [12]);
+        assert!(heights_of(&store, "send_packet", &[4], 13, true).is_empty());
+        assert_eq!(heights_of(&store, "recv_packet", &[1, 2], 13, true), [13]);
+        assert!(heights_of(&store, "write_acknowledgement", &[1], 13, true).is_empty());
+    }
+
+    #[test]
+    fn test_unindex_removed_events() {
+        let mut store = setup();
+        let key_of = |sequence: u64| -> PacketEventKey {
+            (
+                PortId::transfer(),
+                ChannelId::new(0),
+                sequence.into(),
+                "send_packet".to_string(),
+            )
+        };
+        store.set_max_length_of_ibc_events_history(3);
+        assert!(store.packet_event_heights.get(&key_of(1)).is_none());
+        assert_eq!(
+            heights_of(&store, "send_packet", &[1, 2, 3], 13, true),
+            [11, 12]
+        );
+        store.clear_ibc_events_history(Some(&height(12)));
+        assert!(store.packet_event_heights.get(&key_of(2)).is_none());
+        assert_eq!(
+            heights_of(&store, "send_packet", &[1, 2, 3], 13, true),
+            [12]
+        );
+        store.clear_ibc_events_history(None);
+        assert!(heights_of(&store, "send_packet", &[3], 13, true).is_empty());
+        assert!(heights_of(&store, "recv_packet", &[1], 13, true).is_empty());
+    }
+
+    #[test]
+    fn test_scan_events_not_indexed() {
+        let mut store = setup_store();
+        store
+            .ibc_events_history
+            .push_back((height(5), vec![send_packet_event(0, 1)]));
+        store.packet_events_indexed_since = 8;
+        emit_at(&mut store, 10, send_packet_event(0, 2));
+        assert_eq!(heights_of(&store, "send_packet", &[2], 10, true), [5, 10]);
+        assert_eq!(heights_of(&store, "send_packet", &[2], 5, false), [5]);
+        store.clear_ibc_events_history(Some(&height(6)));
+        assert_eq!(heights_of(&store, "send_packet", &[2], 10, true), [10]);
+    }
+}
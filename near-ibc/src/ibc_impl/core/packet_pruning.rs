@@ -0,0 +1,715 @@
+//! Pruning of the packet receipts and acknowledgements of the packets received by this chain.
+//!
+//! The receipts and acknowledgements of a channel are pruned in ascending order of sequence,
+//! from the watermark of the channel (all of the receipts and acknowledgements below it
+//! have been pruned). A sequence can only be pruned when the receipt (for unordered channels)
+//! and the acknowledgement of it are both written, and the caller proves (by the client of
+//! the connection of the channel) that the commitment of the packet has been removed from
+//! the counterparty chain, which means the acknowledgement has been relayed back and the
+//! packet can never be relayed again. The latest `retention` receipts and acknowledgements
+//! of a channel are always kept, where `retention` is set by governance.
+//!
+//! The sequences of an unordered channel which have neither a receipt nor an acknowledgement
+//! (e.g. the packets timed out on the counterparty chain) are skipped by the watermark without
+//! deleting anything, if the absence of the commitments of them is also proved and a later
+//! sequence is pruned in the same call.
+//!
+//! The packets below the watermark are treated as received, so they can not be received again.
+use crate::{
+    context::NearIbcStore, ibc_impl::core::client_state::AnyClientState, prelude::*,
+    types::ProcessingResult, StorageKey,
+};
+use ibc::core::{
+    channel::types::channel::{ChannelEnd, Order},
+    client::{
+        context::{
+            client_state::{ClientStateCommon, ClientStateValidation},
+            consensus_state::ConsensusState,
+        },
+        types::{Height, Status},
+    },
+    commitment_types::commitment::CommitmentProofBytes,
+    host::{
+        types::{
+            identifiers::{ChannelId, PortId, Sequence},
+            path::{
+                AckPath, ChannelEndPath, ClientConsensusStatePath, CommitmentPath, Path,
+                ReceiptPath, SeqRecvPath,
+            },
+        },
+        ValidationContext,
+    },
+};
+use near_sdk::{
+    borsh, env,
+    json_types::{Base64VecU8, U64},
+    log,
+    serde::{Deserialize, Serialize},
+    store::{LookupMap, UnorderedSet},
+};
+
+/// The storage used by a record in NEAR protocol, besides the key and value of it.
+const STORAGE_BYTES_PER_RECORD: u64 = 40;
+
+/// The packet states stored for a channel, and the pruning status of it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PacketStateStorage {
+    pub commitments: u32,
+    pub receipts: u32,
+    pub acknowledgements: u32,
+    /// The estimated storage used by the packet states, in bytes.
+    pub storage_bytes: U64,
+    /// The number of the latest receipts and acknowledgements kept by pruning,
+    /// `None` if the pruning is not enabled for the channel.
+    pub retention: Option<U64>,
+    /// The receipts and acknowledgements below this sequence have been pruned.
+    pub pruned_below: Sequence,
+}
+
+/// The proof of the absence of the commitment of a packet on the counterparty chain.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PacketCommitmentAbsenceProof {
+    pub sequence: Sequence,
+    pub proof: Base64VecU8,
+}
+
+/// Estimate the storage used by a packet state of the given path and value,
+/// with the entry of its sequence in the sequence set with the given storage key.
+fn estimated_storage_bytes_per_packet_state(
+    path: String,
+    value_len: u64,
+    sequence_set_key: StorageKey,
+) -> u64 {
+    // The sequence set stores the sequence in a vector and a lookup map.
+    let sequence_set_entry_bytes = 2
+        * (STORAGE_BYTES_PER_RECORD + borsh::to_vec(&sequence_set_key).unwrap().len() as u64 + 16);
+    path.len() as u64 + value_len + STORAGE_BYTES_PER_RECORD + sequence_set_entry_bytes
+}
+
+fn count_of(
+    sequence_sets: &LookupMap<(PortId, ChannelId), UnorderedSet<Sequence>>,
+    port_channel_id: &(PortId, ChannelId),
+) -> u32 {
+    sequence_sets
+        .get(port_channel_id)
+        .map_or(0, |sequences| sequences.len())
+}
+
+impl NearIbcStore {
+    /// Set the number of the latest receipts and acknowledgements of the given channel
+    /// kept by pruning, or disable the pruning of the channel by `None`.
+    pub fn set_packet_state_retention(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        retention: Option<u64>,
+    ) {
+        let key = (port_id.clone(), channel_id.clone());
+        match retention {
+            Some(retention) => {
+                assert!(retention > 0, "ERR_INVALID_PACKET_STATE_RETENTION");
+                self.packet_state_retentions.insert(key, retention);
+            }
+            None => {
+                self.packet_state_retentions.remove(&key);
+            }
+        }
+        self.packet_state_retentions.flush();
+    }
+    /// Get the sequence below which the receipts and acknowledgements of the given channel
+    /// have been pruned.
+    pub fn pruned_packet_sequence(&self, port_id: &PortId, channel_id: &ChannelId) -> Sequence {
+        self.pruned_packet_sequences
+            .get(&(port_id.clone(), channel_id.clone()))
+            .cloned()
+            .unwrap_or(Sequence::from(1))
+    }
+    /// Whether the receipt of the given path has been pruned.
+    pub fn is_packet_receipt_pruned(&self, receipt_path: &ReceiptPath) -> bool {
+        receipt_path.sequence
+            < self.pruned_packet_sequence(&receipt_path.port_id, &receipt_path.channel_id)
+    }
+    /// Whether the receipt (for unordered channels) and acknowledgement of the given sequence
+    /// can be pruned.
+    fn is_packet_state_prunable(
+        &self,
+        port_channel_id: &(PortId, ChannelId),
+        sequence: &Sequence,
+        ordered: bool,
+        retention: u64,
+    ) -> bool {
+        if !self
+            .packet_acknowledgement_sequence_sets
+            .get(port_channel_id)
+            .is_some_and(|sequences| sequences.contains(sequence))
+        {
+            return false;
+        }
+        if ordered {
+            self.get_next_sequence_recv(&SeqRecvPath::new(&port_channel_id.0, &port_channel_id.1))
+                .is_ok_and(|next_sequence_recv| {
+                    u64::from(*sequence).saturating_add(retention) < u64::from(next_sequence_recv)
+                })
+        } else {
+            self.packet_receipt_sequence_sets
+                .get(port_channel_id)
+                .is_some_and(|sequences| {
+                    sequences.contains(sequence) && u64::from(sequences.len()) > retention
+                })
+        }
+    }
+    /// Whether the given sequence of an unordered channel has neither a receipt
+    /// nor an acknowledgement.
+    fn is_packet_state_missing(
+        &self,
+        port_channel_id: &(PortId, ChannelId),
+        sequence: &Sequence,
+    ) -> bool {
+        [
+            &self.packet_receipt_sequence_sets,
+            &self.packet_acknowledgement_sequence_sets,
+        ]
+        .iter()
+        .all(|sequence_sets| {
+            !sequence_sets
+                .get(port_channel_id)
+                .is_some_and(|sequences| sequences.contains(sequence))
+        })
+    }
+    /// Verify the absence of the commitments of the given packets on the counterparty chain
+    /// of the given channel, by the client of the connection of the channel.
+    fn verify_packet_commitment_absence(
+        &self,
+        channel_end: &ChannelEnd,
+        proof_height: Height,
+        absence_proof: &PacketCommitmentAbsenceProof,
+    ) -> Result<(), String> {
+        let counterparty = channel_end.counterparty();
+        let counterparty_channel_id = counterparty
+            .channel_id()
+            .ok_or_else(|| "ERR_COUNTERPARTY_CHANNEL_NOT_FOUND".to_string())?;
+        let connection_id = channel_end
+            .connection_hops()
+            .first()
+            .ok_or_else(|| "ERR_CONNECTION_NOT_FOUND".to_string())?;
+        let connection_end = self
+            .connection_end(connection_id)
+            .map_err(|e| format!("ERR_CONNECTION_NOT_FOUND: {:?}", e))?;
+        let client_id = connection_end.client_id();
+        let client_state = self
+            .client_state(client_id)
+            .map_err(|e| format!("ERR_CLIENT_STATE_NOT_FOUND: {:?}", e))?;
+        // The proofs of solo machines take the sequences of the clients, which are only
+        // increased by the messages delivered.
+        if matches!(client_state, AnyClientState::SoloMachine(_)) {
+            return Err("ERR_PACKET_STATE_PRUNING_NOT_SUPPORTED_BY_SOLO_MACHINE".to_string());
+        }
+        match client_state
+            .status(self, client_id)
+            .map_err(|e| format!("ERR_CLIENT_STATUS: {:?}", e))?
+        {
+            Status::Active => (),
+            status => return Err(format!("ERR_CLIENT_NOT_ACTIVE: {:?}", status)),
+        }
+        client_state
+            .validate_proof_height(proof_height)
+            .map_err(|e| format!("ERR_INVALID_PROOF_HEIGHT: {:?}", e))?;
+        let consensus_state = self
+            .consensus_state(&ClientConsensusStatePath::new(
+                client_id.clone(),
+                proof_height.revision_number(),
+                proof_height.revision_height(),
+            ))
+            .map_err(|e| format!("ERR_CONSENSUS_STATE_NOT_FOUND: {:?}", e))?;
+        let proof = CommitmentProofBytes::try_from(absence_proof.proof.0.clone())
+            .map_err(|e| format!("ERR_INVALID_PROOF: {:?}", e))?;
+        client_state
+            .verify_non_membership(
+                connection_end.counterparty().prefix(),
+                &proof,
+                consensus_state.root(),
+                Path::Commitment(CommitmentPath::new(
+                    counterparty.port_id(),
+                    counterparty_channel_id,
+                    absence_proof.sequence,
+                )),
+            )
+            .map_err(|e| {
+                format!(
+                    "ERR_INVALID_PACKET_COMMITMENT_ABSENCE_PROOF: sequence {}, {:?}",
+                    absence_proof.sequence, e
+                )
+            })
+    }
+    /// Prune the receipts and acknowledgements of the given channel from the watermark
+    /// of the channel, with the proofs (at `proof_height`) of the absence of the commitments
+    /// of the packets on the counterparty chain. The pruning stops at the first sequence
+    /// which has no proof or can not be pruned, except the sequences of an unordered channel
+    /// which have neither a receipt nor an acknowledgement, which are skipped only if
+    /// a later sequence is pruned.
+    ///
+    /// Returns `ProcessingResult::NeedMoreGas` if the gas is running out before all of the
+    /// proved sequences are processed, and `ProcessingResult::Error` if a proof is invalid,
+    /// while the sequences pruned before are kept pruned.
+    pub fn prune_packet_state(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        proof_height: Height,
+        mut absence_proofs: Vec<PacketCommitmentAbsenceProof>,
+    ) -> ProcessingResult {
+        let port_channel_id = (port_id.clone(), channel_id.clone());
+        let retention = match self.packet_state_retentions.get(&port_channel_id) {
+            Some(retention) => *retention,
+            None => {
+                return ProcessingResult::Error("ERR_PACKET_STATE_PRUNING_NOT_ENABLED".to_string())
+            }
+        };
+        let channel_end = match self.channel_end(&ChannelEndPath::new(port_id, channel_id)) {
+            Ok(channel_end) => channel_end,
+            Err(_) => return ProcessingResult::Error("ERR_CHANNEL_NOT_FOUND".to_string()),
+        };
+        let ordered = *channel_end.ordering() == Order::Ordered;
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        let used_bytes = env::storage_usage();
+        let from_sequence = self.pruned_packet_sequence(port_id, channel_id);
+        absence_proofs.sort_by_key(|absence_proof| absence_proof.sequence);
+        absence_proofs.dedup_by_key(|absence_proof| absence_proof.sequence);
+        absence_proofs.retain(|absence_proof| absence_proof.sequence >= from_sequence);
+        let proof_count = absence_proofs.len();
+        let mut sequence = from_sequence;
+        // The watermark can only be moved after the last pruned sequence, so that the skipped
+        // sequences are never the ones not sent by the counterparty chain yet.
+        let mut pruned_below = from_sequence;
+        let mut result = ProcessingResult::Ok;
+        for (index, absence_proof) in absence_proofs.iter().enumerate() {
+            if absence_proof.sequence != sequence {
+                break;
+            }
+            let missing = !ordered && self.is_packet_state_missing(&port_channel_id, &sequence);
+            if !missing
+                && !self.is_packet_state_prunable(&port_channel_id, &sequence, ordered, retention)
+            {
+                break;
+            }
+            if let Err(err) =
+                self.verify_packet_commitment_absence(&channel_end, proof_height, absence_proof)
+            {
+                result = ProcessingResult::Error(err);
+                break;
+            }
+            if !missing {
+                if !ordered {
+                    self.remove_with_storage_payer(
+                        &ReceiptPath::new(port_id, channel_id, sequence)
+                            .to_string()
+                            .into_bytes(),
+                    );
+                    self.packet_receipt_sequence_sets
+                        .get_mut(&port_channel_id)
+                        .map(|sequences| {
+                            sequences.remove(&sequence);
+                            sequences.flush();
+                        });
+                }
+                self.remove_with_storage_payer(
+                    &AckPath::new(port_id, channel_id, sequence)
+                        .to_string()
+                        .into_bytes(),
+                );
+                self.packet_acknowledgement_sequence_sets
+                    .get_mut(&port_channel_id)
+                    .map(|sequences| {
+                        sequences.remove(&sequence);
+                        sequences.flush();
+                    });
+            }
+            sequence = sequence.increment();
+            if !missing {
+                pruned_below = sequence;
+            }
+            if index + 1 < proof_count && env::used_gas() >= max_gas {
+                result = ProcessingResult::NeedMoreGas;
+                break;
+            }
+        }
+        if pruned_below > from_sequence {
+            self.pruned_packet_sequences
+                .insert(port_channel_id.clone(), pruned_below);
+            self.flush();
+            log!(
+                r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_STATE_PRUNED","port_id":"{}","channel_id":"{}","from_sequence":"{}","to_sequence":"{}","released_bytes":"{}"}}"#,
+                port_id,
+                channel_id,
+                from_sequence,
+                pruned_below,
+                used_bytes.saturating_sub(env::storage_usage())
+            );
+        }
+        result
+    }
+    /// Get the packet states stored for the given channel, and the pruning status of it.
+    pub fn get_packet_state_storage(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> PacketStateStorage {
+        let port_channel_id = (port_id.clone(), channel_id.clone());
+        let commitments = count_of(&self.packet_commitment_sequence_sets, &port_channel_id);
+        let receipts = count_of(&self.packet_receipt_sequence_sets, &port_channel_id);
+        let acknowledgements =
+            count_of(&self.packet_acknowledgement_sequence_sets, &port_channel_id);
+        let pruned_below = self.pruned_packet_sequence(port_id, channel_id);
+        // The storage of the packet states is estimated by the paths of the watermark.
+        let storage_bytes = u64::from(commitments)
+            * estimated_storage_bytes_per_packet_state(
+                CommitmentPath::new(port_id, channel_id, pruned_below).to_string(),
+                32,
+                StorageKey::PacketCommitmentSequenceSet {
+                    port_id: port_id.clone(),
+                    channel_id: channel_id.clone(),
+                },
+            )
+            + u64::from(receipts)
+                * estimated_storage_bytes_per_packet_state(
+                    ReceiptPath::new(port_id, channel_id, pruned_below).to_string(),
+                    1,
+                    StorageKey::PacketReceiptSequenceSet {
+                        port_id: port_id.clone(),
+                        channel_id: channel_id.clone(),
+                    },
+                )
+            + u64::from(acknowledgements)
+                * estimated_storage_bytes_per_packet_state(
+                    AckPath::new(port_id, channel_id, pruned_below).to_string(),
+                    32,
+                    StorageKey::PacketAcknowledgementSequenceSet {
+                        port_id: port_id.clone(),
+                        channel_id: channel_id.clone(),
+                    },
+                );
+        PacketStateStorage {
+            commitments,
+            receipts,
+            acknowledgements,
+            storage_bytes: U64(storage_bytes),
+            retention: self
+                .packet_state_retentions
+                .get(&port_channel_id)
+                .map(|retention| U64(*retention)),
+            pruned_below,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibc_impl::clients::localhost::localhost_connection_id;
+    use ibc::core::{
+        channel::types::{
+            channel::{Counterparty, State},
+            commitment::AcknowledgementCommitment,
+            packet::Receipt,
+            Version,
+        },
+        host::ExecutionContext,
+    };
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, Gas};
+
+    fn context() -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id("v1.nearibc.near".parse().unwrap())
+            .predecessor_account_id("relayer.near".parse().unwrap())
+            .block_height(100);
+        context
+    }
+
+    fn port_id() -> PortId {
+        PortId::transfer()
+    }
+
+    fn channel_id() -> ChannelId {
+        ChannelId::new(0)
+    }
+
+    /// Set up an unordered channel on the localhost connection, whose counterparty is
+    /// `transfer/channel-1`, with the receipts and acknowledgements of the sequences `1..=sequences`.
+    fn setup(sequences: u64, retention: u64) -> NearIbcStore {
+        setup_with(1..=sequences, retention)
+    }
+
+    /// Set up the channel with the receipts and acknowledgements of the given sequences.
+    fn setup_with(sequences: impl Iterator<Item = u64>, retention: u64) -> NearIbcStore {
+        testing_env!(context().build());
+        let mut store = NearIbcStore::new();
+        store
+            .store_channel(
+                &ChannelEndPath::new(&port_id(), &channel_id()),
+                ChannelEnd::new(
+                    State::Open,
+                    Order::Unordered,
+                    Counterparty::new(port_id(), Some(ChannelId::new(1))),
+                    vec![localhost_connection_id()],
+                    Version::new("ics20-1".to_string()),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        for sequence in sequences {
+            // Reset the used gas for setting up a large number of packet states.
+            testing_env!(context().build());
+            let sequence = Sequence::from(sequence);
+            store
+                .store_packet_receipt(
+                    &ReceiptPath::new(&port_id(), &channel_id(), sequence),
+                    Receipt::Ok,
+                )
+                .unwrap();
+            store
+                .store_packet_acknowledgement(
+                    &AckPath::new(&port_id(), &channel_id(), sequence),
+                    AcknowledgementCommitment::from(vec![1; 32]),
+                )
+                .unwrap();
+        }
+        store.set_packet_state_retention(&port_id(), &channel_id(), Some(retention));
+        store.flush();
+        testing_env!(context().build());
+        store
+    }
+
+    fn proofs_of(sequences: impl Iterator<Item = u64>) -> Vec<PacketCommitmentAbsenceProof> {
+        sequences
+            .map(|sequence| PacketCommitmentAbsenceProof {
+                sequence: Sequence::from(sequence),
+                proof: Base64VecU8(vec![0]),
+            })
+            .collect()
+    }
+
+    fn proof_height() -> Height {
+        Height::new(0, 100).unwrap()
+    }
+
+    #[test]
+    fn test_prune_packet_state_advances_watermark() {
+        let mut store = setup(5, 2);
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(1..=5));
+        assert!(matches!(result, ProcessingResult::Ok));
+        // The latest 2 receipts are kept by the retention.
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(4)
+        );
+        let storage = store.get_packet_state_storage(&port_id(), &channel_id());
+        assert_eq!(storage.receipts, 2);
+        assert_eq!(storage.acknowledgements, 2);
+        assert!(store.is_packet_receipt_pruned(&ReceiptPath::new(
+            &port_id(),
+            &channel_id(),
+            Sequence::from(3)
+        )));
+        assert!(!store.is_packet_receipt_pruned(&ReceiptPath::new(
+            &port_id(),
+            &channel_id(),
+            Sequence::from(4)
+        )));
+        // The sequences below the watermark are ignored.
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(1..=3));
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(4)
+        );
+    }
+
+    #[test]
+    fn test_prune_packet_state_stops_at_gap() {
+        let mut store = setup(6, 1);
+        // The proof of sequence 3 is missing.
+        let result = store.prune_packet_state(
+            &port_id(),
+            &channel_id(),
+            proof_height(),
+            proofs_of([1, 2, 4, 5].into_iter()),
+        );
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(3)
+        );
+        // The acknowledgement of sequence 4 is not written.
+        store
+            .delete_packet_acknowledgement(&AckPath::new(
+                &port_id(),
+                &channel_id(),
+                Sequence::from(4),
+            ))
+            .unwrap();
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(3..=6));
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(4)
+        );
+    }
+
+    #[test]
+    fn test_prune_packet_state_skips_missing_sequences() {
+        // The packets of sequence 3 and 4 are timed out, and the packet of sequence 8
+        // is not sent yet.
+        let mut store = setup_with([1, 2, 5, 6, 7].into_iter(), 1);
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(1..=2));
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(3)
+        );
+        // The missing sequences are not skipped without a later sequence pruned.
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(3..=4));
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(3)
+        );
+        // The missing sequences can not be skipped if the commitment still exists.
+        env::storage_write(
+            &CommitmentPath::new(&port_id(), &ChannelId::new(1), Sequence::from(4))
+                .to_string()
+                .into_bytes(),
+            &[1; 32],
+        );
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(3..=6));
+        assert!(matches!(result, ProcessingResult::Error(_)));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(3)
+        );
+        env::storage_remove(
+            &CommitmentPath::new(&port_id(), &ChannelId::new(1), Sequence::from(4))
+                .to_string()
+                .into_bytes(),
+        );
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(3..=8));
+        assert!(matches!(result, ProcessingResult::Ok));
+        // The latest receipt (of sequence 7) is kept by the retention.
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(7)
+        );
+        let storage = store.get_packet_state_storage(&port_id(), &channel_id());
+        assert_eq!(storage.receipts, 1);
+        assert_eq!(storage.acknowledgements, 1);
+    }
+
+    #[test]
+    fn test_prune_packet_state_rejects_existing_commitment() {
+        let mut store = setup(5, 1);
+        // The packet of sequence 3 is still committed on the counterparty chain.
+        env::storage_write(
+            &CommitmentPath::new(&port_id(), &ChannelId::new(1), Sequence::from(3))
+                .to_string()
+                .into_bytes(),
+            &[1; 32],
+        );
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(1..=5));
+        assert!(matches!(result, ProcessingResult::Error(_)));
+        // The sequences pruned before the invalid proof are kept pruned.
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(3)
+        );
+        assert_eq!(
+            store
+                .get_packet_state_storage(&port_id(), &channel_id())
+                .receipts,
+            3
+        );
+        // The proof height must not be greater than the latest height of the client.
+        env::storage_remove(
+            &CommitmentPath::new(&port_id(), &ChannelId::new(1), Sequence::from(3))
+                .to_string()
+                .into_bytes(),
+        );
+        let result = store.prune_packet_state(
+            &port_id(),
+            &channel_id(),
+            Height::new(0, 101).unwrap(),
+            proofs_of(3..=5),
+        );
+        assert!(matches!(result, ProcessingResult::Error(_)));
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(3..=5));
+        assert!(matches!(result, ProcessingResult::Ok));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(5)
+        );
+    }
+
+    #[test]
+    fn test_prune_packet_state_not_enabled() {
+        let mut store = setup(3, 1);
+        store.set_packet_state_retention(&port_id(), &channel_id(), None);
+        let result =
+            store.prune_packet_state(&port_id(), &channel_id(), proof_height(), proofs_of(1..=3));
+        assert!(matches!(result, ProcessingResult::Error(_)));
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(1)
+        );
+    }
+
+    #[test]
+    fn test_prune_packet_state_in_chunks() {
+        let mut store = setup(200, 1);
+        testing_env!(context().prepaid_gas(Gas::from_tgas(30)).build());
+        let result = store.prune_packet_state(
+            &port_id(),
+            &channel_id(),
+            proof_height(),
+            proofs_of(1..=200),
+        );
+        assert!(matches!(result, ProcessingResult::NeedMoreGas));
+        let pruned_below = u64::from(store.pruned_packet_sequence(&port_id(), &channel_id()));
+        assert!(pruned_below > 1 && pruned_below < 200);
+        // Continue the pruning until all of the proved sequences are processed.
+        let mut calls = 1;
+        loop {
+            testing_env!(context().prepaid_gas(Gas::from_tgas(30)).build());
+            calls += 1;
+            match store.prune_packet_state(
+                &port_id(),
+                &channel_id(),
+                proof_height(),
+                proofs_of(1..=200),
+            ) {
+                ProcessingResult::NeedMoreGas => assert!(calls < 200),
+                ProcessingResult::Ok => break,
+                ProcessingResult::Error(err) => panic!("{}", err),
+            }
+        }
+        assert_eq!(
+            store.pruned_packet_sequence(&port_id(), &channel_id()),
+            Sequence::from(200)
+        );
+        assert_eq!(
+            store
+                .get_packet_state_storage(&port_id(), &channel_id())
+                .receipts,
+            1
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use crate::prelude::*;
-use crate::NearIbcContract;
+use crate::{
+    ibc_impl::applications::{ica, nft_transfer},
+    NearIbcContract,
+};
 use ibc::core::{
     host::types::identifiers::PortId,
     router::{module::Module, router::Router, types::module::ModuleId},
@@ -11,6 +14,9 @@ impl Router for NearIbcContract {
         match module_id.to_string().as_str() {
             ibc::apps::transfer::types::MODULE_ID_STR => Some(&self.module_holder.transfer_module),
             octopus_lpos::MODULE_ID_STR => Some(&self.module_holder.octopus_lpos_module),
+            ica::CONTROLLER_MODULE_ID_STR => Some(&self.module_holder.ica_controller_module),
+            ica::HOST_MODULE_ID_STR => Some(&self.module_holder.ica_host_module),
+            nft_transfer::MODULE_ID_STR => Some(&self.module_holder.nft_transfer_module),
             _ => None,
         }
     }
@@ -21,6 +27,9 @@ impl Router for NearIbcContract {
                 Some(&mut self.module_holder.transfer_module)
             }
             octopus_lpos::MODULE_ID_STR => Some(&mut self.module_holder.octopus_lpos_module),
+            ica::CONTROLLER_MODULE_ID_STR => Some(&mut self.module_holder.ica_controller_module),
+            ica::HOST_MODULE_ID_STR => Some(&mut self.module_holder.ica_host_module),
+            nft_transfer::MODULE_ID_STR => Some(&mut self.module_holder.nft_transfer_module),
             _ => None,
         }
     }
@@ -1,5 +1,11 @@
-use super::{client_state::AnyClientState, consensus_state::AnyConsensusState};
-use crate::{context::NearIbcStore, prelude::*};
+use super::{client_state::AnyClientState, consensus_state::AnyConsensusState, host};
+use crate::{
+    context::NearIbcStore,
+    ibc_impl::clients::localhost::{
+        self, client_state::LocalhostClientState, consensus_state::LocalhostConsensusState,
+    },
+    prelude::*,
+};
 use core::{str::FromStr, time::Duration};
 use ibc::{
     core::{
@@ -51,10 +57,15 @@ impl ValidationContext for NearIbcStore {
     type AnyClientState = AnyClientState;
 
     fn client_state(&self, client_id: &ClientId) -> Result<Self::AnyClientState, ContextError> {
+        if *client_id == localhost::localhost_client_id() {
+            return Ok(AnyClientState::Localhost(LocalhostClientState::new(
+                self.host_height()?,
+            )));
+        }
         let client_state_key = ClientStatePath(client_id.clone()).to_string().into_bytes();
         match env::storage_read(&client_state_key) {
             Some(data) => {
-                let result: AnyClientState =
+                let mut result: AnyClientState =
                     Protobuf::<Any>::decode_vec(&data).map_err(|e| ClientError::Other {
                         description: format!(
                             "Decode ClientState failed for client id {}: {:?}",
@@ -62,6 +73,9 @@ impl ValidationContext for NearIbcStore {
                         )
                         .to_string(),
                     })?;
+                if let AnyClientState::SoloMachine(client_state) = &mut result {
+                    client_state.client_id = Some(client_id.clone());
+                }
                 Ok(result)
             }
             None => Err(ContextError::ClientError(
@@ -80,6 +94,11 @@ impl ValidationContext for NearIbcStore {
         &self,
         client_cons_state_path: &ClientConsensusStatePath,
     ) -> Result<Self::AnyConsensusState, ContextError> {
+        if client_cons_state_path.client_id == localhost::localhost_client_id() {
+            return Ok(AnyConsensusState::Localhost(LocalhostConsensusState::new(
+                env::block_timestamp(),
+            )));
+        }
         let consensus_state_key = client_cons_state_path.to_string().into_bytes();
         match env::storage_read(&consensus_state_key) {
             Some(data) => {
@@ -112,11 +131,9 @@ impl ValidationContext for NearIbcStore {
 
     fn host_consensus_state(
         &self,
-        _height: &Height,
+        height: &Height,
     ) -> Result<Self::AnyConsensusState, ContextError> {
-        Err(ContextError::ClientError(ClientError::ClientSpecific {
-            description: format!("The `host_consensus_state` is not supported on NEAR protocol."),
-        }))
+        self.get_host_consensus_state(height)
     }
 
     fn client_counter(&self) -> Result<u64, ContextError> {
@@ -124,6 +141,11 @@ impl ValidationContext for NearIbcStore {
     }
 
     fn connection_end(&self, conn_id: &ConnectionId) -> Result<ConnectionEnd, ContextError> {
+        if *conn_id == localhost::localhost_connection_id() {
+            return Ok(localhost::localhost_connection_end(
+                self.commitment_prefix(),
+            ));
+        }
         let path = ConnectionPath(conn_id.clone());
         let connection_end_key = path.to_string().into_bytes();
         match env::storage_read(&connection_end_key) {
@@ -144,11 +166,9 @@ impl ValidationContext for NearIbcStore {
 
     fn validate_self_client(
         &self,
-        _client_state_of_host_on_counterparty: Any,
+        client_state_of_host_on_counterparty: Any,
     ) -> Result<(), ContextError> {
-        // As we can not validate the client state of self chain,
-        // we return OK until we can find a way to do this.
-        Ok(())
+        host::validate_self_client(client_state_of_host_on_counterparty)
     }
 
     fn commitment_prefix(&self) -> CommitmentPrefix {
@@ -252,6 +272,10 @@ impl ValidationContext for NearIbcStore {
     }
 
     fn get_packet_receipt(&self, receipt_path: &ReceiptPath) -> Result<Receipt, ContextError> {
+        // The packets whose receipts have been pruned are treated as received.
+        if self.is_packet_receipt_pruned(receipt_path) {
+            return Ok(Receipt::Ok);
+        }
         let receipt_key = receipt_path.to_string().into_bytes();
         match env::storage_read(&receipt_key) {
             Some(data) => {
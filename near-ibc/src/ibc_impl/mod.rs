@@ -1,2 +1,3 @@
 pub mod applications;
+pub mod clients;
 pub mod core;
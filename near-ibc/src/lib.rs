@@ -11,7 +11,19 @@ extern crate alloc;
 #[cfg(any(test, feature = "std"))]
 extern crate std;
 
-use crate::{context::NearIbcStore, ibc_impl::applications::transfer::TransferModule, prelude::*};
+use crate::{
+    context::NearIbcStore,
+    governance::GovernanceCouncil,
+    ibc_impl::{
+        applications::transfer::TransferModule,
+        core::{
+            channel_upgrade::{self, ChannelUpgradeContext},
+            packet_pruning::PacketCommitmentAbsenceProof,
+        },
+    },
+    pause::PauseFlags,
+    prelude::*,
+};
 use core::str::FromStr;
 use ibc::{
     apps::transfer::types::{
@@ -33,12 +45,12 @@ use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     collections::LazyOption,
     env,
-    json_types::{Base64VecU8, U128},
+    json_types::{Base58CryptoHash, Base64VecU8, U128},
     log, near_bindgen,
     serde::{Deserialize, Serialize},
     serde_json,
     store::LookupMap,
-    AccountId, BorshStorageKey, NearToken, PanicOnDefault,
+    AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise,
 };
 use octopus_lpos::msgs::MsgValidatorSetChange;
 use types::*;
@@ -47,7 +59,7 @@ use utils::{
         ext_channel_escrow, ext_escrow_factory, ext_process_transfer_request_callback,
         ext_token_factory, TransferRequestHandler,
     },
-    types::{AssetDenom, CrossChainAsset, Ics20TransferRequest},
+    types::{AssetDenom, CrossChainAsset, Ics20TransferRequest, Role, RoleHolders},
     ExtraDepositCost,
 };
 
@@ -55,11 +67,16 @@ pub mod collections;
 mod context;
 mod events;
 mod ext_interfaces;
+pub mod governance;
 mod ibc_impl;
 pub mod migration;
 mod module_holder;
+pub mod pause;
 mod prelude;
+pub mod storage_deposit;
 mod sudo_functions;
+#[cfg(test)]
+mod test_fixtures;
 mod testnet_functions;
 pub mod types;
 pub mod viewer;
@@ -106,6 +123,41 @@ pub enum StorageKey {
     IbcEventsHistoryIndexMap,
     IbcEventsHistoryValueMap,
     ChainIdChannelMap,
+    InFlightForwards,
+    IcaControllerAccounts,
+    IcaControllerAccountsOfOwner {
+        owner: AccountId,
+    },
+    IcaHostAccounts,
+    InterchainAccountWasm,
+    NftTransferWrappedClassIdSet,
+    FeeEnabledChannels,
+    FeePayees,
+    FeeCounterpartyPayees,
+    EscrowedPacketFees,
+    FeeAsyncAckForwardRelayers,
+    RateLimits,
+    GovernanceProposals,
+    WasmLightClientContracts,
+    HostConsensusStatesIndexMap,
+    HostConsensusStatesValueMap,
+    HostClientParams,
+    PacketEventHeights,
+    PacketStateRetentions,
+    PrunedPacketSequences,
+    StorageDeposits,
+    StoragePayers,
+    ClientConsensusStateHeights,
+    ClientConsensusStateHeightsIndexMap {
+        client_id: ClientId,
+    },
+    ClientConsensusStateHeightsValueMap {
+        client_id: ClientId,
+    },
+    ConsensusStateRetentions,
+    EscrowedForwardVouchers,
+    UnpaidTokenFees,
+    EscrowedForwardTokens,
 }
 
 #[near_bindgen]
@@ -116,6 +168,14 @@ pub struct NearIbcContract {
     /// To support the mutable borrow in `Router::get_route_mut`.
     module_holder: ModuleHolder,
     governance_account: AccountId,
+    /// The emergency pause switches of function `deliver`.
+    pause_flags: PauseFlags,
+    /// The council which can propose and approve the calls of sudo functions.
+    governance_council: GovernanceCouncil,
+    /// The holders of the roles for calling sudo functions.
+    role_holders: RoleHolders,
+    /// The account proposed to be the new governance account, which needs to accept it.
+    pending_governance_account: Option<AccountId>,
 }
 
 #[near_bindgen]
@@ -124,10 +184,15 @@ impl NearIbcContract {
     #[init]
     pub fn init(appchain_registry_account: AccountId) -> Self {
         env::storage_write("version".as_bytes(), VERSION.as_bytes());
+        ibc_impl::core::host::init_host_client_params();
         Self {
             near_ibc_store: LazyOption::new(StorageKey::NearIbcStore, Some(&NearIbcStore::new())),
             governance_account: env::current_account_id(),
             module_holder: ModuleHolder::new(appchain_registry_account),
+            pause_flags: PauseFlags::default(),
+            governance_council: GovernanceCouncil::new(),
+            role_holders: RoleHolders::default(),
+            pending_governance_account: None,
         }
     }
     ///
@@ -137,6 +202,14 @@ impl NearIbcContract {
     ///
     #[payable]
     pub fn deliver(&mut self, messages: Vec<Any>) {
+        assert!(!self.pause_flags.deliver_paused, "ERR_DELIVER_PAUSED");
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        assert!(
+            near_ibc_store
+                .unmigrated_consensus_state_height_clients
+                .is_empty(),
+            "ERR_CONSENSUS_STATE_HEIGHTS_NOT_MIGRATED"
+        );
         assert!(
             env::attached_deposit().as_yoctonear()
                 >= utils::MINIMUM_DEPOSIT_FOR_DELEVER_MSG * messages.len() as u128,
@@ -146,37 +219,111 @@ impl NearIbcContract {
         let used_bytes = env::storage_usage();
         ExtraDepositCost::reset();
         // Deliver messages to `ibc-rs`
-        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.storage_payer = Some(env::predecessor_account_id());
+        // The consensus state of the current height may be needed by the counterparty chains
+        // in the connection handshakes, even if no IBC event is emitted by these messages.
+        near_ibc_store.record_host_consensus_state(Height::new(0, env::block_height()).unwrap());
 
         let mut errors_count = 0;
-        messages
-            .into_iter()
-            .for_each(|msg| match MsgEnvelope::try_from(msg.clone()) {
-                Ok(msg) => match ibc::core::handler::entrypoint::dispatch(
-                    &mut near_ibc_store,
-                    self,
-                    msg.clone(),
-                ) {
-                    Ok(()) => (),
-                    Err(e) => {
-                        log!("Error occurred in processing message: {:?}, {:?}", msg, e);
+        messages.into_iter().for_each(|msg| {
+            if channel_upgrade::is_channel_upgrade_message(&msg.type_url) {
+                let result =
+                    channel_upgrade::port_and_channel_of(&msg).and_then(|(port_id, channel_id)| {
+                        self.pause_flags
+                            .check_channel_upgrade(&port_id, &channel_id)?;
+                        ChannelUpgradeContext {
+                            store: &mut near_ibc_store,
+                            modules: &mut self.module_holder,
+                        }
+                        .dispatch(msg.clone())
+                    });
+                near_ibc_store.commit_solo_machine_proofs(result.is_ok());
+                if let Err(e) = result {
+                    log!("Error occurred in processing message: {:?}, {}", msg, e);
+                    errors_count += 1;
+                }
+                return;
+            }
+            match MsgEnvelope::try_from(msg.clone()) {
+                Ok(msg) => {
+                    let result = self.pause_flags.check_message(&msg).and_then(|_| {
+                        ibc::core::handler::entrypoint::dispatch(
+                            &mut near_ibc_store,
+                            self,
+                            msg.clone(),
+                        )
+                        .map_err(|e| format!("{:?}", e))
+                    });
+                    near_ibc_store.commit_solo_machine_proofs(result.is_ok());
+                    if let Err(e) = result {
+                        log!("Error occurred in processing message: {:?}, {}", msg, e);
                         errors_count += 1;
                     }
-                },
+                }
                 Err(e) => {
                     log!("Error occurred in routing message: {:?}, {:?}", msg, e);
                     errors_count += 1;
                 }
-            });
+            }
+        });
         if errors_count > 0 {
             log!(
                 r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ERR_DELIVER_MESSAGE"}}"#,
             );
         }
+        // Process the tasks of packet forward middleware.
+        near_ibc_store.process_forward_tasks();
         near_ibc_store.flush();
         self.near_ibc_store.set(&near_ibc_store);
-        // Refund unused deposit.
-        utils::refund_deposit(used_bytes);
+        // Refund unused deposit. The released storage credited to the storage payers
+        // is not counted as released by this call.
+        utils::refund_deposit(used_bytes.saturating_sub(near_ibc_store.released_storage_bytes));
+    }
+    /// Prune the packet receipts and acknowledgements of the given channel whose pruning
+    /// is enabled by governance, with the proofs (at `proof_height`) of the absence of the
+    /// commitments of the packets on the counterparty chain. Anyone can call this function.
+    pub fn prune_packet_state(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        proof_height: Height,
+        absence_proofs: Vec<PacketCommitmentAbsenceProof>,
+    ) -> ProcessingResult {
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let result =
+            near_ibc_store.prune_packet_state(&port_id, &channel_id, proof_height, absence_proofs);
+        self.near_ibc_store.set(&near_ibc_store);
+        result
+    }
+    /// Withdraw the given amount (or all) of the storage deposit balance of the caller,
+    /// which is credited by the released storage paid by the caller.
+    #[payable]
+    pub fn withdraw_storage_deposit(&mut self, amount: Option<U128>) -> U128 {
+        near_sdk::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let amount =
+            near_ibc_store.withdraw_storage_deposit(&account_id, amount.map(|amount| amount.0));
+        self.near_ibc_store.set(&near_ibc_store);
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(amount + 1));
+        U128(amount)
+    }
+    /// Accept the proposal of transferring the governance account to the caller.
+    pub fn accept_governance_account(&mut self) {
+        let new_governance_account = env::predecessor_account_id();
+        assert_eq!(
+            self.pending_governance_account.as_ref(),
+            Some(&new_governance_account),
+            "ERR_NOT_PENDING_GOVERNANCE_ACCOUNT"
+        );
+        let old_governance_account =
+            core::mem::replace(&mut self.governance_account, new_governance_account.clone());
+        self.pending_governance_account = None;
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"GOVERNANCE_ACCOUNT_TRANSFERRED","old_governance_account":"{}","new_governance_account":"{}"}}"#,
+            old_governance_account,
+            new_governance_account
+        );
     }
     // Assert that the caller is the preset governance account.
     fn assert_governance(&self) {
@@ -186,6 +333,23 @@ impl NearIbcContract {
             "ERR_NOT_GOVERNANCE_ACCOUNT"
         );
     }
+    // Assert that the caller is the preset governance account or has the given role.
+    fn assert_role(&self, role: Role) {
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            predecessor == self.governance_account
+                || self.role_holders.has_role(role, &predecessor),
+            "ERR_NOT_GOVERNANCE_ACCOUNT_OR_ROLE_HOLDER"
+        );
+    }
+    // Assert that the caller is the preset governance account, or has role `Pauser`
+    // when pausing.
+    fn assert_pauser_or_governance(&self, paused: bool) {
+        match paused {
+            true => self.assert_role(Role::Pauser),
+            false => self.assert_governance(),
+        }
+    }
 }
 
 pub struct TransferringCoins {
@@ -238,3 +402,38 @@ pub extern "C" fn remove_storage_keys() {
         );
     }
 }
+
+/// Stores attached data into blob store, which will be deployed to the interchain accounts
+/// hosted by this contract, and returns hash of it.
+/// Implemented to avoid loading the data into WASM for optimal gas usage.
+#[no_mangle]
+pub extern "C" fn store_wasm_of_interchain_account() {
+    env::setup_panic_hook();
+    let _contract: NearIbcContract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+    near_sdk::assert_self();
+    let input = env::input().expect("ERR_NO_INPUT");
+    let sha256_hash = env::sha256(&input);
+
+    let storage_key = borsh::to_vec(&StorageKey::InterchainAccountWasm).unwrap();
+    let current_len = env::storage_read(&storage_key).map_or_else(|| 0, |bytes| bytes.len());
+    let blob_len = input.len();
+    if blob_len > current_len {
+        let storage_cost = (env::storage_usage() + blob_len as u64 - current_len as u64) as u128
+            * env::storage_byte_cost().as_yoctonear();
+        assert!(
+            env::account_balance().as_yoctonear() >= storage_cost,
+            "ERR_NOT_ENOUGH_ACCOUNT_BALANCE, needs {} more.",
+            storage_cost - env::account_balance().as_yoctonear()
+        );
+    }
+
+    env::storage_write(&storage_key, &input);
+
+    let mut blob_hash = [0u8; 32];
+    blob_hash.copy_from_slice(&sha256_hash);
+    let blob_hash_str = serde_json::to_string(&Base58CryptoHash::from(blob_hash))
+        .unwrap()
+        .into_bytes();
+
+    env::value_return(&blob_hash_str);
+}
@@ -1,10 +1,25 @@
 use crate::{
     collections::IndexedAscendingLookupQueue,
     context::{HostHeight, NearTimeStamp},
+    governance::GovernanceCouncil,
+    ibc_impl::applications::{
+        fee::FeeMiddleware,
+        ica::{controller::IcaControllerModule, host::IcaHostModule},
+        nft_transfer::NftTransferModule,
+        octopus_lpos::OctopusLposModule,
+    },
     module_holder::ModuleHolder,
+    pause::PauseFlags,
     *,
 };
-use ibc::core::{handler::types::events::IbcEvent, host::types::identifiers::Sequence};
+use ibc::core::{
+    client::context::consensus_state::ConsensusState,
+    handler::types::events::IbcEvent,
+    host::{
+        types::{identifiers::Sequence, path::ClientConsensusStatePath},
+        ValidationContext,
+    },
+};
 use near_sdk::{
     borsh,
     store::{UnorderedMap, UnorderedSet},
@@ -40,6 +55,16 @@ pub struct OldNearIbcStore {
         LookupMap<(PortId, ChannelId), UnorderedSet<Sequence>>,
     /// The history of IBC events.
     pub ibc_events_history: IndexedAscendingLookupQueue<Height, Vec<IbcEvent>>,
+    /// The max height difference between the latest height and the oldest height
+    /// in IbcEventsHistory.
+    pub max_ibc_events_height_difference: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldModuleHolder {
+    pub transfer_module: TransferModule,
+    pub octopus_lpos_module: OctopusLposModule,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -47,7 +72,7 @@ pub struct OldNearIbcStore {
 pub struct OldContract {
     near_ibc_store: LazyOption<OldNearIbcStore>,
     /// To support the mutable borrow in `Router::get_route_mut`.
-    module_holder: ModuleHolder,
+    module_holder: OldModuleHolder,
     governance_account: AccountId,
 }
 
@@ -60,6 +85,8 @@ impl StorageMigration for NearIbcContract {
         //
         near_sdk::assert_self();
         //
+        crate::ibc_impl::core::host::init_host_client_params();
+        //
         // Create the new contract using the data from the old contract.
         let new_contract = NearIbcContract {
             near_ibc_store: LazyOption::new(
@@ -68,8 +95,18 @@ impl StorageMigration for NearIbcContract {
                     old_contract.near_ibc_store.get().unwrap(),
                 )),
             ),
-            module_holder: old_contract.module_holder,
+            module_holder: ModuleHolder {
+                transfer_module: FeeMiddleware::new(old_contract.module_holder.transfer_module),
+                octopus_lpos_module: old_contract.module_holder.octopus_lpos_module,
+                ica_controller_module: IcaControllerModule::new(),
+                ica_host_module: IcaHostModule::new(),
+                nft_transfer_module: NftTransferModule::new(),
+            },
             governance_account: old_contract.governance_account,
+            pause_flags: PauseFlags::default(),
+            governance_council: GovernanceCouncil::new(),
+            role_holders: RoleHolders::default(),
+            pending_governance_account: None,
         };
         //
         //
@@ -87,12 +124,16 @@ pub fn get_storage_key_of_lookup_map<T: BorshSerialize>(prefix: &StorageKey, ind
 
 impl NearIbcStore {
     pub fn from_old_version(old_version: OldNearIbcStore) -> Self {
+        // The heights of the consensus states are moved into the ordered queues of the clients
+        // by `migrate_consensus_state_heights` later, which may need several calls.
+        let unmigrated_consensus_state_height_clients =
+            old_version.client_id_set.iter().cloned().collect();
         Self {
             client_id_set: old_version.client_id_set,
             client_counter: old_version.client_counter,
             client_processed_times: old_version.client_processed_times,
             client_processed_heights: old_version.client_processed_heights,
-            client_consensus_state_height_sets: old_version.client_consensus_state_height_sets,
+            client_consensus_state_heights: LookupMap::new(StorageKey::ClientConsensusStateHeights),
             connection_id_set: old_version.connection_id_set,
             connection_counter: old_version.connection_counter,
             port_channel_id_set: old_version.port_channel_id_set,
@@ -101,7 +142,188 @@ impl NearIbcStore {
             packet_receipt_sequence_sets: old_version.packet_receipt_sequence_sets,
             packet_acknowledgement_sequence_sets: old_version.packet_acknowledgement_sequence_sets,
             ibc_events_history: old_version.ibc_events_history,
-            max_ibc_events_height_difference: 288000,
+            max_ibc_events_height_difference: old_version.max_ibc_events_height_difference,
+            in_flight_forwards: LookupMap::new(StorageKey::InFlightForwards),
+            host_consensus_states: IndexedAscendingLookupQueue::new(
+                StorageKey::HostConsensusStatesIndexMap,
+                StorageKey::HostConsensusStatesValueMap,
+                u64::MAX,
+            ),
+            packet_event_heights: LookupMap::new(StorageKey::PacketEventHeights),
+            packet_events_indexed_since: env::block_height(),
+            packet_state_retentions: LookupMap::new(StorageKey::PacketStateRetentions),
+            pruned_packet_sequences: LookupMap::new(StorageKey::PrunedPacketSequences),
+            storage_deposits: UnorderedMap::new(StorageKey::StorageDeposits),
+            consensus_state_retentions: LookupMap::new(StorageKey::ConsensusStateRetentions),
+            unmigrated_consensus_state_height_clients,
+            storage_payer: None,
+            released_storage_bytes: 0,
         }
     }
+    /// Move the heights of the consensus states in the unordered sets of the previous version
+    /// into the ordered queues of the clients (with the timestamps of the consensus states),
+    /// in ascending order and within the gas limit of the current call.
+    pub fn migrate_consensus_state_heights(&mut self) -> ProcessingResult {
+        let max_gas = env::prepaid_gas().saturating_mul(4).saturating_div(5);
+        let mut old_height_sets: LookupMap<ClientId, UnorderedSet<Height>> =
+            LookupMap::new(StorageKey::ClientConsensusStateHeightSets);
+        while let Some(client_id) = self
+            .unmigrated_consensus_state_height_clients
+            .first()
+            .cloned()
+        {
+            if let Some(height_set) = old_height_sets.get_mut(&client_id) {
+                let mut heights: Vec<Height> = height_set.iter().cloned().collect();
+                heights.sort();
+                for height in heights {
+                    if let Ok(consensus_state) = ValidationContext::consensus_state(
+                        self,
+                        &ClientConsensusStatePath::new(
+                            client_id.clone(),
+                            height.revision_number(),
+                            height.revision_height(),
+                        ),
+                    ) {
+                        self.record_consensus_state_height(
+                            &client_id,
+                            &height,
+                            consensus_state.timestamp().nanoseconds(),
+                        );
+                    }
+                    height_set.remove(&height);
+                    if env::used_gas() >= max_gas {
+                        height_set.flush();
+                        self.flush();
+                        return ProcessingResult::NeedMoreGas;
+                    }
+                }
+                height_set.clear();
+                height_set.flush();
+            }
+            old_height_sets.remove(&client_id);
+            old_height_sets.flush();
+            self.unmigrated_consensus_state_height_clients.remove(0);
+        }
+        self.flush();
+        ProcessingResult::Ok
+    }
+}
+
+#[near_bindgen]
+impl NearIbcContract {
+    /// Move the heights of the consensus states of the clients into the ordered queues
+    /// after the state migration. This function should be called until `Ok` is returned,
+    /// and function `deliver` is unavailable until then.
+    ///
+    /// Only the governance account can call this function.
+    pub fn migrate_consensus_state_heights(&mut self) -> ProcessingResult {
+        self.assert_governance();
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        let result = near_ibc_store.migrate_consensus_state_heights();
+        self.near_ibc_store.set(&near_ibc_store);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ibc_impl::{
+            clients::solomachine::{
+                consensus_state::SoloMachineConsensusState, SOLO_MACHINE_CLIENT_TYPE,
+            },
+            core::consensus_state::AnyConsensusState,
+        },
+        test_fixtures::{setup_contract, setup_store, CONTRACT_ACCOUNT},
+    };
+    use core::str::FromStr;
+    use ibc::core::host::types::identifiers::ClientType;
+    use ibc_proto::Protobuf;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn client_id(index: u64) -> ClientId {
+        ClientId::new(
+            ClientType::from_str(SOLO_MACHINE_CLIENT_TYPE).unwrap(),
+            index,
+        )
+        .unwrap()
+    }
+
+    fn height(revision_height: u64) -> Height {
+        Height::new(0, revision_height).unwrap()
+    }
+
+    /// Write the heights of the consensus states of the given client into the unordered set
+    /// of the previous version, along with the consensus states at the given heights.
+    fn write_old_heights(client_id: &ClientId, heights: &[u64], consensus_state_heights: &[u64]) {
+        let mut old_height_sets: LookupMap<ClientId, UnorderedSet<Height>> =
+            LookupMap::new(StorageKey::ClientConsensusStateHeightSets);
+        let mut height_set = UnorderedSet::new(StorageKey::ClientConsensusStateHeightSet {
+            client_id: client_id.clone(),
+        });
+        heights.iter().for_each(|h| {
+            height_set.insert(height(*h));
+        });
+        height_set.flush();
+        old_height_sets.insert(client_id.clone(), height_set);
+        old_height_sets.flush();
+        for h in consensus_state_heights {
+            let consensus_state = AnyConsensusState::SoloMachine(SoloMachineConsensusState::new(
+                vec![1; 32],
+                "diversifier".to_string(),
+                h * SECOND,
+            ));
+            env::storage_write(
+                ClientConsensusStatePath::new(client_id.clone(), 0, *h)
+                    .to_string()
+                    .as_bytes(),
+                &Protobuf::<Any>::encode_vec(consensus_state),
+            );
+        }
+    }
+
+    #[test]
+    fn test_migrate_consensus_state_heights() {
+        let mut store = setup_store();
+        write_old_heights(&client_id(0), &[3, 1, 2], &[1, 2, 3]);
+        // The height without a consensus state is dropped.
+        write_old_heights(&client_id(1), &[2, 3, 1], &[1, 3]);
+        store.unmigrated_consensus_state_height_clients = vec![client_id(0), client_id(1)];
+        assert!(matches!(
+            store.migrate_consensus_state_heights(),
+            ProcessingResult::Ok
+        ));
+        assert!(store.unmigrated_consensus_state_height_clients.is_empty());
+        assert_eq!(
+            store.consensus_state_heights_of(&client_id(0)),
+            vec![height(1), height(2), height(3)]
+        );
+        assert_eq!(
+            store.consensus_state_heights_of(&client_id(1)),
+            vec![height(1), height(3)]
+        );
+        assert_eq!(
+            store
+                .client_consensus_state_heights
+                .get(&client_id(1))
+                .and_then(|heights| heights.get_value_by_key(&height(3)).cloned()),
+            Some(3 * SECOND)
+        );
+        let old_height_sets: LookupMap<ClientId, UnorderedSet<Height>> =
+            LookupMap::new(StorageKey::ClientConsensusStateHeightSets);
+        assert!(old_height_sets.get(&client_id(0)).is_none());
+        assert!(old_height_sets.get(&client_id(1)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CONSENSUS_STATE_HEIGHTS_NOT_MIGRATED")]
+    fn test_deliver_before_consensus_state_heights_migrated() {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        let mut near_ibc_store = contract.near_ibc_store.get().unwrap();
+        near_ibc_store.unmigrated_consensus_state_height_clients = vec![client_id(0)];
+        contract.near_ibc_store.set(&near_ibc_store);
+        contract.deliver(vec![]);
+    }
 }
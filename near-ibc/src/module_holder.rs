@@ -1,5 +1,12 @@
 use crate::{
-    ibc_impl::applications::{octopus_lpos::OctopusLposModule, transfer::TransferModule},
+    ibc_impl::applications::{
+        fee::FeeMiddleware,
+        ica::{self, controller::IcaControllerModule, host::IcaHostModule},
+        nft_transfer::{self, NftTransferModule},
+        octopus_lpos::OctopusLposModule,
+        transfer::TransferModule,
+    },
+    ibc_impl::core::channel_upgrade::ChannelUpgradeModule,
     prelude::*,
 };
 use ibc::core::{host::types::identifiers::PortId, router::types::module::ModuleId};
@@ -12,15 +19,22 @@ use near_sdk::{
 #[derive(BorshDeserialize, BorshSerialize)]
 #[borsh(crate = "near_sdk::borsh")]
 pub struct ModuleHolder {
-    pub transfer_module: TransferModule,
+    /// The ICS-20 module wrapped by the fee middleware (ICS-29).
+    pub transfer_module: FeeMiddleware<TransferModule>,
     pub octopus_lpos_module: OctopusLposModule,
+    pub ica_controller_module: IcaControllerModule,
+    pub ica_host_module: IcaHostModule,
+    pub nft_transfer_module: NftTransferModule,
 }
 
 impl ModuleHolder {
     pub fn new(appchain_registry_account: AccountId) -> Self {
         Self {
-            transfer_module: TransferModule(),
+            transfer_module: FeeMiddleware::new(TransferModule()),
             octopus_lpos_module: OctopusLposModule::new(appchain_registry_account),
+            ica_controller_module: IcaControllerModule::new(),
+            ica_host_module: IcaHostModule::new(),
+            nft_transfer_module: NftTransferModule::new(),
         }
     }
     ///
@@ -32,6 +46,32 @@ impl ModuleHolder {
             octopus_lpos::PORT_ID_STR => {
                 Some(ModuleId::new(octopus_lpos::MODULE_ID_STR.to_string()))
             }
+            port_id if port_id.starts_with(ica::CONTROLLER_PORT_ID_PREFIX) => {
+                Some(ModuleId::new(ica::CONTROLLER_MODULE_ID_STR.to_string()))
+            }
+            ica::HOST_PORT_ID_STR => Some(ModuleId::new(ica::HOST_MODULE_ID_STR.to_string())),
+            nft_transfer::PORT_ID_STR => {
+                Some(ModuleId::new(nft_transfer::MODULE_ID_STR.to_string()))
+            }
+            _ => None,
+        }
+    }
+    /// Get the module which supports channel upgrades of the given port.
+    pub fn get_upgrade_module(&self, port_id: &PortId) -> Option<&dyn ChannelUpgradeModule> {
+        match port_id.as_str() {
+            ibc::apps::transfer::types::PORT_ID_STR => Some(&self.transfer_module),
+            octopus_lpos::PORT_ID_STR => Some(&self.octopus_lpos_module),
+            _ => None,
+        }
+    }
+    ///
+    pub fn get_upgrade_module_mut(
+        &mut self,
+        port_id: &PortId,
+    ) -> Option<&mut dyn ChannelUpgradeModule> {
+        match port_id.as_str() {
+            ibc::apps::transfer::types::PORT_ID_STR => Some(&mut self.transfer_module),
+            octopus_lpos::PORT_ID_STR => Some(&mut self.octopus_lpos_module),
             _ => None,
         }
     }
@@ -0,0 +1,278 @@
+use crate::prelude::*;
+use alloc::collections::BTreeSet;
+use ibc::core::{
+    channel::types::{
+        msgs::{ChannelMsg, PacketMsg},
+        packet::Packet,
+    },
+    handler::types::msgs::MsgEnvelope,
+    host::types::identifiers::{ChannelId, PortId},
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    serde::{Deserialize, Serialize},
+};
+
+/// The categories of IBC messages which can be paused separately.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum MessageCategory {
+    /// The messages for creating, updating, upgrading clients and submitting misbehaviours.
+    Client,
+    Connection,
+    Channel,
+    Packet,
+}
+
+/// The emergency pause switches of function `deliver`, set by the governance account.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseFlags {
+    /// Whether function `deliver` is paused as a whole.
+    pub deliver_paused: bool,
+    pub paused_message_categories: BTreeSet<MessageCategory>,
+    /// The paused ports, all channel and packet messages of them are paused.
+    pub paused_ports: BTreeSet<String>,
+    /// The paused channels, keyed by (port id, channel id) on this chain.
+    pub paused_channels: BTreeSet<(String, String)>,
+}
+
+impl MessageCategory {
+    pub fn of(msg: &MsgEnvelope) -> Self {
+        match msg {
+            MsgEnvelope::Client(_) => MessageCategory::Client,
+            MsgEnvelope::Connection(_) => MessageCategory::Connection,
+            MsgEnvelope::Channel(_) => MessageCategory::Channel,
+            MsgEnvelope::Packet(_) => MessageCategory::Packet,
+        }
+    }
+}
+
+impl PauseFlags {
+    /// Check whether the given message is paused by its category, port or channel,
+    /// returns the reason if it is.
+    pub fn check_message(&self, msg: &MsgEnvelope) -> Result<(), String> {
+        let category = MessageCategory::of(msg);
+        if self.paused_message_categories.contains(&category) {
+            return Err(format!("ERR_MESSAGE_CATEGORY_PAUSED: {:?}", category));
+        }
+        match port_and_channel_of(msg) {
+            Some((port_id, channel_id)) => self.check_port_and_channel(port_id, channel_id),
+            None => Ok(()),
+        }
+    }
+    /// Check whether the channel upgrade messages of the given channel are paused,
+    /// which belong to category `Channel`.
+    pub fn check_channel_upgrade(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), String> {
+        if self
+            .paused_message_categories
+            .contains(&MessageCategory::Channel)
+        {
+            return Err(format!(
+                "ERR_MESSAGE_CATEGORY_PAUSED: {:?}",
+                MessageCategory::Channel
+            ));
+        }
+        self.check_port_and_channel(port_id, Some(channel_id))
+    }
+    //
+    fn check_port_and_channel(
+        &self,
+        port_id: &PortId,
+        channel_id: Option<&ChannelId>,
+    ) -> Result<(), String> {
+        if self.paused_ports.contains(port_id.as_str()) {
+            return Err(format!("ERR_PORT_PAUSED: {}", port_id));
+        }
+        match channel_id {
+            Some(channel_id)
+                if self
+                    .paused_channels
+                    .contains(&(port_id.to_string(), channel_id.to_string())) =>
+            {
+                Err(format!("ERR_CHANNEL_PAUSED: {}/{}", port_id, channel_id))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Get the port id and channel id on this chain which the given message applies to.
+///
+/// The channel id is `None` for the messages opening a new channel on this chain.
+fn port_and_channel_of(msg: &MsgEnvelope) -> Option<(&PortId, Option<&ChannelId>)> {
+    match msg {
+        MsgEnvelope::Client(_) | MsgEnvelope::Connection(_) => None,
+        MsgEnvelope::Channel(msg) => Some(match msg {
+            ChannelMsg::OpenInit(msg) => (&msg.port_id_on_a, None),
+            ChannelMsg::OpenTry(msg) => (&msg.port_id_on_b, None),
+            ChannelMsg::OpenAck(msg) => (&msg.port_id_on_a, Some(&msg.chan_id_on_a)),
+            ChannelMsg::OpenConfirm(msg) => (&msg.port_id_on_b, Some(&msg.chan_id_on_b)),
+            ChannelMsg::CloseInit(msg) => (&msg.port_id_on_a, Some(&msg.chan_id_on_a)),
+            ChannelMsg::CloseConfirm(msg) => (&msg.port_id_on_b, Some(&msg.chan_id_on_b)),
+        }),
+        MsgEnvelope::Packet(msg) => Some(match msg {
+            PacketMsg::Recv(msg) => receiving_side_of(&msg.packet),
+            PacketMsg::Ack(msg) => sending_side_of(&msg.packet),
+            PacketMsg::Timeout(msg) => sending_side_of(&msg.packet),
+            PacketMsg::TimeoutOnClose(msg) => sending_side_of(&msg.packet),
+        }),
+    }
+}
+
+fn receiving_side_of(packet: &Packet) -> (&PortId, Option<&ChannelId>) {
+    (&packet.port_id_on_b, Some(&packet.chan_id_on_b))
+}
+
+fn sending_side_of(packet: &Packet) -> (&PortId, Option<&ChannelId>) {
+    (&packet.port_id_on_a, Some(&packet.chan_id_on_a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sudo_functions::SudoFunctions,
+        test_fixtures::{setup_contract, CONTRACT_ACCOUNT},
+    };
+    use core::str::FromStr;
+    use ibc::core::{
+        channel::types::{
+            channel::Order,
+            msgs::{MsgChannelCloseInit, MsgChannelOpenInit},
+            Version,
+        },
+        host::types::identifiers::ConnectionId,
+        primitives::Signer,
+    };
+
+    fn open_init_msg(port_id: &str) -> MsgEnvelope {
+        MsgEnvelope::Channel(ChannelMsg::OpenInit(MsgChannelOpenInit {
+            port_id_on_a: PortId::from_str(port_id).unwrap(),
+            connection_hops_on_a: vec![ConnectionId::new(0)],
+            port_id_on_b: PortId::transfer(),
+            ordering: Order::Unordered,
+            signer: Signer::from("alice.testnet".to_string()),
+            version_proposal: Version::new("ics20-1".to_string()),
+        }))
+    }
+
+    fn close_init_msg(port_id: &str, channel_id: u64) -> MsgEnvelope {
+        MsgEnvelope::Channel(ChannelMsg::CloseInit(MsgChannelCloseInit {
+            port_id_on_a: PortId::from_str(port_id).unwrap(),
+            chan_id_on_a: ChannelId::new(channel_id),
+            signer: Signer::from("alice.testnet".to_string()),
+        }))
+    }
+
+    #[test]
+    fn test_check_message() {
+        let mut pause_flags = PauseFlags::default();
+        assert!(pause_flags
+            .check_message(&close_init_msg("transfer", 0))
+            .is_ok());
+        pause_flags
+            .paused_channels
+            .insert(("transfer".to_string(), "channel-0".to_string()));
+        assert_eq!(
+            pause_flags.check_message(&close_init_msg("transfer", 0)),
+            Err("ERR_CHANNEL_PAUSED: transfer/channel-0".to_string())
+        );
+        assert!(pause_flags
+            .check_message(&close_init_msg("transfer", 1))
+            .is_ok());
+        assert!(pause_flags
+            .check_message(&open_init_msg("transfer"))
+            .is_ok());
+        pause_flags.paused_ports.insert("transfer".to_string());
+        assert_eq!(
+            pause_flags.check_message(&open_init_msg("transfer")),
+            Err("ERR_PORT_PAUSED: transfer".to_string())
+        );
+        assert!(pause_flags
+            .check_message(&close_init_msg("nft-transfer", 0))
+            .is_ok());
+        pause_flags
+            .paused_message_categories
+            .insert(MessageCategory::Channel);
+        assert_eq!(
+            pause_flags.check_message(&close_init_msg("nft-transfer", 0)),
+            Err("ERR_MESSAGE_CATEGORY_PAUSED: Channel".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_channel_upgrade() {
+        let mut pause_flags = PauseFlags::default();
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        assert!(pause_flags
+            .check_channel_upgrade(&port_id, &channel_id)
+            .is_ok());
+        pause_flags
+            .paused_message_categories
+            .insert(MessageCategory::Packet);
+        assert!(pause_flags
+            .check_channel_upgrade(&port_id, &channel_id)
+            .is_ok());
+        pause_flags
+            .paused_channels
+            .insert(("transfer".to_string(), "channel-0".to_string()));
+        assert!(pause_flags
+            .check_channel_upgrade(&port_id, &channel_id)
+            .is_err());
+        assert!(pause_flags
+            .check_channel_upgrade(&port_id, &ChannelId::new(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_set_pause_flags() {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        contract.set_message_category_paused(MessageCategory::Packet, true);
+        contract.set_port_paused(PortId::transfer(), true);
+        contract.set_channel_paused(PortId::transfer(), ChannelId::new(0), true);
+        assert!(contract
+            .pause_flags
+            .paused_message_categories
+            .contains(&MessageCategory::Packet));
+        assert!(contract.pause_flags.paused_ports.contains("transfer"));
+        contract.set_port_paused(PortId::transfer(), false);
+        contract.set_channel_paused(PortId::transfer(), ChannelId::new(0), false);
+        assert!(contract.pause_flags.paused_ports.is_empty());
+        assert!(contract.pause_flags.paused_channels.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELIVER_PAUSED")]
+    fn test_deliver_paused() {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        contract.set_deliver_paused(true);
+        contract.deliver(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT_OR_ROLE_HOLDER")]
+    fn test_set_deliver_paused_by_other_account() {
+        let mut contract = setup_contract("alice.testnet");
+        contract.set_deliver_paused(true);
+    }
+}
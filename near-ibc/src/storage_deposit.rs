@@ -0,0 +1,189 @@
+//! The ledger of the storage deposits paid by relayers.
+//!
+//! The consensus states, packet receipts and packet acknowledgements written in function
+//! `deliver` are recorded with the caller of it, who pays for the storage of them. When these
+//! states are removed later (e.g. the consensus states pruned by client updates, or the packet
+//! states pruned by function `prune_packet_state`), the storage deposit of the released storage
+//! is credited to the relayer who paid for it, which can be withdrawn by the relayer.
+use crate::{context::NearIbcStore, migration, prelude::*, StorageKey};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+    json_types::{U128, U64},
+    log,
+    serde::{Deserialize, Serialize},
+    AccountId,
+};
+
+/// The storage deposit of a relayer.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, Default)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageDeposit {
+    /// The storage (in bytes) of the recorded states paid by the relayer, which are still stored.
+    pub used_bytes: U64,
+    /// The storage deposit (in yocto NEAR) of the released storage, which can be withdrawn.
+    pub balance: U128,
+}
+
+/// Get the storage key of the record of the payer of the given storage key.
+fn storage_payer_key(key: &[u8]) -> Vec<u8> {
+    migration::get_storage_key_of_lookup_map(&StorageKey::StoragePayers, &key.to_vec())
+}
+
+impl NearIbcStore {
+    /// Write the given key and value to storage, and record the current storage payer (if any)
+    /// as the payer of the storage of it.
+    pub fn write_with_storage_payer(&mut self, key: &[u8], value: &[u8]) {
+        let used_bytes = env::storage_usage();
+        // The storage of an existing key is still owned by the payer of it.
+        if env::storage_write(key, value) {
+            return;
+        }
+        if let Some(payer) = self.storage_payer.clone() {
+            env::storage_write(&storage_payer_key(key), &borsh::to_vec(&payer).unwrap());
+            let mut deposit = self.get_storage_deposit(&payer);
+            deposit.used_bytes =
+                U64(deposit.used_bytes.0 + env::storage_usage().saturating_sub(used_bytes));
+            self.storage_deposits.insert(payer, deposit);
+        }
+    }
+    /// Remove the given key from storage, and credit the storage deposit of the released
+    /// storage to the payer of it.
+    pub fn remove_with_storage_payer(&mut self, key: &[u8]) {
+        let used_bytes = env::storage_usage();
+        env::storage_remove(key);
+        let payer_key = storage_payer_key(key);
+        if let Some(payer) =
+            env::storage_read(&payer_key).and_then(|bytes| AccountId::try_from_slice(&bytes).ok())
+        {
+            env::storage_remove(&payer_key);
+            let released_bytes = used_bytes.saturating_sub(env::storage_usage());
+            self.released_storage_bytes += released_bytes;
+            let mut deposit = self.get_storage_deposit(&payer);
+            deposit.used_bytes = U64(deposit.used_bytes.0.saturating_sub(released_bytes));
+            deposit.balance = U128(
+                deposit.balance.0
+                    + released_bytes as u128 * env::storage_byte_cost().as_yoctonear(),
+            );
+            self.storage_deposits.insert(payer, deposit);
+        }
+    }
+    /// Get the storage deposit of the given relayer.
+    pub fn get_storage_deposit(&self, account_id: &AccountId) -> StorageDeposit {
+        self.storage_deposits
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// Withdraw the given amount (or all) of the storage deposit balance of the given relayer,
+    /// returns the withdrawn amount.
+    pub fn withdraw_storage_deposit(
+        &mut self,
+        account_id: &AccountId,
+        amount: Option<u128>,
+    ) -> u128 {
+        let mut deposit = self.get_storage_deposit(account_id);
+        let amount = amount.unwrap_or(deposit.balance.0);
+        assert!(amount > 0, "ERR_INVALID_AMOUNT");
+        assert!(
+            amount <= deposit.balance.0,
+            "ERR_NOT_ENOUGH_STORAGE_DEPOSIT_BALANCE"
+        );
+        deposit.balance = U128(deposit.balance.0 - amount);
+        match deposit.used_bytes.0 == 0 && deposit.balance.0 == 0 {
+            true => self.storage_deposits.remove(account_id),
+            false => self.storage_deposits.insert(account_id.clone(), deposit),
+        };
+        self.storage_deposits.flush();
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"STORAGE_DEPOSIT_WITHDRAWN","account_id":"{}","amount":"{}"}}"#,
+            account_id,
+            amount
+        );
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{account, setup_store};
+
+    #[test]
+    fn test_credit_released_storage_to_payer() {
+        let mut store = setup_store();
+        store.storage_payer = Some(account("alice.testnet"));
+        store.write_with_storage_payer(b"key", &[1; 100]);
+        let used_bytes = store
+            .get_storage_deposit(&account("alice.testnet"))
+            .used_bytes;
+        assert!(used_bytes.0 >= 100);
+        // The storage of an existing key is not charged to another payer.
+        store.storage_payer = Some(account("bob.testnet"));
+        store.write_with_storage_payer(b"key", &[2; 100]);
+        assert_eq!(
+            store
+                .get_storage_deposit(&account("bob.testnet"))
+                .used_bytes,
+            U64(0)
+        );
+        store.remove_with_storage_payer(b"key");
+        assert!(env::storage_read(b"key").is_none());
+        let deposit = store.get_storage_deposit(&account("alice.testnet"));
+        assert_eq!(deposit.used_bytes, U64(0));
+        assert_eq!(store.released_storage_bytes, used_bytes.0);
+        assert_eq!(
+            deposit.balance,
+            U128(used_bytes.0 as u128 * env::storage_byte_cost().as_yoctonear())
+        );
+    }
+
+    #[test]
+    fn test_remove_storage_without_payer() {
+        let mut store = setup_store();
+        store.write_with_storage_payer(b"key", &[1; 100]);
+        store.remove_with_storage_payer(b"key");
+        assert!(env::storage_read(b"key").is_none());
+        assert_eq!(store.released_storage_bytes, 0);
+        assert!(store.storage_deposits.is_empty());
+    }
+
+    #[test]
+    fn test_withdraw_storage_deposit() {
+        let mut store = setup_store();
+        store.storage_payer = Some(account("alice.testnet"));
+        store.write_with_storage_payer(b"key", &[1; 100]);
+        store.remove_with_storage_payer(b"key");
+        let balance = store
+            .get_storage_deposit(&account("alice.testnet"))
+            .balance
+            .0;
+        assert_eq!(
+            store.withdraw_storage_deposit(&account("alice.testnet"), Some(1)),
+            1
+        );
+        assert_eq!(
+            store.withdraw_storage_deposit(&account("alice.testnet"), None),
+            balance - 1
+        );
+        assert!(store
+            .storage_deposits
+            .get(&account("alice.testnet"))
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_STORAGE_DEPOSIT_BALANCE")]
+    fn test_withdraw_more_than_balance() {
+        let mut store = setup_store();
+        store.storage_payer = Some(account("alice.testnet"));
+        store.write_with_storage_payer(b"key", &[1; 100]);
+        store.remove_with_storage_payer(b"key");
+        let balance = store
+            .get_storage_deposit(&account("alice.testnet"))
+            .balance
+            .0;
+        store.withdraw_storage_deposit(&account("alice.testnet"), Some(balance + 1));
+    }
+}
@@ -1,26 +1,36 @@
-use crate::{context::NearEd25519Verifier, ibc_impl::core::client_state::AnyClientState, *};
+use crate::{
+    context::NearEd25519Verifier,
+    ibc_impl::{
+        applications::transfer::rate_limit::{self, RateLimitConfig},
+        clients::wasm,
+        core::{
+            channel_upgrade::ChannelUpgradeContext,
+            client_state::AnyClientState,
+            consensus_state_retention::ConsensusStateRetention,
+            host::{self, HostClientParams},
+        },
+    },
+    pause::MessageCategory,
+    *,
+};
 use ibc::{
     clients::tendermint::{
         client_state::ClientState as TmClientState, types::ClientState as TmClientStateType,
     },
-    core::host::types::path::ClientStatePath,
+    core::{channel::types::Version, host::types::path::ClientStatePath},
 };
 use ibc_proto::Protobuf;
 use near_sdk::json_types::U64;
+use utils::interfaces::{ext_role_holders_receiver, ext_wrapped_token};
 
 pub trait SudoFunctions {
     /// Cancel the transfer request in the channel escrow contract.
-    fn cancel_transfer_request_in_channel_escrow(
-        &mut self,
-        channel_id: String,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
-        amount: U128,
-    );
+    ///
+    /// Only the governance account or the holders of role `AssetManager` can call this function.
+    fn cancel_transfer_request_in_channel_escrow(&mut self, channel_id: String, request_id: U64);
     /// Setup the token contract for the given asset denom with the given metadata.
     ///
-    /// Only the governance account can call this function.
+    /// Only the governance account or the holders of role `AssetManager` can call this function.
     fn setup_wrapped_token(
         &mut self,
         trace_path: String,
@@ -29,15 +39,19 @@ pub trait SudoFunctions {
     );
     /// Set the max length of the IBC events history queue.
     ///
-    /// Only the governance account can call this function.
+    /// Only the governance account or the holders of role `Operator` can call this function.
     fn set_max_length_of_ibc_events_history(&mut self, max_length: u64) -> ProcessingResult;
     /// Setup the escrow contract for the given channel.
     ///
-    /// Only the governance account can call this function.
+    /// Only the governance account or the holders of role `Operator` can call this function.
     fn setup_channel_escrow(&mut self, channel_id: String);
+    /// Setup the NFT escrow contract for the given channel.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn setup_nft_channel_escrow(&mut self, channel_id: String);
     /// Register the given token contract for the given channel.
     ///
-    /// Only the governance account can call this function.
+    /// Only the governance account or the holders of role `AssetManager` can call this function.
     fn register_asset_for_channel(
         &mut self,
         channel_id: String,
@@ -46,27 +60,148 @@ pub trait SudoFunctions {
     );
     /// Unregister the given asset from the given channel.
     ///
-    /// Only the governance account can call this function.
+    /// Only the governance account or the holders of role `AssetManager` can call this function.
     fn unregister_asset_from_channel(&mut self, channel_id: String, base_denom: String);
     /// Force clear the frozen height of the given tendermint client.
+    ///
+    /// Only the governance account or the holders of role `ClientGuardian` can call this function.
     fn force_clear_frozen_height_of_tendermint_client(&mut self, client_id: ClientId);
     /// Change the max IBC events height difference.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
     fn change_max_ibc_events_height_difference(&mut self, max_height_difference: U64);
+    /// Set the rate limit of the ICS-20 flows of the given denom through the given channel.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn set_rate_limit(&mut self, channel_id: String, denom: String, config: RateLimitConfig);
+    /// Remove the rate limit of the given channel and denom.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn remove_rate_limit(&mut self, channel_id: String, denom: String);
+    /// Reset the current usage of the rate limit of the given channel and denom.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn reset_rate_limit_usage(&mut self, channel_id: String, denom: String);
+    /// Pause or resume function `deliver` as a whole.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_deliver_paused(&mut self, paused: bool);
+    /// Pause or resume the given category of messages in function `deliver`.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_message_category_paused(&mut self, category: MessageCategory, paused: bool);
+    /// Pause or resume the channel and packet messages of the given port in function `deliver`.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_port_paused(&mut self, port_id: PortId, paused: bool);
+    /// Pause or resume the channel and packet messages of the given channel
+    /// in function `deliver`.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_channel_paused(&mut self, port_id: PortId, channel_id: ChannelId, paused: bool);
+    /// Pause or resume the outgoing transfers in the escrow contract of the given channel.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_channel_escrow_paused(&mut self, channel_id: String, paused: bool);
+    /// Pause or resume the outgoing transfers in the wrapped token contract
+    /// of the given asset id.
+    ///
+    /// Only the governance account can call this function, and the holders of role `Pauser`
+    /// can also call it for pausing.
+    fn set_wrapped_token_paused(&mut self, asset_id: String, paused: bool);
+    /// Set the members of the governance council, the number of approvals needed for
+    /// executing a proposal, and the timelock of approved proposals.
+    ///
+    /// Only the governance account can call this function.
+    fn set_governance_council(
+        &mut self,
+        members: Vec<AccountId>,
+        threshold: u32,
+        timelock_seconds: U64,
+    );
+    /// Grant the given role to the given account, and sync the role holders
+    /// to the factory contracts.
+    ///
+    /// Only the governance account can call this function.
+    fn grant_role(&mut self, role: Role, account_id: AccountId);
+    /// Revoke the given role from the given account, and sync the role holders
+    /// to the factory contracts.
+    ///
+    /// Only the governance account can call this function.
+    fn revoke_role(&mut self, role: Role, account_id: AccountId);
+    /// Propose to transfer the governance account to the given account, which needs to
+    /// call function `accept_governance_account` to accept it. Proposing again replaces
+    /// the previous proposal, and proposing the current governance account cancels it.
+    /// Once the new account accepts it, the governance council can no longer create or
+    /// execute proposals, and its pending proposals can only be cancelled.
+    ///
+    /// Only the governance account can call this function.
+    fn propose_governance_account(&mut self, account_id: AccountId);
+    /// Register the light client contract of `08-wasm` clients with the given code hash,
+    /// which is deployed to the given account.
+    ///
+    /// Only the governance account or the holders of role `ClientGuardian` can call this function.
+    fn register_wasm_light_client(&mut self, code_hash: Base58CryptoHash, contract_id: AccountId);
+    /// Unregister the light client contract with the given code hash.
+    ///
+    /// Only the governance account or the holders of role `ClientGuardian` can call this function.
+    fn unregister_wasm_light_client(&mut self, code_hash: Base58CryptoHash);
+    /// Set the parameters of NEAR protocol, which the client states of this chain
+    /// on counterparty chains are validated against in connection handshakes.
+    ///
+    /// Only the governance account or the holders of role `ClientGuardian` can call this function.
+    fn set_host_client_params(&mut self, params: HostClientParams);
+    /// Initialize an upgrade of the given channel with the given version and connection hops
+    /// (the current ones if not specified). The counterparty chain must initialize the same
+    /// upgrade, then the upgrade handshake can be relayed.
+    ///
+    /// Only the governance account can call this function.
+    fn init_channel_upgrade(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        version: String,
+        connection_hops: Option<Vec<ConnectionId>>,
+    );
+    /// Cancel the upgrade of the given channel, which has not yet completed flushing.
+    ///
+    /// Only the governance account can call this function.
+    fn cancel_channel_upgrade(&mut self, port_id: PortId, channel_id: ChannelId);
+    /// Set the number of the latest packet receipts and acknowledgements of the given channel
+    /// which are kept by function `prune_packet_state`, or disable the pruning of the channel
+    /// by `None`.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn set_packet_state_retention(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        retention: Option<U64>,
+    );
+    /// Set the retention of the consensus states of the given client, or reset it to
+    /// the default by `None`. The consensus states exceeding the retention are pruned
+    /// immediately. If `ProcessingResult::NeedMoreGas` is returned, this function should
+    /// be called again with the same arguments to prune the rest of them.
+    ///
+    /// Only the governance account or the holders of role `Operator` can call this function.
+    fn set_consensus_state_retention(
+        &mut self,
+        client_id: ClientId,
+        retention: Option<ConsensusStateRetention>,
+    ) -> ProcessingResult;
 }
 
 #[near_bindgen]
 impl SudoFunctions for NearIbcContract {
     //
     #[payable]
-    fn cancel_transfer_request_in_channel_escrow(
-        &mut self,
-        channel_id: String,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
-        amount: U128,
-    ) {
-        self.assert_governance();
+    fn cancel_transfer_request_in_channel_escrow(&mut self, channel_id: String, request_id: U64) {
+        self.assert_role(Role::AssetManager);
         near_sdk::assert_one_yocto();
         let channel_escrow_id =
             format!("{}.{}", channel_id, utils::get_escrow_factory_contract_id());
@@ -76,7 +211,7 @@ impl SudoFunctions for NearIbcContract {
         .with_attached_deposit(NearToken::from_yoctonear(0))
         .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
         .with_unused_gas_weight(0)
-        .cancel_transfer_request(trace_path, base_denom, sender_id, amount);
+        .cancel_transfer_request(request_id);
     }
     //
     #[payable]
@@ -86,7 +221,7 @@ impl SudoFunctions for NearIbcContract {
         base_denom: String,
         metadata: FungibleTokenMetadata,
     ) {
-        self.assert_governance();
+        self.assert_role(Role::AssetManager);
         assert!(
             env::prepaid_gas() >= utils::GAS_FOR_COMPLEX_FUNCTION_CALL,
             "ERR_NOT_ENOUGH_GAS"
@@ -125,17 +260,17 @@ impl SudoFunctions for NearIbcContract {
     //
     #[payable]
     fn set_max_length_of_ibc_events_history(&mut self, max_length: u64) -> ProcessingResult {
-        self.assert_governance();
+        self.assert_role(Role::Operator);
         near_sdk::assert_one_yocto();
         let mut near_ibc_store = self.near_ibc_store.get().unwrap();
-        let result = near_ibc_store.ibc_events_history.set_max_length(max_length);
+        let result = near_ibc_store.set_max_length_of_ibc_events_history(max_length);
         self.near_ibc_store.set(&near_ibc_store);
         result
     }
     //
     #[payable]
     fn setup_channel_escrow(&mut self, channel_id: String) {
-        self.assert_governance();
+        self.assert_role(Role::Operator);
         assert!(
             env::prepaid_gas() >= utils::GAS_FOR_COMPLEX_FUNCTION_CALL,
             "ERR_NOT_ENOUGH_GAS"
@@ -164,13 +299,43 @@ impl SudoFunctions for NearIbcContract {
     }
     //
     #[payable]
+    fn setup_nft_channel_escrow(&mut self, channel_id: String) {
+        self.assert_role(Role::Operator);
+        assert!(
+            env::prepaid_gas() >= utils::GAS_FOR_COMPLEX_FUNCTION_CALL,
+            "ERR_NOT_ENOUGH_GAS"
+        );
+        let minimum_deposit = utils::INIT_BALANCE_FOR_CHANNEL_ESCROW_CONTRACT
+            + env::storage_byte_cost().as_yoctonear()
+                * (borsh::to_vec(&channel_id).unwrap().len() + 16) as u128;
+        assert!(
+            env::attached_deposit().as_yoctonear() >= minimum_deposit,
+            "ERR_NOT_ENOUGH_DEPOSIT, must not less than {} yocto",
+            minimum_deposit
+        );
+        let used_bytes = env::storage_usage();
+        ExtraDepositCost::reset();
+        ext_escrow_factory::ext(utils::get_nft_escrow_factory_contract_id())
+            .with_attached_deposit(NearToken::from_yoctonear(minimum_deposit))
+            .with_static_gas(
+                utils::GAS_FOR_COMPLEX_FUNCTION_CALL
+                    .checked_sub(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                    .unwrap(),
+            )
+            .with_unused_gas_weight(0)
+            .create_escrow(ChannelId::from_str(channel_id.as_str()).unwrap());
+        ExtraDepositCost::add(minimum_deposit);
+        utils::refund_deposit(used_bytes);
+    }
+    //
+    #[payable]
     fn register_asset_for_channel(
         &mut self,
         channel_id: String,
         base_denom: String,
         token_contract: AccountId,
     ) {
-        self.assert_governance();
+        self.assert_role(Role::AssetManager);
         let prefixed_base_account = format!(".{}", env::current_account_id());
         assert!(
             !token_contract
@@ -206,7 +371,7 @@ impl SudoFunctions for NearIbcContract {
     //
     #[payable]
     fn unregister_asset_from_channel(&mut self, channel_id: String, base_denom: String) {
-        self.assert_governance();
+        self.assert_role(Role::AssetManager);
         near_sdk::assert_one_yocto();
         let asset_denom = AssetDenom {
             trace_path: String::new(),
@@ -227,7 +392,7 @@ impl SudoFunctions for NearIbcContract {
     //
     #[payable]
     fn force_clear_frozen_height_of_tendermint_client(&mut self, client_id: ClientId) {
-        self.assert_governance();
+        self.assert_role(Role::ClientGuardian);
         near_sdk::assert_one_yocto();
         let client_state_key = ClientStatePath(client_id.clone()).to_string().into_bytes();
         if let Some(bytes) = env::storage_read(&client_state_key) {
@@ -254,6 +419,7 @@ impl SudoFunctions for NearIbcContract {
                     log!("New client state of {}: {:?}", client_id, new_cs);
                     env::storage_write(&client_state_key, &new_cs.encode_vec());
                 }
+                _ => panic!("Not a tendermint client."),
             }
         } else {
             panic!("Invalid client id.");
@@ -261,7 +427,7 @@ impl SudoFunctions for NearIbcContract {
     }
     //
     fn change_max_ibc_events_height_difference(&mut self, max_height_difference: U64) {
-        self.assert_governance();
+        self.assert_role(Role::Operator);
         let mut near_ibc_store = self.near_ibc_store.get().unwrap();
         assert!(
             max_height_difference.0 != near_ibc_store.max_ibc_events_height_difference,
@@ -274,4 +440,416 @@ impl SudoFunctions for NearIbcContract {
         near_ibc_store.max_ibc_events_height_difference = max_height_difference.into();
         self.near_ibc_store.set(&near_ibc_store);
     }
+    //
+    fn set_rate_limit(&mut self, channel_id: String, denom: String, config: RateLimitConfig) {
+        self.assert_role(Role::Operator);
+        rate_limit::set_rate_limit(channel_id, denom, config);
+    }
+    //
+    fn remove_rate_limit(&mut self, channel_id: String, denom: String) {
+        self.assert_role(Role::Operator);
+        rate_limit::remove_rate_limit(channel_id, denom);
+    }
+    //
+    fn reset_rate_limit_usage(&mut self, channel_id: String, denom: String) {
+        self.assert_role(Role::Operator);
+        rate_limit::reset_rate_limit_usage(channel_id, denom);
+    }
+    //
+    fn set_deliver_paused(&mut self, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        self.pause_flags.deliver_paused = paused;
+        log_pause_flag_changed("deliver", paused);
+    }
+    //
+    fn set_message_category_paused(&mut self, category: MessageCategory, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        match paused {
+            true => self.pause_flags.paused_message_categories.insert(category),
+            false => self.pause_flags.paused_message_categories.remove(&category),
+        };
+        log_pause_flag_changed(format!("{:?}", category).as_str(), paused);
+    }
+    //
+    fn set_port_paused(&mut self, port_id: PortId, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        match paused {
+            true => self.pause_flags.paused_ports.insert(port_id.to_string()),
+            false => self.pause_flags.paused_ports.remove(port_id.as_str()),
+        };
+        log_pause_flag_changed(port_id.as_str(), paused);
+    }
+    //
+    fn set_channel_paused(&mut self, port_id: PortId, channel_id: ChannelId, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        let key = (port_id.to_string(), channel_id.to_string());
+        match paused {
+            true => self.pause_flags.paused_channels.insert(key),
+            false => self.pause_flags.paused_channels.remove(&key),
+        };
+        log_pause_flag_changed(format!("{}/{}", port_id, channel_id).as_str(), paused);
+    }
+    //
+    fn set_channel_escrow_paused(&mut self, channel_id: String, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        let channel_escrow_id =
+            format!("{}.{}", channel_id, utils::get_escrow_factory_contract_id());
+        ext_channel_escrow::ext(AccountId::from_str(channel_escrow_id.as_str()).unwrap())
+            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+            .with_unused_gas_weight(0)
+            .set_paused(paused);
+    }
+    //
+    fn set_wrapped_token_paused(&mut self, asset_id: String, paused: bool) {
+        self.assert_pauser_or_governance(paused);
+        let token_contract_id = format!("{}.{}", asset_id, utils::get_token_factory_contract_id());
+        ext_wrapped_token::ext(AccountId::from_str(token_contract_id.as_str()).unwrap())
+            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+            .with_unused_gas_weight(0)
+            .set_paused(paused);
+    }
+    //
+    fn set_governance_council(
+        &mut self,
+        members: Vec<AccountId>,
+        threshold: u32,
+        timelock_seconds: U64,
+    ) {
+        self.assert_governance();
+        self.governance_council
+            .set_council(members, threshold, timelock_seconds.0);
+    }
+    //
+    fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_governance();
+        assert!(
+            self.role_holders.grant(role, account_id.clone()),
+            "ERR_ROLE_ALREADY_GRANTED"
+        );
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ROLE_GRANTED","role":"{:?}","account_id":"{}"}}"#,
+            role,
+            account_id
+        );
+        self.sync_role_holders_to_factories();
+    }
+    //
+    fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        self.assert_governance();
+        assert!(
+            self.role_holders.revoke(role, &account_id),
+            "ERR_ROLE_NOT_GRANTED"
+        );
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ROLE_REVOKED","role":"{:?}","account_id":"{}"}}"#,
+            role,
+            account_id
+        );
+        self.sync_role_holders_to_factories();
+    }
+    //
+    fn propose_governance_account(&mut self, account_id: AccountId) {
+        self.assert_governance();
+        if account_id == self.governance_account {
+            self.pending_governance_account = None;
+            log!(
+                r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"GOVERNANCE_ACCOUNT_PROPOSAL_CANCELLED"}}"#
+            );
+            return;
+        }
+        self.pending_governance_account = Some(account_id.clone());
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"GOVERNANCE_ACCOUNT_PROPOSED","governance_account":"{}","proposed_account":"{}"}}"#,
+            self.governance_account,
+            account_id
+        );
+    }
+    //
+    fn register_wasm_light_client(&mut self, code_hash: Base58CryptoHash, contract_id: AccountId) {
+        self.assert_role(Role::ClientGuardian);
+        wasm::register_light_client_contract(code_hash.into(), contract_id.clone());
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"WASM_LIGHT_CLIENT_REGISTERED","code_hash":"{}","contract_id":"{}"}}"#,
+            String::from(&code_hash),
+            contract_id
+        );
+    }
+    //
+    fn unregister_wasm_light_client(&mut self, code_hash: Base58CryptoHash) {
+        self.assert_role(Role::ClientGuardian);
+        wasm::unregister_light_client_contract(&code_hash.into());
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"WASM_LIGHT_CLIENT_UNREGISTERED","code_hash":"{}"}}"#,
+            String::from(&code_hash)
+        );
+    }
+    //
+    fn set_host_client_params(&mut self, params: HostClientParams) {
+        self.assert_role(Role::ClientGuardian);
+        host::set_host_client_params(params.clone());
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"HOST_CLIENT_PARAMS_SET","chain_id":"{}","epoch_length":"{}"}}"#,
+            params.chain_id,
+            params.epoch_length.0
+        );
+    }
+    //
+    fn init_channel_upgrade(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        version: String,
+        connection_hops: Option<Vec<ConnectionId>>,
+    ) {
+        self.assert_governance();
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        ChannelUpgradeContext {
+            store: &mut near_ibc_store,
+            modules: &mut self.module_holder,
+        }
+        .upgrade_init(
+            &port_id,
+            &channel_id,
+            Version::new(version.clone()),
+            connection_hops,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"CHANNEL_UPGRADE_INITIALIZED","port_id":"{}","channel_id":"{}","version":"{}"}}"#,
+            port_id,
+            channel_id,
+            version
+        );
+    }
+    //
+    fn cancel_channel_upgrade(&mut self, port_id: PortId, channel_id: ChannelId) {
+        self.assert_governance();
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        ChannelUpgradeContext {
+            store: &mut near_ibc_store,
+            modules: &mut self.module_holder,
+        }
+        .upgrade_cancel_by_governance(&port_id, &channel_id)
+        .unwrap_or_else(|e| panic!("{}", e));
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"CHANNEL_UPGRADE_CANCELLED","port_id":"{}","channel_id":"{}"}}"#,
+            port_id,
+            channel_id
+        );
+    }
+    //
+    fn set_packet_state_retention(
+        &mut self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        retention: Option<U64>,
+    ) {
+        self.assert_role(Role::Operator);
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.set_packet_state_retention(
+            &port_id,
+            &channel_id,
+            retention.map(|retention| retention.0),
+        );
+        self.near_ibc_store.set(&near_ibc_store);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"PACKET_STATE_RETENTION_SET","port_id":"{}","channel_id":"{}","retention":{}}}"#,
+            port_id,
+            channel_id,
+            retention.map_or("null".to_string(), |retention| format!(
+                "\"{}\"",
+                retention.0
+            ))
+        );
+    }
+    //
+    fn set_consensus_state_retention(
+        &mut self,
+        client_id: ClientId,
+        retention: Option<ConsensusStateRetention>,
+    ) -> ProcessingResult {
+        self.assert_role(Role::Operator);
+        let mut near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.set_consensus_state_retention(&client_id, retention.clone());
+        let result = near_ibc_store.prune_consensus_states(&client_id);
+        near_ibc_store.flush();
+        self.near_ibc_store.set(&near_ibc_store);
+        log!(
+            r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"CONSENSUS_STATE_RETENTION_SET","client_id":"{}","retention":{}}}"#,
+            client_id,
+            retention.map_or("null".to_string(), |retention| format!(
+                r#"{{"max_count":"{}","prune_expired":{}}}"#,
+                retention.max_count.0, retention.prune_expired
+            ))
+        );
+        result
+    }
+}
+
+impl NearIbcContract {
+    // Sync the role holders to the factory contracts, for checking the permissions there.
+    fn sync_role_holders_to_factories(&self) {
+        [
+            utils::get_token_factory_contract_id(),
+            utils::get_escrow_factory_contract_id(),
+            utils::get_nft_factory_contract_id(),
+            utils::get_nft_escrow_factory_contract_id(),
+        ]
+        .into_iter()
+        .for_each(|factory_contract_id| {
+            ext_role_holders_receiver::ext(factory_contract_id)
+                .with_attached_deposit(NearToken::from_yoctonear(0))
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                .with_unused_gas_weight(0)
+                .sync_role_holders(self.role_holders.clone());
+        });
+    }
+}
+
+fn log_pause_flag_changed(target: &str, paused: bool) {
+    log!(
+        r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"{}","target":"{}"}}"#,
+        match paused {
+            true => "PAUSED",
+            false => "RESUMED",
+        },
+        target
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_fixtures::{account, call_as, setup_contract, CONTRACT_ACCOUNT},
+        viewer::Viewer,
+    };
+    use ibc::core::host::types::identifiers::ClientType;
+
+    fn setup() -> NearIbcContract {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        contract.grant_role(Role::Pauser, account("alice.testnet"));
+        contract.grant_role(Role::Operator, account("bob.testnet"));
+        contract.grant_role(Role::ClientGuardian, account("carol.testnet"));
+        contract
+    }
+
+    #[test]
+    fn test_grant_and_revoke_role() {
+        let mut contract = setup();
+        assert_eq!(
+            contract.get_role_holders(Role::Pauser),
+            vec![account("alice.testnet")]
+        );
+        call_as("alice.testnet");
+        contract.set_deliver_paused(true);
+        assert!(contract.pause_flags.deliver_paused);
+        call_as("bob.testnet");
+        assert!(matches!(
+            contract.set_max_length_of_ibc_events_history(100),
+            ProcessingResult::Ok
+        ));
+        call_as(CONTRACT_ACCOUNT);
+        contract.revoke_role(Role::Pauser, account("alice.testnet"));
+        assert!(contract.get_role_holders(Role::Pauser).is_empty());
+        contract.set_deliver_paused(false);
+        assert!(!contract.pause_flags.deliver_paused);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT")]
+    fn test_resume_by_pauser() {
+        let mut contract = setup();
+        call_as("alice.testnet");
+        contract.set_deliver_paused(true);
+        contract.set_deliver_paused(false);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT_OR_ROLE_HOLDER")]
+    fn test_call_by_holder_of_other_role() {
+        let mut contract = setup();
+        call_as("alice.testnet");
+        contract.set_max_length_of_ibc_events_history(100);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT_OR_ROLE_HOLDER")]
+    fn test_call_after_role_revoked() {
+        let mut contract = setup();
+        contract.revoke_role(Role::Pauser, account("alice.testnet"));
+        call_as("alice.testnet");
+        contract.set_deliver_paused(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT")]
+    fn test_grant_role_by_role_holder() {
+        let mut contract = setup();
+        call_as("bob.testnet");
+        contract.grant_role(Role::Operator, account("carol.testnet"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ROLE_ALREADY_GRANTED")]
+    fn test_grant_role_twice() {
+        let mut contract = setup();
+        contract.grant_role(Role::Pauser, account("alice.testnet"));
+    }
+
+    #[test]
+    fn test_call_by_client_guardian() {
+        let mut contract = setup();
+        call_as("carol.testnet");
+        contract.register_wasm_light_client([1; 32].into(), account("light-client.testnet"));
+        assert_eq!(
+            wasm::get_light_client_contract(&[1; 32]),
+            Some(account("light-client.testnet"))
+        );
+        contract.unregister_wasm_light_client([1; 32].into());
+        assert!(wasm::get_light_client_contract(&[1; 32]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_GOVERNANCE_ACCOUNT_OR_ROLE_HOLDER")]
+    fn test_cancel_transfer_request_by_operator() {
+        let mut contract = setup();
+        call_as("bob.testnet");
+        contract.cancel_transfer_request_in_channel_escrow("channel-0".to_string(), U64(0));
+    }
+
+    #[test]
+    fn test_set_consensus_state_retention() {
+        let mut contract = setup();
+        let client_id = ClientId::new(ClientType::from_str("07-tendermint").unwrap(), 0).unwrap();
+        let mut near_ibc_store = contract.near_ibc_store.get().unwrap();
+        for revision_height in 1..=5 {
+            near_ibc_store.record_consensus_state_height(
+                &client_id,
+                &Height::new(0, revision_height).unwrap(),
+                0,
+            );
+        }
+        near_ibc_store.flush();
+        contract.near_ibc_store.set(&near_ibc_store);
+        let retention = ConsensusStateRetention {
+            max_count: U64(2),
+            prune_expired: false,
+        };
+        call_as("bob.testnet");
+        assert!(matches!(
+            contract.set_consensus_state_retention(client_id.clone(), Some(retention)),
+            ProcessingResult::Ok
+        ));
+        assert_eq!(
+            contract.get_client_consensus_heights(client_id),
+            vec![Height::new(0, 4).unwrap(), Height::new(0, 5).unwrap()]
+        );
+    }
 }
@@ -0,0 +1,61 @@
+//! The fixtures shared by the unit tests of the contract.
+use crate::{context::NearIbcStore, NearIbcContract};
+use near_sdk::{
+    test_utils::VMContextBuilder, testing_env, AccountId, NearToken, PromiseResult,
+    RuntimeFeesConfig,
+};
+
+/// The account of the contract in the tests.
+pub const CONTRACT_ACCOUNT: &str = "near-ibc.testnet";
+
+pub fn account(name: &str) -> AccountId {
+    name.parse().unwrap()
+}
+
+/// Get the context of calling the contract by the given account, with 1 yocto NEAR attached.
+pub fn context_of(predecessor: &str) -> VMContextBuilder {
+    let mut context = VMContextBuilder::new();
+    context
+        .current_account_id(account(CONTRACT_ACCOUNT))
+        .predecessor_account_id(account(predecessor))
+        .attached_deposit(NearToken::from_yoctonear(1));
+    context
+}
+
+/// Set up the context of calling the contract by the given account.
+pub fn call_as(predecessor: &str) {
+    testing_env!(context_of(predecessor).build());
+}
+
+/// Set up the context of calling the contract by the given account at the given block timestamp.
+pub fn call_at(predecessor: &str, block_timestamp: u64) {
+    testing_env!(context_of(predecessor)
+        .block_timestamp(block_timestamp)
+        .build());
+}
+
+/// Set up the context of the callback of the contract with the given promise results.
+pub fn callback_with(promise_results: Vec<PromiseResult>) {
+    testing_env!(
+        context_of(CONTRACT_ACCOUNT).build(),
+        near_sdk::test_vm_config(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        promise_results,
+    );
+}
+
+/// Initialize the contract by the contract account, and set up the context of
+/// calling the contract by the given account.
+pub fn setup_contract(predecessor: &str) -> NearIbcContract {
+    call_as(CONTRACT_ACCOUNT);
+    let contract = NearIbcContract::init(account("registry.testnet"));
+    call_as(predecessor);
+    contract
+}
+
+/// Set up an empty store in a new context.
+pub fn setup_store() -> NearIbcStore {
+    testing_env!(VMContextBuilder::new().build());
+    NearIbcStore::new()
+}
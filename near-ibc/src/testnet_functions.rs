@@ -159,7 +159,7 @@ impl TestnetFunctions for NearIbcContract {
                 .into_bytes(),
         );
         near_ibc_store
-            .client_consensus_state_height_sets
+            .client_consensus_state_heights
             .remove(&client_id);
         near_ibc_store.client_id_set.remove(&client_id);
         self.near_ibc_store.set(&near_ibc_store);
@@ -186,9 +186,13 @@ impl TestnetFunctions for NearIbcContract {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ibc::core::host::types::identifiers::ClientType;
-    use itertools::Itertools;
-    use near_sdk::store::UnorderedSet;
+    use ibc::{
+        core::{
+            client::context::{ClientExecutionContext, ClientValidationContext},
+            host::types::identifiers::ClientType,
+        },
+        primitives::Timestamp,
+    };
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::testing_env;
 
@@ -200,23 +204,10 @@ mod tests {
         let mut near_ibc_store = near_ibc_contract.near_ibc_store.get().unwrap();
 
         let client_id = test_client_id();
-        near_ibc_store.client_consensus_state_height_sets.insert(
-            client_id.clone(),
-            UnorderedSet::new(StorageKey::ClientConsensusStateHeightSet {
-                client_id: client_id.clone(),
-            }),
-        );
-
-        let height_set = near_ibc_store
-            .client_consensus_state_height_sets
-            .get_mut(&client_id)
-            .unwrap();
-
         for h in heights {
-            height_set.insert(h.clone());
+            near_ibc_store.record_consensus_state_height(&client_id, h, 0);
         }
 
-        height_set.flush();
         near_ibc_store.flush();
         near_ibc_contract.near_ibc_store.set(&near_ibc_store);
     }
@@ -228,11 +219,7 @@ mod tests {
         let near_ibc_store = near_ibc_contract.near_ibc_store.get().unwrap();
 
         let client_id = test_client_id();
-        let height_set = near_ibc_store
-            .client_consensus_state_height_sets
-            .get(&client_id)
-            .unwrap();
-        let sorted_heights = height_set.iter().sorted().collect_vec();
+        let sorted_heights = near_ibc_store.consensus_state_heights_of(&client_id);
         dbg!(&sorted_heights, &aim_sorted_heights);
 
         for i in 0..aim_sorted_heights.len() {
@@ -267,4 +254,88 @@ mod tests {
         near_ibc_contract.clear_consensus_state_by(test_client_id(), None);
         assert_consensus_state_heights(&mut near_ibc_contract, &vec![]);
     }
+
+    #[test]
+    fn test_clear_consensus_state_by_after_out_of_order_insert() {
+        let test_account: AccountId = "account.testnet".parse().unwrap();
+        let mut context: VMContextBuilder = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(test_account.clone())
+            .current_account_id(test_account.clone())
+            .build());
+
+        let mut near_ibc_contract = NearIbcContract::init(test_account);
+        let height = vec![
+            Height::new(0, 3).unwrap(),
+            Height::new(0, 1).unwrap(),
+            Height::new(0, 4).unwrap(),
+            Height::new(0, 2).unwrap(),
+        ];
+        write_height(&mut near_ibc_contract, &height);
+        let sorted_heights: Vec<Height> = (1..=4).map(|h| Height::new(0, h).unwrap()).collect();
+        assert_eq!(
+            near_ibc_contract
+                .near_ibc_store
+                .get()
+                .unwrap()
+                .consensus_state_heights_of(&test_client_id()),
+            sorted_heights
+        );
+
+        near_ibc_contract.clear_consensus_state_by(test_client_id(), Some(sorted_heights[2]));
+        assert_eq!(
+            near_ibc_contract
+                .near_ibc_store
+                .get()
+                .unwrap()
+                .consensus_state_heights_of(&test_client_id()),
+            sorted_heights[2..].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_clear_consensus_state_by_removes_processed_times_and_heights() {
+        let test_account: AccountId = "account.testnet".parse().unwrap();
+        let mut context: VMContextBuilder = VMContextBuilder::new();
+        testing_env!(context
+            .predecessor_account_id(test_account.clone())
+            .current_account_id(test_account.clone())
+            .build());
+
+        let mut near_ibc_contract = NearIbcContract::init(test_account);
+        let heights: Vec<Height> = (1..=3).map(|h| Height::new(0, h).unwrap()).collect();
+        write_height(&mut near_ibc_contract, &heights);
+        let mut near_ibc_store = near_ibc_contract.near_ibc_store.get().unwrap();
+        for height in heights.iter() {
+            near_ibc_store
+                .store_update_time(
+                    test_client_id(),
+                    *height,
+                    Timestamp::from_nanoseconds(height.revision_height()).unwrap(),
+                )
+                .unwrap();
+            near_ibc_store
+                .store_update_height(test_client_id(), *height, Height::new(0, 100).unwrap())
+                .unwrap();
+        }
+        near_ibc_store.flush();
+        near_ibc_contract.near_ibc_store.set(&near_ibc_store);
+
+        near_ibc_contract.clear_consensus_state_by(test_client_id(), Some(heights[2]));
+        let near_ibc_store = near_ibc_contract.near_ibc_store.get().unwrap();
+        for height in heights[..2].iter() {
+            assert!(near_ibc_store
+                .client_update_time(&test_client_id(), height)
+                .is_err());
+            assert!(near_ibc_store
+                .client_update_height(&test_client_id(), height)
+                .is_err());
+        }
+        assert!(near_ibc_store
+            .client_update_time(&test_client_id(), &heights[2])
+            .is_ok());
+        assert!(near_ibc_store
+            .client_update_height(&test_client_id(), &heights[2])
+            .is_ok());
+    }
 }
@@ -1,10 +1,10 @@
 use crate::prelude::*;
 use ibc::core::{
-    client::types::Height,
+    client::types::{Height, Status},
     host::types::identifiers::{ChannelId, PortId, Sequence},
 };
 use near_sdk::{
-    json_types::U64,
+    json_types::{Base64VecU8, U64},
     serde::{Deserialize, Serialize},
 };
 
@@ -34,6 +34,29 @@ pub struct QueryPacketEventDataRequest {
     pub height: Qualified<QueryHeight>,
 }
 
+pub use utils::types::{Page, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+
+/// The status of a client, for filtering the clients in view functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ClientStatus {
+    Active,
+    Frozen,
+    Expired,
+    Unauthorized,
+}
+
+impl From<Status> for ClientStatus {
+    fn from(value: Status) -> Self {
+        match value {
+            Status::Active => ClientStatus::Active,
+            Status::Frozen => ClientStatus::Frozen,
+            Status::Expired => ClientStatus::Expired,
+            Status::Unauthorized => ClientStatus::Unauthorized,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ProcessingResult {
@@ -56,3 +79,12 @@ pub struct VscPacketData {
     pub validator_set_id: U64,
     pub slash_acks: Vec<String>,
 }
+
+/// The value of an IBC path of the counterparty chain, verified by a light client contract.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VerifiedPathValue {
+    pub path: String,
+    /// The value of the path, `None` if the path is verified to be absent.
+    pub value: Option<Base64VecU8>,
+}
@@ -1,29 +1,45 @@
 use crate::{
     collections::IndexedAscendingQueueViewer,
-    ibc_impl::core::{client_state::AnyClientState, consensus_state::AnyConsensusState},
-    types::{Qualified, QueryHeight, QueryPacketEventDataRequest},
+    governance::{GovernanceCouncilStatus, Proposal},
+    ibc_impl::{
+        applications::{
+            fee::{self, EscrowedPacketFee, FeeAsset},
+            ica::{controller::InterchainAccount, host::HostedInterchainAccount},
+            transfer::rate_limit::{self, RateLimitStatus},
+        },
+        clients::wasm,
+        core::{
+            channel_upgrade,
+            client_state::AnyClientState,
+            consensus_state::AnyConsensusState,
+            consensus_state_retention::ConsensusStateRetention,
+            host::{self, HostClientParams},
+            packet_events,
+            packet_pruning::PacketStateStorage,
+        },
+    },
+    pause::PauseFlags,
+    storage_deposit::StorageDeposit,
+    types::{ClientStatus, Page, Qualified, QueryHeight, QueryPacketEventDataRequest},
     *,
 };
-use ibc::{
-    clients::tendermint::context::CommonContext,
-    core::{
-        channel::types::{
-            channel::{ChannelEnd, IdentifiedChannelEnd, Order},
-            commitment::{AcknowledgementCommitment, PacketCommitment},
-        },
-        client::types::Height,
-        connection::types::{ConnectionEnd, IdentifiedConnectionEnd},
-        handler::types::events::IbcEvent,
-        host::{
-            types::{
-                identifiers::{ChannelId, ClientId, ConnectionId, PortId, Sequence},
-                path::{
-                    AckPath, ChannelEndPath, ClientConnectionPath, ClientConsensusStatePath,
-                    ClientStatePath, CommitmentPath, ReceiptPath, SeqRecvPath,
-                },
+use ibc::core::{
+    channel::types::{
+        channel::{ChannelEnd, IdentifiedChannelEnd, Order},
+        commitment::{AcknowledgementCommitment, PacketCommitment},
+    },
+    client::{context::client_state::ClientStateValidation, types::Height},
+    connection::types::{ConnectionEnd, IdentifiedConnectionEnd},
+    handler::types::events::IbcEvent,
+    host::{
+        types::{
+            identifiers::{ChannelId, ClientId, ConnectionId, PortId, Sequence},
+            path::{
+                AckPath, ChannelEndPath, ClientConnectionPath, ClientConsensusStatePath,
+                ClientStatePath, CommitmentPath, ReceiptPath, SeqRecvPath,
             },
-            ValidationContext,
         },
+        ValidationContext,
     },
 };
 use ibc_proto::Protobuf;
@@ -31,7 +47,7 @@ use itertools::Itertools;
 use near_sdk::{
     borsh::{BorshDeserialize, BorshSerialize},
     env,
-    json_types::U64,
+    json_types::{U128, U64},
     near_bindgen,
 };
 
@@ -122,6 +138,143 @@ pub trait Viewer {
     /// Get the maximum height difference between the latest height and the height
     /// of the ibc events.
     fn get_max_ibc_events_height_difference(&self) -> U64;
+    /// Get the interchain accounts registered by the given owner.
+    fn get_interchain_accounts_of(&self, owner: AccountId) -> Vec<InterchainAccount>;
+    /// Get the interchain accounts hosted by this contract for the controllers on other chains.
+    fn get_hosted_interchain_accounts(&self) -> Vec<HostedInterchainAccount>;
+    /// Get the ICS-20 channels whose version is negotiated with ICS-29 (fee middleware).
+    fn get_fee_enabled_channels(&self) -> Vec<(PortId, ChannelId)>;
+    /// Get the fees escrowed for the given packet sent by this contract.
+    fn get_escrowed_packet_fees(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    ) -> Vec<EscrowedPacketFee>;
+    /// Get the amount of the token fees which failed to be paid to the given account,
+    /// which can be claimed by function `claim_unpaid_token_fee`.
+    fn get_unpaid_token_fee(&self, account_id: AccountId, asset: FeeAsset) -> U128;
+    /// Get the payee in NEAR protocol registered by the given relayer for the given channel.
+    fn get_payee(&self, channel_id: ChannelId, relayer: AccountId) -> Option<AccountId>;
+    /// Get the payee on the counterparty chain registered by the given relayer
+    /// for the given channel.
+    fn get_counterparty_payee(&self, channel_id: ChannelId, relayer: AccountId) -> Option<String>;
+    /// Get the rate limits of ICS-20 flows, with their current usage.
+    fn get_rate_limits(&self) -> Vec<RateLimitStatus>;
+    /// Get the emergency pause switches of function `deliver`.
+    fn get_pause_flags(&self) -> PauseFlags;
+    /// Get the members, threshold and timelock of the governance council.
+    fn get_governance_council(&self) -> GovernanceCouncilStatus;
+    /// Get the pending proposals of the governance council.
+    fn get_pending_proposals(&self) -> Vec<Proposal>;
+    /// Get the holders of the given role.
+    fn get_role_holders(&self, role: Role) -> Vec<AccountId>;
+    /// Get the governance account.
+    fn get_governance_account(&self) -> AccountId;
+    /// Get the account proposed to be the new governance account, if any.
+    fn get_pending_governance_account(&self) -> Option<AccountId>;
+    /// Get the registered light client contracts of `08-wasm` clients,
+    /// with their code hashes.
+    fn get_wasm_light_client_contracts(&self) -> Vec<(Base58CryptoHash, AccountId)>;
+    /// Get the parameters of NEAR protocol which the client states of this chain
+    /// on counterparty chains must match.
+    fn get_host_client_params(&self) -> Option<HostClientParams>;
+    /// Get the raw upgrade of the given channel, which is empty if the channel is not
+    /// being upgraded.
+    fn get_channel_upgrade(&self, port_id: PortId, channel_id: ChannelId) -> Vec<u8>;
+    /// Get the raw error receipt of the last failed upgrade of the given channel.
+    fn get_channel_upgrade_error(&self, port_id: PortId, channel_id: ChannelId) -> Vec<u8>;
+    /// Get the numbers and the estimated storage of the packet states of the given channel,
+    /// and the pruning status of it.
+    fn get_packet_state_storage(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+    ) -> PacketStateStorage;
+    /// Get the storage deposit of the given relayer.
+    fn get_storage_deposit(&self, account_id: AccountId) -> StorageDeposit;
+    /// Get the retention of the consensus states of the given client.
+    fn get_consensus_state_retention(&self, client_id: ClientId) -> ConsensusStateRetention;
+    /// Get a page of the storage deposits of the relayers.
+    fn get_storage_deposits_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<(AccountId, StorageDeposit)>;
+    //
+    // The paginated versions of the list view functions, for the deployments with
+    // large collections which exceed the gas limit of view calls.
+    //
+    // At most `limit` entries (100 by default, 500 at most) are scanned from `from_index`
+    // for a page, and `next_index` of the returned page is the `from_index` of the next page.
+    //
+    /// Get a page of the raw client states stored on this host,
+    /// optionally filtered by the status of the clients.
+    fn get_clients_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        status: Option<ClientStatus>,
+    ) -> Page<(ClientId, Vec<u8>)>;
+    /// Get a page of the heights of the stored consensus states associated with
+    /// the given client identifier, in the order of storage.
+    fn get_client_consensus_heights_paged(
+        &self,
+        client_id: ClientId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Height>;
+    /// Get a page of the connection ends stored on this host,
+    /// optionally filtered by the client identifier.
+    fn get_connections_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        client_id: Option<ClientId>,
+    ) -> Page<IdentifiedConnectionEnd>;
+    /// Get a page of the channel ends stored on this host,
+    /// optionally filtered by the port identifier and the connection identifier.
+    fn get_channels_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        port_id: Option<PortId>,
+        connection_id: Option<ConnectionId>,
+    ) -> Page<IdentifiedChannelEnd>;
+    /// Get a page of the packet commitment sequences associated with the given port, channel.
+    fn get_packet_commitment_sequences_paged(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Sequence>;
+    /// Get a page of the packet acknowledgement sequences associated with the given port, channel.
+    fn get_packet_acknowledgement_sequences_paged(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Sequence>;
+    /// Get a page of the heights that ibc events happened on, in ascending order.
+    fn get_ibc_events_heights_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Height>;
+    /// Get a page of the interchain accounts hosted by this contract.
+    fn get_hosted_interchain_accounts_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<HostedInterchainAccount>;
+    /// Get a page of the ICS-20 channels whose version is negotiated with ICS-29.
+    fn get_fee_enabled_channels_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<(PortId, ChannelId)>;
 }
 
 #[near_bindgen]
@@ -175,11 +328,7 @@ impl Viewer for NearIbcContract {
     //
     fn get_client_consensus_heights(&self, client_id: ClientId) -> Vec<Height> {
         let near_ibc_store = self.near_ibc_store.get().unwrap();
-        let mut result = near_ibc_store
-            .consensus_state_heights(&client_id)
-            .unwrap_or(Vec::new());
-        result.sort();
-        result
+        near_ibc_store.consensus_state_heights_of(&client_id)
     }
     //
     fn get_client_consensus(&self, client_id: ClientId, consensus_height: Height) -> Vec<u8> {
@@ -242,12 +391,14 @@ impl Viewer for NearIbcContract {
                     .cloned()
                     .collect()
             } else {
+                let pruned_sequence = near_ibc_store.pruned_packet_sequence(&port_id, &channel_id);
                 let stored_sequences = near_ibc_store
                     .packet_receipt_sequence_sets
                     .get(&(port_id, channel_id))
                     .map_or_else(|| vec![], |receipts| receipts.iter().collect());
                 sequences
                     .iter()
+                    .filter(|sequence| **sequence >= pruned_sequence)
                     .filter(|sequence| !stored_sequences.contains(&sequence))
                     .cloned()
                     .collect()
@@ -414,35 +565,40 @@ impl Viewer for NearIbcContract {
                 QueryHeight::Specific(height) => (Some(height), false),
             },
         };
-        if need_to_search_in_range {
-            if let Some(height) = target_height {
+        if let Some(target_height) = target_height {
+            let heights = if packet_events::is_indexed_event_type(&request.event_type) {
+                near_ibc_store.get_packet_event_heights(
+                    &request.event_type,
+                    &request.source_port_id,
+                    &request.source_channel_id,
+                    &request.sequences,
+                    target_height,
+                    need_to_search_in_range,
+                )
+            } else if need_to_search_in_range {
                 near_ibc_store
                     .ibc_events_history
                     .keys()
                     .iter()
                     .filter(|key| key.is_some())
-                    .map(|key| key.unwrap())
-                    .filter(|key| *key <= height)
-                    .for_each(|key| {
-                        gether_ibc_events_with_height(
-                            &mut result,
-                            key,
-                            near_ibc_store
-                                .ibc_events_history
-                                .get_value_by_key(&key)
-                                .map(|events| events.clone())
-                                .unwrap_or_else(|| vec![]),
-                            &request,
-                        );
-                    });
-            }
-        } else {
-            let ibc_events = near_ibc_store
-                .ibc_events_history
-                .get_value_by_key(&target_height.unwrap())
-                .map(|events| events.clone())
-                .unwrap_or_else(|| vec![]);
-            gether_ibc_events_with_height(&mut result, target_height.unwrap(), ibc_events, &request)
+                    .map(|key| key.unwrap().clone())
+                    .filter(|key| key <= target_height)
+                    .collect()
+            } else {
+                vec![target_height.clone()]
+            };
+            heights.iter().for_each(|height| {
+                gether_ibc_events_with_height(
+                    &mut result,
+                    height,
+                    near_ibc_store
+                        .ibc_events_history
+                        .get_value_by_key(height)
+                        .map(|events| events.clone())
+                        .unwrap_or_else(|| vec![]),
+                    &request,
+                );
+            });
         }
         result
     }
@@ -471,6 +627,347 @@ impl Viewer for NearIbcContract {
         let near_ibc_store = self.near_ibc_store.get().unwrap();
         U64::from(near_ibc_store.max_ibc_events_height_difference)
     }
+    //
+    fn get_interchain_accounts_of(&self, owner: AccountId) -> Vec<InterchainAccount> {
+        self.module_holder
+            .ica_controller_module
+            .get_interchain_accounts_of(&owner)
+    }
+    //
+    fn get_hosted_interchain_accounts(&self) -> Vec<HostedInterchainAccount> {
+        self.module_holder
+            .ica_host_module
+            .get_hosted_interchain_accounts()
+    }
+    //
+    fn get_fee_enabled_channels(&self) -> Vec<(PortId, ChannelId)> {
+        self.module_holder
+            .transfer_module
+            .get_fee_enabled_channels()
+    }
+    //
+    fn get_escrowed_packet_fees(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    ) -> Vec<EscrowedPacketFee> {
+        self.module_holder.transfer_module.get_escrowed_fees(&(
+            port_id,
+            channel_id,
+            u64::from(sequence),
+        ))
+    }
+    //
+    fn get_unpaid_token_fee(&self, account_id: AccountId, asset: FeeAsset) -> U128 {
+        U128(fee::unpaid_token_fee_of(&account_id, &asset))
+    }
+    //
+    fn get_payee(&self, channel_id: ChannelId, relayer: AccountId) -> Option<AccountId> {
+        self.module_holder
+            .transfer_module
+            .get_payee(&channel_id, &relayer)
+    }
+    //
+    fn get_counterparty_payee(&self, channel_id: ChannelId, relayer: AccountId) -> Option<String> {
+        self.module_holder
+            .transfer_module
+            .get_counterparty_payee(&channel_id, &relayer)
+    }
+    //
+    fn get_rate_limits(&self) -> Vec<RateLimitStatus> {
+        rate_limit::get_rate_limits()
+    }
+    //
+    fn get_pause_flags(&self) -> PauseFlags {
+        self.pause_flags.clone()
+    }
+    //
+    fn get_governance_council(&self) -> GovernanceCouncilStatus {
+        self.governance_council.get_status()
+    }
+    //
+    fn get_pending_proposals(&self) -> Vec<Proposal> {
+        self.governance_council.get_pending_proposals()
+    }
+    //
+    fn get_role_holders(&self, role: Role) -> Vec<AccountId> {
+        self.role_holders.holders_of(role)
+    }
+    //
+    fn get_governance_account(&self) -> AccountId {
+        self.governance_account.clone()
+    }
+    //
+    fn get_pending_governance_account(&self) -> Option<AccountId> {
+        self.pending_governance_account.clone()
+    }
+    //
+    fn get_wasm_light_client_contracts(&self) -> Vec<(Base58CryptoHash, AccountId)> {
+        wasm::get_light_client_contracts()
+            .into_iter()
+            .map(|(code_hash, contract_id)| (Base58CryptoHash::from(code_hash), contract_id))
+            .collect()
+    }
+    //
+    fn get_host_client_params(&self) -> Option<HostClientParams> {
+        host::get_host_client_params()
+    }
+    //
+    fn get_channel_upgrade(&self, port_id: PortId, channel_id: ChannelId) -> Vec<u8> {
+        channel_upgrade::get_upgrade(&port_id, &channel_id)
+    }
+    //
+    fn get_channel_upgrade_error(&self, port_id: PortId, channel_id: ChannelId) -> Vec<u8> {
+        channel_upgrade::get_upgrade_error(&port_id, &channel_id)
+    }
+    //
+    fn get_packet_state_storage(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+    ) -> PacketStateStorage {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.get_packet_state_storage(&port_id, &channel_id)
+    }
+    //
+    fn get_storage_deposit(&self, account_id: AccountId) -> StorageDeposit {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.get_storage_deposit(&account_id)
+    }
+    //
+    fn get_consensus_state_retention(&self, client_id: ClientId) -> ConsensusStateRetention {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store.consensus_state_retention(&client_id)
+    }
+    //
+    fn get_storage_deposits_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<(AccountId, StorageDeposit)> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        Page::scan(
+            near_ibc_store.storage_deposits.iter(),
+            near_ibc_store.storage_deposits.len().into(),
+            from_index,
+            limit,
+            |(account_id, deposit)| Some((account_id.clone(), deposit.clone())),
+        )
+    }
+    //
+    fn get_clients_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        status: Option<ClientStatus>,
+    ) -> Page<(ClientId, Vec<u8>)> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        Page::scan(
+            near_ibc_store.client_id_set.iter(),
+            near_ibc_store.client_id_set.len().into(),
+            from_index,
+            limit,
+            |client_id| {
+                if let Some(status) = status {
+                    let client_status = near_ibc_store
+                        .client_state(client_id)
+                        .and_then(|client_state| client_state.status(&near_ibc_store, client_id))
+                        .map(ClientStatus::from)
+                        .ok()?;
+                    if client_status != status {
+                        return None;
+                    }
+                }
+                Some((client_id.clone(), self.get_client_state(client_id.clone())))
+            },
+        )
+    }
+    //
+    fn get_client_consensus_heights_paged(
+        &self,
+        client_id: ClientId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Height> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store
+            .client_consensus_state_heights
+            .get(&client_id)
+            .map_or(
+                Page {
+                    items: vec![],
+                    next_index: None,
+                },
+                |heights| {
+                    Page::scan(
+                        heights.start_index()..heights.end_index() + 1,
+                        heights.len(),
+                        from_index,
+                        limit,
+                        |index| heights.get_key_by_index(&index).cloned(),
+                    )
+                },
+            )
+    }
+    //
+    fn get_connections_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        client_id: Option<ClientId>,
+    ) -> Page<IdentifiedConnectionEnd> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        Page::scan(
+            near_ibc_store.connection_id_set.iter(),
+            near_ibc_store.connection_id_set.len().into(),
+            from_index,
+            limit,
+            |connection_id| {
+                let connection_end = near_ibc_store.connection_end(connection_id).ok()?;
+                if client_id
+                    .as_ref()
+                    .is_some_and(|client_id| connection_end.client_id() != client_id)
+                {
+                    return None;
+                }
+                Some(IdentifiedConnectionEnd {
+                    connection_id: connection_id.clone(),
+                    connection_end,
+                })
+            },
+        )
+    }
+    //
+    fn get_channels_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        port_id: Option<PortId>,
+        connection_id: Option<ConnectionId>,
+    ) -> Page<IdentifiedChannelEnd> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        Page::scan(
+            near_ibc_store.port_channel_id_set.iter(),
+            near_ibc_store.port_channel_id_set.len().into(),
+            from_index,
+            limit,
+            |(chan_port_id, chan_id)| {
+                if port_id
+                    .as_ref()
+                    .is_some_and(|port_id| port_id != chan_port_id)
+                {
+                    return None;
+                }
+                let channel_end = near_ibc_store
+                    .channel_end(&ChannelEndPath::new(chan_port_id, chan_id))
+                    .ok()?;
+                if connection_id.as_ref().is_some_and(|connection_id| {
+                    !channel_end.connection_hops.contains(connection_id)
+                }) {
+                    return None;
+                }
+                Some(IdentifiedChannelEnd {
+                    port_id: chan_port_id.clone(),
+                    channel_id: chan_id.clone(),
+                    channel_end,
+                })
+            },
+        )
+    }
+    //
+    fn get_packet_commitment_sequences_paged(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Sequence> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store
+            .packet_commitment_sequence_sets
+            .get(&(port_id, channel_id))
+            .map_or(
+                Page {
+                    items: vec![],
+                    next_index: None,
+                },
+                |sequences| {
+                    Page::scan(
+                        sequences.iter(),
+                        sequences.len().into(),
+                        from_index,
+                        limit,
+                        |seq| Some(seq.clone()),
+                    )
+                },
+            )
+    }
+    //
+    fn get_packet_acknowledgement_sequences_paged(
+        &self,
+        port_id: PortId,
+        channel_id: ChannelId,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Sequence> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        near_ibc_store
+            .packet_acknowledgement_sequence_sets
+            .get(&(port_id, channel_id))
+            .map_or(
+                Page {
+                    items: vec![],
+                    next_index: None,
+                },
+                |sequences| {
+                    Page::scan(
+                        sequences.iter(),
+                        sequences.len().into(),
+                        from_index,
+                        limit,
+                        |seq| Some(seq.clone()),
+                    )
+                },
+            )
+    }
+    //
+    fn get_ibc_events_heights_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<Height> {
+        let near_ibc_store = self.near_ibc_store.get().unwrap();
+        let events_history = &near_ibc_store.ibc_events_history;
+        Page::scan(
+            (events_history.start_index()..events_history.end_index() + 1)
+                .map(|index| events_history.get_key_by_index(&index)),
+            events_history.len(),
+            from_index,
+            limit,
+            |height| height.cloned(),
+        )
+    }
+    //
+    fn get_hosted_interchain_accounts_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<HostedInterchainAccount> {
+        self.module_holder
+            .ica_host_module
+            .get_hosted_interchain_accounts_paged(from_index, limit)
+    }
+    //
+    fn get_fee_enabled_channels_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+    ) -> Page<(PortId, ChannelId)> {
+        self.module_holder
+            .transfer_module
+            .get_fee_enabled_channels_paged(from_index, limit)
+    }
 }
 
 fn gether_ibc_events_with_height(
@@ -518,3 +1015,99 @@ fn gether_ibc_events_with_height(
         result.push((height.clone(), events));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{setup_contract, CONTRACT_ACCOUNT};
+    use ibc::core::{
+        channel::types::{
+            channel::{Counterparty, State},
+            Version,
+        },
+        host::ExecutionContext,
+    };
+
+    /// Set up a contract with channels `transfer/channel-0` to `transfer/channel-2`
+    /// on connections `connection-0`, `connection-1` and `connection-0`,
+    /// and channel `nft-transfer/channel-3` on `connection-1`.
+    fn setup() -> NearIbcContract {
+        let mut contract = setup_contract(CONTRACT_ACCOUNT);
+        let mut near_ibc_store = contract.near_ibc_store.get().unwrap();
+        for (port_id, channel_index, connection_index) in [
+            ("transfer", 0, 0),
+            ("transfer", 1, 1),
+            ("transfer", 2, 0),
+            ("nft-transfer", 3, 1),
+        ] {
+            let port_id = PortId::from_str(port_id).unwrap();
+            near_ibc_store
+                .store_channel(
+                    &ChannelEndPath::new(&port_id, &ChannelId::new(channel_index)),
+                    ChannelEnd::new(
+                        State::Open,
+                        Order::Unordered,
+                        Counterparty::new(port_id.clone(), Some(ChannelId::new(channel_index))),
+                        vec![ConnectionId::new(connection_index)],
+                        Version::new("ics20-1".to_string()),
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+        }
+        near_ibc_store.flush();
+        contract.near_ibc_store.set(&near_ibc_store);
+        contract
+    }
+
+    fn channel_ids_of(page: &Page<IdentifiedChannelEnd>) -> Vec<String> {
+        page.items
+            .iter()
+            .map(|channel| format!("{}/{}", channel.port_id, channel.channel_id))
+            .collect()
+    }
+
+    #[test]
+    fn test_get_channels_paged() {
+        let contract = setup();
+        let page = contract.get_channels_paged(None, Some(U64(3)), None, None);
+        assert_eq!(
+            channel_ids_of(&page),
+            vec![
+                "transfer/channel-0",
+                "transfer/channel-1",
+                "transfer/channel-2"
+            ]
+        );
+        assert_eq!(page.next_index, Some(U64(3)));
+        let page = contract.get_channels_paged(page.next_index, Some(U64(3)), None, None);
+        assert_eq!(channel_ids_of(&page), vec!["nft-transfer/channel-3"]);
+        assert_eq!(page.next_index, None);
+    }
+
+    #[test]
+    fn test_get_channels_paged_with_filters() {
+        let contract = setup();
+        let page = contract.get_channels_paged(
+            None,
+            Some(U64(2)),
+            Some(PortId::transfer()),
+            Some(ConnectionId::new(0)),
+        );
+        assert_eq!(channel_ids_of(&page), vec!["transfer/channel-0"]);
+        assert_eq!(page.next_index, Some(U64(2)));
+        let page = contract.get_channels_paged(
+            page.next_index,
+            Some(U64(2)),
+            Some(PortId::transfer()),
+            Some(ConnectionId::new(0)),
+        );
+        assert_eq!(channel_ids_of(&page), vec!["transfer/channel-2"]);
+        assert_eq!(page.next_index, None);
+        let page = contract.get_channels_paged(None, None, None, Some(ConnectionId::new(1)));
+        assert_eq!(
+            channel_ids_of(&page),
+            vec!["transfer/channel-1", "nft-transfer/channel-3"]
+        );
+    }
+}
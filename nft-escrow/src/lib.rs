@@ -0,0 +1,266 @@
+#![deny(
+    warnings,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_import_braces,
+    unused_qualifications,
+    rust_2018_idioms
+)]
+
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+use near_contract_standards::non_fungible_token::{core::ext_nft_core, TokenId};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::U64,
+    log, near_bindgen,
+    serde::{Deserialize, Serialize},
+    serde_json,
+    store::UnorderedMap,
+    AccountId, BorshStorageKey, NearToken, PanicOnDefault, PromiseOrValue, PromiseResult,
+};
+use utils::{
+    interfaces::{
+        ext_nft_transfer_request_handler, NearIbcAccountAssertion, NftEscrow,
+        ProcessTransferRequestCallback,
+    },
+    types::Ics721TransferRequest,
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    PendingTransferRequestsById,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftOnTransferMsg {
+    pub receiver: String,
+    #[serde(default)]
+    pub timeout_seconds: Option<U64>,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// The escrow of NEP-171 NFTs native in NEAR protocol, for a certain ICS-721 channel.
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The account id of IBC/TAO implementation.
+    near_ibc_account: AccountId,
+    /// Accounting for the pending transfer requests, keyed by request id.
+    pending_transfer_requests: UnorderedMap<u64, Ics721TransferRequest>,
+    /// The id for the next transfer request.
+    next_request_id: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(near_ibc_account: AccountId) -> Self {
+        let account_id = String::from(env::current_account_id().as_str());
+        let parts = account_id.split(".").collect::<Vec<&str>>();
+        assert!(
+            parts.len() > 2,
+            "ERR_CONTRACT_MUST_BE_DEPLOYED_IN_SUB_ACCOUNT",
+        );
+        Self {
+            near_ibc_account,
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
+            next_request_id: 1,
+        }
+    }
+    ///
+    pub fn version(&self) -> String {
+        VERSION.to_string()
+    }
+    /// Callback function for `nft_transfer_call` of NEP-171 compatible contracts.
+    ///
+    /// The NFT is kept in this contract until it is sent back from the counterparty chain.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let parse_result: Result<NftOnTransferMsg, _> = serde_json::from_str(msg.as_str());
+        assert!(
+            parse_result.is_ok(),
+            "Invalid msg '{}' attached in `nft_transfer_call`. Return the NFT.",
+            msg
+        );
+        let msg = parse_result.unwrap();
+        utils::assert_valid_memo(&msg.memo);
+        let current_account_id = env::current_account_id();
+        let (channel_id, _) = current_account_id.as_str().split_once(".").unwrap();
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let transfer_request = Ics721TransferRequest {
+            request_id: U64(request_id),
+            port_on_a: utils::NFT_TRANSFER_PORT_ID_STR.to_string(),
+            chan_on_a: channel_id.to_string(),
+            class_id: env::predecessor_account_id().to_string(),
+            class_uri: None,
+            class_data: None,
+            token_id,
+            token_uri: None,
+            token_data: None,
+            sender: previous_owner_id.to_string(),
+            receiver: msg.receiver,
+            timeout_seconds: msg.timeout_seconds,
+            memo: msg.memo,
+        };
+        log!(
+            "NFT {} of {} is sent to escrow by {}.",
+            transfer_request.token_id,
+            transfer_request.class_id,
+            sender_id
+        );
+        ext_nft_transfer_request_handler::ext(self.near_ibc_account())
+            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_static_gas(utils::GAS_FOR_COMPLEX_FUNCTION_CALL)
+            .with_unused_gas_weight(0)
+            .process_nft_transfer_request(transfer_request.clone());
+        self.pending_transfer_requests
+            .insert(request_id, transfer_request);
+
+        PromiseOrValue::Value(false)
+    }
+    /// Remove the pending transfer request with the given id and return it.
+    fn checked_remove_pending_transfer_request(
+        &mut self,
+        request_id: U64,
+    ) -> Ics721TransferRequest {
+        self.pending_transfer_requests
+            .remove(&request_id.0)
+            .expect("ERR_NO_PENDING_TRANSFER_REQUEST")
+    }
+}
+
+#[ext_contract(ext_nft_transfer_callback)]
+pub trait NftTransferCallback {
+    fn nft_transfer_callback(
+        &mut self,
+        nft_contract: AccountId,
+        token_id: TokenId,
+        receiver_id: AccountId,
+    );
+}
+
+#[near_bindgen]
+impl NftTransferCallback for Contract {
+    #[private]
+    fn nft_transfer_callback(
+        &mut self,
+        nft_contract: AccountId,
+        token_id: TokenId,
+        receiver_id: AccountId,
+    ) {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_bytes) => {
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"NFT_TRANSFER_SUCCEEDED","nft_contract":"{}","token_id":"{}","receiver_id":"{}"}}"#,
+                    nft_contract,
+                    token_id,
+                    receiver_id,
+                );
+            }
+            PromiseResult::Failed => {
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ERR_NFT_TRANSFER","nft_contract":"{}","token_id":"{}","receiver_id":"{}"}}"#,
+                    nft_contract,
+                    token_id,
+                    receiver_id,
+                );
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl NftEscrow for Contract {
+    //
+    #[payable]
+    fn do_nft_transfer(
+        &mut self,
+        nft_contract: AccountId,
+        token_id: TokenId,
+        receiver_id: AccountId,
+    ) {
+        self.assert_near_ibc_account();
+        near_sdk::assert_one_yocto();
+        ext_nft_core::ext(nft_contract.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+            .with_unused_gas_weight(0)
+            .nft_transfer(receiver_id.clone(), token_id.clone(), None, None)
+            .then(
+                ext_nft_transfer_callback::ext(env::current_account_id())
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                    .with_unused_gas_weight(0)
+                    .nft_transfer_callback(nft_contract, token_id, receiver_id),
+            );
+    }
+}
+
+#[near_bindgen]
+impl ProcessTransferRequestCallback for Contract {
+    fn apply_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        self.checked_remove_pending_transfer_request(request_id);
+    }
+
+    fn cancel_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        let req = self.checked_remove_pending_transfer_request(request_id);
+        ext_nft_core::ext(AccountId::from_str(req.class_id.as_str()).unwrap())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+            .with_unused_gas_weight(0)
+            .nft_transfer(
+                AccountId::from_str(req.sender.as_str()).unwrap(),
+                req.token_id,
+                None,
+                None,
+            );
+    }
+}
+
+impl NearIbcAccountAssertion for Contract {
+    fn near_ibc_account(&self) -> AccountId {
+        self.near_ibc_account.clone()
+    }
+}
+
+/// View functions.
+pub trait Viewer {
+    /// Get all pending transfer requests.
+    fn get_pending_transfer_requests(&self) -> Vec<Ics721TransferRequest>;
+    /// Get the pending transfer request with the given id.
+    fn get_pending_transfer_request(&self, request_id: U64) -> Option<Ics721TransferRequest>;
+}
+
+#[near_bindgen]
+impl Viewer for Contract {
+    ///
+    fn get_pending_transfer_requests(&self) -> Vec<Ics721TransferRequest> {
+        self.pending_transfer_requests.values().cloned().collect()
+    }
+    ///
+    fn get_pending_transfer_request(&self, request_id: U64) -> Option<Ics721TransferRequest> {
+        self.pending_transfer_requests
+            .get(&request_id.0)
+            .map(|req| req.clone())
+    }
+}
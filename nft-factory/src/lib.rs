@@ -0,0 +1,309 @@
+#![deny(
+    warnings,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_import_braces,
+    unused_qualifications,
+    rust_2018_idioms
+)]
+
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::Base58CryptoHash,
+    log, near_bindgen,
+    serde::{Deserialize, Serialize},
+    store::{LookupMap, UnorderedMap},
+    AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise, PromiseResult,
+};
+use utils::{
+    interfaces::{ext_wrapped_nft, NftFactory, RoleHoldersReceiver},
+    types::{NftClass, Role, RoleHolders},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    NftContractWasm,
+    CollectionIdMappings,
+    ClassIdToCollectionIdMap,
+}
+
+/// A class of NFTs from other chains, and the id of its wrapped NFT contract.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrossChainNftClass {
+    pub collection_id: String,
+    pub nft_class: NftClass,
+}
+
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// Maps collection id to cross chain NFT class.
+    collection_id_mappings: UnorderedMap<String, CrossChainNftClass>,
+    /// Maps class id to collection id.
+    class_id_to_collection_id_map: LookupMap<String, String>,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        let account_id = String::from(env::current_account_id().as_str());
+        let parts = account_id.split(".").collect::<Vec<&str>>();
+        assert!(
+            parts.len() > 2,
+            "ERR_CONTRACT_MUST_BE_DEPLOYED_IN_SUB_ACCOUNT",
+        );
+        Self {
+            collection_id_mappings: UnorderedMap::new(StorageKey::CollectionIdMappings),
+            class_id_to_collection_id_map: LookupMap::new(StorageKey::ClassIdToCollectionIdMap),
+        }
+    }
+    ///
+    pub fn version(&self) -> String {
+        VERSION.to_string()
+    }
+    /// Generate the collection id for the given class id, which is also derived by
+    /// the `near-ibc` contract to check the sender of NFT transfer requests.
+    fn generate_collection_id(&self, class_id: &String) -> String {
+        let collection_id = utils::collection_id_of(class_id);
+        assert!(
+            !self.collection_id_mappings.contains_key(&collection_id),
+            "ERR_COLLECTION_ID_ALREADY_EXISTS"
+        );
+        collection_id
+    }
+}
+
+#[near_bindgen]
+impl NftFactory for Contract {
+    #[payable]
+    fn mint_nft(
+        &mut self,
+        nft_class: NftClass,
+        token_id: String,
+        token_uri: Option<String>,
+        token_data: Option<String>,
+        token_owner: AccountId,
+    ) {
+        utils::assert_ancestor_account();
+        let maybe_collection_id = self
+            .class_id_to_collection_id_map
+            .get(&nft_class.class_id)
+            .map(|v| v.clone());
+        let (nft_contract_id, promise) = match maybe_collection_id {
+            Some(collection_id) => {
+                let nft_contract_id: AccountId =
+                    format!("{}.{}", collection_id, env::current_account_id())
+                        .parse()
+                        .unwrap();
+                (
+                    nft_contract_id.clone(),
+                    ext_wrapped_nft::ext(nft_contract_id)
+                        .with_attached_deposit(env::attached_deposit())
+                        .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+                        .with_unused_gas_weight(0)
+                        .mint(token_id.clone(), token_uri, token_data, token_owner.clone()),
+                )
+            }
+            None => {
+                assert!(
+                    env::attached_deposit().as_yoctonear()
+                        >= utils::INIT_BALANCE_FOR_WRAPPED_NFT_CONTRACT
+                            + utils::STORAGE_DEPOSIT_FOR_MINT_NFT,
+                    "ERR_NOT_ENOUGH_DEPOSIT, must not less than {} yocto",
+                    utils::INIT_BALANCE_FOR_WRAPPED_NFT_CONTRACT
+                        + utils::STORAGE_DEPOSIT_FOR_MINT_NFT
+                );
+                #[derive(Serialize, Deserialize, Clone)]
+                #[serde(crate = "near_sdk::serde")]
+                struct MintInput {
+                    token_id: String,
+                    token_uri: Option<String>,
+                    token_data: Option<String>,
+                    token_owner: AccountId,
+                }
+                let mint_args = near_sdk::serde_json::to_vec(&MintInput {
+                    token_id: token_id.clone(),
+                    token_uri,
+                    token_data,
+                    token_owner: token_owner.clone(),
+                })
+                .expect("ERR_SERIALIZE_ARGS_FOR_MINT_FUNCTION");
+                let collection_id = self.generate_collection_id(&nft_class.class_id);
+                let nft_contract_id: AccountId =
+                    format!("{}.{}", collection_id, env::current_account_id())
+                        .parse()
+                        .unwrap();
+                #[derive(Serialize, Deserialize, Clone)]
+                #[serde(crate = "near_sdk::serde")]
+                struct Input {
+                    nft_class: NftClass,
+                    near_ibc_account: AccountId,
+                }
+                let args = near_sdk::serde_json::to_vec(&Input {
+                    nft_class: nft_class.clone(),
+                    near_ibc_account: env::predecessor_account_id(),
+                })
+                .expect("ERR_SERIALIZE_ARGS_FOR_NFT_CONTRACT_INIT");
+                // Store mappings.
+                self.collection_id_mappings.insert(
+                    collection_id.clone(),
+                    CrossChainNftClass {
+                        collection_id: collection_id.clone(),
+                        nft_class: nft_class.clone(),
+                    },
+                );
+                self.class_id_to_collection_id_map
+                    .insert(nft_class.class_id.clone(), collection_id);
+                (
+                    nft_contract_id.clone(),
+                    Promise::new(nft_contract_id)
+                        .create_account()
+                        .transfer(NearToken::from_yoctonear(
+                            utils::INIT_BALANCE_FOR_WRAPPED_NFT_CONTRACT,
+                        ))
+                        .deploy_contract(
+                            env::storage_read(
+                                &borsh::to_vec(&StorageKey::NftContractWasm).unwrap(),
+                            )
+                            .expect("ERR_NFT_CONTRACT_WASM_NOT_STORED"),
+                        )
+                        .function_call(
+                            "new".to_string(),
+                            args,
+                            NearToken::from_yoctonear(0),
+                            utils::GAS_FOR_SIMPLE_FUNCTION_CALL,
+                        )
+                        .function_call(
+                            "mint".to_string(),
+                            mint_args,
+                            NearToken::from_yoctonear(utils::STORAGE_DEPOSIT_FOR_MINT_NFT),
+                            utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2),
+                        ),
+                )
+            }
+        };
+        promise.then(
+            ext_mint_nft_callback::ext(env::current_account_id())
+                .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                .with_unused_gas_weight(0)
+                .mint_nft_callback(nft_class.class_id, nft_contract_id, token_id, token_owner),
+        );
+    }
+}
+
+#[ext_contract(ext_mint_nft_callback)]
+pub trait MintNftCallback {
+    fn mint_nft_callback(
+        &mut self,
+        class_id: String,
+        nft_contract: AccountId,
+        token_id: String,
+        token_owner: AccountId,
+    );
+}
+
+#[near_bindgen]
+impl MintNftCallback for Contract {
+    #[private]
+    fn mint_nft_callback(
+        &mut self,
+        class_id: String,
+        nft_contract: AccountId,
+        token_id: String,
+        token_owner: AccountId,
+    ) {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_bytes) => {
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"MINT_NFT_SUCCEEDED","class_id":"{}","nft_contract":"{}","token_id":"{}","token_owner":"{}"}}"#,
+                    class_id,
+                    nft_contract,
+                    token_id,
+                    token_owner,
+                );
+            }
+            PromiseResult::Failed => {
+                log!(
+                    r#"EVENT_JSON:{{"standard":"nep297","version":"1.0.0","event":"ERR_MINT_NFT","class_id":"{}","nft_contract":"{}","token_id":"{}","token_owner":"{}"}}"#,
+                    class_id,
+                    nft_contract,
+                    token_id,
+                    token_owner,
+                );
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl RoleHoldersReceiver for Contract {
+    fn sync_role_holders(&mut self, role_holders: RoleHolders) {
+        utils::assert_ancestor_account();
+        utils::set_synced_role_holders(&role_holders);
+    }
+}
+
+/// View functions.
+pub trait Viewer {
+    /// Get all cross chain NFT classes.
+    fn get_cross_chain_nft_classes(&self) -> Vec<CrossChainNftClass>;
+}
+
+#[near_bindgen]
+impl Viewer for Contract {
+    fn get_cross_chain_nft_classes(&self) -> Vec<CrossChainNftClass> {
+        self.collection_id_mappings.values().cloned().collect()
+    }
+}
+
+/// Stores attached data into blob store and returns hash of it.
+/// Implemented to avoid loading the data into WASM for optimal gas usage.
+#[no_mangle]
+pub extern "C" fn store_wasm_of_nft_contract() {
+    env::setup_panic_hook();
+    let _contract: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+    utils::assert_self_or_role(Role::Operator);
+    let input = env::input().expect("ERR_NO_INPUT");
+    let sha256_hash = env::sha256(&input);
+
+    let current_len = env::storage_read(&borsh::to_vec(&StorageKey::NftContractWasm).unwrap())
+        .map_or_else(|| 0, |bytes| bytes.len());
+    let blob_len = input.len();
+    if blob_len > current_len {
+        let storage_cost = (env::storage_usage() + blob_len as u64 - current_len as u64) as u128
+            * env::storage_byte_cost().as_yoctonear();
+        assert!(
+            env::account_balance().as_yoctonear() >= storage_cost,
+            "ERR_NOT_ENOUGH_ACCOUNT_BALANCE, needs {} more.",
+            storage_cost - env::account_balance().as_yoctonear()
+        );
+    }
+
+    env::storage_write(
+        &borsh::to_vec(&StorageKey::NftContractWasm).unwrap(),
+        &input,
+    );
+
+    let mut blob_hash = [0u8; 32];
+    blob_hash.copy_from_slice(&sha256_hash);
+    let blob_hash_str = near_sdk::serde_json::to_string(&Base58CryptoHash::from(blob_hash))
+        .unwrap()
+        .into_bytes();
+
+    env::value_return(&blob_hash_str);
+}
@@ -9,8 +9,8 @@ use near_sdk::{
     AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise, PromiseResult,
 };
 use utils::{
-    interfaces::{ext_wrapped_token, TokenFactory},
-    types::{AssetDenom, CrossChainAsset},
+    interfaces::{ext_wrapped_token, RoleHoldersReceiver, TokenFactory},
+    types::{AssetDenom, CrossChainAsset, Role, RoleHolders},
     ExtraDepositCost,
 };
 
@@ -97,11 +97,7 @@ impl TokenFactory for Contract {
         let used_bytes = env::storage_usage();
         ExtraDepositCost::reset();
         // Generate asset id.
-        let mut asset_id =
-            hex::encode(env::sha256(borsh::to_vec(&asset_denom).unwrap().as_slice()))
-                .get(0..32)
-                .unwrap()
-                .to_string();
+        let mut asset_id = utils::asset_id_of(&asset_denom);
         let mut retry: u8 = 0;
         while self.asset_id_mappings.contains_key(&asset_id) {
             let mut bytes = borsh::to_vec(&asset_denom).unwrap();
@@ -196,6 +192,38 @@ impl TokenFactory for Contract {
                     ),
             );
     }
+
+    #[payable]
+    fn mint_asset_and_call(
+        &mut self,
+        trace_path: String,
+        base_denom: String,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise {
+        utils::assert_ancestor_account();
+        let asset_denom = AssetDenom {
+            trace_path,
+            base_denom,
+        };
+        let maybe_asset_id = self
+            .denom_to_asset_id_map
+            .get(&asset_denom)
+            .map(|v| v.clone());
+        assert!(maybe_asset_id.is_some(), "ERR_ASSET_NEEDS_TO_BE_SETUP");
+        // Mint tokens and call the receiver contract.
+        let token_contract_id: AccountId =
+            format!("{}.{}", maybe_asset_id.unwrap(), env::current_account_id())
+                .parse()
+                .unwrap();
+        ext_wrapped_token::ext(token_contract_id)
+            .with_attached_deposit(env::attached_deposit())
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(8))
+            .with_unused_gas_weight(1)
+            .mint_and_call(receiver_id, amount, msg, refund_receiver)
+    }
 }
 
 #[ext_contract(ext_mint_callback)]
@@ -242,6 +270,14 @@ impl MintCallback for Contract {
     }
 }
 
+#[near_bindgen]
+impl RoleHoldersReceiver for Contract {
+    fn sync_role_holders(&mut self, role_holders: RoleHolders) {
+        utils::assert_ancestor_account();
+        utils::set_synced_role_holders(&role_holders);
+    }
+}
+
 /// View functions.
 pub trait Viewer {
     /// Get all cross chain assets.
@@ -265,11 +301,7 @@ impl Viewer for Contract {
 pub extern "C" fn store_wasm_of_token_contract() {
     env::setup_panic_hook();
     let _contract: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
-    assert_eq!(
-        env::predecessor_account_id(),
-        env::current_account_id(),
-        "ERR_NOT_ALLOWED"
-    );
+    utils::assert_self_or_role(Role::Operator);
     let input = env::input().expect("ERR_NO_INPUT");
     let sha256_hash = env::sha256(&input);
 
@@ -1,10 +1,16 @@
-use crate::{prelude::*, types::Ics20TransferRequest};
+use crate::{
+    prelude::*,
+    types::{
+        Ics20MultiTransferRequest, Ics20TransferRequest, Ics721TransferRequest,
+        InterchainAccountAction, NftClass, PacketFee, RoleHolders,
+    },
+};
 use ibc::core::host::types::identifiers::ChannelId;
 use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
 use near_sdk::{
     ext_contract,
     json_types::{U128, U64},
-    AccountId,
+    AccountId, Promise,
 };
 
 pub trait CheckStorageAndRefund {
@@ -31,6 +37,13 @@ pub trait NearIbcAccountAssertion {
     }
 }
 
+/// Interfaces for the factory contracts to receive the role holders of `near-ibc` contract.
+#[ext_contract(ext_role_holders_receiver)]
+pub trait RoleHoldersReceiver {
+    /// Replace the role holders synced from `near-ibc` contract.
+    fn sync_role_holders(&mut self, role_holders: RoleHolders);
+}
+
 /// Interfaces for the escrow factory contract.
 #[ext_contract(ext_escrow_factory)]
 pub trait EscrowFactory {
@@ -46,12 +59,42 @@ pub trait ChannelEscrow {
     /// Unregister an asset which has already been registered.
     fn unregister_asset(&mut self, base_denom: String);
     /// Send a certain amount of tokens to a certain account.
+    ///
+    /// The promise returns the amount transferred, which is `0` if the transfer failed.
     fn do_transfer(
         &mut self,
         trace_path: String,
         base_denom: String,
         receiver_id: AccountId,
         amount: U128,
+    ) -> Promise;
+    /// Send a certain amount of tokens to a certain contract by `ft_transfer_call`
+    /// with the given `msg`.
+    ///
+    /// The unused part of the tokens will be sent to `refund_receiver`, if the receiver
+    /// contract used some of the tokens. Returns the amount of used tokens.
+    fn do_transfer_call(
+        &mut self,
+        trace_path: String,
+        base_denom: String,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise;
+    /// Pause or resume the outgoing transfers (`ft_on_transfer`) of this contract.
+    fn set_paused(&mut self, paused: bool);
+    /// Record the wrapped tokens locked by a wrapped token contract for a multi-denom
+    /// transfer through the channel of this contract.
+    ///
+    /// Only the wrapped token contracts whose trace path starts with the channel
+    /// of this contract can call this method.
+    fn on_wrapped_token_deposit(
+        &mut self,
+        sender_id: AccountId,
+        trace_path: String,
+        base_denom: String,
+        amount: U128,
     );
 }
 
@@ -73,6 +116,19 @@ pub trait TokenFactory {
         token_owner: AccountId,
         amount: U128,
     );
+    /// Mint a certain amount of tokens and send them to a certain contract
+    /// by `ft_transfer_call` with the given `msg`.
+    ///
+    /// Returns the amount of tokens used by the receiver contract.
+    fn mint_asset_and_call(
+        &mut self,
+        trace_path: String,
+        base_denom: String,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise;
 }
 
 /// Interfaces for wrapped token contracts.
@@ -80,18 +136,123 @@ pub trait TokenFactory {
 pub trait WrappedToken {
     /// Mint a certain amount of tokens to a certain account.
     fn mint(&mut self, account_id: AccountId, amount: U128);
+    /// Mint a certain amount of tokens and send them to a certain contract
+    /// by `ft_transfer_call` with the given `msg`.
+    ///
+    /// If none of the tokens are used by the receiver contract, they will be burnt.
+    /// Otherwise, the unused part will be sent to `refund_receiver`.
+    /// Returns the amount of used tokens.
+    fn mint_and_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise;
     /// Set the icon of the token.
     fn set_icon(&mut self, icon: String);
+    /// Pause or resume the outgoing transfers (`request_transfer`) of this contract.
+    fn set_paused(&mut self, paused: bool);
+    /// Burn a certain amount of the tokens locked for multi-denom transfers,
+    /// as the transfer is applied.
+    ///
+    /// Only the channel escrow of the channel in the trace path can call this method.
+    fn burn_locked_tokens(&mut self, amount: U128);
+    /// Return a certain amount of the tokens locked for multi-denom transfers
+    /// to a certain account.
+    ///
+    /// Only the channel escrow of the channel in the trace path can call this method.
+    fn unlock_tokens(&mut self, receiver_id: AccountId, amount: U128);
+}
+
+/// Interfaces for the NFT escrow contracts.
+#[ext_contract(ext_nft_escrow)]
+pub trait NftEscrow {
+    /// Send a certain NFT of a certain NFT contract to a certain account.
+    fn do_nft_transfer(
+        &mut self,
+        nft_contract: AccountId,
+        token_id: String,
+        receiver_id: AccountId,
+    );
+}
+
+/// Interfaces for the NFT factory contract.
+#[ext_contract(ext_nft_factory)]
+pub trait NftFactory {
+    /// Mint a certain NFT of a certain class to a certain account.
+    ///
+    /// The wrapped NFT contract of the class will be created if it doesn't exist.
+    fn mint_nft(
+        &mut self,
+        nft_class: NftClass,
+        token_id: String,
+        token_uri: Option<String>,
+        token_data: Option<String>,
+        token_owner: AccountId,
+    );
+}
+
+/// Interfaces for wrapped NFT contracts.
+#[ext_contract(ext_wrapped_nft)]
+pub trait WrappedNft {
+    /// Mint a certain NFT to a certain account.
+    fn mint(
+        &mut self,
+        token_id: String,
+        token_uri: Option<String>,
+        token_data: Option<String>,
+        token_owner: AccountId,
+    );
+}
+
+/// Interfaces for the interchain account contracts hosted by the `near-ibc` contract.
+#[ext_contract(ext_interchain_account)]
+pub trait InterchainAccount {
+    /// Execute the given actions by this account.
+    ///
+    /// Returns the results of the actions, refer to `InterchainAccountActionResult`.
+    fn execute(&mut self, actions: Vec<InterchainAccountAction>) -> Promise;
 }
 
 /// Interfaces for transfer request handler contract (the `near-ibc` contract).
 #[ext_contract(ext_transfer_request_handler)]
 pub trait TransferRequestHandler {
     /// Process a certain transfer request.
-    fn process_transfer_request(&mut self, transfer_request: Ics20TransferRequest);
+    ///
+    /// Only the channel escrow of the channel (for tokens native in NEAR protocol) or
+    /// the wrapped token contract of the denom (for tokens from other chains) in the request
+    /// can call this method.
+    ///
+    /// The optional `fee` is escrowed for the relayers of the packet (ICS-29).
+    /// It is paid by the NEAR attached, or by the tokens held by the caller
+    /// if the caller is a channel escrow.
+    fn process_transfer_request(
+        &mut self,
+        transfer_request: Ics20TransferRequest,
+        fee: Option<PacketFee>,
+    );
+    /// Process a certain multi-denom transfer request, by sending an ICS-20 v2 packet
+    /// with all of the tokens in the request.
+    ///
+    /// Only the channel escrow of the channel can call this method. The optional `fee`
+    /// is paid by the NEAR attached.
+    fn process_multi_transfer_request(
+        &mut self,
+        transfer_request: Ics20MultiTransferRequest,
+        fee: Option<PacketFee>,
+    );
+}
+
+/// Interfaces for NFT transfer request handler contract (the `near-ibc` contract).
+#[ext_contract(ext_nft_transfer_request_handler)]
+pub trait NftTransferRequestHandler {
+    /// Process a certain NFT transfer request.
+    fn process_nft_transfer_request(&mut self, transfer_request: Ics721TransferRequest);
 }
 
-/// The callback interface for `ext_transfer_request_handler`.
+/// The callback interface for `ext_transfer_request_handler`
+/// and `ext_nft_transfer_request_handler`.
 #[ext_contract(ext_process_transfer_request_callback)]
 pub trait ProcessTransferRequestCallback {
     /// Apply a certain pending transfer request.
@@ -100,26 +261,14 @@ pub trait ProcessTransferRequestCallback {
     ///
     /// The calling is triggered by the IBC/TAO implementation, when all checkings
     /// are passed for a `send_transfer` request from this contract.
-    /// The account id and amount must match the current pending transfer request.
-    fn apply_transfer_request(
-        &mut self,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
-        amount: U128,
-    );
+    /// The request id must match a current pending transfer request.
+    fn apply_transfer_request(&mut self, request_id: U64);
     /// Cancel a certain pending transfer request.
     ///
     /// Only the `near-ibc` account can call this method.
     ///
     /// The calling is triggered by the IBC/TAO implementation, when error happens
     /// in processing a `send_transfer` request from this contract.
-    /// The account id and amount must match the current pending transfer request.
-    fn cancel_transfer_request(
-        &mut self,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
-        amount: U128,
-    );
+    /// The request id must match a current pending transfer request.
+    fn cancel_transfer_request(&mut self, request_id: U64);
 }
@@ -17,6 +17,7 @@ use near_sdk::{
     env, AccountId, Gas, NearToken, Promise,
 };
 use prelude::*;
+use types::{Role, RoleHolders};
 
 pub mod interfaces;
 mod prelude;
@@ -36,8 +37,26 @@ pub const STORAGE_DEPOSIT_FOR_MINT_TOKEN: Balance = 12_500_000_000_000_000_000_0
 pub const INIT_BALANCE_FOR_WRAPPED_TOKEN_CONTRACT: Balance = 3_500_000_000_000_000_000_000_000;
 /// Initial balance for the channel escrow to cover storage deposit.
 pub const INIT_BALANCE_FOR_CHANNEL_ESCROW_CONTRACT: Balance = 3_000_000_000_000_000_000_000_000;
+/// Initial balance for the wrapped NFT contract to cover storage deposit.
+pub const INIT_BALANCE_FOR_WRAPPED_NFT_CONTRACT: Balance = 3_500_000_000_000_000_000_000_000;
+/// The storage deposit for minting an NFT in the wrapped NFT contract. (0.1 NEAR)
+pub const STORAGE_DEPOSIT_FOR_MINT_NFT: Balance = 100_000_000_000_000_000_000_000;
+/// Initial balance for the interchain account contract to cover storage deposit.
+pub const INIT_BALANCE_FOR_INTERCHAIN_ACCOUNT_CONTRACT: Balance = 1_000_000_000_000_000_000_000_000;
+
+/// The port id of ICS-721 NFT transfer.
+pub const NFT_TRANSFER_PORT_ID_STR: &str = "nft-transfer";
+
+/// The max length (in bytes) of the memo attached to an ICS-20 transfer request.
+/// This is the same as the limit used in `ibc-go`.
+pub const MAX_LENGTH_OF_MEMO: usize = 32768;
+
+/// The max number of tokens in a multi-denom transfer (ICS-20 v2), limited by the gas
+/// for unescrowing or minting each of the tokens when the packet is received or refunded.
+pub const MAX_TOKENS_IN_MULTI_DENOM_TRANSFER: usize = 5;
 
 const STORAGE_KEY_FOR_EXTRA_DEPOSIT_COST: &[u8] = b"extra_deposit_cost";
+const STORAGE_KEY_FOR_SYNCED_ROLE_HOLDERS: &[u8] = b"synced_role_holders";
 
 #[derive(BorshSerialize, BorshDeserialize)]
 #[borsh(crate = "near_sdk::borsh")]
@@ -69,6 +88,36 @@ impl ExtraDepositCost {
     }
 }
 
+/// Get the role holders synced from `near-ibc` contract, in the contracts of sub-accounts.
+pub fn get_synced_role_holders() -> RoleHolders {
+    match env::storage_read(STORAGE_KEY_FOR_SYNCED_ROLE_HOLDERS) {
+        Some(bytes) => RoleHolders::try_from_slice(&bytes).unwrap(),
+        None => RoleHolders::default(),
+    }
+}
+
+/// Save the role holders synced from `near-ibc` contract, in the contracts of sub-accounts.
+///
+/// They are stored in a separated storage key, so that the contract state
+/// doesn't need to be migrated.
+pub fn set_synced_role_holders(role_holders: &RoleHolders) {
+    env::storage_write(
+        STORAGE_KEY_FOR_SYNCED_ROLE_HOLDERS,
+        &borsh::to_vec(role_holders).unwrap(),
+    );
+}
+
+/// Asserts that the predecessor is the current account, or has the given role
+/// synced from `near-ibc` contract.
+pub fn assert_self_or_role(role: Role) {
+    let predecessor = env::predecessor_account_id();
+    assert!(
+        predecessor == env::current_account_id()
+            || get_synced_role_holders().has_role(role, &predecessor),
+        "ERR_NOT_ALLOWED"
+    );
+}
+
 /// Check the usage of storage of current account and refund the unused attached deposit.
 ///
 /// For calling this function, at least `GAS_FOR_CHECK_STORAGE_AND_REFUND` gas is needed.
@@ -100,6 +149,17 @@ pub fn refund_deposit(previously_used_bytes: u64) {
     Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(refund_amount));
 }
 
+/// Asserts that the length of the given memo does not exceed `MAX_LENGTH_OF_MEMO`.
+pub fn assert_valid_memo(memo: &Option<String>) {
+    if let Some(memo) = memo {
+        assert!(
+            memo.len() <= MAX_LENGTH_OF_MEMO,
+            "ERR_MEMO_TOO_LONG, the max length is {} bytes.",
+            MAX_LENGTH_OF_MEMO
+        );
+    }
+}
+
 /// Asserts that the predecessor account is the root account.
 pub fn assert_root_account() {
     let account_id = String::from(env::current_account_id().as_str());
@@ -176,6 +236,30 @@ pub fn get_token_factory_contract_id() -> AccountId {
         .unwrap()
 }
 
+/// Get the asset id of the given asset denom, which is the name of the sub account
+/// of the token factory contract for the wrapped token of the asset.
+///
+/// The token factory only derives another asset id (by appending a retry counter to the
+/// hashed bytes) when this one collides with an existing asset, which never happens
+/// in practice, as the asset id is 128 bits of the SHA-256 hash.
+pub fn asset_id_of(asset_denom: &types::AssetDenom) -> String {
+    hex::encode(env::sha256(borsh::to_vec(asset_denom).unwrap().as_slice()))
+        .get(0..32)
+        .unwrap()
+        .to_string()
+}
+
+/// Get the account id of the wrapped token contract of the given asset denom.
+pub fn wrapped_token_contract_id_of(asset_denom: &types::AssetDenom) -> AccountId {
+    format!(
+        "{}.{}",
+        asset_id_of(asset_denom),
+        get_token_factory_contract_id()
+    )
+    .parse()
+    .unwrap()
+}
+
 /// Get the escrow factory contract id by directly appending a certain suffix
 /// to the current account id.
 pub fn get_escrow_factory_contract_id() -> AccountId {
@@ -183,3 +267,47 @@ pub fn get_escrow_factory_contract_id() -> AccountId {
         .parse()
         .unwrap()
 }
+
+/// Get the NFT factory contract id by directly appending a certain suffix
+/// to the current account id.
+pub fn get_nft_factory_contract_id() -> AccountId {
+    format!(
+        "nf.{}.{}",
+        NFT_TRANSFER_PORT_ID_STR,
+        env::current_account_id()
+    )
+    .parse()
+    .unwrap()
+}
+
+/// Get the collection id of the wrapped NFT contract of the given (prefixed) class id,
+/// which is the name of the sub-account of the NFT factory contract.
+pub fn collection_id_of(class_id: &str) -> String {
+    hex::encode(env::sha256(class_id.as_bytes()))
+        .get(0..32)
+        .unwrap()
+        .to_string()
+}
+
+/// Get the account id of the wrapped NFT contract of the given (prefixed) class id.
+pub fn wrapped_nft_contract_id_of(class_id: &str) -> AccountId {
+    format!(
+        "{}.{}",
+        collection_id_of(class_id),
+        get_nft_factory_contract_id()
+    )
+    .parse()
+    .unwrap()
+}
+
+/// Get the NFT escrow factory contract id by directly appending a certain suffix
+/// to the current account id.
+pub fn get_nft_escrow_factory_contract_id() -> AccountId {
+    format!(
+        "ef.{}.{}",
+        NFT_TRANSFER_PORT_ID_STR,
+        env::current_account_id()
+    )
+    .parse()
+    .unwrap()
+}
@@ -1,9 +1,11 @@
 use crate::prelude::*;
+use alloc::collections::{BTreeMap, BTreeSet};
 use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
 use near_sdk::{
     borsh::{BorshDeserialize, BorshSerialize},
-    json_types::{U128, U64},
+    json_types::{Base64VecU8, U128, U64},
     serde::{Deserialize, Serialize},
+    AccountId,
 };
 
 #[derive(
@@ -29,6 +31,8 @@ pub struct CrossChainAsset {
 #[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
 pub struct Ics20TransferRequest {
+    /// The id of the request, which is unique in the contract that generates it.
+    pub request_id: U64,
     pub port_on_a: String,
     pub chan_on_a: String,
     pub token_trace_path: String,
@@ -37,6 +41,102 @@ pub struct Ics20TransferRequest {
     pub sender: String,
     pub receiver: String,
     pub timeout_seconds: Option<U64>,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// A certain amount of a token in a multi-denom transfer (ICS-20 v2).
+#[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Ics20Token {
+    pub trace_path: String,
+    pub base_denom: String,
+    pub amount: U128,
+}
+
+/// The request for transferring several tokens in one ICS-20 v2 packet.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Ics20MultiTransferRequest {
+    /// The id of the request, which is unique in the contract that generates it.
+    pub request_id: U64,
+    pub port_on_a: String,
+    pub chan_on_a: String,
+    pub tokens: Vec<Ics20Token>,
+    pub sender: String,
+    pub receiver: String,
+    pub timeout_seconds: Option<U64>,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// The fees for relaying a packet, in ICS-29 of IBC protocol.
+///
+/// The fees are paid in NEAR or in the NEP-141 token being transferred,
+/// depending on how the transfer request is created.
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize,
+)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct PacketFee {
+    /// The fee paid to the relayer who relays the packet to the counterparty chain.
+    pub recv_fee: U128,
+    /// The fee paid to the relayer who relays the acknowledgement back.
+    pub ack_fee: U128,
+    /// The fee paid to the relayer who relays the timeout of the packet.
+    pub timeout_fee: U128,
+}
+
+impl PacketFee {
+    /// The total amount to be escrowed for the fees.
+    ///
+    /// The unused part is refunded when the packet is acknowledged or timed out.
+    pub fn total(&self) -> u128 {
+        self.recv_fee.0 + self.ack_fee.0 + self.timeout_fee.0
+    }
+}
+
+/// The class of NFTs in ICS-721 of IBC protocol.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftClass {
+    /// The class id prefixed by the trace path.
+    pub class_id: String,
+    #[serde(default)]
+    pub class_uri: Option<String>,
+    #[serde(default)]
+    pub class_data: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Ics721TransferRequest {
+    /// The id of the request, which is unique in the contract that generates it.
+    pub request_id: U64,
+    pub port_on_a: String,
+    pub chan_on_a: String,
+    /// The account id of the NFT contract for NFTs native in NEAR protocol,
+    /// or the class id prefixed by the trace path for wrapped NFTs.
+    pub class_id: String,
+    #[serde(default)]
+    pub class_uri: Option<String>,
+    #[serde(default)]
+    pub class_data: Option<String>,
+    pub token_id: String,
+    #[serde(default)]
+    pub token_uri: Option<String>,
+    #[serde(default)]
+    pub token_data: Option<String>,
+    pub sender: String,
+    pub receiver: String,
+    pub timeout_seconds: Option<U64>,
+    #[serde(default)]
+    pub memo: Option<String>,
 }
 
 impl ToString for AssetDenom {
@@ -44,3 +144,199 @@ impl ToString for AssetDenom {
         format!("{}/{}", self.trace_path, self.base_denom)
     }
 }
+
+/// An action to be executed by an interchain account hosted in NEAR protocol.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum InterchainAccountAction {
+    /// Call a function of a contract.
+    FunctionCall {
+        receiver_id: AccountId,
+        method_name: String,
+        args: Base64VecU8,
+        deposit: U128,
+        gas: U64,
+    },
+    /// Transfer NEAR to an account.
+    Transfer {
+        receiver_id: AccountId,
+        amount: U128,
+    },
+}
+
+/// The result of an action executed by an interchain account.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InterchainAccountActionResult {
+    pub success: bool,
+    /// The return value of the action, only available when it succeeded.
+    #[serde(default)]
+    pub result: Option<Base64VecU8>,
+}
+
+/// The roles for the operational and risk control tasks of `near-ibc` contract,
+/// which are granted by the governance account.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// For changing the parameters of the contract, setting up escrows, rate limits and
+    /// the retention of states, and uploading the wasm of the sub-account contracts
+    /// to the factories.
+    Operator,
+    /// For setting up wrapped tokens, registering assets for channel escrows and
+    /// cancelling the transfer requests in channel escrows.
+    AssetManager,
+    /// For unfreezing clients, registering light client contracts and setting
+    /// the host client parameters.
+    ClientGuardian,
+    /// For pausing (but not resuming) the contracts.
+    Pauser,
+}
+
+/// The holders of the roles.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default, Serialize, Deserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleHolders(BTreeMap<Role, BTreeSet<AccountId>>);
+
+impl RoleHolders {
+    /// Grant the role to the account, returns false if the account already has the role.
+    pub fn grant(&mut self, role: Role, account_id: AccountId) -> bool {
+        self.0.entry(role).or_default().insert(account_id)
+    }
+    /// Revoke the role from the account, returns false if the account doesn't have the role.
+    pub fn revoke(&mut self, role: Role, account_id: &AccountId) -> bool {
+        match self.0.get_mut(&role) {
+            Some(holders) => {
+                let revoked = holders.remove(account_id);
+                if holders.is_empty() {
+                    self.0.remove(&role);
+                }
+                revoked
+            }
+            None => false,
+        }
+    }
+    ///
+    pub fn has_role(&self, role: Role, account_id: &AccountId) -> bool {
+        self.0
+            .get(&role)
+            .map_or(false, |holders| holders.contains(account_id))
+    }
+    ///
+    pub fn holders_of(&self, role: Role) -> Vec<AccountId> {
+        self.0
+            .get(&role)
+            .map_or_else(Vec::new, |holders| holders.iter().cloned().collect())
+    }
+}
+
+/// The default number of entries scanned for a page of a list view function.
+pub const DEFAULT_PAGE_LIMIT: u64 = 100;
+/// The max number of entries scanned for a page of a list view function.
+pub const MAX_PAGE_LIMIT: u64 = 500;
+
+/// A page of the result of a list view function.
+///
+/// At most `limit` entries are scanned for a page, so a page may contain less items
+/// when a filter is applied. The next page can be queried with `next_index`,
+/// which is `None` if all of the entries are scanned.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_index: Option<U64>,
+}
+
+impl<T> Page<T> {
+    /// Build a page by scanning the entries of the given iterator (with `total` entries)
+    /// from `from_index`, and keeping the ones mapped to `Some` by the given function.
+    pub fn scan<I, F>(
+        iter: I,
+        total: u64,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        f: F,
+    ) -> Self
+    where
+        I: Iterator,
+        F: FnMut(I::Item) -> Option<T>,
+    {
+        let from_index = from_index.map_or(0, |index| index.0);
+        let limit = limit.map_or(DEFAULT_PAGE_LIMIT, |limit| limit.0.min(MAX_PAGE_LIMIT));
+        let items = iter
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(f)
+            .collect();
+        let next_index = from_index.saturating_add(limit);
+        Self {
+            items,
+            next_index: match next_index < total {
+                true => Some(U64(next_index)),
+                false => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_of(from_index: Option<u64>, limit: Option<u64>, total: u64) -> Page<u64> {
+        Page::scan(
+            0..total,
+            total,
+            from_index.map(U64),
+            limit.map(U64),
+            |x| match x % 2 == 0 {
+                true => Some(x),
+                false => None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_scan_pages() {
+        let page = page_of(None, Some(4), 10);
+        assert_eq!(page.items, vec![0, 2]);
+        assert_eq!(page.next_index, Some(U64(4)));
+        let page = page_of(Some(4), Some(4), 10);
+        assert_eq!(page.items, vec![4, 6]);
+        assert_eq!(page.next_index, Some(U64(8)));
+        let page = page_of(Some(8), Some(4), 10);
+        assert_eq!(page.items, vec![8]);
+        assert_eq!(page.next_index, None);
+        let page = page_of(Some(12), None, 10);
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_index, None);
+    }
+
+    #[test]
+    fn test_scan_with_default_and_max_limit() {
+        let page = page_of(None, None, 1000);
+        assert_eq!(page.items.len() as u64, DEFAULT_PAGE_LIMIT / 2);
+        assert_eq!(page.next_index, Some(U64(DEFAULT_PAGE_LIMIT)));
+        let page = page_of(None, Some(u64::MAX), 1000);
+        assert_eq!(page.items.len() as u64, MAX_PAGE_LIMIT / 2);
+        assert_eq!(page.next_index, Some(U64(MAX_PAGE_LIMIT)));
+        let page = page_of(Some(u64::MAX), Some(MAX_PAGE_LIMIT), 1000);
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_index, None);
+    }
+}
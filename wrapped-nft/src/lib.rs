@@ -0,0 +1,433 @@
+#![deny(
+    warnings,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_import_braces,
+    unused_qualifications,
+    rust_2018_idioms
+)]
+
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+use near_contract_standards::non_fungible_token::{
+    approval::NonFungibleTokenApproval,
+    core::{NonFungibleTokenCore, NonFungibleTokenResolver},
+    enumeration::NonFungibleTokenEnumeration,
+    events::{NftBurn, NftMint},
+    metadata::{
+        NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
+    },
+    NonFungibleToken, Token, TokenId,
+};
+use near_sdk::{
+    borsh::{BorshDeserialize, BorshSerialize},
+    collections::LazyOption,
+    env,
+    json_types::{U128, U64},
+    near_bindgen,
+    store::UnorderedMap,
+    AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise, PromiseOrValue,
+};
+use std::collections::HashMap;
+use utils::{
+    interfaces::{
+        ext_nft_transfer_request_handler, NearIbcAccountAssertion, ProcessTransferRequestCallback,
+        WrappedNft,
+    },
+    types::{Ics721TransferRequest, NftClass},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(BorshSerialize, BorshStorageKey)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    NonFungibleToken,
+    TokenMetadata,
+    Enumeration,
+    Approval,
+    Metadata,
+    PendingTransferRequestsById,
+}
+
+/// A simple wrapper of NEP-171 non-fungible token.
+///
+/// An instance of this contract is used to represent a certain class of NFTs
+/// from another chain on NEAR protocol.
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    /// The NEP-171 non-fungible token implementation.
+    tokens: NonFungibleToken,
+    /// The metadata of the NFT contract.
+    metadata: LazyOption<NFTContractMetadata>,
+    /// The class of the NFTs, in ICS-721 of IBC protocol.
+    nft_class: NftClass,
+    /// The account id of IBC/TAO implementation.
+    near_ibc_account: AccountId,
+    /// Accounting for the pending transfer requests, keyed by request id.
+    pending_transfer_requests: UnorderedMap<u64, Ics721TransferRequest>,
+    /// The id for the next transfer request.
+    next_request_id: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(nft_class: NftClass, near_ibc_account: AccountId) -> Self {
+        let account_id = String::from(env::current_account_id().as_str());
+        let parts = account_id.split(".").collect::<Vec<&str>>();
+        assert!(
+            parts.len() > 3,
+            "ERR_CONTRACT_MUST_BE_DEPLOYED_IN_SUB_ACCOUNT_OF_FACTORY",
+        );
+        assert!(
+            env::current_account_id()
+                .to_string()
+                .ends_with(near_ibc_account.as_str()),
+            "ERR_NEAR_IBC_ACCOUNT_MUST_HAVE_THE_SAME_ROOT_ACOUNT_AS_CURRENT_ACCOUNT"
+        );
+        // As this contract will only be initialized by the first time an NFT of the class
+        // is received by `near-ibc`, the class id will at least have 1 trace prefix
+        // which is composed of the receiving port id and receiving channel id.
+        let class_id_parts: Vec<&str> = nft_class.class_id.splitn(3, '/').collect();
+        assert!(
+            class_id_parts.len() == 3 && class_id_parts.iter().all(|part| !part.is_empty()),
+            "ERR_CLASS_ID_MUST_HAVE_TRACE_PREFIX"
+        );
+        let metadata = NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: nft_class.class_id.clone(),
+            symbol: class_id_parts[2].to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        };
+        Self {
+            tokens: NonFungibleToken::new(
+                StorageKey::NonFungibleToken,
+                env::current_account_id(),
+                Some(StorageKey::TokenMetadata),
+                Some(StorageKey::Enumeration),
+                Some(StorageKey::Approval),
+            ),
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+            nft_class,
+            near_ibc_account,
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
+            next_request_id: 1,
+        }
+    }
+    ///
+    pub fn version(&self) -> String {
+        VERSION.to_string()
+    }
+    ///
+    pub fn nft_class(&self) -> NftClass {
+        self.nft_class.clone()
+    }
+    /// Request a transfer of a certain NFT for sending it back to the source chain.
+    ///
+    /// The NFT is locked in this contract until the transfer request is applied
+    /// (the NFT will be burnt) or cancelled (the NFT will be returned to the owner).
+    #[payable]
+    pub fn request_transfer(
+        &mut self,
+        token_id: TokenId,
+        receiver_id: String,
+        timeout_seconds: Option<U64>,
+        memo: Option<String>,
+    ) {
+        near_sdk::assert_one_yocto();
+        utils::assert_valid_memo(&memo);
+        let sender_id = env::predecessor_account_id();
+        let token = self
+            .tokens
+            .nft_token(token_id.clone())
+            .expect("ERR_TOKEN_NOT_FOUND");
+        assert_eq!(token.owner_id, sender_id, "ERR_NOT_TOKEN_OWNER");
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        let class_id_parts: Vec<&str> = self.nft_class.class_id.split('/').collect();
+        // As the class id is already validated in the constructor, we can safely use
+        // the first 2 parts of it as the source port id and source channel id.
+        let transfer_request = Ics721TransferRequest {
+            request_id: U64(request_id),
+            port_on_a: class_id_parts[0].to_string(),
+            chan_on_a: class_id_parts[1].to_string(),
+            class_id: self.nft_class.class_id.clone(),
+            class_uri: self.nft_class.class_uri.clone(),
+            class_data: self.nft_class.class_data.clone(),
+            token_id: token_id.clone(),
+            token_uri: token
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.reference.clone()),
+            token_data: token.metadata.and_then(|metadata| metadata.extra),
+            sender: sender_id.to_string(),
+            receiver: receiver_id,
+            timeout_seconds,
+            memo,
+        };
+        ext_nft_transfer_request_handler::ext(self.near_ibc_account.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_static_gas(utils::GAS_FOR_COMPLEX_FUNCTION_CALL)
+            .with_unused_gas_weight(0)
+            .process_nft_transfer_request(transfer_request.clone());
+        // Record the pending transfer request.
+        self.pending_transfer_requests
+            .insert(request_id, transfer_request);
+        // Transfer the NFT to the current account.
+        self.tokens.internal_transfer(
+            &sender_id,
+            &env::current_account_id(),
+            &token_id,
+            None,
+            None,
+        );
+    }
+    /// Remove the pending transfer request with the given id and return it.
+    fn checked_remove_pending_transfer_request(
+        &mut self,
+        request_id: U64,
+    ) -> Ics721TransferRequest {
+        self.pending_transfer_requests
+            .remove(&request_id.0)
+            .expect("ERR_NO_PENDING_TRANSFER_REQUEST")
+    }
+    /// Remove the given token from the contract.
+    fn internal_burn(&mut self, token_id: &TokenId) {
+        let owner_id = self
+            .tokens
+            .owner_by_id
+            .remove(token_id)
+            .expect("ERR_TOKEN_NOT_FOUND");
+        if let Some(token_metadata_by_id) = &mut self.tokens.token_metadata_by_id {
+            token_metadata_by_id.remove(token_id);
+        }
+        if let Some(tokens_per_owner) = &mut self.tokens.tokens_per_owner {
+            if let Some(mut token_ids) = tokens_per_owner.get(&owner_id) {
+                token_ids.remove(token_id);
+                if token_ids.is_empty() {
+                    tokens_per_owner.remove(&owner_id);
+                } else {
+                    tokens_per_owner.insert(&owner_id, &token_ids);
+                }
+            }
+        }
+        if let Some(approvals_by_id) = &mut self.tokens.approvals_by_id {
+            approvals_by_id.remove(token_id);
+        }
+        NftBurn {
+            owner_id: &owner_id,
+            token_ids: &[token_id.as_str()],
+            authorized_id: None,
+            memo: None,
+        }
+        .emit();
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenCore for Contract {
+    #[payable]
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        self.tokens
+            .nft_transfer(receiver_id, token_id, approval_id, memo)
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        self.tokens
+            .nft_transfer_call(receiver_id, token_id, approval_id, memo, msg)
+    }
+
+    fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.tokens.nft_token(token_id)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenResolver for Contract {
+    #[private]
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+    ) -> bool {
+        self.tokens.nft_resolve_transfer(
+            previous_owner_id,
+            receiver_id,
+            token_id,
+            approved_account_ids,
+        )
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenApproval for Contract {
+    #[payable]
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        self.tokens.nft_approve(token_id, account_id, msg)
+    }
+
+    #[payable]
+    fn nft_revoke(&mut self, token_id: TokenId, account_id: AccountId) {
+        self.tokens.nft_revoke(token_id, account_id)
+    }
+
+    #[payable]
+    fn nft_revoke_all(&mut self, token_id: TokenId) {
+        self.tokens.nft_revoke_all(token_id)
+    }
+
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        self.tokens
+            .nft_is_approved(token_id, approved_account_id, approval_id)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenEnumeration for Contract {
+    fn nft_total_supply(&self) -> U128 {
+        self.tokens.nft_total_supply()
+    }
+
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        self.tokens.nft_tokens(from_index, limit)
+    }
+
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
+        self.tokens.nft_supply_for_owner(account_id)
+    }
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        self.tokens
+            .nft_tokens_for_owner(account_id, from_index, limit)
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenMetadataProvider for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[near_bindgen]
+impl WrappedNft for Contract {
+    #[payable]
+    fn mint(
+        &mut self,
+        token_id: TokenId,
+        token_uri: Option<String>,
+        token_data: Option<String>,
+        token_owner: AccountId,
+    ) {
+        utils::assert_parent_account();
+        let token_metadata = TokenMetadata {
+            title: None,
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: token_data,
+            reference: token_uri,
+            reference_hash: None,
+        };
+        // The storage deposit is attached by the NFT factory,
+        // the unused part is kept in this contract.
+        self.tokens.internal_mint_with_refund(
+            token_id.clone(),
+            token_owner.clone(),
+            Some(token_metadata),
+            None,
+        );
+        NftMint {
+            owner_id: &token_owner,
+            token_ids: &[token_id.as_str()],
+            memo: None,
+        }
+        .emit();
+    }
+}
+
+#[near_bindgen]
+impl ProcessTransferRequestCallback for Contract {
+    fn apply_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        let req = self.checked_remove_pending_transfer_request(request_id);
+        self.internal_burn(&req.token_id);
+    }
+
+    fn cancel_transfer_request(&mut self, request_id: U64) {
+        self.assert_near_ibc_account();
+        let req = self.checked_remove_pending_transfer_request(request_id);
+        self.tokens.internal_transfer(
+            &env::current_account_id(),
+            &AccountId::from_str(req.sender.as_str()).unwrap(),
+            &req.token_id,
+            None,
+            None,
+        );
+    }
+}
+
+impl NearIbcAccountAssertion for Contract {
+    fn near_ibc_account(&self) -> AccountId {
+        self.near_ibc_account.clone()
+    }
+}
+
+/// Re-deploy the contract code.
+/// Implemented to avoid loading the data into WASM for optimal gas usage.
+#[no_mangle]
+pub extern "C" fn update_contract_code() {
+    env::setup_panic_hook();
+    let _contract: Contract = env::state_read().expect("ERR_CONTRACT_IS_NOT_INITIALIZED");
+    utils::assert_parent_account();
+
+    let input = env::input().expect("ERR_NO_INPUT");
+    Promise::new(env::current_account_id()).deploy_contract(input);
+}
@@ -19,6 +19,7 @@ use near_contract_standards::{
     fungible_token::{
         events::{FtBurn, FtMint},
         metadata::{FungibleTokenMetadata, FungibleTokenMetadataProvider},
+        receiver::ext_ft_receiver,
         FungibleToken, FungibleTokenCore, FungibleTokenResolver,
     },
     storage_management::{StorageBalance, StorageBalanceBounds, StorageManagement},
@@ -26,20 +27,22 @@ use near_contract_standards::{
 use near_sdk::{
     borsh::{BorshDeserialize, BorshSerialize},
     collections::LazyOption,
-    env,
+    env, ext_contract,
     json_types::{U128, U64},
     near_bindgen,
     store::UnorderedMap,
-    AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise, PromiseOrValue,
+    AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise, PromiseOrValue, PromiseResult,
 };
 use utils::{
     interfaces::{
-        ext_transfer_request_handler, NearIbcAccountAssertion, ProcessTransferRequestCallback,
-        WrappedToken,
+        ext_channel_escrow, ext_transfer_request_handler, NearIbcAccountAssertion,
+        ProcessTransferRequestCallback, WrappedToken,
     },
-    types::Ics20TransferRequest,
+    types::{Ics20TransferRequest, PacketFee, Page},
 };
 
+mod migration;
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -48,6 +51,7 @@ pub enum StorageKey {
     Token,
     Metadata,
     PendingBurnings,
+    PendingTransferRequestsById,
 }
 
 /// A simple wrapper of NEP-141 fungible token.
@@ -68,8 +72,12 @@ pub struct Contract {
     base_denom: String,
     /// The account id of IBC/TAO implementation.
     near_ibc_account: AccountId,
-    /// Accounting for the pending transfer requests.
-    pending_transfer_requests: UnorderedMap<AccountId, Ics20TransferRequest>,
+    /// Accounting for the pending transfer requests, keyed by request id.
+    pending_transfer_requests: UnorderedMap<u64, Ics20TransferRequest>,
+    /// The id for the next transfer request.
+    next_request_id: u64,
+    /// Whether the outgoing transfers are paused.
+    paused: bool,
 }
 
 #[near_bindgen]
@@ -109,7 +117,9 @@ impl Contract {
             trace_path,
             base_denom,
             near_ibc_account,
-            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingBurnings),
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
+            next_request_id: 1,
+            paused: false,
         };
         this.token
             .internal_register_account(&env::current_account_id());
@@ -125,28 +135,43 @@ impl Contract {
     /// This function is called by a certain token holder, when he/she wants to redeem
     /// the token on NEAR protocol back to the source chain. It will send
     /// a transfer plan to the IBC/TAO implementation.
+    ///
+    /// The optional `memo` will be carried in the outgoing ICS-20 packet.
+    ///
+    /// The optional `fee` is the fee for relayers (ICS-29), which is paid in NEAR
+    /// and must be attached exactly.
+    #[payable]
     pub fn request_transfer(
         &mut self,
         receiver_id: String,
         amount: U128,
         timeout_seconds: Option<U64>,
+        memo: Option<String>,
+        fee: Option<PacketFee>,
     ) {
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
         assert!(amount.0 > 0, "ERR_AMOUNT_MUST_BE_GREATER_THAN_ZERO");
+        utils::assert_valid_memo(&memo);
+        let fee_amount = fee.as_ref().map_or(0, |fee| fee.total());
+        assert_eq!(
+            env::attached_deposit().as_yoctonear(),
+            fee_amount,
+            "ERR_ATTACHED_DEPOSIT_MUST_BE_EQUAL_TO_THE_FEE"
+        );
         let sender_id = env::predecessor_account_id();
         assert!(
             self.token.ft_balance_of(sender_id.clone()) >= amount,
             "ERR_NOT_ENOUGH_BALANCE"
         );
-        assert!(
-            !self.pending_transfer_requests.contains_key(&sender_id),
-            "ERR_PENDING_TRANSFER_REQUEST_EXISTS"
-        );
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
         let trace_path_parts: Vec<&str> = self.trace_path.split('/').collect();
         // Schedule a call to `process_transfer_request` on `near-ibc` contract.
         // As the `self.trace_path` is already validated in the constructor,
         // we can safely use the first 2 parts of `self.trace_path` as the source port id
         // and source channel id.
         let transfer_request = Ics20TransferRequest {
+            request_id: U64(request_id),
             port_on_a: trace_path_parts[0].to_string(),
             chan_on_a: trace_path_parts[1].to_string(),
             token_trace_path: self.trace_path.clone(),
@@ -155,15 +180,16 @@ impl Contract {
             sender: sender_id.to_string(),
             receiver: receiver_id,
             timeout_seconds,
+            memo,
         };
         ext_transfer_request_handler::ext(self.near_ibc_account.clone())
-            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_attached_deposit(NearToken::from_yoctonear(fee_amount))
             .with_static_gas(utils::GAS_FOR_COMPLEX_FUNCTION_CALL)
             .with_unused_gas_weight(0)
-            .process_transfer_request(transfer_request.clone());
+            .process_transfer_request(transfer_request.clone(), fee);
         // Record the pending transfer request.
         self.pending_transfer_requests
-            .insert(sender_id.clone(), transfer_request);
+            .insert(request_id, transfer_request);
         // Transfer the tokens to the current account.
         self.token.internal_withdraw(&sender_id, amount.into());
         self.token
@@ -182,42 +208,121 @@ impl Contract {
         }
         .emit();
     }
-    /// Assert that the given account has a pending transfer request with the given amount.
-    fn checked_remove_pending_transfer_request(
-        &mut self,
-        trace_path: &String,
-        base_denom: &String,
-        account_id: &AccountId,
-        amount: U128,
-    ) {
+    /// Lock a certain amount of tokens in this contract and deposit them to the channel escrow
+    /// of the source channel, for a multi-denom transfer (ICS-20 v2) along with other tokens.
+    ///
+    /// The multi-denom transfer is requested by `request_multi_transfer` of the channel escrow,
+    /// and the locked tokens will be burnt when the transfer is applied.
+    pub fn deposit_for_multi_transfer(&mut self, amount: U128) {
+        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
+        assert!(amount.0 > 0, "ERR_AMOUNT_MUST_BE_GREATER_THAN_ZERO");
+        let sender_id = env::predecessor_account_id();
         assert!(
-            self.pending_transfer_requests.contains_key(account_id),
-            "ERR_NO_PENDING_TRANSFER_REQUEST"
+            self.token.ft_balance_of(sender_id.clone()) >= amount,
+            "ERR_NOT_ENOUGH_BALANCE"
         );
-        let req = self.pending_transfer_requests.get(account_id).unwrap();
-        assert!(
-            req.amount == amount
-                && req.token_denom.eq(base_denom)
-                && req.token_trace_path.eq(trace_path),
-            "ERR_PENDING_TRANSFER_REQUEST_NOT_MATCHED"
+        self.token
+            .internal_transfer(&sender_id, &env::current_account_id(), amount.into(), None);
+        ext_channel_escrow::ext(self.channel_escrow_account())
+            .with_attached_deposit(NearToken::from_yoctonear(0))
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+            .with_unused_gas_weight(0)
+            .on_wrapped_token_deposit(
+                sender_id.clone(),
+                self.trace_path.clone(),
+                self.base_denom.clone(),
+                amount,
+            )
+            .then(
+                ext_deposit_for_multi_transfer_resolver::ext(env::current_account_id())
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL)
+                    .with_unused_gas_weight(0)
+                    .resolve_deposit_for_multi_transfer(sender_id, amount),
+            );
+    }
+    /// Get the account id of the channel escrow of the first trace prefix of this token.
+    fn channel_escrow_account(&self) -> AccountId {
+        let trace_path_parts: Vec<&str> = self.trace_path.split('/').collect();
+        AccountId::from_str(
+            format!(
+                "{}.ef.{}.{}",
+                trace_path_parts[1], trace_path_parts[0], self.near_ibc_account
+            )
+            .as_str(),
+        )
+        .unwrap()
+    }
+    /// Asserts that the predecessor is the channel escrow of the first trace prefix.
+    fn assert_channel_escrow_account(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.channel_escrow_account(),
+            "ERR_ONLY_CHANNEL_ESCROW_CAN_CALL_THIS_METHOD"
         );
-        self.pending_transfer_requests.remove(account_id);
     }
-    ///
-    pub fn get_pending_accounts(&self) -> Vec<AccountId> {
+    /// Remove the pending transfer request with the given id and return it.
+    fn checked_remove_pending_transfer_request(&mut self, request_id: U64) -> Ics20TransferRequest {
         self.pending_transfer_requests
-            .keys()
-            .map(|account_id| account_id.clone())
-            .collect()
+            .remove(&request_id.0)
+            .expect("ERR_NO_PENDING_TRANSFER_REQUEST")
     }
-    ///
-    pub fn get_pending_transfer_request_of(
+    /// Get all accounts which have pending transfer requests.
+    pub fn get_pending_accounts(&self) -> Vec<AccountId> {
+        let mut accounts: Vec<AccountId> = self
+            .pending_transfer_requests
+            .values()
+            .map(|req| AccountId::from_str(req.sender.as_str()).unwrap())
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+    /// Get pending transfer requests of the given account.
+    pub fn get_pending_transfer_requests_of(
         &self,
         account_id: AccountId,
-    ) -> Option<Ics20TransferRequest> {
+    ) -> Vec<Ics20TransferRequest> {
         self.pending_transfer_requests
-            .get(&account_id)
+            .values()
+            .filter(|req| req.sender.eq(account_id.as_str()))
             .map(|req| req.clone())
+            .collect()
+    }
+    /// Get the pending transfer request with the given id.
+    pub fn get_pending_transfer_request(&self, request_id: U64) -> Option<Ics20TransferRequest> {
+        self.pending_transfer_requests
+            .get(&request_id.0)
+            .map(|req| req.clone())
+    }
+    /// Get a page of the pending transfer requests, optionally filtered by the sender.
+    ///
+    /// At most `limit` requests (100 by default, 500 at most) are scanned from `from_index`
+    /// for a page, and `next_index` of the returned page is the `from_index` of the next page.
+    pub fn get_pending_transfer_requests_paged(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        account_id: Option<AccountId>,
+    ) -> Page<Ics20TransferRequest> {
+        Page::scan(
+            self.pending_transfer_requests.values(),
+            self.pending_transfer_requests.len().into(),
+            from_index,
+            limit,
+            |req| {
+                if account_id
+                    .as_ref()
+                    .is_some_and(|account_id| !req.sender.eq(account_id.as_str()))
+                {
+                    return None;
+                }
+                Some(req.clone())
+            },
+        )
+    }
+    /// Whether the outgoing transfers are paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 }
 
@@ -303,6 +408,45 @@ impl WrappedToken for Contract {
         .emit();
     }
 
+    #[payable]
+    fn mint_and_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        refund_receiver: AccountId,
+    ) -> Promise {
+        utils::assert_parent_account();
+        let used_bytes = env::storage_usage();
+        let current_account_id = env::current_account_id();
+        for account_id in [&current_account_id, &receiver_id, &refund_receiver] {
+            if !self.token.accounts.contains_key(account_id) {
+                self.token.internal_register_account(account_id);
+            }
+        }
+        self.token
+            .internal_deposit(&current_account_id, amount.into());
+        FtMint {
+            owner_id: &current_account_id,
+            amount,
+            memo: None,
+        }
+        .emit();
+        self.token
+            .internal_transfer(&current_account_id, &receiver_id, amount.into(), None);
+        utils::refund_deposit(used_bytes);
+        ext_ft_receiver::ext(receiver_id.clone())
+            .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(4))
+            .with_unused_gas_weight(1)
+            .ft_on_transfer(current_account_id.clone(), amount, msg)
+            .then(
+                ext_mint_and_call_resolver::ext(current_account_id)
+                    .with_static_gas(utils::GAS_FOR_SIMPLE_FUNCTION_CALL.saturating_mul(2))
+                    .with_unused_gas_weight(0)
+                    .resolve_mint_and_call(receiver_id, amount, refund_receiver),
+            )
+    }
+
     #[payable]
     fn set_icon(&mut self, icon: String) {
         utils::assert_parent_account();
@@ -319,50 +463,135 @@ impl WrappedToken for Contract {
         // Refund the unused attached deposit.
         utils::refund_deposit(used_bytes);
     }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.assert_near_ibc_account();
+        self.paused = paused;
+    }
+
+    fn burn_locked_tokens(&mut self, amount: U128) {
+        self.assert_channel_escrow_account();
+        self.token
+            .internal_withdraw(&env::current_account_id(), amount.into());
+        FtBurn {
+            owner_id: &env::current_account_id(),
+            amount,
+            memo: None,
+        }
+        .emit();
+    }
+
+    fn unlock_tokens(&mut self, receiver_id: AccountId, amount: U128) {
+        self.assert_channel_escrow_account();
+        self.token.internal_transfer(
+            &env::current_account_id(),
+            &receiver_id,
+            amount.into(),
+            None,
+        );
+    }
+}
+
+#[ext_contract(ext_deposit_for_multi_transfer_resolver)]
+pub trait DepositForMultiTransferResolver {
+    fn resolve_deposit_for_multi_transfer(&mut self, sender_id: AccountId, amount: U128);
 }
 
 #[near_bindgen]
-impl ProcessTransferRequestCallback for Contract {
-    fn apply_transfer_request(
+impl DepositForMultiTransferResolver for Contract {
+    #[private]
+    fn resolve_deposit_for_multi_transfer(&mut self, sender_id: AccountId, amount: U128) {
+        if let PromiseResult::Failed = env::promise_result(0) {
+            // The channel escrow refused the deposit, return the locked tokens.
+            self.token.internal_transfer(
+                &env::current_account_id(),
+                &sender_id,
+                amount.into(),
+                None,
+            );
+        }
+    }
+}
+
+#[ext_contract(ext_mint_and_call_resolver)]
+pub trait MintAndCallResolver {
+    fn resolve_mint_and_call(
         &mut self,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
+        receiver_id: AccountId,
         amount: U128,
-    ) {
+        refund_receiver: AccountId,
+    ) -> U128;
+}
+
+#[near_bindgen]
+impl MintAndCallResolver for Contract {
+    #[private]
+    fn resolve_mint_and_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        refund_receiver: AccountId,
+    ) -> U128 {
+        let current_account_id = env::current_account_id();
+        let (used_amount, _burned_amount) =
+            self.token
+                .internal_ft_resolve_transfer(&current_account_id, receiver_id, amount);
+        let unused_amount = amount.0 - used_amount;
+        if unused_amount > 0 {
+            if used_amount == 0 {
+                // The tokens will be refunded on the source chain, burn them here.
+                self.token
+                    .internal_withdraw(&current_account_id, unused_amount);
+                FtBurn {
+                    owner_id: &current_account_id,
+                    amount: U128(unused_amount),
+                    memo: None,
+                }
+                .emit();
+            } else {
+                self.token.internal_transfer(
+                    &current_account_id,
+                    &refund_receiver,
+                    unused_amount,
+                    None,
+                );
+            }
+        }
+        U128(used_amount)
+    }
+}
+
+#[near_bindgen]
+impl ProcessTransferRequestCallback for Contract {
+    fn apply_transfer_request(&mut self, request_id: U64) {
         self.assert_near_ibc_account();
-        self.checked_remove_pending_transfer_request(&trace_path, &base_denom, &sender_id, amount);
+        let req = self.checked_remove_pending_transfer_request(request_id);
         self.token
-            .internal_withdraw(&env::current_account_id(), amount.into());
+            .internal_withdraw(&env::current_account_id(), req.amount.into());
         FtBurn {
             owner_id: &env::current_account_id(),
-            amount,
+            amount: req.amount,
             memo: None,
         }
         .emit()
     }
 
-    fn cancel_transfer_request(
-        &mut self,
-        trace_path: String,
-        base_denom: String,
-        sender_id: AccountId,
-        amount: U128,
-    ) {
+    fn cancel_transfer_request(&mut self, request_id: U64) {
         self.assert_near_ibc_account();
-        self.checked_remove_pending_transfer_request(&trace_path, &base_denom, &sender_id, amount);
+        let req = self.checked_remove_pending_transfer_request(request_id);
+        let sender_id = AccountId::from_str(req.sender.as_str()).unwrap();
         self.token
-            .internal_withdraw(&env::current_account_id(), amount.into());
-        self.token.internal_deposit(&sender_id, amount.into());
+            .internal_withdraw(&env::current_account_id(), req.amount.into());
+        self.token.internal_deposit(&sender_id, req.amount.into());
         FtBurn {
             owner_id: &env::current_account_id(),
-            amount,
+            amount: req.amount,
             memo: None,
         }
         .emit();
         FtMint {
             owner_id: &sender_id,
-            amount,
+            amount: req.amount,
             memo: None,
         }
         .emit();
@@ -0,0 +1,86 @@
+use crate::*;
+
+pub trait StorageMigration {
+    fn migrate_state() -> Self;
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldIcs20TransferRequest {
+    pub port_on_a: String,
+    pub chan_on_a: String,
+    pub token_trace_path: String,
+    pub token_denom: String,
+    pub amount: U128,
+    pub sender: String,
+    pub receiver: String,
+    pub timeout_seconds: Option<U64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldContract {
+    /// The NEP-141 fungible token implementation.
+    token: FungibleToken,
+    /// The metadata of the token.
+    metadata: LazyOption<FungibleTokenMetadata>,
+    /// The trace path of the token, in ICS-20 of IBC protocol.
+    trace_path: String,
+    /// The base denom of the token, in ICS-20 of IBC protocol.
+    base_denom: String,
+    /// The account id of IBC/TAO implementation.
+    near_ibc_account: AccountId,
+    /// Accounting for the pending transfer requests.
+    pending_transfer_requests: UnorderedMap<AccountId, OldIcs20TransferRequest>,
+}
+
+#[near_bindgen]
+impl StorageMigration for Contract {
+    #[init(ignore_state)]
+    fn migrate_state() -> Self {
+        // Deserialize the state using the old contract structure.
+        let mut old_contract: OldContract = env::state_read().expect("Old state doesn't exist");
+        //
+        utils::assert_parent_account();
+        //
+        // Create the new contract using the data from the old contract.
+        let mut new_contract = Contract {
+            token: old_contract.token,
+            metadata: old_contract.metadata,
+            trace_path: old_contract.trace_path,
+            base_denom: old_contract.base_denom,
+            near_ibc_account: old_contract.near_ibc_account,
+            pending_transfer_requests: UnorderedMap::new(StorageKey::PendingTransferRequestsById),
+            next_request_id: 1,
+            paused: false,
+        };
+        //
+        // Move the pending transfer requests to the new collection keyed by request id.
+        old_contract
+            .pending_transfer_requests
+            .iter()
+            .for_each(|(_, req)| {
+                let request_id = new_contract.next_request_id;
+                new_contract.next_request_id += 1;
+                new_contract.pending_transfer_requests.insert(
+                    request_id,
+                    Ics20TransferRequest {
+                        request_id: U64(request_id),
+                        port_on_a: req.port_on_a.clone(),
+                        chan_on_a: req.chan_on_a.clone(),
+                        token_trace_path: req.token_trace_path.clone(),
+                        token_denom: req.token_denom.clone(),
+                        amount: req.amount,
+                        sender: req.sender.clone(),
+                        receiver: req.receiver.clone(),
+                        timeout_seconds: req.timeout_seconds,
+                        memo: None,
+                    },
+                );
+            });
+        old_contract.pending_transfer_requests.clear();
+        old_contract.pending_transfer_requests.flush();
+        //
+        new_contract
+    }
+}